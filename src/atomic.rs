@@ -0,0 +1,45 @@
+use anyhow::Result;
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Writes `contents` to a temporary file next to `path`, then renames it over `path`, so a
+/// crash mid-write leaves either the old file or the new one, never a truncated mix.
+/// The permissions of an existing file are kept.
+pub fn write(path: &Path, contents: impl AsRef<[u8]>) -> Result<()> {
+    let temp = sibling(path, &format!(".tmp{}", std::process::id()), true);
+
+    let written = fs::write(&temp, contents).and_then(|_| {
+        if let Ok(metadata) = fs::metadata(path) {
+            fs::set_permissions(&temp, metadata.permissions())?;
+        }
+        fs::rename(&temp, path)
+    });
+
+    if let Err(e) = written {
+        let _ = fs::remove_file(&temp);
+        return Err(anyhow::anyhow!("Error writing {}: {}", path.display(), e));
+    }
+
+    Ok(())
+}
+
+/// Copies the file at `path` to `<path>.bak`, replacing any previous backup.
+/// Returns the path of the backup.
+pub fn backup(path: &Path) -> Result<PathBuf> {
+    let backup = sibling(path, ".bak", false);
+    fs::copy(path, &backup)
+        .map_err(|e| anyhow::anyhow!("Error backing up {}: {}", path.display(), e))?;
+    Ok(backup)
+}
+
+/// `path` with `suffix` appended to its file name, hidden with a leading dot if asked to
+fn sibling(path: &Path, suffix: &str, hidden: bool) -> PathBuf {
+    let mut name = OsString::new();
+    if hidden {
+        name.push(".");
+    }
+    name.push(path.file_name().unwrap_or_default());
+    name.push(suffix);
+    path.with_file_name(name)
+}
@@ -0,0 +1,64 @@
+//! The `#. ai-translated: <model> <date>` comment `--annotate` adds to the entries it
+//! translates, telling reviewers machine output apart from human translations, and `--only-ai`
+//! finds again.
+
+use chrono::Local;
+
+use crate::po::PoEntry;
+
+/// The start of the attribution comment, after `#.`
+const PREFIX: &str = "ai-translated:";
+
+/// Returns true if `entry` has an attribution comment
+pub fn is_ai_translated(entry: &PoEntry) -> bool {
+    entry
+        .extracted_comments()
+        .iter()
+        .any(|comment| is_attribution(comment))
+}
+
+/// Returns true for the text of an attribution comment, which isn't a note for the model
+pub fn is_attribution(comment: &str) -> bool {
+    comment.starts_with(PREFIX)
+}
+
+/// Replaces the attribution comment of `entry` with one naming `model` and today's date, or
+/// removes it when a human wrote the translation
+pub fn annotate(entry: &mut PoEntry, model: Option<&str>) {
+    entry.comments.retain(|line| {
+        !line
+            .trim()
+            .strip_prefix("#.")
+            .is_some_and(|comment| is_attribution(comment.trim()))
+    });
+    let Some(model) = model else {
+        return;
+    };
+
+    // Extracted comments come after the translator's, and before references and flags
+    let position = match entry
+        .comments
+        .iter()
+        .rposition(|line| line.trim().starts_with("#."))
+    {
+        Some(last) => last + 1,
+        None => entry
+            .comments
+            .iter()
+            .position(|line| {
+                ["#:", "#,", "#|"]
+                    .iter()
+                    .any(|prefix| line.trim().starts_with(prefix))
+            })
+            .unwrap_or(entry.comments.len()),
+    };
+    entry.comments.insert(
+        position,
+        format!(
+            "#. {} {} {}",
+            PREFIX,
+            model,
+            Local::now().format("%Y-%m-%d")
+        ),
+    );
+}
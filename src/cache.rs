@@ -0,0 +1,190 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::atomic;
+
+pub const DEFAULT_CACHE_FILE: &str = ".gettext-translator-cache.json";
+
+/// Identifies a translation: the entry's strings, the target language and the project context
+pub struct CacheKey<'a> {
+    pub msgctxt: Option<&'a str>,
+    pub msgid: &'a str,
+    pub msgid_plural: Option<&'a str>,
+    pub lang: &'a str,
+    pub context: Option<&'a str>,
+}
+
+impl CacheKey<'_> {
+    /// Joins the fields with a separator that never appears in .po strings. The project
+    /// context has already been hashed at this point.
+    fn encode(&self, context_hash: Option<&str>) -> String {
+        format!(
+            "{}\u{1f}{}\u{1f}{}\u{1f}{}\u{1f}{}",
+            self.lang,
+            context_hash.unwrap_or_default(),
+            self.msgctxt.unwrap_or_default(),
+            self.msgid,
+            self.msgid_plural.unwrap_or_default()
+        )
+    }
+}
+
+/// A persistent translation memory keyed by (msgctxt, msgid, msgid_plural, lang, context)
+pub struct TranslationCache {
+    path: Option<PathBuf>,
+    entries: BTreeMap<String, CacheEntry>,
+    dirty: bool,
+    /// The translations made during the run, by the same key, kept even with `--no-cache`
+    /// and `--force` so that a msgid found in several catalogs gets one translation
+    run: HashMap<String, Vec<String>>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct CacheEntry {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    msgctxt: Option<String>,
+    msgid: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    msgid_plural: Option<String>,
+    lang: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    context: Option<String>,
+    msgstr: Vec<String>,
+}
+
+impl TranslationCache {
+    /// Loads the cache at `path`, starting empty if the file does not exist yet
+    pub fn load(path: &Path) -> Result<Self> {
+        let mut entries = BTreeMap::new();
+
+        if path.is_file() {
+            let content = fs::read_to_string(path)?;
+            let records: Vec<CacheEntry> = serde_json::from_str(&content).map_err(|e| {
+                anyhow::anyhow!("Error reading cache file {}: {}", path.display(), e)
+            })?;
+
+            for record in records {
+                let key = CacheKey {
+                    msgctxt: record.msgctxt.as_deref(),
+                    msgid: &record.msgid,
+                    msgid_plural: record.msgid_plural.as_deref(),
+                    lang: &record.lang,
+                    context: None,
+                }
+                .encode(record.context.as_deref());
+                entries.insert(key, record);
+            }
+        }
+
+        Ok(Self {
+            path: Some(path.to_path_buf()),
+            entries,
+            dirty: false,
+            run: HashMap::new(),
+        })
+    }
+
+    /// A cache that never hits and is never written, used with `--no-cache`. It still
+    /// remembers the translations of the run.
+    pub fn disabled() -> Self {
+        Self {
+            path: None,
+            entries: BTreeMap::new(),
+            dirty: false,
+            run: HashMap::new(),
+        }
+    }
+
+    /// The translation made earlier in the run, if any
+    pub fn recall(&self, key: &CacheKey) -> Option<Vec<String>> {
+        let context = key.context.map(context_hash);
+        self.run.get(&key.encode(context.as_deref())).cloned()
+    }
+
+    /// Remembers `msgstr` for the rest of the run, unless a translation was made earlier,
+    /// like by a catalog translated at the same time, which is returned instead
+    pub fn remember(&mut self, key: &CacheKey, msgstr: Vec<String>) -> Vec<String> {
+        let context = key.context.map(context_hash);
+        self.run
+            .entry(key.encode(context.as_deref()))
+            .or_insert(msgstr)
+            .clone()
+    }
+
+    /// Replaces the translation remembered for the run, with one given by a reviewer
+    pub fn correct(&mut self, key: &CacheKey, msgstr: Vec<String>) {
+        let context = key.context.map(context_hash);
+        self.run.insert(key.encode(context.as_deref()), msgstr);
+    }
+
+    pub fn get(&self, key: &CacheKey) -> Option<Vec<String>> {
+        self.path.as_ref()?;
+
+        let context = key.context.map(context_hash);
+        let key = key.encode(context.as_deref());
+        self.entries.get(&key).map(|entry| entry.msgstr.clone())
+    }
+
+    pub fn insert(&mut self, key: &CacheKey, msgstr: Vec<String>) {
+        if self.path.is_none() {
+            return;
+        }
+
+        let context = key.context.map(context_hash);
+        let encoded = key.encode(context.as_deref());
+        self.entries.insert(
+            encoded,
+            CacheEntry {
+                msgctxt: key.msgctxt.map(str::to_string),
+                msgid: key.msgid.to_string(),
+                msgid_plural: key.msgid_plural.map(str::to_string),
+                lang: key.lang.to_string(),
+                context,
+                msgstr,
+            },
+        );
+        self.dirty = true;
+    }
+
+    /// Forgets a translation, so that the next run asks for it again
+    pub fn remove(&mut self, key: &CacheKey) {
+        let context = key.context.map(context_hash);
+        if self
+            .entries
+            .remove(&key.encode(context.as_deref()))
+            .is_some()
+        {
+            self.dirty = true;
+        }
+    }
+
+    /// Writes the cache back to disk if anything was added since the last save
+    pub fn save(&mut self) -> Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+
+        if !self.dirty {
+            return Ok(());
+        }
+
+        let records: Vec<&CacheEntry> = self.entries.values().collect();
+        atomic::write(path, serde_json::to_string_pretty(&records)?)?;
+        self.dirty = false;
+
+        Ok(())
+    }
+}
+
+/// Stable FNV-1a hash of the context file, so the cache does not store the whole context per entry
+fn context_hash(context: &str) -> String {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in context.as_bytes() {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    format!("{:016x}", hash)
+}
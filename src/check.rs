@@ -0,0 +1,169 @@
+use anyhow::Result;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+use crate::icu;
+use crate::logger::{log_check_issue, log_check_summary};
+use crate::placeholders;
+use crate::po::{PoCatalog, PoEntry};
+
+/// A problem found in a translated entry
+pub enum Issue {
+    Empty,
+    Missing(String),
+    Unexpected(String),
+    CountMismatch {
+        placeholder: String,
+        expected: usize,
+        found: usize,
+    },
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Issue::Empty => write!(f, "msgstr is empty"),
+            Issue::Missing(placeholder) => write!(f, "missing placeholder {}", placeholder),
+            Issue::Unexpected(placeholder) => {
+                write!(f, "placeholder {} is not in the msgid", placeholder)
+            }
+            Issue::CountMismatch {
+                placeholder,
+                expected,
+                found,
+            } => write!(
+                f,
+                "placeholder {} appears {} time(s) in the msgid but {} in the msgstr",
+                placeholder, expected, found
+            ),
+        }
+    }
+}
+
+/// Scans every .po file under `root` and reports invalid entries.
+/// Returns the number of issues found.
+pub fn run(root: PathBuf) -> Result<usize> {
+    let mut files = 0;
+    let mut issues = 0;
+
+    for entry in WalkDir::new(root).into_iter().filter_map(|e| e.ok()) {
+        let path = entry.path();
+        if path.extension().map(|e| e == "po").unwrap_or(false) {
+            files += 1;
+            issues += check_file(path)?;
+        }
+    }
+
+    log_check_summary(files, issues);
+    Ok(issues)
+}
+
+fn check_file(path: &Path) -> Result<usize> {
+    let mut catalog = PoCatalog::read(path)?;
+
+    let mut issues = 0;
+    for entry in catalog.entries_mut() {
+        if entry.is_header() || entry.msgstr.is_empty() {
+            continue;
+        }
+
+        for issue in check_entry(entry) {
+            log_check_issue(
+                &path.display().to_string(),
+                entry.line,
+                &entry.msgid,
+                &issue,
+            );
+            issues += 1;
+        }
+    }
+
+    Ok(issues)
+}
+
+/// Compares the placeholders of each msgstr with those of its source strings.
+/// Plural forms must keep the placeholders shared by msgid and msgid_plural, may use any of
+/// them, and must not introduce new ones.
+pub fn check_entry(entry: &PoEntry) -> Vec<Issue> {
+    check_msgstrs(entry, &entry.msgstr)
+}
+
+/// `check_entry` for proposed `msgstrs`, before they are written to the entry
+pub fn check_msgstrs(entry: &PoEntry, msgstrs: &[String]) -> Vec<Issue> {
+    if msgstrs.is_empty() || msgstrs.iter().any(|msgstr| msgstr.is_empty()) {
+        return vec![Issue::Empty];
+    }
+
+    let singular = count(&entry.msgid);
+    let Some(msgid_plural) = &entry.msgid_plural else {
+        return compare(&singular, &count(&msgstrs[0]), true);
+    };
+
+    let plural = count(msgid_plural);
+    let required: BTreeMap<String, usize> = singular
+        .iter()
+        .filter(|(placeholder, _)| plural.contains_key(*placeholder))
+        .map(|(placeholder, count)| (placeholder.clone(), *count))
+        .collect();
+    let mut allowed = plural.clone();
+    allowed.extend(singular);
+
+    msgstrs
+        .iter()
+        .flat_map(|msgstr| {
+            let found = count(msgstr);
+            let mut issues = compare(&required, &found, false);
+            issues.extend(
+                found
+                    .keys()
+                    .filter(|placeholder| !allowed.contains_key(*placeholder))
+                    .map(|placeholder| Issue::Unexpected(placeholder.clone())),
+            );
+            issues
+        })
+        .collect()
+}
+
+/// Counts the placeholders of `text`, only in the text parts of an ICU message, whose
+/// branches like `{She}` aren't placeholders
+fn count(text: &str) -> BTreeMap<String, usize> {
+    match icu::parse(text) {
+        Some(pattern) => placeholders::count(&pattern.text()),
+        None => placeholders::count(text),
+    }
+}
+
+/// Reports placeholders of `expected` missing (or with another count) in `found`, and, if
+/// `strict`, placeholders of `found` that `expected` does not have
+fn compare(
+    expected: &BTreeMap<String, usize>,
+    found: &BTreeMap<String, usize>,
+    strict: bool,
+) -> Vec<Issue> {
+    let mut issues = Vec::new();
+
+    for (placeholder, count) in expected {
+        match found.get(placeholder) {
+            None => issues.push(Issue::Missing(placeholder.clone())),
+            Some(found) if found != count => issues.push(Issue::CountMismatch {
+                placeholder: placeholder.clone(),
+                expected: *count,
+                found: *found,
+            }),
+            Some(_) => {}
+        }
+    }
+
+    if strict {
+        issues.extend(
+            found
+                .keys()
+                .filter(|placeholder| !expected.contains_key(*placeholder))
+                .map(|placeholder| Issue::Unexpected(placeholder.clone())),
+        );
+    }
+
+    issues
+}
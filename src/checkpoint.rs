@@ -0,0 +1,73 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::atomic;
+
+pub const DEFAULT_CHECKPOINT_FILE: &str = ".gettext-translator-checkpoint.json";
+
+/// Records the catalogs a run has finished, so an interrupted run can `--resume`
+pub struct Checkpoint {
+    path: Option<PathBuf>,
+    state: CheckpointState,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct CheckpointState {
+    completed: BTreeSet<String>,
+}
+
+impl Checkpoint {
+    /// Opens the checkpoint at `path`. Unless `resume` is set, previous progress is discarded.
+    pub fn open(path: &Path, resume: bool) -> Result<Self> {
+        let state = if resume && path.is_file() {
+            let content = fs::read_to_string(path)?;
+            serde_json::from_str(&content).map_err(|e| {
+                anyhow::anyhow!("Error reading checkpoint file {}: {}", path.display(), e)
+            })?
+        } else {
+            CheckpointState::default()
+        };
+
+        Ok(Self {
+            path: Some(path.to_path_buf()),
+            state,
+        })
+    }
+
+    /// A checkpoint that records nothing, used for dry runs and library callers
+    pub fn disabled() -> Self {
+        Self {
+            path: None,
+            state: CheckpointState::default(),
+        }
+    }
+
+    pub fn is_completed(&self, file: &Path, lang: &str) -> bool {
+        self.state.completed.contains(&key(file, lang))
+    }
+
+    /// Marks a catalog as done and writes the checkpoint to disk
+    pub fn complete(&mut self, file: &Path, lang: &str) -> Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+
+        self.state.completed.insert(key(file, lang));
+        atomic::write(path, serde_json::to_string_pretty(&self.state)?)
+    }
+
+    /// Removes the checkpoint once the whole run has finished
+    pub fn finish(&self) -> Result<()> {
+        match &self.path {
+            Some(path) if path.is_file() => Ok(fs::remove_file(path)?),
+            _ => Ok(()),
+        }
+    }
+}
+
+fn key(file: &Path, lang: &str) -> String {
+    format!("{}:{}", lang, file.display())
+}
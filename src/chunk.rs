@@ -0,0 +1,117 @@
+//! Splitting of very long messages, like help texts and email templates, into parts that
+//! are translated one by one, so that no answer gets cut short by the model's output limit.
+//! Messages are only split between paragraphs, and put back together with their original
+//! line breaks.
+
+use regex::Regex;
+use std::sync::LazyLock;
+
+use crate::placeholders;
+
+/// A blank line between two paragraphs, with the indentation around it
+static PARAGRAPH_BREAK: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\n[ \t]*\n\s*").unwrap());
+
+/// A message split into parts of whole paragraphs
+pub struct Chunks {
+    pub parts: Vec<String>,
+    /// The line breaks between each part and the next one
+    pub breaks: Vec<String>,
+}
+
+impl Chunks {
+    /// The parts before and after part `index`, given to the model as context only
+    pub fn surroundings(&self, index: usize) -> (Option<&str>, Option<&str>) {
+        let before = index
+            .checked_sub(1)
+            .map(|previous| last_paragraph(&self.parts[previous]));
+        let after = self.parts.get(index + 1).map(|next| first_paragraph(next));
+        (before, after)
+    }
+
+    /// Puts the translations of the parts back together
+    pub fn join(&self, translations: &[String]) -> String {
+        let mut joined = String::new();
+        for (index, translation) in translations.iter().enumerate() {
+            joined.push_str(translation.trim());
+            if let Some(line_break) = self.breaks.get(index) {
+                joined.push_str(line_break);
+            }
+        }
+        joined
+    }
+}
+
+/// Splits `text` into parts of at most about `max` characters, on paragraph boundaries.
+/// Returns None if it fits in one part; a paragraph longer than `max` is a part of its own.
+pub fn split(text: &str, max: usize) -> Option<Chunks> {
+    if text.chars().count() <= max {
+        return None;
+    }
+
+    let mut chunks = Chunks {
+        parts: Vec::new(),
+        breaks: Vec::new(),
+    };
+    let mut part = String::new();
+    let mut start = 0;
+    for line_break in PARAGRAPH_BREAK.find_iter(text) {
+        let paragraph = &text[start..line_break.start()];
+        if !part.is_empty() && part.chars().count() + paragraph.chars().count() > max {
+            let (part, line_break) = split_off_break(&mut part);
+            chunks.parts.push(part);
+            chunks.breaks.push(line_break);
+        }
+        part.push_str(paragraph);
+        part.push_str(line_break.as_str());
+        start = line_break.end();
+    }
+
+    let paragraph = &text[start..];
+    if !part.is_empty() && part.chars().count() + paragraph.chars().count() > max {
+        let (part, line_break) = split_off_break(&mut part);
+        chunks.parts.push(part);
+        chunks.breaks.push(line_break);
+    }
+    part.push_str(paragraph);
+    chunks.parts.push(part);
+
+    (chunks.parts.len() > 1).then_some(chunks)
+}
+
+/// Takes a part ending in a paragraph break, returning it without the break, and the break
+fn split_off_break(part: &mut String) -> (String, String) {
+    let mut part = std::mem::take(part);
+    let end = PARAGRAPH_BREAK
+        .find_iter(&part)
+        .last()
+        .filter(|line_break| line_break.end() == part.len())
+        .map_or(part.len(), |line_break| line_break.start());
+    let line_break = part.split_off(end);
+    (part, line_break)
+}
+
+/// The number of paragraphs of `text`
+pub fn paragraphs(text: &str) -> usize {
+    PARAGRAPH_BREAK.split(text.trim()).count()
+}
+
+/// Checks the translation of a part, or of the whole message put back together: it must keep
+/// the paragraphs and the placeholders of the source. Returns what doesn't match.
+pub fn mismatch(source: &str, translation: &str) -> Option<String> {
+    let (expected, got) = (paragraphs(source), paragraphs(translation));
+    if expected != got {
+        return Some(format!("{} paragraphs instead of {}", got, expected));
+    }
+    if placeholders::count(source) != placeholders::count(translation) {
+        return Some("different placeholders".to_string());
+    }
+    None
+}
+
+fn first_paragraph(text: &str) -> &str {
+    PARAGRAPH_BREAK.split(text).next().unwrap_or(text)
+}
+
+fn last_paragraph(text: &str) -> &str {
+    PARAGRAPH_BREAK.split(text).last().unwrap_or(text)
+}
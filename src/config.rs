@@ -0,0 +1,240 @@
+use anyhow::{Result, bail};
+use clap::{Arg, ArgAction, Command};
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+use toml::{Table, Value};
+
+pub const DEFAULT_CONFIG_FILE: &str = "gettext-translator.toml";
+
+/// Applies a `gettext-translator.toml` to the command line. Top-level keys set the flag of the
+/// same name on every subcommand that has it, and `[translator]`, `[inline]`, ... tables only
+/// that subcommand's:
+///
+/// ```toml
+/// provider = "anthropic"
+/// model = "claude-3-5-haiku-latest"
+///
+/// [translator]
+/// lang = ["es", "fr"]
+/// concurrency = 4
+/// glossary = "glossary.csv"
+/// ```
+///
+/// A `[lang.ja]` table, at the top level or in a subcommand's, gives another provider or model
+/// to a language, as a `--lang-override`:
+///
+/// ```toml
+/// [lang.ja]
+/// provider = "openai"
+/// model = "gpt-4o"
+/// ```
+///
+/// A `[lang_map]` table names languages for the model, as `--lang-map`:
+///
+/// ```toml
+/// [lang_map]
+/// es_MX = "Mexican Spanish"
+/// de_CH = "Swiss German"
+/// ```
+///
+/// The values are inserted as flags right after the subcommand, before the ones typed by the
+/// user, so the command line wins (the command must be built with `args_override_self`).
+pub fn apply(args: Vec<OsString>, command: &Command) -> Result<Vec<OsString>> {
+    let Some(path) = config_path(&args) else {
+        return Ok(args);
+    };
+
+    let content = fs::read_to_string(&path)
+        .map_err(|e| anyhow::anyhow!("Error reading config file {}: {}", path.display(), e))?;
+    let config: Table = toml::from_str(&content)
+        .map_err(|e| anyhow::anyhow!("Error reading config file {}: {}", path.display(), e))?;
+
+    let Some((position, subcommand)) = find_subcommand(&args, command) else {
+        return Ok(args);
+    };
+
+    let mut flags = Vec::new();
+    for (key, value) in &config {
+        match value {
+            Value::Table(table) if command.find_subcommand(key).is_some() => {
+                if key == subcommand.get_name() {
+                    for (key, value) in table {
+                        push_flag(&mut flags, &args, subcommand, key, value, true)?;
+                    }
+                }
+            }
+            _ => {
+                let known = command
+                    .get_subcommands()
+                    .any(|subcommand| find_arg(subcommand, key).is_some());
+                if !known {
+                    bail!("Unknown key `{}` in {}", key, path.display());
+                }
+                push_flag(&mut flags, &args, subcommand, key, value, false)?;
+            }
+        }
+    }
+
+    let mut args = args;
+    args.splice(position + 1..position + 1, flags);
+    Ok(args)
+}
+
+/// The file given with `--config`, or `gettext-translator.toml` in the working directory
+fn config_path(args: &[OsString]) -> Option<PathBuf> {
+    let explicit = args.iter().enumerate().find_map(|(index, arg)| {
+        let arg = arg.to_str()?;
+        match arg.strip_prefix("--config=") {
+            Some(path) => Some(PathBuf::from(path)),
+            None if arg == "--config" => args.get(index + 1).map(PathBuf::from),
+            None => None,
+        }
+    });
+
+    explicit.or_else(|| {
+        let default = Path::new(DEFAULT_CONFIG_FILE);
+        default.is_file().then(|| default.to_path_buf())
+    })
+}
+
+/// The position and definition of the subcommand in `args`
+fn find_subcommand<'a>(args: &[OsString], command: &'a Command) -> Option<(usize, &'a Command)> {
+    args.iter().enumerate().skip(1).find_map(|(index, arg)| {
+        let subcommand = command.find_subcommand(arg.to_str()?)?;
+        Some((index, subcommand))
+    })
+}
+
+fn find_arg<'a>(subcommand: &'a Command, key: &str) -> Option<&'a clap::Arg> {
+    let long = key.replace('_', "-");
+    subcommand
+        .get_arguments()
+        .find(|arg| arg.get_long() == Some(long.as_str()))
+}
+
+/// Turns `key = value` into `--key value`. Keys the subcommand doesn't have are skipped,
+/// unless they come from its own table, and so are those conflicting with a typed flag
+/// (e.g. `lang` when `--all-langs` is given). The values of repeatable flags like `include`
+/// or `header` are skipped once the flag is typed, so that they don't add up with it.
+fn push_flag(
+    flags: &mut Vec<OsString>,
+    args: &[OsString],
+    subcommand: &Command,
+    key: &str,
+    value: &Value,
+    strict: bool,
+) -> Result<()> {
+    if let (Value::Table(langs), "lang") = (value, key) {
+        return push_lang_overrides(flags, subcommand, langs);
+    }
+    if let (Value::Table(names), "lang_map") = (value, key) {
+        return push_lang_names(flags, subcommand, names);
+    }
+    let Some(arg) = find_arg(subcommand, key) else {
+        if strict {
+            bail!(
+                "Unknown key `{}` for the {} subcommand in the config file",
+                key,
+                subcommand.get_name()
+            );
+        }
+        return Ok(());
+    };
+    let flag = OsString::from(format!("--{}", arg.get_long().unwrap_or(key)));
+
+    let conflicts = |a: &Arg, b: &Arg| {
+        subcommand
+            .get_arg_conflicts_with(a)
+            .iter()
+            .any(|conflict| conflict.get_id() == b.get_id())
+    };
+    let typed = |other: &Arg| {
+        other.get_long().is_some_and(|long| {
+            let flag = format!("--{}", long);
+            args.iter()
+                .filter_map(|a| a.to_str())
+                .any(|a| a == flag || a.starts_with(&format!("{}=", flag)))
+        })
+    };
+    let repeated = matches!(arg.get_action(), ArgAction::Append) && typed(arg);
+    let overridden = repeated
+        || subcommand
+            .get_arguments()
+            .filter(|other| conflicts(arg, other) || conflicts(other, arg))
+            .any(typed);
+    if overridden {
+        return Ok(());
+    }
+
+    match value {
+        Value::Boolean(true) => flags.push(flag),
+        Value::Boolean(false) => {}
+        Value::Array(values) if matches!(arg.get_action(), ArgAction::Append) => {
+            for value in values {
+                flags.push(flag.clone());
+                flags.push(scalar(key, value)?.into());
+            }
+        }
+        Value::Array(values) => {
+            let values: Vec<String> = values
+                .iter()
+                .map(|value| scalar(key, value))
+                .collect::<Result<_>>()?;
+            flags.push(flag);
+            flags.push(values.join(",").into());
+        }
+        value => {
+            flags.push(flag);
+            flags.push(scalar(key, value)?.into());
+        }
+    }
+
+    Ok(())
+}
+
+/// Turns the `[lang.<code>]` tables into `--lang-override <code>:<key>=<value>,...`. Those
+/// typed on the command line come after, and win.
+fn push_lang_overrides(
+    flags: &mut Vec<OsString>,
+    subcommand: &Command,
+    langs: &Table,
+) -> Result<()> {
+    if find_arg(subcommand, "lang-override").is_none() {
+        return Ok(());
+    }
+    for (lang, settings) in langs {
+        let Value::Table(settings) = settings else {
+            bail!("Expected a [lang.{}] table in the config file", lang);
+        };
+        let settings: Vec<String> = settings
+            .iter()
+            .map(|(key, value)| Ok(format!("{}={}", key, scalar(key, value)?)))
+            .collect::<Result<_>>()?;
+        flags.push("--lang-override".into());
+        flags.push(format!("{}:{}", lang, settings.join(",")).into());
+    }
+    Ok(())
+}
+
+/// Turns a `[lang_map]` table of names by code into `--lang-map <code>=<name>`
+fn push_lang_names(flags: &mut Vec<OsString>, subcommand: &Command, names: &Table) -> Result<()> {
+    if find_arg(subcommand, "lang-map").is_none() {
+        return Ok(());
+    }
+    for (code, name) in names {
+        flags.push("--lang-map".into());
+        flags.push(format!("{}={}", code, scalar(code, name)?).into());
+    }
+    Ok(())
+}
+
+fn scalar(key: &str, value: &Value) -> Result<String> {
+    match value {
+        Value::String(value) => Ok(value.clone()),
+        Value::Integer(value) => Ok(value.to_string()),
+        Value::Float(value) => Ok(value.to_string()),
+        Value::Boolean(value) => Ok(value.to_string()),
+        _ => bail!("Unsupported value for `{}` in the config file", key),
+    }
+}
@@ -0,0 +1,205 @@
+//! The `consistency` subcommand: finds the msgids translated in different ways across the
+//! catalogs of a language, like "Sign in" as "Iniciar sesión" in one file and "Entrar" in
+//! another. Short msgids are terms, whose wording is also looked for in the translations of
+//! the longer messages using them. The model can propose the translation to use everywhere.
+
+use anyhow::Result;
+use regex::Regex;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::coverage::lang_of;
+use crate::filter::PathFilter;
+use crate::logger::{log_consistency_summary, log_inconsistency};
+use crate::po::PoCatalog;
+use crate::prompt::{INJECTION_GUARD, delimit, strip_delimiters};
+use crate::provider::{AiRequest, TranslationProvider};
+use crate::sanitize;
+use crate::translator::iso_to_name;
+
+/// Longest msgid, in words, looked for inside longer messages
+const MAX_TERM_WORDS: usize = 4;
+
+/// One of the translations of a msgid, with where it is used
+pub struct Variant {
+    pub translation: String,
+    /// `path:line` of the entries, and of the longer messages using it
+    pub locations: Vec<String>,
+}
+
+/// A translated entry of a catalog
+struct Translated {
+    msgctxt: Option<String>,
+    msgid: String,
+    msgstr: String,
+    location: String,
+}
+
+/// Reports the msgids of the catalogs under `root`, in `langs` (every language if empty),
+/// that have several translations in a language. With a `provider`, the model is asked which
+/// one to use. Returns the number of msgids found.
+pub async fn run(
+    root: &Path,
+    langs: &[String],
+    provider: Option<&dyn TranslationProvider>,
+) -> Result<usize> {
+    let mut catalogs: BTreeMap<String, Vec<Translated>> = BTreeMap::new();
+    let mut paths: Vec<PathBuf> = PathFilter::default()
+        .files(root, root)
+        .filter(|path| path.extension().is_some_and(|e| e == "po"))
+        .collect();
+    paths.sort();
+
+    let mut files = 0;
+    for path in paths {
+        let lang = lang_of(root, &path);
+        if !langs.is_empty() && !langs.iter().any(|known| known.trim() == lang) {
+            continue;
+        }
+
+        files += 1;
+        let catalog = PoCatalog::read(&path)?;
+        let entries = catalogs.entry(lang).or_default();
+        for entry in catalog.entries() {
+            if entry.is_header()
+                || entry.is_plural()
+                || entry.has_flag("fuzzy")
+                || entry.msgstr.iter().all(String::is_empty)
+            {
+                continue;
+            }
+            entries.push(Translated {
+                msgctxt: entry.msgctxt.clone(),
+                msgid: entry.msgid.clone(),
+                msgstr: entry.msgstr.join(""),
+                location: format!("{}:{}", path.display(), entry.line),
+            });
+        }
+    }
+
+    let mut found = 0;
+    for (lang, entries) in &catalogs {
+        for (msgid, variants) in conflicts(entries) {
+            let suggestion = match provider {
+                Some(provider) => Some(suggest(provider, lang, msgid, &variants).await?),
+                None => None,
+            };
+            log_inconsistency(lang, msgid, &variants, suggestion.as_deref());
+            found += 1;
+        }
+    }
+
+    log_consistency_summary(files, found);
+    Ok(found)
+}
+
+/// The msgids of `entries` with more than one translation, by msgid
+fn conflicts(entries: &[Translated]) -> Vec<(&str, Vec<Variant>)> {
+    let mut terms: BTreeMap<(&Option<String>, &str), Vec<&Translated>> = BTreeMap::new();
+    for entry in entries {
+        terms
+            .entry((&entry.msgctxt, &entry.msgid))
+            .or_default()
+            .push(entry);
+    }
+
+    let mut conflicts = Vec::new();
+    for ((_, msgid), translations) in &terms {
+        let mut variants: Vec<Variant> = Vec::new();
+        for entry in translations {
+            match variants
+                .iter_mut()
+                .find(|variant| normalize(&variant.translation) == normalize(&entry.msgstr))
+            {
+                Some(variant) => variant.locations.push(entry.location.clone()),
+                None => variants.push(Variant {
+                    translation: entry.msgstr.trim().to_string(),
+                    locations: vec![entry.location.clone()],
+                }),
+            }
+        }
+        if variants.len() < 2 {
+            continue;
+        }
+
+        if let Some(term) = term_pattern(msgid) {
+            for entry in entries {
+                if entry.msgid.len() <= msgid.len() || !term.is_match(&entry.msgid) {
+                    continue;
+                }
+                let msgstr = entry.msgstr.to_lowercase();
+                let used = variants
+                    .iter_mut()
+                    .filter(|variant| msgstr.contains(&normalize(&variant.translation)))
+                    .max_by_key(|variant| variant.translation.len());
+                if let Some(variant) = used {
+                    variant.locations.push(entry.location.clone());
+                }
+            }
+        }
+
+        variants.sort_by_key(|variant| std::cmp::Reverse(variant.locations.len()));
+        conflicts.push((*msgid, variants));
+    }
+    conflicts
+}
+
+/// The translation compared with the others: trimmed, lowercased and without the final
+/// punctuation
+fn normalize(translation: &str) -> String {
+    translation
+        .trim()
+        .trim_end_matches(['.', ':', '!', '?', '…', '。', '：'])
+        .trim()
+        .to_lowercase()
+}
+
+/// Matches the term `msgid` as whole words in another msgid, if it is short enough to be a
+/// term
+fn term_pattern(msgid: &str) -> Option<Regex> {
+    let term = normalize(msgid);
+    let words = term.split_whitespace().count();
+    if words == 0 || words > MAX_TERM_WORDS || !term.chars().any(char::is_alphabetic) {
+        return None;
+    }
+    Regex::new(&format!(r"(?i)\b{}\b", regex::escape(&term))).ok()
+}
+
+/// Asks the model which of the `variants` translating `msgid` to `lang` to use everywhere
+async fn suggest(
+    provider: &dyn TranslationProvider,
+    lang: &str,
+    msgid: &str,
+    variants: &[Variant],
+) -> Result<String> {
+    let language = &iso_to_name(lang);
+    let instructions = format!(
+        "You are a professional translator keeping the terminology of an application consistent in {}.\n\n{}",
+        language, INJECTION_GUARD
+    );
+
+    let mut prompt = format!(
+        "This gettext message is translated to {} in several ways across the application:\n",
+        language
+    );
+    for variant in variants {
+        prompt.push_str(&format!(
+            "- {} ({} times)\n",
+            delimit(&variant.translation),
+            variant.locations.len()
+        ));
+    }
+    prompt.push_str(&format!(
+        "\n
+    Important:
+		- Answer with the translation to use everywhere: one of those, or a better one if none fits.
+		- Just return the translation, do not add any other text or comments.
+
+    Message:\n{}",
+        delimit(msgid)
+    ));
+
+    let request = AiRequest::new(instructions, prompt).with_texts(lang, vec![msgid.to_string()]);
+    let answer = provider.send(&request).await?.text;
+    Ok(sanitize::clean(msgid, &strip_delimiters(answer)))
+}
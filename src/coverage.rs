@@ -0,0 +1,106 @@
+use anyhow::Result;
+use std::collections::BTreeMap;
+use std::path::{Component, Path, PathBuf};
+
+use crate::filter::PathFilter;
+use crate::logger::{log_coverage, log_coverage_below};
+use crate::po::PoCatalog;
+
+/// Entry counts of the catalogs of one language
+#[derive(Default, Clone)]
+pub struct Coverage {
+    pub files: usize,
+    pub translated: usize,
+    pub fuzzy: usize,
+    pub untranslated: usize,
+}
+
+impl Coverage {
+    pub fn total(&self) -> usize {
+        self.translated + self.fuzzy + self.untranslated
+    }
+
+    /// Percentage of entries translated and not fuzzy; 100 for empty catalogs
+    pub fn percent(&self) -> f64 {
+        match self.total() {
+            0 => 100.0,
+            total => self.translated as f64 * 100.0 / total as f64,
+        }
+    }
+
+    fn add(&mut self, other: &Coverage) {
+        self.files += other.files;
+        self.translated += other.translated;
+        self.fuzzy += other.fuzzy;
+        self.untranslated += other.untranslated;
+    }
+}
+
+/// Counts the translated, fuzzy and untranslated entries of every .po file under `root`, per
+/// language folder, without calling any API. Returns false if a language is below
+/// `min_coverage` percent.
+pub fn run(root: PathBuf, min_coverage: Option<f64>) -> Result<bool> {
+    let mut langs: BTreeMap<String, Coverage> = BTreeMap::new();
+
+    for path in PathFilter::default().files(&root, &root) {
+        if !path.extension().map(|e| e == "po").unwrap_or(false) {
+            continue;
+        }
+
+        let coverage = langs.entry(lang_of(&root, &path)).or_default();
+        coverage.files += 1;
+        count_file(&path, coverage)?;
+    }
+
+    let mut total = Coverage::default();
+    for coverage in langs.values() {
+        total.add(coverage);
+    }
+    log_coverage(&langs, &total);
+
+    let Some(min_coverage) = min_coverage else {
+        return Ok(true);
+    };
+
+    let mut passed = true;
+    for (lang, coverage) in &langs {
+        if coverage.percent() < min_coverage {
+            log_coverage_below(lang, coverage.percent(), min_coverage);
+            passed = false;
+        }
+    }
+
+    Ok(passed)
+}
+
+fn count_file(path: &Path, coverage: &mut Coverage) -> Result<()> {
+    let catalog = PoCatalog::read(path)?;
+
+    for entry in catalog.entries().filter(|entry| !entry.is_header()) {
+        if entry.msgstr.is_empty() || entry.msgstr.iter().any(|msgstr| msgstr.is_empty()) {
+            coverage.untranslated += 1;
+        } else if entry.has_flag("fuzzy") {
+            coverage.fuzzy += 1;
+        } else {
+            coverage.translated += 1;
+        }
+    }
+
+    Ok(())
+}
+
+/// The language folder a catalog is in, or its file name for catalogs right under `root`
+/// (e.g. `po/es.po`)
+pub(crate) fn lang_of(root: &Path, path: &Path) -> String {
+    let relative = path.strip_prefix(root).unwrap_or(path);
+    let mut components = relative.components();
+
+    match (components.next(), components.next()) {
+        (Some(Component::Normal(lang)), Some(_)) => lang.to_string_lossy().into_owned(),
+        _ => path
+            .file_stem()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .into_owned(),
+    }
+}
@@ -0,0 +1,134 @@
+//! The `extract` subcommand: builds the .pot template of a project from the gettext calls of
+//! its source files, like xgettext, with the calls `inline` finds. With `sync` and
+//! `translator`, the whole extract, merge and translate loop is done by this tool.
+
+use anyhow::Result;
+use chrono::Local;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::filter::PathFilter;
+use crate::inline;
+use crate::logger::{log_template_extracted, log_unified_diff};
+use crate::po::{PoCatalog, PoEntry};
+use crate::scanner::Scanner;
+
+/// The .pot file written when `--output` isn't given, in the scanned folder
+pub const DEFAULT_OUTPUT: &str = "messages.pot";
+
+/// Settings of an extract run
+pub struct ExtractOptions {
+    /// Extensions of the files scanned, with or without the leading dot
+    pub extensions: Vec<String>,
+    /// Gettext functions whose messages are extracted, as `name` or `name:N`
+    pub functions: Vec<String>,
+    pub filter: PathFilter,
+    /// The .pot file to create or update
+    pub output: PathBuf,
+    pub dry_run: bool,
+}
+
+/// Writes the messages of the gettext calls under `folder` to the .pot file of the options,
+/// with a `#: file:line` reference for every call, in the order they are found. The header of
+/// an existing file is kept, and the file only rewritten if its messages changed. Returns the
+/// number of messages.
+pub fn run(folder: &Path, options: &ExtractOptions) -> Result<usize> {
+    let scanner = Scanner::new(&options.functions)?;
+    let mut paths: Vec<PathBuf> = options
+        .filter
+        .files(folder, folder)
+        .filter(|path| inline::is_processable_file(path, &options.extensions))
+        .collect();
+    paths.sort();
+
+    let mut messages: Vec<(String, Option<String>, Vec<String>)> = Vec::new();
+    let mut index: HashMap<String, usize> = HashMap::new();
+    for path in &paths {
+        let content = fs::read_to_string(path)?;
+        let file = path
+            .strip_prefix(folder)
+            .unwrap_or(path)
+            .to_string_lossy()
+            .replace('\\', "/");
+
+        for call in scanner.calls(path, &content) {
+            let msgid = call.message.value(&content);
+            if msgid.is_empty() {
+                continue;
+            }
+            let line = content[..call.range.start].matches('\n').count() + 1;
+            let reference = format!("{}:{}", file, line);
+            match index.get(&msgid) {
+                Some(&at) => messages[at].2.push(reference),
+                None => {
+                    let plural = call.plural.as_ref().map(|plural| plural.value(&content));
+                    index.insert(msgid.clone(), messages.len());
+                    messages.push((msgid, plural, vec![reference]));
+                }
+            }
+        }
+    }
+
+    let existing = match options.output.is_file() {
+        true => Some(PoCatalog::read(&options.output)?),
+        false => None,
+    };
+    let mut pot = PoCatalog::from_entries(Vec::new());
+    match existing.as_ref().and_then(|pot| pot.header()) {
+        Some(header) => pot.push(header.clone()),
+        None => {
+            pot.set_header_field("Project-Id-Version", "PACKAGE VERSION");
+            pot.set_header_field("POT-Creation-Date", &creation_date());
+            pot.set_header_field("MIME-Version", "1.0");
+            pot.set_header_field("Content-Type", "text/plain; charset=UTF-8");
+            pot.set_header_field("Content-Transfer-Encoding", "8bit");
+        }
+    }
+    for (msgid, plural, references) in &messages {
+        let msgstr = match plural {
+            Some(_) => vec![String::new(); 2],
+            None => Vec::new(),
+        };
+        let mut entry = PoEntry::from_strings(None, msgid.clone(), plural.clone(), msgstr, &[]);
+        entry.comments.push(format!("#: {}", references.join(" ")));
+        pot.push(entry);
+    }
+
+    let original = existing.as_ref().map(PoCatalog::to_content);
+    if original.as_deref() == Some(pot.to_content().as_str()) {
+        log_template_extracted(
+            &options.output.display().to_string(),
+            messages.len(),
+            paths.len(),
+            false,
+            options.dry_run,
+        );
+        return Ok(messages.len());
+    }
+    if existing.is_some() {
+        pot.set_header_field("POT-Creation-Date", &creation_date());
+    }
+
+    log_template_extracted(
+        &options.output.display().to_string(),
+        messages.len(),
+        paths.len(),
+        true,
+        options.dry_run,
+    );
+    match options.dry_run {
+        true => log_unified_diff(
+            &options.output.display().to_string(),
+            original.as_deref().unwrap_or_default(),
+            &pot.to_content(),
+        ),
+        false => pot.write(&options.output)?,
+    }
+    Ok(messages.len())
+}
+
+/// The date of a `POT-Creation-Date` header, like `2024-05-01 12:30+0200`
+fn creation_date() -> String {
+    Local::now().format("%Y-%m-%d %H:%M%z").to_string()
+}
@@ -0,0 +1,88 @@
+use anyhow::Result;
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::atomic;
+use crate::po::PoEntry;
+use crate::stats::EntryFailure;
+
+pub const DEFAULT_FAILURES_FILE: &str = "translation-failures.json";
+
+/// Writes the entries a run left untranslated to the report at `path`, for a later
+/// `--retry-failures`. A run without failures removes the report of a previous one.
+pub fn write_report(path: &Path, failures: &[EntryFailure]) -> Result<()> {
+    if failures.is_empty() {
+        return match path.is_file() {
+            true => Ok(fs::remove_file(path)?),
+            false => Ok(()),
+        };
+    }
+    atomic::write(path, serde_json::to_string_pretty(failures)?)
+}
+
+/// The entries of a failure report, the only ones a `--retry-failures` run translates
+pub struct RetryFilter {
+    failures: Vec<EntryFailure>,
+    /// The catalog, language, msgctxt and msgid of every entry
+    entries: BTreeSet<(PathBuf, String, Option<String>, String)>,
+}
+
+impl RetryFilter {
+    /// Reads the report written by a previous run
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)
+            .map_err(|e| anyhow::anyhow!("Error reading {}: {}", path.display(), e))?;
+        let failures: Vec<EntryFailure> = serde_json::from_str(&content).map_err(|e| {
+            anyhow::anyhow!("Error reading failure report {}: {}", path.display(), e)
+        })?;
+
+        let entries = failures
+            .iter()
+            .filter_map(|failure| {
+                let file = failure.file.as_deref()?;
+                Some((
+                    normalize(file),
+                    failure.lang.clone(),
+                    failure.msgctxt.clone(),
+                    failure.msgid.clone(),
+                ))
+            })
+            .collect();
+        Ok(Self { failures, entries })
+    }
+
+    /// The languages of the failed entries, in the order of the report
+    pub fn langs(&self) -> Vec<String> {
+        let mut langs: Vec<String> = Vec::new();
+        for failure in &self.failures {
+            if !langs.contains(&failure.lang) {
+                langs.push(failure.lang.clone());
+            }
+        }
+        langs
+    }
+
+    /// Returns true if an entry of the catalog at `path` failed in `lang`
+    pub fn includes_file(&self, path: &Path, lang: &str) -> bool {
+        let path = normalize(path);
+        self.entries
+            .iter()
+            .any(|(file, failed_lang, _, _)| *file == path && failed_lang == lang)
+    }
+
+    /// Returns true if `entry` of the catalog at `path` failed in `lang`
+    pub fn includes(&self, path: &Path, lang: &str, entry: &PoEntry) -> bool {
+        self.entries.contains(&(
+            normalize(path),
+            lang.to_string(),
+            entry.msgctxt.clone(),
+            entry.msgid.clone(),
+        ))
+    }
+}
+
+/// The same path for `./locales/es/app.po` and `locales/es/app.po`
+fn normalize(path: &Path) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
@@ -0,0 +1,166 @@
+use anyhow::Result;
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use regex::Regex;
+use std::fs;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+use crate::attribution;
+use crate::po::PoEntry;
+
+/// Folders never worth scanning: dependencies, build output and version control
+const SKIPPED_DIRS: &[&str] = &["node_modules", ".git", "target"];
+
+/// Decides which files a traversal visits, from `--include` and `--exclude` globs matched
+/// against paths relative to the scanned folder. With no includes every file is a candidate;
+/// excludes win over includes, and also prune whole folders.
+#[derive(Default)]
+pub struct PathFilter {
+    include: Option<GlobSet>,
+    exclude: Option<GlobSet>,
+}
+
+impl PathFilter {
+    pub fn new(include: &[String], exclude: &[String]) -> Result<Self> {
+        Ok(Self {
+            include: build_set(include)?,
+            exclude: build_set(exclude)?,
+        })
+    }
+
+    /// The files under `dir` that pass the filter, with patterns relative to `root`
+    pub fn files<'a>(&'a self, root: &'a Path, dir: &Path) -> impl Iterator<Item = PathBuf> + 'a {
+        WalkDir::new(dir)
+            .into_iter()
+            .filter_entry(move |entry| {
+                if entry.depth() == 0 || !entry.file_type().is_dir() {
+                    return true;
+                }
+                let skipped = entry
+                    .file_name()
+                    .to_str()
+                    .is_some_and(|name| SKIPPED_DIRS.contains(&name));
+                !skipped && !self.is_excluded(root, entry.path())
+            })
+            .filter_map(|e| e.ok())
+            .filter(|entry| entry.file_type().is_file())
+            .map(|entry| entry.into_path())
+            .filter(move |path| self.includes(root, path))
+    }
+
+    /// Returns true if the file at `path` passes the filter, with patterns relative to `root`
+    pub fn includes(&self, root: &Path, path: &Path) -> bool {
+        let relative = path.strip_prefix(root).unwrap_or(path);
+        let included = self
+            .include
+            .as_ref()
+            .is_none_or(|include| include.is_match(relative));
+
+        included && !self.is_excluded(root, path)
+    }
+
+    fn is_excluded(&self, root: &Path, path: &Path) -> bool {
+        let relative = path.strip_prefix(root).unwrap_or(path);
+        self.exclude
+            .as_ref()
+            .is_some_and(|exclude| exclude.is_match(relative))
+    }
+}
+
+/// The catalogs and entries a run is restricted to, from `--file`, `--only-msgid`, `--match`
+/// and `--only-ai`, to fix a few strings without translating, or with `--force` re-translating,
+/// everything else. An entry must pass every filter given.
+#[derive(Default)]
+pub struct Subset {
+    files: Vec<PathBuf>,
+    msgids: Vec<String>,
+    pattern: Option<Regex>,
+    /// Only the entries with the comment of `--annotate`
+    only_ai: bool,
+}
+
+impl Subset {
+    /// `files` are relative to the working directory, or else to `root`
+    pub fn new(
+        root: &Path,
+        files: &[PathBuf],
+        msgids: &[String],
+        pattern: Option<&str>,
+        only_ai: bool,
+    ) -> Result<Self> {
+        let files = files
+            .iter()
+            .map(|file| {
+                let path = match file.exists() {
+                    true => file.clone(),
+                    false => root.join(file),
+                };
+                fs::canonicalize(&path)
+                    .map_err(|e| anyhow::anyhow!("Error reading {}: {}", file.display(), e))
+            })
+            .collect::<Result<_>>()?;
+        let pattern = pattern
+            .map(|pattern| {
+                Regex::new(pattern)
+                    .map_err(|e| anyhow::anyhow!("Invalid --match pattern {}: {}", pattern, e))
+            })
+            .transpose()?;
+        Ok(Self {
+            files,
+            msgids: msgids.to_vec(),
+            pattern,
+            only_ai,
+        })
+    }
+
+    /// The languages of the `--file`s: the folders of `root` they are in
+    pub fn langs(&self, root: &Path) -> Vec<String> {
+        let root = fs::canonicalize(root).unwrap_or_else(|_| root.to_path_buf());
+        let mut langs: Vec<String> = Vec::new();
+        for file in &self.files {
+            let lang = file
+                .strip_prefix(&root)
+                .ok()
+                .and_then(|relative| relative.components().next())
+                .and_then(|lang| lang.as_os_str().to_str());
+            if let Some(lang) = lang
+                && !langs.iter().any(|known| known == lang)
+            {
+                langs.push(lang.to_string());
+            }
+        }
+        langs
+    }
+
+    /// Returns true if the catalog at `path` is one of the `--file`s, or none were given
+    pub fn includes_file(&self, path: &Path) -> bool {
+        self.files.is_empty() || fs::canonicalize(path).is_ok_and(|path| self.files.contains(&path))
+    }
+
+    /// Returns true if `entry` has one of the msgids of `--only-msgid`, matches `--match` (its
+    /// msgid or msgid_plural) and, with `--only-ai`, was translated by a model
+    pub fn includes(&self, entry: &PoEntry) -> bool {
+        let msgids = || std::iter::once(&entry.msgid).chain(entry.msgid_plural.as_ref());
+        let listed = self.msgids.is_empty() || self.msgids.contains(&entry.msgid);
+        let matched = self
+            .pattern
+            .as_ref()
+            .is_none_or(|pattern| msgids().any(|msgid| pattern.is_match(msgid)));
+        listed && matched && (!self.only_ai || attribution::is_ai_translated(entry))
+    }
+}
+
+fn build_set(patterns: &[String]) -> Result<Option<GlobSet>> {
+    if patterns.is_empty() {
+        return Ok(None);
+    }
+
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        let glob = Glob::new(pattern)
+            .map_err(|e| anyhow::anyhow!("Invalid glob pattern {}: {}", pattern, e))?;
+        builder.add(glob);
+    }
+
+    Ok(Some(builder.build()?))
+}
@@ -0,0 +1,722 @@
+use anyhow::Result;
+use regex::Regex;
+use std::fs;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+
+use super::xml::{attribute, unescape};
+use super::{Plurals, Target, Unit, files, save, start_file, translate_units};
+use crate::filter::PathFilter;
+use crate::translator::Translator;
+
+static STRING: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?s)<string(\s[^>]*?)(?:/>|>(.*?)</string>)").unwrap());
+static PLURALS: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?s)<plurals(\s[^>]*)>(.*?)</plurals>").unwrap());
+static ITEM: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?s)<item(\s[^>]*?)(?:/>|>(.*?)</item>)").unwrap());
+static COMMENT: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?s)<!--(.*?)-->").unwrap());
+static CDATA: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?s)^\s*<!\[CDATA\[(.*)\]\]>\s*$").unwrap());
+static RESOURCES: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"<resources\b[^>]*>").unwrap());
+static TAG: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"<[^>]*>").unwrap());
+/// The language qualifier of a `values-<qualifier>` folder: `es`, `pt-rBR` or `b+sr+Latn`
+static LANGUAGE_QUALIFIER: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^(?:[a-z]{2,3}(?:-r(?:[A-Z]{2}|\d{3}))?|b\+[a-z]{2,3}(?:\+[A-Za-z0-9]+)*)$")
+        .unwrap()
+});
+
+/// Qualifiers of `values-*` folders that look like language codes but aren't
+const NOT_LANGUAGES: &[&str] = &["car", "hdr"];
+
+/// A `<string>` or `<plurals>` element of a resources file
+struct Resource {
+    name: String,
+    /// Byte range of the element in the file
+    range: Range<usize>,
+    /// The opening tag, like `<string name="title" formatted="false">`
+    tag: String,
+    /// The content of a `<string>`, or the `quantity` and content of each `<item>` of a
+    /// `<plurals>`
+    kind: Kind,
+    /// The `<!-- comment -->` right before the element
+    comment: Option<String>,
+}
+
+enum Kind {
+    String(String),
+    Plurals(Vec<(String, String)>),
+}
+
+/// The content of a string as sent to the model, and how to write its translation back
+#[derive(Default)]
+struct Text {
+    text: String,
+    cdata: bool,
+    /// The string is written in double quotes, which keep its spaces
+    quoted: bool,
+    /// The string holds tags like `<b>` or `<xliff:g>`, so it is sent, and its translation
+    /// written, as XML
+    markup: bool,
+}
+
+/// Every `values/strings.xml` under `root`, paired with the `values-<lang>/strings.xml` of
+/// each target language next to it. Languages default to the `values-*` folders found whose
+/// qualifier is a language, like `values-es` or `values-pt-rBR`.
+pub fn targets(
+    root: &Path,
+    source_lang: &str,
+    langs: Option<&[String]>,
+    filter: &PathFilter,
+) -> Result<Vec<Target>> {
+    let sources: Vec<PathBuf> = files(root, &["xml"], filter)
+        .into_iter()
+        .filter(|path| {
+            path.file_name().is_some_and(|name| name == "strings.xml")
+                && path
+                    .parent()
+                    .and_then(Path::file_name)
+                    .is_some_and(|name| name == "values")
+        })
+        .collect();
+
+    if sources.is_empty() {
+        anyhow::bail!("{}: found no values/strings.xml file", root.display());
+    }
+
+    let mut found = Vec::new();
+    for source in &sources {
+        let Some(res) = source.parent().and_then(Path::parent) else {
+            continue;
+        };
+        for entry in fs::read_dir(res)?.filter_map(|e| e.ok()) {
+            let name = entry.file_name();
+            let Some(qualifier) = name.to_str().and_then(|name| name.strip_prefix("values-"))
+            else {
+                continue;
+            };
+            if LANGUAGE_QUALIFIER.is_match(qualifier) && !NOT_LANGUAGES.contains(&qualifier) {
+                let lang = language(qualifier);
+                if !found.contains(&lang) {
+                    found.push(lang);
+                }
+            }
+        }
+    }
+
+    let mut langs: Vec<String> = match langs {
+        Some(langs) => langs.to_vec(),
+        None => found,
+    };
+    langs.retain(|lang| lang != source_lang);
+    langs.sort();
+
+    let mut targets = Vec::new();
+    for lang in &langs {
+        for source in &sources {
+            let res = source.parent().and_then(Path::parent).unwrap_or(root);
+            targets.push(Target {
+                source: source.clone(),
+                source_lang: source_lang.to_string(),
+                path: res
+                    .join(format!("values-{}", qualifier(lang)))
+                    .join("strings.xml"),
+                lang: lang.clone(),
+            });
+        }
+    }
+    Ok(targets)
+}
+
+/// Adds the `<string>` and `<plurals>` of the source file missing from the target, translated,
+/// before its `</resources>`. Strings marked `translatable="false"` are left out, and the rest
+/// of the target is left as written. Returns the number of strings translated.
+pub async fn translate_file(translator: &Translator, target: &Target) -> Result<usize> {
+    let source = fs::read_to_string(&target.source)
+        .map_err(|e| anyhow::anyhow!("Error reading {}: {}", target.source.display(), e))?;
+    let original = match target.path.exists() {
+        true => fs::read_to_string(&target.path)
+            .map_err(|e| anyhow::anyhow!("Error reading {}: {}", target.path.display(), e))?,
+        false => String::new(),
+    };
+    let lang = &target.lang;
+    start_file(translator, &target.path, lang);
+
+    let resources: Vec<Resource> = parse(&source)
+        .into_iter()
+        .filter(|resource| {
+            attribute(&resource.tag, "translatable").as_deref() != Some("false")
+                && match &resource.kind {
+                    Kind::String(text) => !text.trim().is_empty(),
+                    Kind::Plurals(items) => !items.is_empty(),
+                }
+        })
+        .collect();
+    let existing = parse(&original);
+    let plurals = Plurals::for_language(lang);
+
+    let units: Vec<Unit> = resources
+        .iter()
+        .map(|resource| {
+            unit(
+                resource,
+                existing.iter().find(|e| e.name == resource.name),
+                &plurals,
+            )
+        })
+        .collect();
+    let translated = translate_units(translator, &target.path, &units, lang).await?;
+    let changes = translated.iter().filter(|t| t.is_some()).count();
+
+    let indent = match existing.is_empty() {
+        true => indentation(&source),
+        false => indentation(&original),
+    };
+    let mut modified = match original.trim().is_empty() {
+        // With the namespaces the source declares, like `xliff` for `<xliff:g>`
+        true => format!(
+            "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n{}\n</resources>\n",
+            RESOURCES
+                .find(&source)
+                .map_or("<resources>", |tag| tag.as_str())
+        ),
+        false => original.clone(),
+    };
+
+    // Strings the target has are replaced where they are, from the end so that the ranges of
+    // the others stay valid; new ones are added at the end in the order of the source
+    let mut replaced: Vec<(Range<usize>, String)> = Vec::new();
+    let mut added = String::new();
+    for (index, resource) in resources.iter().enumerate() {
+        let Some(translated) = &translated[index] else {
+            continue;
+        };
+        match existing.iter().find(|e| e.name == resource.name) {
+            Some(current) => {
+                let line_start = original[..current.range.start]
+                    .rfind('\n')
+                    .map_or(0, |i| i + 1);
+                let current_indent = &original[line_start..current.range.start];
+                let current_indent = match current_indent.trim().is_empty() {
+                    true => current_indent,
+                    false => indent.as_str(),
+                };
+                replaced.push((
+                    current.range.clone(),
+                    element(
+                        resource,
+                        &translated.target,
+                        &plurals,
+                        current_indent,
+                        &indent,
+                    ),
+                ));
+            }
+            None => {
+                if let Some(comment) = &resource.comment {
+                    added.push_str(&format!("{}<!-- {} -->\n", indent, comment));
+                }
+                added.push_str(&format!(
+                    "{}{}\n",
+                    indent,
+                    element(resource, &translated.target, &plurals, &indent, &indent)
+                ));
+            }
+        }
+    }
+
+    replaced.sort_by_key(|(range, _)| std::cmp::Reverse(range.start));
+    for (range, text) in replaced {
+        modified.replace_range(range, &text);
+    }
+    if !added.is_empty() {
+        let Some(end) = modified.rfind("</resources>") else {
+            anyhow::bail!(
+                "{}: no </resources> to add the strings to",
+                target.path.display()
+            );
+        };
+        let line_start = modified[..end].rfind('\n').map_or(0, |i| i + 1);
+        let at = match modified[line_start..end].trim().is_empty() {
+            true => line_start,
+            false => {
+                added.insert(0, '\n');
+                end
+            }
+        };
+        modified.insert_str(at, &added);
+    }
+
+    save(
+        &target.path,
+        lang,
+        &original,
+        &modified,
+        changes,
+        translator.options(),
+    )?;
+    Ok(changes)
+}
+
+fn unit(resource: &Resource, current: Option<&Resource>, plurals: &Plurals) -> Unit {
+    let (source, source_plural, target, markup) = match &resource.kind {
+        Kind::String(raw) => {
+            let text = decode(raw);
+            let target = match current.map(|c| &c.kind) {
+                Some(Kind::String(raw)) => Some(decode(raw).text).filter(|t| !t.is_empty()),
+                _ => None,
+            };
+            (text.text, None, target.into_iter().collect(), text.markup)
+        }
+        Kind::Plurals(items) => {
+            let form = |items: &[(String, String)], quantity: &str| {
+                items
+                    .iter()
+                    .find(|(name, _)| name == quantity)
+                    .map(|(_, raw)| decode(raw))
+            };
+            let other = form(items, "other").or_else(|| items.last().map(|(_, raw)| decode(raw)));
+            let other = other.unwrap_or(Text::default());
+            let one = form(items, "one").map_or_else(|| other.text.clone(), |one| one.text);
+
+            let target = match current.map(|c| &c.kind) {
+                Some(Kind::Plurals(current)) => plurals
+                    .forms()
+                    .iter()
+                    .map(|category| form(current, category).map(|t| t.text).unwrap_or_default())
+                    .collect(),
+                _ => Vec::new(),
+            };
+            let target = match target.iter().all(String::is_empty) {
+                true => Vec::new(),
+                false => target,
+            };
+            (one, Some(other.text), target, other.markup)
+        }
+    };
+
+    let mut notes: Vec<String> = resource.comment.iter().cloned().collect();
+    if markup {
+        notes.push(
+            "The string is XML: keep its tags, like `<b>` or `<xliff:g>`, and their attributes as they are, and translate only the text."
+                .to_string(),
+        );
+    }
+    Unit {
+        context: Some(resource.name.clone()),
+        source,
+        source_plural,
+        target,
+        notes,
+        needs_review: false,
+    }
+}
+
+/// The element of `resource` with its translation: the forms of the target language for a
+/// `<plurals>`, each item one `step` deeper than `indent`
+fn element(
+    resource: &Resource,
+    forms: &[String],
+    plurals: &Plurals,
+    indent: &str,
+    step: &str,
+) -> String {
+    match &resource.kind {
+        Kind::String(raw) => {
+            let text = forms.first().cloned().unwrap_or_default();
+            format!(
+                "{}{}</string>",
+                open_tag(&resource.tag),
+                encode(&text, &decode(raw))
+            )
+        }
+        Kind::Plurals(items) => {
+            let format = items
+                .iter()
+                .find(|(quantity, _)| quantity == "other")
+                .or(items.last())
+                .map(|(_, raw)| decode(raw));
+            let format = format.unwrap_or(Text::default());
+
+            let mut element = resource.tag.clone();
+            element.push('\n');
+            for (position, category) in plurals.categories.iter().enumerate() {
+                let form = forms.get(position).or(forms.last()).cloned();
+                element.push_str(&format!(
+                    "{}{}<item quantity=\"{}\">{}</item>\n",
+                    indent,
+                    step,
+                    category,
+                    encode(&form.unwrap_or_default(), &format)
+                ));
+            }
+            element.push_str(&format!("{}</plurals>", indent));
+            element
+        }
+    }
+}
+
+/// The opening tag of a `<string>`, also when the source has it empty like `<string name="x"/>`
+fn open_tag(tag: &str) -> String {
+    match tag.strip_suffix("/>") {
+        Some(head) => format!("{}>", head.trim_end()),
+        None => tag.to_string(),
+    }
+}
+
+/// The `<string>` and `<plurals>` elements of a resources file, outside comments
+fn parse(content: &str) -> Vec<Resource> {
+    let comments: Vec<Range<usize>> = COMMENT.find_iter(content).map(|m| m.range()).collect();
+    let commented = |position: usize| comments.iter().any(|range| range.contains(&position));
+
+    let mut resources = Vec::new();
+    for captures in STRING.captures_iter(content) {
+        let whole = captures.get(0).unwrap();
+        if commented(whole.start()) {
+            continue;
+        }
+        let tag = format!("<string{}>", &captures[1]);
+        let Some(name) = attribute(&tag, "name") else {
+            continue;
+        };
+        let kind = Kind::String(captures.get(2).map_or("", |m| m.as_str()).to_string());
+        resources.push(resource(content, name, whole.range(), tag, kind));
+    }
+
+    for captures in PLURALS.captures_iter(content) {
+        let whole = captures.get(0).unwrap();
+        if commented(whole.start()) {
+            continue;
+        }
+        let tag = format!("<plurals{}>", &captures[1]);
+        let Some(name) = attribute(&tag, "name") else {
+            continue;
+        };
+        let items = ITEM
+            .captures_iter(&captures[2])
+            .filter_map(|item| {
+                let quantity = attribute(&format!("<item{}>", &item[1]), "quantity")?;
+                Some((quantity, item.get(2).map_or("", |m| m.as_str()).to_string()))
+            })
+            .collect();
+        resources.push(resource(
+            content,
+            name,
+            whole.range(),
+            tag,
+            Kind::Plurals(items),
+        ));
+    }
+
+    resources.sort_by_key(|resource| resource.range.start);
+    resources
+}
+
+fn resource(content: &str, name: String, range: Range<usize>, tag: String, kind: Kind) -> Resource {
+    let before = content[..range.start].trim_end();
+    let comment = before
+        .strip_suffix("-->")
+        .and_then(|before| before.rfind("<!--").map(|start| &before[start + 4..]))
+        .map(|comment| comment.trim().to_string())
+        // Commented-out elements aren't notes
+        .filter(|comment| !comment.is_empty() && !TAG.is_match(comment));
+    Resource {
+        name,
+        range,
+        tag,
+        kind,
+        comment,
+    }
+}
+
+/// The indentation of the elements of a resources file, four spaces if it has none
+fn indentation(content: &str) -> String {
+    content
+        .lines()
+        .find(|line| {
+            let line = line.trim_start();
+            line.starts_with("<string") || line.starts_with("<plurals")
+        })
+        .map(|line| line[..line.len() - line.trim_start().len()].to_string())
+        .filter(|indent| !indent.is_empty())
+        .unwrap_or_else(|| "    ".to_string())
+}
+
+/// The text of a string resource as Android shows it: without its CDATA section, XML
+/// entities (unless it holds tags), double quotes around it and Android escapes like `\'` and
+/// `\n`
+fn decode(raw: &str) -> Text {
+    let (text, cdata, markup) = match CDATA.captures(raw) {
+        Some(captures) => (captures[1].to_string(), true, captures[1].contains('<')),
+        None if TAG.is_match(raw) => (raw.to_string(), false, true),
+        None => (unescape(raw), false, false),
+    };
+
+    let quoted = text.len() >= 2
+        && text.starts_with('"')
+        && text.ends_with('"')
+        && !text[..text.len() - 1].ends_with('\\');
+    let text = match quoted {
+        true => &text[1..text.len() - 1],
+        false => &text,
+    };
+    Text {
+        text: android_unescape(text),
+        cdata,
+        quoted,
+        markup,
+    }
+}
+
+/// Writes a translation the way the source string is written: in a CDATA section, as XML, or
+/// as text
+fn encode(text: &str, format: &Text) -> String {
+    let escape = |text: &str| match format.quoted {
+        true => format!("\"{}\"", android_escape(text)),
+        false => android_escape(text),
+    };
+    if format.cdata {
+        return format!("<![CDATA[{}]]>", escape(text).replace("]]>", "]]&gt;"));
+    }
+    if format.markup {
+        // Only the text between tags is escaped, not the quotes of their attributes
+        let mut encoded = String::new();
+        let mut last = 0;
+        for tag in TAG.find_iter(text) {
+            encoded.push_str(&android_escape(&text[last..tag.start()]));
+            encoded.push_str(tag.as_str());
+            last = tag.end();
+        }
+        encoded.push_str(&android_escape(&text[last..]));
+        return match format.quoted {
+            true => format!("\"{}\"", encoded),
+            false => encoded,
+        };
+    }
+    escape(text)
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// Decodes the escapes of Android string resources: `\'`, `\"`, `\n`, `\t`, `\uXXXX`, `\@`,
+/// `\?` and `\\`
+fn android_unescape(text: &str) -> String {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => unescaped.push('\n'),
+            Some('t') => unescaped.push('\t'),
+            Some('u') => {
+                let code: String = chars.by_ref().take(4).collect();
+                if let Some(c) = u32::from_str_radix(&code, 16).ok().and_then(char::from_u32) {
+                    unescaped.push(c);
+                }
+            }
+            Some(c) => unescaped.push(c),
+            None => {}
+        }
+    }
+    unescaped
+}
+
+/// Escapes the characters Android gives a meaning in string resources: apostrophes and
+/// quotes, backslashes, newlines and tabs, and a leading `@` or `?`, which would make the
+/// string a reference
+fn android_escape(text: &str) -> String {
+    let escaped = text
+        .replace('\\', "\\\\")
+        .replace('\'', "\\'")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+        .replace('\t', "\\t");
+    match escaped.starts_with(['@', '?']) {
+        true => format!("\\{}", escaped),
+        false => escaped,
+    }
+}
+
+/// The language code of a `values-*` qualifier: `pt-BR` for `pt-rBR`, `sr-Latn` for
+/// `b+sr+Latn`
+fn language(qualifier: &str) -> String {
+    match qualifier.strip_prefix("b+") {
+        Some(tag) => tag.replace('+', "-"),
+        None => qualifier.replacen("-r", "-", 1),
+    }
+}
+
+/// The `values-*` qualifier of a language code: `pt-rBR` for `pt-BR` or `pt_BR`, `b+sr+Latn`
+/// for `sr-Latn`
+fn qualifier(lang: &str) -> String {
+    let parts: Vec<&str> = lang.split(['-', '_']).collect();
+    match parts.as_slice() {
+        [lang] => lang.to_string(),
+        [lang, region]
+            if region.len() == 2 && region.chars().all(|c| c.is_ascii_alphabetic())
+                || region.len() == 3 && region.chars().all(|c| c.is_ascii_digit()) =>
+        {
+            format!("{}-r{}", lang, region.to_uppercase())
+        }
+        parts => format!("b+{}", parts.join("+")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::provider::{Pseudo, pseudolocalize};
+    use crate::translator::TranslateOptions;
+    use std::sync::Arc;
+
+    const STRINGS: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<resources>
+    <string name="app_name" translatable="false">Notes</string>
+    <!-- The save button -->
+    <string name="save">Save</string>
+    <!-- <string name="old">Old</string> -->
+    <string name="hello">Hello</string>
+    <plurals name="notes">
+        <item quantity="one">%d note</item>
+        <item quantity="other">%d notes</item>
+    </plurals>
+    <string name="empty"/>
+</resources>
+"#;
+
+    fn temp_dir(test: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "ai-gettext-translator-android-{}-{}",
+            test,
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("values")).unwrap();
+        fs::create_dir_all(dir.join("values-es")).unwrap();
+        dir
+    }
+
+    #[test]
+    fn parses_resources_outside_comments() {
+        let resources = parse(STRINGS);
+        let names: Vec<&str> = resources.iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(names, ["app_name", "save", "hello", "notes", "empty"]);
+        assert_eq!(
+            attribute(&resources[0].tag, "translatable").as_deref(),
+            Some("false")
+        );
+        assert_eq!(resources[1].comment.as_deref(), Some("The save button"));
+        // A commented-out element isn't the note of the next one
+        assert_eq!(resources[2].comment, None);
+        assert!(matches!(&resources[2].kind, Kind::String(text) if text == "Hello"));
+        let Kind::Plurals(items) = &resources[3].kind else {
+            panic!("notes isn't a <plurals>");
+        };
+        assert_eq!(
+            items,
+            &[
+                ("one".to_string(), "%d note".to_string()),
+                ("other".to_string(), "%d notes".to_string())
+            ]
+        );
+        assert!(matches!(&resources[4].kind, Kind::String(text) if text.is_empty()));
+        assert_eq!(open_tag(&resources[4].tag), "<string name=\"empty\">");
+        assert_eq!(indentation(STRINGS), "    ");
+    }
+
+    #[test]
+    fn decodes_and_encodes_android_strings() {
+        let text = decode(r"Don\'t &amp; é\n");
+        assert_eq!(text.text, "Don't & é\n");
+        assert!(!text.cdata && !text.quoted && !text.markup);
+        assert_eq!(
+            encode("Tom & Jerry's <3", &text),
+            r"Tom &amp; Jerry\'s &lt;3"
+        );
+
+        let quoted = decode("\"  spaced  \"");
+        assert_eq!(quoted.text, "  spaced  ");
+        assert!(quoted.quoted);
+        assert_eq!(encode(" a \"b\" ", &quoted), r#"" a \"b\" ""#);
+
+        let cdata = decode("<![CDATA[<b>Bold</b> text]]>");
+        assert_eq!(cdata.text, "<b>Bold</b> text");
+        assert!(cdata.cdata && cdata.markup);
+        assert_eq!(encode("<b>x</b>]]>", &cdata), "<![CDATA[<b>x</b>]]&gt;]]>");
+
+        let markup = decode(r#"Hi <xliff:g id="name">%s</xliff:g>'s"#);
+        assert!(markup.markup && !markup.cdata);
+        assert_eq!(
+            encode(r#"Hola <xliff:g id="name">%s</xliff:g>'s"#, &markup),
+            r#"Hola <xliff:g id="name">%s</xliff:g>\'s"#
+        );
+
+        assert_eq!(android_escape("@string/x"), r"\@string/x");
+        assert_eq!(android_escape("?attr\tand\\"), r"\?attr\tand\\");
+        assert_eq!(android_unescape(r"\@a\?b\\c"), r"@a?b\c");
+    }
+
+    #[test]
+    fn maps_language_codes_and_qualifiers() {
+        assert_eq!(language("es"), "es");
+        assert_eq!(language("pt-rBR"), "pt-BR");
+        assert_eq!(language("b+sr+Latn"), "sr-Latn");
+        assert_eq!(qualifier("es"), "es");
+        assert_eq!(qualifier("pt_BR"), "pt-rBR");
+        assert_eq!(qualifier("es-419"), "es-r419");
+        assert_eq!(qualifier("sr-Latn"), "b+sr+Latn");
+        for qualifier in ["es", "pt-rBR", "b+sr+Latn"] {
+            assert!(LANGUAGE_QUALIFIER.is_match(qualifier), "{}", qualifier);
+        }
+        assert!(!LANGUAGE_QUALIFIER.is_match("land"));
+    }
+
+    #[tokio::test]
+    async fn adds_missing_strings_before_the_end_of_resources() {
+        let dir = temp_dir("fill");
+        fs::write(dir.join("values/strings.xml"), STRINGS).unwrap();
+        fs::write(
+            dir.join("values-es/strings.xml"),
+            "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<resources>\n    <string name=\"hello\">Hola</string>\n</resources>\n",
+        )
+        .unwrap();
+
+        let targets = targets(&dir, "en", None, &PathFilter::default()).unwrap();
+        assert_eq!(targets.len(), 1);
+        assert_eq!(targets[0].lang, "es");
+        let translator = Translator::new(Arc::new(Pseudo), TranslateOptions::default()).unwrap();
+        assert_eq!(translate_file(&translator, &targets[0]).await.unwrap(), 2);
+
+        let translated = fs::read_to_string(&targets[0].path).unwrap();
+        let save = format!(
+            "    <string name=\"hello\">Hola</string>\n    <!-- The save button -->\n    <string name=\"save\">{}</string>\n    <plurals name=\"notes\">\n",
+            encode(&pseudolocalize("Save"), &Text::default())
+        );
+        assert!(translated.contains(&save), "{}", translated);
+        assert!(!translated.contains("app_name"), "{}", translated);
+        assert!(!translated.contains("empty"), "{}", translated);
+        assert!(
+            translated.ends_with("    </plurals>\n</resources>\n"),
+            "{}",
+            translated
+        );
+        let notes = parse(&translated).pop().unwrap();
+        let Kind::Plurals(items) = notes.kind else {
+            panic!("notes isn't a <plurals>");
+        };
+        assert!(items.iter().any(|(quantity, _)| quantity == "other"));
+        assert!(
+            items
+                .iter()
+                .all(|(_, text)| text.contains("%d") && text.starts_with("[!!!")),
+            "{:?}",
+            items
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,761 @@
+use anyhow::Result;
+use encoding_rs::{UTF_16BE, UTF_16LE};
+use regex::Regex;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+
+use super::xml::{escape, unescape};
+use super::{Plurals, Target, Unit, files, save, start_file, translate_units};
+use crate::filter::PathFilter;
+use crate::translator::Translator;
+
+/// Extensions of the files `apple` translates in an `.lproj` folder
+pub const EXTENSIONS: &[&str] = &["strings", "stringsdict"];
+
+static PLIST_TOKEN: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?s)<\?.*?\?>|<!DOCTYPE[^>]*>|<!--.*?-->|<(/?)(\w+)[^>]*?(/?)>").unwrap()
+});
+static FORMAT_VARIABLE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"%#@\w+@").unwrap());
+
+/// Comments Xcode writes for strings without one, which tell the model nothing
+const NO_COMMENT: &str = "No comment provided by engineer.";
+
+/// A `"key" = "value";` line of a .strings file, with the comment before it
+struct Entry {
+    comment: Option<String>,
+    key: String,
+    value: String,
+}
+
+/// A value of a .stringsdict property list
+enum Plist {
+    Dict(Vec<(String, Plist)>),
+    String(String),
+    /// Integers, booleans, arrays... copied as written
+    Other(String),
+}
+
+/// The .strings and .stringsdict files of every `<source_lang>.lproj` folder under `root`,
+/// each paired with the same file in the `<lang>.lproj` folder next to it. Languages default to
+/// the other `.lproj` folders found, except `Base.lproj`.
+pub fn targets(
+    root: &Path,
+    source_lang: &str,
+    langs: Option<&[String]>,
+    filter: &PathFilter,
+) -> Result<Vec<Target>> {
+    let folder = format!("{}.lproj", source_lang);
+    let sources: Vec<PathBuf> = files(root, EXTENSIONS, filter)
+        .into_iter()
+        .filter(|path| {
+            path.parent()
+                .and_then(Path::file_name)
+                .is_some_and(|name| name == folder.as_str())
+        })
+        .collect();
+
+    if sources.is_empty() {
+        anyhow::bail!(
+            "{}: found no .strings or .stringsdict file in a {} folder",
+            root.display(),
+            folder
+        );
+    }
+
+    let mut found = Vec::new();
+    for source in &sources {
+        let Some(projects) = source.parent().and_then(Path::parent) else {
+            continue;
+        };
+        for entry in fs::read_dir(projects)?.filter_map(|e| e.ok()) {
+            let name = entry.file_name();
+            let Some(lang) = name.to_str().and_then(|name| name.strip_suffix(".lproj")) else {
+                continue;
+            };
+            if lang != "Base" && !found.iter().any(|found| found == lang) {
+                found.push(lang.to_string());
+            }
+        }
+    }
+
+    let mut langs: Vec<String> = match langs {
+        Some(langs) => langs.to_vec(),
+        None => found,
+    };
+    langs.retain(|lang| lang != source_lang);
+    langs.sort();
+
+    let mut targets = Vec::new();
+    for lang in &langs {
+        for source in &sources {
+            let projects = source.parent().and_then(Path::parent).unwrap_or(root);
+            targets.push(Target {
+                source: source.clone(),
+                source_lang: source_lang.to_string(),
+                path: projects
+                    .join(format!("{}.lproj", lang))
+                    .join(source.file_name().unwrap_or_default()),
+                lang: lang.clone(),
+            });
+        }
+    }
+    Ok(targets)
+}
+
+/// Writes the target file with the strings of the source file, in its order with its
+/// comments: the translations the target has, and new ones for the rest. Returns the number of
+/// strings translated.
+pub async fn translate_file(translator: &Translator, target: &Target) -> Result<usize> {
+    let source = read(&target.source)?;
+    let original = match target.path.exists() {
+        true => read(&target.path)?,
+        false => String::new(),
+    };
+    start_file(translator, &target.path, &target.lang);
+
+    let (modified, changes) = match target
+        .source
+        .extension()
+        .is_some_and(|e| e == "stringsdict")
+    {
+        true => translate_stringsdict(translator, target, &source, &original).await?,
+        false => translate_strings(translator, target, &source, &original).await?,
+    };
+
+    save(
+        &target.path,
+        &target.lang,
+        &original,
+        &modified,
+        changes,
+        translator.options(),
+    )?;
+    Ok(changes)
+}
+
+async fn translate_strings(
+    translator: &Translator,
+    target: &Target,
+    source: &str,
+    original: &str,
+) -> Result<(String, usize)> {
+    let lang = &target.lang;
+    let entries = parse_strings(source)?;
+    let existing = parse_strings(original)?;
+    let current = |key: &str| {
+        existing
+            .iter()
+            .find(|entry| entry.key == key)
+            .map(|entry| entry.value.clone())
+            .filter(|value| !value.is_empty())
+    };
+
+    let units: Vec<Unit> = entries
+        .iter()
+        .map(|entry| Unit {
+            // Keys are often the English text itself, which tells nothing more
+            context: Some(entry.key.clone()).filter(|key| *key != entry.value),
+            source: entry.value.clone(),
+            source_plural: None,
+            target: current(&entry.key).into_iter().collect(),
+            notes: entry
+                .comment
+                .iter()
+                .filter(|comment| comment.as_str() != NO_COMMENT)
+                .cloned()
+                .collect(),
+            needs_review: false,
+        })
+        .collect();
+    let translated = translate_units(translator, &target.path, &units, lang).await?;
+    let changes = translated.iter().filter(|t| t.is_some()).count();
+
+    let mut content = String::new();
+    for (index, entry) in entries.iter().enumerate() {
+        let value = match &translated[index] {
+            Some(translated) => translated.target.first(),
+            None => units[index].target.first(),
+        };
+        let Some(value) = value else {
+            continue;
+        };
+        if let Some(comment) = &entry.comment {
+            content.push_str(&format!("/* {} */\n", comment));
+        }
+        content.push_str(&format!(
+            "\"{}\" = \"{}\";\n\n",
+            escape_string(&entry.key),
+            escape_string(value)
+        ));
+    }
+    for entry in &existing {
+        if !entries.iter().any(|source| source.key == entry.key) {
+            content.push_str(&format!(
+                "\"{}\" = \"{}\";\n\n",
+                escape_string(&entry.key),
+                escape_string(&entry.value)
+            ));
+        }
+    }
+
+    Ok((content.trim_end().to_string() + "\n", changes))
+}
+
+async fn translate_stringsdict(
+    translator: &Translator,
+    target: &Target,
+    source: &str,
+    original: &str,
+) -> Result<(String, usize)> {
+    let lang = &target.lang;
+    let source = parse_plist(source)?;
+    let existing = match original.trim().is_empty() {
+        true => Plist::Dict(Vec::new()),
+        false => parse_plist(original)?,
+    };
+    let plurals = Plurals::for_language(lang);
+
+    // The strings to translate, by their path of keys in the property list
+    let mut paths: Vec<Vec<&str>> = Vec::new();
+    let mut units = Vec::new();
+    for (key, rule) in source.entries() {
+        for (name, value) in rule.entries() {
+            let path = vec![key.as_str(), name.as_str()];
+            if let Plist::String(format) = value {
+                let text = FORMAT_VARIABLE.replace_all(format, "");
+                if name == "NSStringLocalizedFormatKey" && text.chars().any(char::is_alphabetic) {
+                    let current = existing.string(&path).filter(|s| !s.is_empty());
+                    units.push(unit(
+                        &path,
+                        format.clone(),
+                        None,
+                        current.into_iter().collect(),
+                    ));
+                    paths.push(path);
+                }
+                continue;
+            }
+            if value.string(&["NSStringFormatSpecTypeKey"]).as_deref()
+                != Some("NSStringPluralRuleType")
+            {
+                continue;
+            }
+
+            let form = |plist: &Plist, category: &str| {
+                plist.string(&[key.as_str(), name.as_str(), category])
+            };
+            let other = form(&source, "other").unwrap_or_default();
+            let one = form(&source, "one").unwrap_or_else(|| other.clone());
+            let current: Vec<String> = plurals
+                .forms()
+                .iter()
+                .map(|category| form(&existing, category).unwrap_or_default())
+                .collect();
+            let current = match current.iter().all(String::is_empty) {
+                true => Vec::new(),
+                false => current,
+            };
+            units.push(unit(&path, one, Some(other), current));
+            paths.push(path.clone());
+
+            // `zero` is an extra form for the count 0 in every language
+            if let Some(zero) = form(&source, "zero")
+                && !plurals.categories.contains(&"zero")
+            {
+                let path = vec![key.as_str(), name.as_str(), "zero"];
+                let current = existing.string(&path).filter(|s| !s.is_empty());
+                units.push(unit(&path, zero, None, current.into_iter().collect()));
+                paths.push(path);
+            }
+        }
+    }
+
+    let translated = translate_units(translator, &target.path, &units, lang).await?;
+    let changes = translated.iter().filter(|t| t.is_some()).count();
+    let text = |path: &[&str]| {
+        let index = paths.iter().position(|p| p == path)?;
+        let text = match &translated[index] {
+            Some(translated) => &translated.target,
+            None => &units[index].target,
+        };
+        Some(text.clone()).filter(|text| text.iter().any(|form| !form.is_empty()))
+    };
+
+    let mut rules = Vec::new();
+    for (key, rule) in source.entries() {
+        let mut entries = Vec::new();
+        let mut complete = true;
+        for (name, value) in rule.entries() {
+            let path = [key.as_str(), name.as_str()];
+            let value = match value {
+                Plist::String(format) if name == "NSStringLocalizedFormatKey" => {
+                    match paths.iter().any(|p| p == &path) {
+                        true => match text(&path) {
+                            Some(format) => Plist::String(format.join("")),
+                            None => {
+                                complete = false;
+                                break;
+                            }
+                        },
+                        false => Plist::String(format.clone()),
+                    }
+                }
+                Plist::Dict(variable) if paths.iter().any(|p| p == &path) => {
+                    let Some(forms) = text(&path) else {
+                        complete = false;
+                        break;
+                    };
+                    let mut variable: Vec<(String, Plist)> = variable
+                        .iter()
+                        .filter(|(name, _)| !PLURAL_CATEGORIES.contains(&name.as_str()))
+                        .map(|(name, value)| (name.clone(), value.copy()))
+                        .collect();
+                    if let Some(zero) = text(&[path[0], path[1], "zero"]) {
+                        variable.push(("zero".to_string(), Plist::String(zero.join(""))));
+                    }
+                    for (position, category) in plurals.categories.iter().enumerate() {
+                        let form = forms.get(position).or(forms.last()).cloned();
+                        variable.push((
+                            category.to_string(),
+                            Plist::String(form.unwrap_or_default()),
+                        ));
+                    }
+                    Plist::Dict(variable)
+                }
+                value => value.copy(),
+            };
+            entries.push((name.clone(), value));
+        }
+        if complete {
+            rules.push((key.clone(), Plist::Dict(entries)));
+        }
+    }
+
+    Ok((write_plist(&Plist::Dict(rules)), changes))
+}
+
+/// The plural keys of a .stringsdict variable
+const PLURAL_CATEGORIES: &[&str] = &["zero", "one", "two", "few", "many", "other"];
+
+fn unit(path: &[&str], source: String, source_plural: Option<String>, target: Vec<String>) -> Unit {
+    let mut notes = Vec::new();
+    if FORMAT_VARIABLE.is_match(&source) {
+        notes.push(
+            "Keep the `%#@...@` variables as they are: they are replaced by the plural forms."
+                .to_string(),
+        );
+    }
+    Unit {
+        context: Some(path.join(".")),
+        source,
+        source_plural,
+        target,
+        notes,
+        needs_review: false,
+    }
+}
+
+/// Reads a .strings file in UTF-8, or in UTF-16 with a byte order mark as older Xcode wrote
+fn read(path: &Path) -> Result<String> {
+    let bytes =
+        fs::read(path).map_err(|e| anyhow::anyhow!("Error reading {}: {}", path.display(), e))?;
+    let content = match bytes.as_slice() {
+        [0xFF, 0xFE, ..] => UTF_16LE.decode(&bytes).0.into_owned(),
+        [0xFE, 0xFF, ..] => UTF_16BE.decode(&bytes).0.into_owned(),
+        _ => String::from_utf8_lossy(&bytes).into_owned(),
+    };
+    Ok(content.trim_start_matches('\u{feff}').to_string())
+}
+
+/// Parses the `"key" = "value";` entries of a .strings file, with the comment before each one
+fn parse_strings(content: &str) -> Result<Vec<Entry>> {
+    let mut entries = Vec::new();
+    let mut comment = None;
+    let mut rest = content;
+
+    loop {
+        rest = rest.trim_start();
+        if rest.is_empty() {
+            break;
+        }
+        if let Some(after) = rest.strip_prefix("/*") {
+            let end = after
+                .find("*/")
+                .ok_or_else(|| anyhow::anyhow!("unterminated /* comment"))?;
+            comment = Some(after[..end].trim().to_string());
+            rest = &after[end + 2..];
+            continue;
+        }
+        if let Some(after) = rest.strip_prefix("//") {
+            let end = after.find('\n').unwrap_or(after.len());
+            comment = Some(after[..end].trim().to_string());
+            rest = &after[end..];
+            continue;
+        }
+
+        let (key, after) = token(rest)?;
+        let after = after
+            .trim_start()
+            .strip_prefix('=')
+            .ok_or_else(|| anyhow::anyhow!("expected `=` after \"{}\"", key))?;
+        let (value, after) = token(after.trim_start())?;
+        rest = after
+            .trim_start()
+            .strip_prefix(';')
+            .ok_or_else(|| anyhow::anyhow!("expected `;` after \"{}\"", key))?;
+        entries.push(Entry {
+            comment: comment.take(),
+            key,
+            value,
+        });
+    }
+
+    Ok(entries)
+}
+
+/// A quoted string, or an unquoted word, at the start of `text`, and what follows it
+fn token(text: &str) -> Result<(String, &str)> {
+    let Some(quoted) = text.strip_prefix('"') else {
+        let end = text
+            .find(|c: char| !(c.is_alphanumeric() || "_.-$".contains(c)))
+            .unwrap_or(text.len());
+        if end == 0 {
+            anyhow::bail!("unexpected `{}`", text.chars().next().unwrap_or_default());
+        }
+        return Ok((text[..end].to_string(), &text[end..]));
+    };
+
+    let mut value = String::new();
+    let mut chars = quoted.char_indices();
+    while let Some((index, c)) = chars.next() {
+        match c {
+            '"' => return Ok((value, &quoted[index + 1..])),
+            '\\' => match chars.next().map(|(_, c)| c) {
+                Some('n') => value.push('\n'),
+                Some('t') => value.push('\t'),
+                Some('r') => value.push('\r'),
+                Some('U' | 'u') => {
+                    let code: String = chars.by_ref().take(4).map(|(_, c)| c).collect();
+                    if let Some(c) = u32::from_str_radix(&code, 16).ok().and_then(char::from_u32) {
+                        value.push(c);
+                    }
+                }
+                Some(c) => value.push(c),
+                None => {}
+            },
+            c => value.push(c),
+        }
+    }
+    anyhow::bail!("unterminated string")
+}
+
+fn escape_string(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+        .replace('\t', "\\t")
+}
+
+impl Plist {
+    /// The keys and values of a dictionary; none for other values
+    fn entries(&self) -> impl Iterator<Item = &(String, Plist)> {
+        let entries = match self {
+            Plist::Dict(entries) => entries.as_slice(),
+            _ => &[],
+        };
+        entries.iter()
+    }
+
+    /// The string at the end of a path of dictionary keys
+    fn string(&self, path: &[&str]) -> Option<String> {
+        let mut value = self;
+        for key in path {
+            value = &value.entries().find(|(name, _)| name == key)?.1;
+        }
+        match value {
+            Plist::String(text) => Some(text.clone()),
+            _ => None,
+        }
+    }
+
+    fn copy(&self) -> Plist {
+        match self {
+            Plist::Dict(entries) => Plist::Dict(
+                entries
+                    .iter()
+                    .map(|(name, value)| (name.clone(), value.copy()))
+                    .collect(),
+            ),
+            Plist::String(text) => Plist::String(text.clone()),
+            Plist::Other(raw) => Plist::Other(raw.clone()),
+        }
+    }
+}
+
+/// Parses the root dictionary of a property list
+fn parse_plist(content: &str) -> Result<Plist> {
+    let start = content
+        .find("<dict")
+        .ok_or_else(|| anyhow::anyhow!("no <dict> in the property list"))?;
+    let mut position = start;
+    parse_value(content, &mut position)
+}
+
+/// Parses the element at `position`, moving past it
+fn parse_value(content: &str, position: &mut usize) -> Result<Plist> {
+    let (name, empty) = next_tag(content, position, false)?;
+    match (name.as_str(), empty) {
+        ("string", true) => Ok(Plist::String(String::new())),
+        ("string", false) => Ok(Plist::String(unescape(&text_until(
+            content, position, "string",
+        )?))),
+        ("dict", true) => Ok(Plist::Dict(Vec::new())),
+        ("dict", false) => {
+            let mut entries = Vec::new();
+            loop {
+                let (tag, _) = next_tag(content, position, true)?;
+                match tag.as_str() {
+                    "/dict" => return Ok(Plist::Dict(entries)),
+                    "key" => {
+                        let key = unescape(&text_until(content, position, "key")?);
+                        entries.push((key, parse_value(content, position)?));
+                    }
+                    tag => anyhow::bail!("unexpected <{}> in a <dict>", tag),
+                }
+            }
+        }
+        (name, true) => Ok(Plist::Other(format!("<{}/>", name))),
+        (name, false) => {
+            let start = *position;
+            let closing = format!("</{}>", name);
+            let end = content[start..]
+                .find(&closing)
+                .ok_or_else(|| anyhow::anyhow!("unterminated <{}>", name))?;
+            *position = start + end + closing.len();
+            Ok(Plist::Other(format!(
+                "<{}>{}{}",
+                name,
+                &content[start..start + end],
+                closing
+            )))
+        }
+    }
+}
+
+/// The next tag from `position`, skipping the prolog and comments: its name (with a `/` for
+/// closing tags, if `closing` allows them) and whether it is empty like `<string/>`
+fn next_tag(content: &str, position: &mut usize, closing: bool) -> Result<(String, bool)> {
+    for captures in PLIST_TOKEN.captures_iter(&content[*position..]) {
+        let whole = captures.get(0).unwrap();
+        let Some(name) = captures.get(2) else {
+            continue;
+        };
+        *position += whole.end();
+        let slash = !captures[1].is_empty();
+        if slash && !closing {
+            anyhow::bail!("unexpected </{}>", name.as_str());
+        }
+        let name = match slash {
+            true => format!("/{}", name.as_str()),
+            false => name.as_str().to_string(),
+        };
+        return Ok((name, !captures[3].is_empty()));
+    }
+    anyhow::bail!("unexpected end of the property list")
+}
+
+/// The text from `position` to the closing tag of `name`, moving past it
+fn text_until(content: &str, position: &mut usize, name: &str) -> Result<String> {
+    let closing = format!("</{}>", name);
+    let end = content[*position..]
+        .find(&closing)
+        .ok_or_else(|| anyhow::anyhow!("unterminated <{}>", name))?;
+    let text = content[*position..*position + end].to_string();
+    *position += end + closing.len();
+    Ok(text)
+}
+
+/// Writes a property list as Xcode does, indented with tabs
+fn write_plist(plist: &Plist) -> String {
+    let mut content = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<!DOCTYPE plist PUBLIC \"-//Apple//DTD PLIST 1.0//EN\" \"http://www.apple.com/DTDs/PropertyList-1.0.dtd\">\n<plist version=\"1.0\">\n",
+    );
+    write_value(plist, 0, &mut content);
+    content.push_str("</plist>\n");
+    content
+}
+
+fn write_value(plist: &Plist, depth: usize, content: &mut String) {
+    let indent = "\t".repeat(depth);
+    match plist {
+        Plist::Dict(entries) => {
+            content.push_str(&format!("{}<dict>\n", indent));
+            for (key, value) in entries {
+                content.push_str(&format!("{}\t<key>{}</key>\n", indent, escape(key)));
+                write_value(value, depth + 1, content);
+            }
+            content.push_str(&format!("{}</dict>\n", indent));
+        }
+        Plist::String(text) => {
+            content.push_str(&format!("{}<string>{}</string>\n", indent, escape(text)));
+        }
+        Plist::Other(raw) => content.push_str(&format!("{}{}\n", indent, raw)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::provider::{Pseudo, pseudolocalize};
+    use crate::translator::TranslateOptions;
+    use std::sync::Arc;
+
+    const DICT: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+	<key>files</key>
+	<dict>
+		<key>NSStringLocalizedFormatKey</key>
+		<string>%#@files@</string>
+		<key>files</key>
+		<dict>
+			<key>NSStringFormatSpecTypeKey</key>
+			<string>NSStringPluralRuleType</string>
+			<key>NSStringFormatValueTypeKey</key>
+			<string>d</string>
+			<key>one</key>
+			<string>%d file</string>
+			<key>other</key>
+			<string>%d files</string>
+		</dict>
+	</dict>
+</dict>
+</plist>
+"#;
+
+    fn temp_dir(test: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "ai-gettext-translator-apple-{}-{}",
+            test,
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("en.lproj")).unwrap();
+        fs::create_dir_all(dir.join("es.lproj")).unwrap();
+        dir
+    }
+
+    fn target(dir: &Path, file: &str) -> Target {
+        Target {
+            source: dir.join("en.lproj").join(file),
+            source_lang: "en".to_string(),
+            path: dir.join("es.lproj").join(file),
+            lang: "es".to_string(),
+        }
+    }
+
+    fn translator() -> Translator {
+        Translator::new(Arc::new(Pseudo), TranslateOptions::default()).unwrap()
+    }
+
+    #[test]
+    fn parses_strings_files() {
+        let content = "/* The title */\n\"title\" = \"Say \\\"hi\\\"\\n\";\n// Unquoted\nkey_2 = \"Caf\\U00e9\";\n\"empty\"=\"\";";
+        let entries = parse_strings(content).unwrap();
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0].comment.as_deref(), Some("The title"));
+        assert_eq!(entries[0].key, "title");
+        assert_eq!(entries[0].value, "Say \"hi\"\n");
+        assert_eq!(entries[1].comment.as_deref(), Some("Unquoted"));
+        assert_eq!(entries[1].key, "key_2");
+        assert_eq!(entries[1].value, "Café");
+        assert_eq!(entries[2].comment, None);
+        assert_eq!(escape_string(&entries[0].value), "Say \\\"hi\\\"\\n");
+
+        assert!(parse_strings("\"key\" \"value\";").is_err());
+        assert!(parse_strings("\"key\" = \"value\"").is_err());
+        assert!(parse_strings("\"key\" = \"value;").is_err());
+        assert!(parse_strings("/* open").is_err());
+    }
+
+    #[test]
+    fn reads_utf16_files() {
+        let dir = temp_dir("utf16");
+        let path = dir.join("en.lproj/Localizable.strings");
+        let mut bytes = vec![0xFF, 0xFE];
+        for unit in "\"a\" = \"é\";".encode_utf16() {
+            bytes.extend(unit.to_le_bytes());
+        }
+        fs::write(&path, bytes).unwrap();
+        assert_eq!(read(&path).unwrap(), "\"a\" = \"é\";");
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn writes_the_property_lists_it_reads() {
+        let plist = parse_plist(DICT).unwrap();
+        assert_eq!(
+            plist.string(&["files", "files", "other"]).as_deref(),
+            Some("%d files")
+        );
+        assert_eq!(plist.string(&["files", "missing"]), None);
+        assert_eq!(write_plist(&plist), DICT);
+        assert!(parse_plist("<plist></plist>").is_err());
+        assert!(parse_plist("<dict><key>a</key><string>open</dict>").is_err());
+    }
+
+    #[tokio::test]
+    async fn fills_strings_files() {
+        let dir = temp_dir("strings");
+        fs::write(
+            dir.join("en.lproj/Localizable.strings"),
+            "/* No comment provided by engineer. */\n\"Hello\" = \"Hello\";\n\n/* Button */\n\"save\" = \"Save\";\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.join("es.lproj/Localizable.strings"),
+            "\"Hello\" = \"Hola\";\n\"old\" = \"Viejo\";\n",
+        )
+        .unwrap();
+
+        let target = target(&dir, "Localizable.strings");
+        assert_eq!(translate_file(&translator(), &target).await.unwrap(), 1);
+        assert_eq!(
+            fs::read_to_string(&target.path).unwrap(),
+            format!(
+                "/* No comment provided by engineer. */\n\"Hello\" = \"Hola\";\n\n/* Button */\n\"save\" = \"{}\";\n\n\"old\" = \"Viejo\";\n",
+                escape_string(&pseudolocalize("Save"))
+            )
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn fills_stringsdict_plurals() {
+        let dir = temp_dir("stringsdict");
+        fs::write(dir.join("en.lproj/Localizable.stringsdict"), DICT).unwrap();
+
+        let target = target(&dir, "Localizable.stringsdict");
+        assert_eq!(translate_file(&translator(), &target).await.unwrap(), 1);
+        let plist = parse_plist(&fs::read_to_string(&target.path).unwrap()).unwrap();
+        assert_eq!(
+            plist
+                .string(&["files", "NSStringLocalizedFormatKey"])
+                .as_deref(),
+            Some("%#@files@")
+        );
+        assert_eq!(
+            plist
+                .string(&["files", "files", "NSStringFormatValueTypeKey"])
+                .as_deref(),
+            Some("d")
+        );
+        let one = plist.string(&["files", "files", "one"]).unwrap();
+        let other = plist.string(&["files", "files", "other"]).unwrap();
+        assert!(one.contains("%d") && one != "%d file", "{}", one);
+        assert!(other.contains("%d") && other != "%d files", "{}", other);
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,251 @@
+use anyhow::Result;
+use regex::Regex;
+use serde_json::{Map, Value};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+
+use super::json::{read, write};
+use super::{Target, Translated, Unit, files, save, start_file, translate_units};
+use crate::filter::PathFilter;
+use crate::translator::Translator;
+
+/// Extensions of the files `arb` translates in a folder
+pub const EXTENSIONS: &[&str] = &["arb"];
+
+static ICU_ARGUMENT: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\{\s*\w+\s*,\s*(plural|select|selectordinal)\s*,").unwrap());
+
+/// The .arb files under `root` whose locale is `source_lang` (the template, like
+/// `app_en.arb`), each paired with the file of every target language, like `app_es.arb`.
+/// Languages default to the locales of the other files found.
+pub fn targets(
+    root: &Path,
+    source_lang: &str,
+    langs: Option<&[String]>,
+    filter: &PathFilter,
+) -> Result<Vec<Target>> {
+    let mut sources = Vec::new();
+    let mut found = Vec::new();
+    for path in files(root, EXTENSIONS, filter) {
+        let lang = locale(&path)?;
+        match lang == source_lang {
+            true => sources.push(path),
+            false if !found.contains(&lang) => found.push(lang),
+            false => {}
+        }
+    }
+
+    if sources.is_empty() {
+        anyhow::bail!(
+            "{}: found no .arb file of locale `{}` to translate from",
+            root.display(),
+            source_lang
+        );
+    }
+
+    let mut langs: Vec<String> = match langs {
+        Some(langs) => langs.to_vec(),
+        None => found,
+    };
+    langs.retain(|lang| lang != source_lang);
+    langs.sort();
+
+    let mut targets = Vec::new();
+    for lang in &langs {
+        for source in &sources {
+            targets.push(Target {
+                source: source.clone(),
+                source_lang: source_lang.to_string(),
+                path: target_path(source, source_lang, lang),
+                lang: lang.clone(),
+            });
+        }
+    }
+    Ok(targets)
+}
+
+/// Adds the messages of the template missing from the target, translated, in the order of the
+/// template. The target's `@key` metadata and the keys only it has are kept. Returns the
+/// number of messages translated.
+pub async fn translate_file(translator: &Translator, target: &Target) -> Result<usize> {
+    let source = match read(&target.source)? {
+        Some(Value::Object(source)) => source,
+        _ => anyhow::bail!("{}: not a JSON object", target.source.display()),
+    };
+    let original = match target.path.exists() {
+        true => fs::read_to_string(&target.path)
+            .map_err(|e| anyhow::anyhow!("Error reading {}: {}", target.path.display(), e))?,
+        false => String::new(),
+    };
+    let existing = match read(&target.path)? {
+        Some(Value::Object(existing)) => existing,
+        _ => Map::new(),
+    };
+    let lang = &target.lang;
+    start_file(translator, &target.path, lang);
+
+    let messages: Vec<&String> = source
+        .iter()
+        .filter(|(key, value)| !key.starts_with('@') && value.is_string())
+        .map(|(key, _)| key)
+        .collect();
+    let units: Vec<Unit> = messages
+        .iter()
+        .map(|key| {
+            let current = existing
+                .get(key.as_str())
+                .and_then(Value::as_str)
+                .filter(|text| !text.is_empty());
+            Unit {
+                context: Some(key.to_string()),
+                source: source[key.as_str()]
+                    .as_str()
+                    .unwrap_or_default()
+                    .to_string(),
+                source_plural: None,
+                target: current.map(str::to_string).into_iter().collect(),
+                notes: notes(&source, key),
+                needs_review: false,
+            }
+        })
+        .collect();
+    let translated = translate_units(translator, &target.path, &units, lang).await?;
+    let changes = translated.iter().filter(|t| t.is_some()).count();
+
+    let modified = merge(&source, &existing, lang, &messages, &units, &translated);
+    let template = match original.is_empty() {
+        true => fs::read_to_string(&target.source)?,
+        false => original.clone(),
+    };
+    let modified = write(&Value::Object(modified), &template)?;
+    save(
+        &target.path,
+        lang,
+        &original,
+        &modified,
+        changes,
+        translator.options(),
+    )?;
+    Ok(changes)
+}
+
+/// The target file: its locale, then the messages of the template, each followed by the
+/// target's `@key` metadata, then the keys only the target has
+fn merge(
+    source: &Map<String, Value>,
+    existing: &Map<String, Value>,
+    lang: &str,
+    messages: &[&String],
+    units: &[Unit],
+    translated: &[Option<Translated>],
+) -> Map<String, Value> {
+    let mut merged = Map::new();
+    merged.insert("@@locale".to_string(), Value::String(lang.to_string()));
+    for (key, value) in existing {
+        if key.starts_with("@@") && key != "@@locale" {
+            merged.insert(key.clone(), value.clone());
+        }
+    }
+
+    for (index, key) in messages.iter().enumerate() {
+        let text = match &translated[index] {
+            Some(translated) => translated.target.first(),
+            None => units[index].target.first(),
+        };
+        if let Some(text) = text {
+            merged.insert(key.to_string(), Value::String(text.clone()));
+        }
+        let metadata = format!("@{}", key);
+        if let Some(value) = existing.get(&metadata) {
+            merged.insert(metadata, value.clone());
+        }
+    }
+
+    for (key, value) in existing {
+        let message = key.strip_prefix('@').unwrap_or(key);
+        if !merged.contains_key(key) && !source.contains_key(message) {
+            merged.insert(key.clone(), value.clone());
+        }
+    }
+    merged
+}
+
+/// Notes for the model from the `@key` metadata of the template: the description and the
+/// placeholders, with their examples
+fn notes(source: &Map<String, Value>, key: &str) -> Vec<String> {
+    let mut notes = Vec::new();
+    let metadata = source.get(&format!("@{}", key));
+
+    if let Some(description) = metadata
+        .and_then(|metadata| metadata.get("description"))
+        .and_then(Value::as_str)
+    {
+        notes.push(description.to_string());
+    }
+
+    if let Some(placeholders) = metadata
+        .and_then(|metadata| metadata.get("placeholders"))
+        .and_then(Value::as_object)
+    {
+        let placeholders: Vec<String> = placeholders
+            .iter()
+            .map(
+                |(name, placeholder)| match placeholder.get("example").and_then(Value::as_str) {
+                    Some(example) => format!("{{{}}} (e.g. {})", name, example),
+                    None => format!("{{{}}}", name),
+                },
+            )
+            .collect();
+        notes.push(format!(
+            "Placeholders, which you must keep as they are: {}.",
+            placeholders.join(", ")
+        ));
+    }
+
+    let message = source.get(key).and_then(Value::as_str).unwrap_or_default();
+    if ICU_ARGUMENT.is_match(message) {
+        notes.push(
+            "The message uses ICU MessageFormat: keep the argument names, the `plural`/`select` keywords and the case keys (`=0`, `one`, `other`...) as they are, and translate only the text inside the cases. Keep the same cases, even if the target language has other plural forms.".to_string(),
+        );
+    }
+    notes
+}
+
+/// The locale of an .arb file: its `@@locale`, or else the suffix of its name, like `es` in
+/// `app_es.arb`
+fn locale(path: &Path) -> Result<String> {
+    let declared = read(path)?
+        .as_ref()
+        .and_then(|value| value.get("@@locale"))
+        .and_then(Value::as_str)
+        .map(str::to_string);
+    if let Some(declared) = declared {
+        return Ok(declared);
+    }
+
+    let stem = path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or_default();
+    Ok(stem
+        .split_once('_')
+        .map(|(_, lang)| lang)
+        .unwrap_or(stem)
+        .to_string())
+}
+
+/// The file of `lang` for the template `path`: `app_es.arb` for `app_en.arb`
+fn target_path(path: &Path, source_lang: &str, lang: &str) -> PathBuf {
+    let stem = path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or_default();
+    let name = match stem.strip_suffix(source_lang) {
+        Some(prefix) if prefix.is_empty() || prefix.ends_with(['_', '-']) => {
+            format!("{}{}.arb", prefix, lang)
+        }
+        _ => format!("{}_{}.arb", stem, lang),
+    };
+    path.with_file_name(name)
+}
@@ -0,0 +1,380 @@
+use anyhow::Result;
+use regex::Regex;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+
+use super::{Unit, save, start_file, translate_units};
+use crate::translator::Translator;
+
+static LANGUAGE_CODE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^[a-z]{2,3}(?:[-_][A-Za-z0-9]{2,8})*$").unwrap());
+
+/// Which columns of a spreadsheet hold what, and where to write it
+pub struct Columns {
+    /// The field delimiter; a tab for .tsv files and a comma for others by default
+    pub delimiter: Option<char>,
+    pub source_lang: String,
+    /// The column of the strings to translate, by header or 1-based number. Defaults to the
+    /// column named like the source language, else the first one.
+    pub source: Option<String>,
+    /// A column sent as the context of each string, like a key or identifier
+    pub key: Option<String>,
+    /// A column of notes for the translators, sent with each string
+    pub note: Option<String>,
+    /// The target languages. Default to the columns named like a language code, and those
+    /// of `headers`.
+    pub langs: Option<Vec<String>>,
+    /// The header of the column of each language not named by its code, like `es=Spanish`
+    pub headers: Vec<(String, String)>,
+    /// The file the spreadsheet is written to, instead of its own
+    pub output: Option<PathBuf>,
+}
+
+impl Columns {
+    /// Parses `--column` mappings like `es=Spanish`
+    pub fn parse_headers(mappings: &[String]) -> Result<Vec<(String, String)>> {
+        mappings
+            .iter()
+            .map(|mapping| match mapping.split_once('=') {
+                Some((lang, header)) if !lang.trim().is_empty() && !header.trim().is_empty() => {
+                    Ok((lang.trim().to_string(), header.trim().to_string()))
+                }
+                _ => anyhow::bail!("invalid column `{}`: expected <lang>=<header>", mapping),
+            })
+            .collect()
+    }
+
+    /// Parses a `--delimiter`: a single character, or `tab`
+    pub fn parse_delimiter(delimiter: &str) -> Result<char> {
+        match delimiter {
+            "tab" | "\\t" => Ok('\t'),
+            delimiter => {
+                let mut chars = delimiter.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) if c != '"' && c != '\n' => Ok(c),
+                    _ => anyhow::bail!(
+                        "invalid delimiter `{}`: expected one character, or `tab`",
+                        delimiter
+                    ),
+                }
+            }
+        }
+    }
+}
+
+/// Fills the column of each target language of the spreadsheet at `path`, adding it after the
+/// others when missing. Cells that already have a translation are kept, and rows without a
+/// source string are left empty. Returns the number of strings translated.
+pub async fn translate_file(
+    translator: &Translator,
+    path: &Path,
+    columns: &Columns,
+) -> Result<usize> {
+    let content = fs::read_to_string(path)
+        .map_err(|e| anyhow::anyhow!("Error reading {}: {}", path.display(), e))?;
+    let delimiter = columns.delimiter.unwrap_or_else(|| {
+        match path
+            .extension()
+            .is_some_and(|e| e.eq_ignore_ascii_case("tsv"))
+        {
+            true => '\t',
+            false => ',',
+        }
+    });
+    let bom = content.starts_with('\u{feff}');
+    let newline = match content.contains("\r\n") {
+        true => "\r\n",
+        false => "\n",
+    };
+    let mut rows = parse(content.trim_start_matches('\u{feff}'), delimiter)
+        .map_err(|e| anyhow::anyhow!("{}: {}", path.display(), e))?;
+    let Some(header) = rows.first().cloned() else {
+        anyhow::bail!("{}: the spreadsheet is empty", path.display());
+    };
+
+    let column = |spec: &str| -> Result<usize> {
+        if let Some(index) = header.iter().position(|name| name.trim() == spec) {
+            return Ok(index);
+        }
+        match spec.parse::<usize>() {
+            Ok(number) if number >= 1 && number <= header.len() => Ok(number - 1),
+            _ => anyhow::bail!("{}: no column `{}` in the header", path.display(), spec),
+        }
+    };
+    let source = match &columns.source {
+        Some(spec) => column(spec)?,
+        None => column(&columns.source_lang).unwrap_or(0),
+    };
+    let key = columns.key.as_deref().map(column).transpose()?;
+    let note = columns.note.as_deref().map(column).transpose()?;
+
+    let langs = match &columns.langs {
+        Some(langs) => langs.clone(),
+        None => {
+            let mut langs: Vec<String> = header
+                .iter()
+                .enumerate()
+                .filter(|(index, name)| {
+                    ![Some(source), key, note].contains(&Some(*index))
+                        && LANGUAGE_CODE.is_match(name.trim())
+                })
+                .map(|(_, name)| name.trim().to_string())
+                .collect();
+            for (lang, _) in &columns.headers {
+                if !langs.contains(lang) {
+                    langs.push(lang.clone());
+                }
+            }
+            langs
+        }
+    };
+    let langs: Vec<String> = langs
+        .into_iter()
+        .filter(|lang| *lang != columns.source_lang)
+        .collect();
+    if langs.is_empty() {
+        anyhow::bail!(
+            "{}: no column named like a language code to translate into, pass --lang",
+            path.display()
+        );
+    }
+
+    let output = columns.output.as_deref().unwrap_or(path);
+    let mut original = match output.exists() {
+        true => fs::read_to_string(output)
+            .map_err(|e| anyhow::anyhow!("Error reading {}: {}", output.display(), e))?,
+        false => String::new(),
+    };
+
+    let mut changes = 0;
+    for lang in &langs {
+        let name = columns
+            .headers
+            .iter()
+            .find(|(code, _)| code == lang)
+            .map_or(lang.as_str(), |(_, header)| header.as_str());
+        let target = match rows[0].iter().position(|cell| cell.trim() == name) {
+            Some(index) => index,
+            None => {
+                rows[0].push(name.to_string());
+                let width = rows[0].len();
+                for row in &mut rows[1..] {
+                    row.resize(row.len().max(width), String::new());
+                }
+                width - 1
+            }
+        };
+        start_file(translator, output, lang);
+
+        let cell = |row: &Vec<String>, index: usize| row.get(index).cloned().unwrap_or_default();
+        let strings: Vec<usize> = (1..rows.len())
+            .filter(|row| !cell(&rows[*row], source).trim().is_empty())
+            .collect();
+        let units: Vec<Unit> = strings
+            .iter()
+            .map(|row| {
+                let row = &rows[*row];
+                let current = cell(row, target);
+                Unit {
+                    context: key.map(|key| cell(row, key)).filter(|key| !key.is_empty()),
+                    source: cell(row, source),
+                    source_plural: None,
+                    target: Some(current)
+                        .filter(|c| !c.is_empty())
+                        .into_iter()
+                        .collect(),
+                    notes: note
+                        .map(|note| cell(row, note))
+                        .filter(|note| !note.is_empty())
+                        .into_iter()
+                        .collect(),
+                    needs_review: false,
+                }
+            })
+            .collect();
+        let translated = translate_units(translator, path, &units, lang).await?;
+        let translated_count = translated.iter().filter(|t| t.is_some()).count();
+
+        for (index, row) in strings.iter().enumerate() {
+            let Some(translated) = &translated[index] else {
+                continue;
+            };
+            let row = &mut rows[*row];
+            if row.len() <= target {
+                row.resize(target + 1, String::new());
+            }
+            row[target] = translated.target.first().cloned().unwrap_or_default();
+        }
+
+        let mut modified = write(&rows, delimiter, newline);
+        if bom {
+            modified.insert(0, '\u{feff}');
+        }
+        save(
+            output,
+            lang,
+            &original,
+            &modified,
+            translated_count,
+            translator.options(),
+        )?;
+        if translated_count > 0 {
+            original = modified;
+        }
+        changes += translated_count;
+    }
+    Ok(changes)
+}
+
+/// Splits a spreadsheet into rows of fields, honoring double-quoted fields, which may hold
+/// delimiters, newlines and `""` escapes
+fn parse(content: &str, delimiter: char) -> Result<Vec<Vec<String>>> {
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = content.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match (c, quoted) {
+            ('"', true) if chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            ('"', true) => quoted = false,
+            ('"', false) if field.is_empty() => quoted = true,
+            (c, false) if c == delimiter => row.push(std::mem::take(&mut field)),
+            ('\r', false) if chars.peek() == Some(&'\n') => {}
+            ('\n', false) => {
+                row.push(std::mem::take(&mut field));
+                rows.push(std::mem::take(&mut row));
+            }
+            (c, _) => field.push(c),
+        }
+    }
+
+    if quoted {
+        anyhow::bail!("unterminated quoted field in row {}", rows.len() + 1);
+    }
+    if !field.is_empty() || !row.is_empty() {
+        row.push(field);
+        rows.push(row);
+    }
+    Ok(rows)
+}
+
+/// Writes rows of fields, quoting those that need it
+pub(crate) fn write(rows: &[Vec<String>], delimiter: char, newline: &str) -> String {
+    let mut content = String::new();
+    for row in rows {
+        let fields: Vec<String> = row
+            .iter()
+            .map(|field| {
+                let needs_quotes = field.contains([delimiter, '"', '\n', '\r'])
+                    || field.starts_with(' ')
+                    || field.ends_with(' ');
+                match needs_quotes {
+                    true => format!("\"{}\"", field.replace('"', "\"\"")),
+                    false => field.clone(),
+                }
+            })
+            .collect();
+        content.push_str(&fields.join(&delimiter.to_string()));
+        content.push_str(newline);
+    }
+    content
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::provider::{Pseudo, pseudolocalize};
+    use crate::translator::TranslateOptions;
+    use std::sync::Arc;
+
+    fn row(fields: &[&str]) -> Vec<String> {
+        fields.iter().map(|field| field.to_string()).collect()
+    }
+
+    #[test]
+    fn parses_quoted_fields() {
+        let content = "key,en\r\nwelcome,\"Hello, \"\"friend\"\"\"\r\nbye,\"See\nyou\"\r\nempty,";
+        assert_eq!(
+            parse(content, ',').unwrap(),
+            vec![
+                row(&["key", "en"]),
+                row(&["welcome", "Hello, \"friend\""]),
+                row(&["bye", "See\nyou"]),
+                row(&["empty", ""]),
+            ]
+        );
+        assert_eq!(parse("a\tb\n", '\t').unwrap(), vec![row(&["a", "b"])]);
+        assert!(parse("key,en\nx,\"open", ',').is_err());
+    }
+
+    #[test]
+    fn writes_what_it_parses() {
+        let rows = vec![
+            row(&["key", "en"]),
+            row(&["welcome", "Hello, \"friend\""]),
+            row(&["bye", "See\nyou"]),
+            row(&["space", " padded "]),
+        ];
+        let content = write(&rows, ',', "\r\n");
+        assert_eq!(
+            content,
+            "key,en\r\nwelcome,\"Hello, \"\"friend\"\"\"\r\nbye,\"See\nyou\"\r\nspace,\" padded \"\r\n"
+        );
+        assert_eq!(parse(&content, ',').unwrap(), rows);
+    }
+
+    #[test]
+    fn parses_options() {
+        assert_eq!(Columns::parse_delimiter("tab").unwrap(), '\t');
+        assert_eq!(Columns::parse_delimiter(";").unwrap(), ';');
+        assert!(Columns::parse_delimiter(";;").is_err());
+        assert!(Columns::parse_delimiter("\"").is_err());
+        assert_eq!(
+            Columns::parse_headers(&["es = Spanish".to_string()]).unwrap(),
+            vec![("es".to_string(), "Spanish".to_string())]
+        );
+        assert!(Columns::parse_headers(&["Spanish".to_string()]).is_err());
+    }
+
+    #[tokio::test]
+    async fn fills_the_language_columns() {
+        let dir =
+            std::env::temp_dir().join(format!("ai-gettext-translator-csv-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("strings.csv");
+        fs::write(
+            &path,
+            "key,en,es,note\nhello,Hello,Hola,\nbye,Bye,,Short\nblank,,,\n",
+        )
+        .unwrap();
+
+        let translator = Translator::new(Arc::new(Pseudo), TranslateOptions::default()).unwrap();
+        let columns = Columns {
+            delimiter: None,
+            source_lang: "en".to_string(),
+            source: None,
+            key: Some("key".to_string()),
+            note: Some("note".to_string()),
+            langs: None,
+            headers: vec![("fr".to_string(), "French".to_string())],
+            output: None,
+        };
+        assert_eq!(
+            translate_file(&translator, &path, &columns).await.unwrap(),
+            3
+        );
+        let rows = parse(&fs::read_to_string(&path).unwrap(), ',').unwrap();
+        assert_eq!(rows[0], row(&["key", "en", "es", "note", "French"]));
+        assert_eq!(rows[1][2], "Hola");
+        assert_eq!(rows[1][4], pseudolocalize("Hello"));
+        assert_eq!(rows[2][2], pseudolocalize("Bye"));
+        assert_eq!(rows[3], row(&["blank", "", "", "", ""]));
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
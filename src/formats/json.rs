@@ -0,0 +1,388 @@
+use anyhow::Result;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::{Plurals, Target, Translated, Unit, files, save, start_file, translate_units};
+use crate::filter::PathFilter;
+use crate::translator::Translator;
+
+/// The suffixes i18next gives the plural forms of a key, like `item_one` and `item_other`
+const PLURAL_SUFFIXES: &[&str] = &["zero", "one", "two", "few", "many", "other"];
+
+/// A value of the source file, as written to the target
+enum Node {
+    /// A string, translated by the unit at this index
+    Text(usize),
+    /// The `<key>_<category>` keys of an i18next plural, translated by the unit at this index
+    Plural(usize),
+    Object(Vec<(String, Node)>),
+    Array(Vec<Node>),
+    /// Numbers, booleans and nulls, and keys of the target the source doesn't have
+    Copy(Value),
+}
+
+/// The locale files under `root` to translate from `source_lang`: either one file per
+/// language (`es.json` next to `en.json`) or one folder per language (`es/common.json` next to
+/// `en/common.json`). Languages default to the other files or folders found.
+pub fn targets(
+    root: &Path,
+    source_lang: &str,
+    langs: Option<&[String]>,
+    filter: &PathFilter,
+) -> Result<Vec<Target>> {
+    let flat = root.join(format!("{}.json", source_lang));
+    let nested = root.join(source_lang);
+
+    let (sources, found): (Vec<PathBuf>, Vec<String>) = if flat.is_file() {
+        let found = fs::read_dir(root)?
+            .filter_map(|e| e.ok())
+            .map(|e| e.path())
+            .filter(|path| path.is_file() && path.extension().is_some_and(|e| e == "json"))
+            .filter_map(|path| Some(path.file_stem()?.to_str()?.to_string()))
+            .collect();
+        (vec![flat.clone()], found)
+    } else if nested.is_dir() {
+        let found = fs::read_dir(root)?
+            .filter_map(|e| e.ok())
+            .map(|e| e.path())
+            .filter(|path| path.is_dir())
+            .filter_map(|path| Some(path.file_name()?.to_str()?.to_string()))
+            .filter(|name| !name.starts_with('.'))
+            .collect();
+        (files(&nested, &["json"], filter), found)
+    } else {
+        anyhow::bail!(
+            "{}: found no {}.json file or {} folder to translate from",
+            root.display(),
+            source_lang,
+            source_lang
+        );
+    };
+
+    let mut langs: Vec<String> = match langs {
+        Some(langs) => langs.to_vec(),
+        None => found,
+    };
+    langs.retain(|lang| lang != source_lang);
+    langs.sort();
+
+    let mut targets = Vec::new();
+    for lang in &langs {
+        for source in &sources {
+            let path = match source == &flat {
+                true => root.join(format!("{}.json", lang)),
+                false => root
+                    .join(lang)
+                    .join(source.strip_prefix(&nested).unwrap_or(source)),
+            };
+            targets.push(Target {
+                source: source.clone(),
+                source_lang: source_lang.to_string(),
+                path,
+                lang: lang.clone(),
+            });
+        }
+    }
+    Ok(targets)
+}
+
+/// Adds the keys of the source file missing from the target, translated, in the order of the
+/// source. Keys only the target has are kept after the others. Returns the number of strings
+/// translated.
+pub async fn translate_file(translator: &Translator, target: &Target) -> Result<usize> {
+    let source = read(&target.source)?.unwrap_or(Value::Object(Map::new()));
+    let original = match target.path.exists() {
+        true => fs::read_to_string(&target.path)
+            .map_err(|e| anyhow::anyhow!("Error reading {}: {}", target.path.display(), e))?,
+        false => String::new(),
+    };
+    let existing = read(&target.path)?;
+    let lang = &target.lang;
+    start_file(translator, &target.path, lang);
+
+    // Rails-style files nest everything under the language code: {"en": {...}}
+    let source_root = rails_root(&source, &target.source_lang);
+    let (source_value, existing_value) = match source_root {
+        Some(value) => (
+            value,
+            existing
+                .as_ref()
+                .and_then(|existing| existing.get(lang.as_str())),
+        ),
+        None => (&source, existing.as_ref()),
+    };
+
+    let plurals = Plurals::for_language(lang);
+    let mut units = Vec::new();
+    let node = plan(source_value, existing_value, "", &plurals, &mut units);
+    let translated = translate_units(translator, &target.path, &units, lang).await?;
+    let changes = translated.iter().filter(|t| t.is_some()).count();
+
+    let mut value = render(&node, &units, &translated, plurals.categories);
+    if source_root.is_some() {
+        let mut root = Map::new();
+        root.insert(lang.clone(), value);
+        value = Value::Object(root);
+    }
+
+    let template = match original.is_empty() {
+        true => fs::read_to_string(&target.source)?,
+        false => original.clone(),
+    };
+    let modified = write(&value, &template)?;
+    save(
+        &target.path,
+        lang,
+        &original,
+        &modified,
+        changes,
+        translator.options(),
+    )?;
+    Ok(changes)
+}
+
+/// The JSON value of the file at `path`, or None if it doesn't exist or is empty
+pub(crate) fn read(path: &Path) -> Result<Option<Value>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    let content = fs::read_to_string(path)
+        .map_err(|e| anyhow::anyhow!("Error reading {}: {}", path.display(), e))?;
+    if content.trim().is_empty() {
+        return Ok(None);
+    }
+    serde_json::from_str(&content)
+        .map(Some)
+        .map_err(|e| anyhow::anyhow!("{}: invalid JSON: {}", path.display(), e))
+}
+
+/// The object under the language key of a Rails-style file, whose only key is its language
+fn rails_root<'a>(value: &'a Value, lang: &str) -> Option<&'a Value> {
+    let object = value.as_object()?;
+    match object.len() {
+        1 => object.get(lang).filter(|value| value.is_object()),
+        _ => None,
+    }
+}
+
+/// Walks the source value, adding a unit for each string and i18next plural
+fn plan(
+    source: &Value,
+    existing: Option<&Value>,
+    key: &str,
+    plurals: &Plurals,
+    units: &mut Vec<Unit>,
+) -> Node {
+    match source {
+        Value::String(text) => {
+            let target = existing
+                .and_then(Value::as_str)
+                .filter(|text| !text.is_empty())
+                .map(str::to_string);
+            units.push(unit(key, text.clone(), None, target.into_iter().collect()));
+            Node::Text(units.len() - 1)
+        }
+        Value::Array(items) => Node::Array(
+            items
+                .iter()
+                .enumerate()
+                .map(|(index, item)| {
+                    let existing = existing.and_then(|existing| existing.get(index));
+                    plan(
+                        item,
+                        existing,
+                        &join(key, &index.to_string()),
+                        plurals,
+                        units,
+                    )
+                })
+                .collect(),
+        ),
+        Value::Object(object) => {
+            let existing = existing.and_then(Value::as_object);
+            let mut children = Vec::new();
+            let mut planned: Vec<&str> = Vec::new();
+
+            for (name, value) in object {
+                if let Some(base) = plural_base(name, object) {
+                    if planned.contains(&base) {
+                        continue;
+                    }
+                    planned.push(base);
+                    units.push(plural_unit(
+                        object,
+                        existing,
+                        base,
+                        &join(key, base),
+                        plurals,
+                    ));
+                    children.push((base.to_string(), Node::Plural(units.len() - 1)));
+                    continue;
+                }
+
+                planned.push(name);
+                let existing = existing.and_then(|existing| existing.get(name));
+                children.push((
+                    name.clone(),
+                    plan(value, existing, &join(key, name), plurals, units),
+                ));
+            }
+
+            // Keys of the target the source doesn't have, like extra plural forms
+            if let Some(existing) = existing {
+                for (name, value) in existing {
+                    let known = planned.contains(&name.as_str())
+                        || plural_base(name, existing).is_some_and(|base| planned.contains(&base));
+                    if !known {
+                        children.push((name.clone(), Node::Copy(value.clone())));
+                    }
+                }
+            }
+            Node::Object(children)
+        }
+        other => Node::Copy(other.clone()),
+    }
+}
+
+/// The base of an i18next plural key like `item_one`, when `object` has its `<base>_other`
+fn plural_base<'a>(name: &'a str, object: &Map<String, Value>) -> Option<&'a str> {
+    let (base, suffix) = name.rsplit_once('_')?;
+    let is_plural = PLURAL_SUFFIXES.contains(&suffix)
+        && object
+            .get(&format!("{}_other", base))
+            .is_some_and(Value::is_string);
+    is_plural.then_some(base)
+}
+
+/// A plural unit, from the `_one` (or `_other`) and `_other` forms of the source
+fn plural_unit(
+    source: &Map<String, Value>,
+    existing: Option<&Map<String, Value>>,
+    base: &str,
+    key: &str,
+    plurals: &Plurals,
+) -> Unit {
+    let form = |object: &Map<String, Value>, category: &str| {
+        object
+            .get(&format!("{}_{}", base, category))
+            .and_then(Value::as_str)
+            .map(str::to_string)
+    };
+
+    let other = form(source, "other").unwrap_or_default();
+    let one = form(source, "one").unwrap_or_else(|| other.clone());
+
+    // Forms the target already has, by msgstr index; missing ones are left empty
+    let categories = plurals.forms();
+    let target = match existing {
+        Some(existing) if categories.iter().any(|c| form(existing, c).is_some()) => categories
+            .iter()
+            .map(|category| form(existing, category).unwrap_or_default())
+            .collect(),
+        _ => Vec::new(),
+    };
+
+    unit(key, one, Some(other), target)
+}
+
+fn unit(key: &str, source: String, source_plural: Option<String>, target: Vec<String>) -> Unit {
+    let mut notes = Vec::new();
+    if source.contains("{{") || source.contains("$t(") {
+        notes.push(
+            "Keep the `{{...}}` interpolations and `$t(...)` references as they are.".to_string(),
+        );
+    }
+
+    Unit {
+        context: Some(key.to_string()),
+        source,
+        source_plural,
+        target,
+        notes,
+        needs_review: false,
+    }
+}
+
+/// Builds the target value: new translations, else the current ones. Strings left without
+/// a translation are left out, so the app falls back to the source language.
+fn render(
+    node: &Node,
+    units: &[Unit],
+    translated: &[Option<Translated>],
+    categories: &[&str],
+) -> Value {
+    match node {
+        Node::Text(index) => Value::String(text(*index, units, translated).join("")),
+        Node::Array(items) => Value::Array(
+            items
+                .iter()
+                .map(|item| render(item, units, translated, categories))
+                .collect(),
+        ),
+        Node::Object(children) => {
+            let mut object = Map::new();
+            for (name, child) in children {
+                match child {
+                    Node::Plural(index) => {
+                        let forms = text(*index, units, translated);
+                        let Some(last) = forms.last().filter(|form| !form.is_empty()) else {
+                            continue;
+                        };
+                        for (position, category) in categories.iter().enumerate() {
+                            let form = forms.get(position).unwrap_or(last);
+                            object.insert(
+                                format!("{}_{}", name, category),
+                                Value::String(form.clone()),
+                            );
+                        }
+                    }
+                    Node::Text(index) if text(*index, units, translated).is_empty() => {}
+                    child => {
+                        object.insert(name.clone(), render(child, units, translated, categories));
+                    }
+                }
+            }
+            Value::Object(object)
+        }
+        Node::Plural(_) => unreachable!("plurals are keys of an object"),
+        Node::Copy(value) => value.clone(),
+    }
+}
+
+/// The translated forms of a unit, or its current ones; empty if it has none
+fn text<'a>(index: usize, units: &'a [Unit], translated: &'a [Option<Translated>]) -> &'a [String] {
+    match &translated[index] {
+        Some(translated) => &translated.target,
+        None => &units[index].target,
+    }
+}
+
+/// Serializes `value` with the indentation of `template` and its trailing newline
+pub(crate) fn write(value: &Value, template: &str) -> Result<String> {
+    let indent = template
+        .lines()
+        .nth(1)
+        .map(|line| &line[..line.len() - line.trim_start().len()])
+        .filter(|indent| !indent.is_empty())
+        .unwrap_or("  ");
+
+    let mut bytes = Vec::new();
+    let formatter = serde_json::ser::PrettyFormatter::with_indent(indent.as_bytes());
+    let mut serializer = serde_json::Serializer::with_formatter(&mut bytes, formatter);
+    value.serialize(&mut serializer)?;
+
+    let mut content = String::from_utf8(bytes)?;
+    if template.ends_with('\n') || template.is_empty() {
+        content.push('\n');
+    }
+    Ok(content)
+}
+
+fn join(key: &str, name: &str) -> String {
+    match key.is_empty() {
+        true => name.to_string(),
+        false => format!("{}.{}", key, name),
+    }
+}
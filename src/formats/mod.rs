@@ -0,0 +1,220 @@
+//! Translation of file formats other than gettext catalogs. Each format reads its strings
+//! into `Unit`s, which are translated as the entries of an in-memory `PoCatalog`, so the
+//! cache, glossary, prompts and checks of .po files apply to them too. The file is then
+//! edited in place, leaving everything else as written.
+
+pub mod android;
+pub mod apple;
+pub mod arb;
+pub mod csv;
+pub mod json;
+pub mod xliff;
+pub(crate) mod xml;
+pub mod yaml;
+
+use anyhow::Result;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::atomic;
+use crate::filter::PathFilter;
+use crate::logger::{
+    log_error, log_file_started, log_file_success, log_no_changes, log_summary, log_unified_diff,
+};
+use crate::plural::{PluralForms, categories_for_language};
+use crate::po::{PoCatalog, PoEntry};
+use crate::provider::TranslationProvider;
+use crate::translator::{self, TranslateOptions, Translator};
+
+/// A translatable string of a file
+pub struct Unit {
+    /// Tells identical strings apart, and the model where the string is used
+    pub context: Option<String>,
+    pub source: String,
+    pub source_plural: Option<String>,
+    /// The current translation, one string per plural form; empty when missing
+    pub target: Vec<String>,
+    /// Notes from the developers, sent with the string like `#.` comments
+    pub notes: Vec<String>,
+    /// The current translation must be reviewed, like a fuzzy entry
+    pub needs_review: bool,
+}
+
+/// A locale file to fill with the strings of the file of the source language
+pub struct Target {
+    pub source: PathBuf,
+    pub source_lang: String,
+    pub path: PathBuf,
+    pub lang: String,
+}
+
+/// The plural forms of a language, by CLDR name (`one`, `few`...) as formats like i18next and
+/// Rails write them, and as msgstrs
+pub struct Plurals {
+    pub categories: &'static [&'static str],
+    pub nplurals: usize,
+}
+
+impl Plurals {
+    pub fn for_language(lang: &str) -> Self {
+        Self {
+            categories: categories_for_language(lang),
+            nplurals: PluralForms::for_language(lang).nplurals,
+        }
+    }
+
+    /// The categories that get their own msgstr, in msgstr order
+    pub fn forms(&self) -> &'static [&'static str] {
+        &self.categories[..self.nplurals]
+    }
+}
+
+/// The new translation of a `Unit`
+pub struct Translated {
+    pub target: Vec<String>,
+    pub needs_review: bool,
+}
+
+/// Translates the units of the file at `path` that need it, as `translate_catalog` would the
+/// entries of a .po file. Returns the new translation of every unit that changed, by unit
+/// index.
+pub async fn translate_units(
+    translator: &Translator,
+    path: &Path,
+    units: &[Unit],
+    lang: &str,
+) -> Result<Vec<Option<Translated>>> {
+    let entries = units
+        .iter()
+        .map(|unit| {
+            let mut entry = PoEntry::from_strings(
+                unit.context.clone(),
+                unit.source.clone(),
+                unit.source_plural.clone(),
+                unit.target.clone(),
+                &unit.notes,
+            );
+            if unit.needs_review {
+                entry.add_flag("fuzzy");
+            }
+            entry
+        })
+        .collect();
+
+    let mut catalog = PoCatalog::from_entries(entries);
+    translator
+        .translate_catalog_of(&mut catalog, lang, path)
+        .await?;
+
+    Ok(catalog
+        .entries()
+        .zip(units)
+        .map(|(entry, unit)| {
+            let needs_review = entry.has_flag("fuzzy");
+            let untranslated = entry.msgstr.iter().all(String::is_empty);
+            let changed = match unit.target.is_empty() {
+                true => !untranslated,
+                false => entry.msgstr != unit.target || needs_review != unit.needs_review,
+            };
+            match changed {
+                true => Some(Translated {
+                    target: entry.msgstr.clone(),
+                    needs_review,
+                }),
+                false => None,
+            }
+        })
+        .collect())
+}
+
+/// Writes the translated content of a file, or shows its diff in dry-run mode
+pub fn save(
+    path: &Path,
+    lang: &str,
+    original: &str,
+    modified: &str,
+    changes: usize,
+    options: &TranslateOptions,
+) -> Result<()> {
+    let display = path.display().to_string();
+    if changes == 0 {
+        log_no_changes(lang, &display);
+        return Ok(());
+    }
+
+    log_file_success(lang, changes, &display, options.dry_run);
+    if options.dry_run {
+        log_unified_diff(&display, original, modified);
+        return Ok(());
+    }
+
+    if options.backup && path.exists() {
+        atomic::backup(path)?;
+    }
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    atomic::write(path, modified)
+}
+
+/// Translates the `files` of one format with a translator set up for the `root` folder.
+/// `translate_file` returns the number of strings it changed in a file. Prints the summary and
+/// returns the number of strings that failed, like `translator::run`.
+pub async fn run<T, F>(
+    root: &Path,
+    files: Vec<T>,
+    context: Option<PathBuf>,
+    provider: Arc<dyn TranslationProvider>,
+    options: TranslateOptions,
+    translate_file: F,
+) -> Result<usize>
+where
+    F: AsyncFn(&Translator, &T) -> Result<usize>,
+{
+    let translator = translator::for_folder(root, &context, provider, options).await?;
+    for file in files {
+        if crate::interrupt::is_interrupted() {
+            break;
+        }
+        if let Err(e) = translate_file(&translator, &file).await {
+            log_error(&e);
+            return Err(e);
+        }
+    }
+
+    let stats = translator.stats();
+    log_summary(&stats);
+    translator.write_report(&stats, root)?;
+    Ok(stats.failures.len())
+}
+
+/// Logs the start of a file and counts it in the stats of `lang`
+pub fn start_file(translator: &Translator, path: &Path, lang: &str) {
+    log_file_started(lang, &path.display().to_string());
+    translator.stats().lang(lang).files += 1;
+}
+
+/// `path` if it is a folder, else the folder of the file
+pub fn folder(path: &Path) -> PathBuf {
+    match path.is_dir() {
+        true => path.to_path_buf(),
+        false => path.parent().map(Path::to_path_buf).unwrap_or_default(),
+    }
+}
+
+/// The file at `path`, or the files with one of the `extensions` in the folder at `path`
+pub fn files(path: &Path, extensions: &[&str], filter: &PathFilter) -> Vec<PathBuf> {
+    if !path.is_dir() {
+        return vec![path.to_path_buf()];
+    }
+
+    filter
+        .files(path, path)
+        .filter(|file| {
+            file.extension()
+                .and_then(|e| e.to_str())
+                .is_some_and(|e| extensions.contains(&e))
+        })
+        .collect()
+}
@@ -0,0 +1,306 @@
+use anyhow::Result;
+use regex::Regex;
+use std::fs;
+use std::ops::Range;
+use std::path::Path;
+use std::sync::LazyLock;
+
+use super::xml::{attribute, escape, set_attribute, unescape};
+use super::{Translated, Unit, save, start_file, translate_units};
+use crate::translator::Translator;
+
+/// Extensions of the files `xliff` translates in a folder
+pub const EXTENSIONS: &[&str] = &["xlf", "xliff"];
+
+static XLIFF_TAG: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"<xliff\b[^>]*>").unwrap());
+static FILE_TAG: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"<file\b[^>]*>").unwrap());
+static TRANS_UNIT: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?s)<trans-unit\b[^>]*>.*?</trans-unit>").unwrap());
+static UNIT: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?s)<unit\b[^>]*>.*?</unit>").unwrap());
+static SEGMENT: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?s)<segment\b[^>]*>.*?</segment>").unwrap());
+static SOURCE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?s)([ \t]*)<source\b[^>]*>(.*?)</source>").unwrap());
+static TARGET: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?s)<target\b[^>]*?(?:/>|>(.*?)</target>)").unwrap());
+static NOTE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?s)<note\b[^>]*>(.*?)</note>").unwrap());
+static OPENING_TAG: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^<[^>]*>").unwrap());
+
+#[derive(Clone, Copy, PartialEq)]
+enum Version {
+    /// `<file target-language>` with `<trans-unit>` elements, the state on each `<target>`
+    V1,
+    /// `<xliff trgLang>` with `<unit>`s of `<segment>`s, the state on each `<segment>`
+    V2,
+}
+
+/// A `<trans-unit>` (1.2) or `<segment>` (2.0) to translate
+struct Slot {
+    /// Byte range of the element in the file
+    range: Range<usize>,
+    /// The source holds inline elements like `<g>` or `<ph/>`, so it is sent, and its
+    /// translation written, as XML
+    markup: bool,
+}
+
+/// Translates the `<target>`s of an XLIFF 1.2 or 2.0 file into `lang`, or else the file's own
+/// target language. Returns the number of segments translated.
+pub async fn translate_file(
+    translator: &Translator,
+    path: &Path,
+    lang: Option<&str>,
+) -> Result<usize> {
+    let original = fs::read_to_string(path)
+        .map_err(|e| anyhow::anyhow!("Error reading {}: {}", path.display(), e))?;
+    let version = match XLIFF_TAG
+        .find(&original)
+        .and_then(|tag| attribute(tag.as_str(), "version"))
+    {
+        Some(version) if version.starts_with('2') => Version::V2,
+        _ => Version::V1,
+    };
+
+    let declared = match version {
+        Version::V1 => FILE_TAG
+            .find(&original)
+            .and_then(|tag| attribute(tag.as_str(), "target-language")),
+        Version::V2 => XLIFF_TAG
+            .find(&original)
+            .and_then(|tag| attribute(tag.as_str(), "trgLang")),
+    };
+    let Some(lang) = lang.map(str::to_string).or(declared.clone()) else {
+        anyhow::bail!(
+            "{}: no target language declared, pass it with --lang",
+            path.display()
+        );
+    };
+    start_file(translator, path, &lang);
+
+    let (slots, units): (Vec<Slot>, Vec<Unit>) = parse(&original, version).into_iter().unzip();
+    let translated = translate_units(translator, path, &units, &lang).await?;
+
+    let mut modified = String::with_capacity(original.len());
+    let mut position = 0;
+    let mut changes = 0;
+    for (slot, translated) in slots.iter().zip(&translated) {
+        let Some(translated) = translated else {
+            continue;
+        };
+        modified.push_str(&original[position..slot.range.start]);
+        modified.push_str(&rewrite(
+            &original[slot.range.clone()],
+            translated,
+            slot.markup,
+            version,
+        ));
+        position = slot.range.end;
+        changes += 1;
+    }
+    modified.push_str(&original[position..]);
+
+    if changes > 0 && declared.is_none() {
+        modified = declare_language(&modified, &lang, version);
+    }
+
+    save(
+        path,
+        &lang,
+        &original,
+        &modified,
+        changes,
+        translator.options(),
+    )?;
+    Ok(changes)
+}
+
+fn parse(content: &str, version: Version) -> Vec<(Slot, Unit)> {
+    let mut slots = Vec::new();
+
+    match version {
+        Version::V1 => {
+            for unit in TRANS_UNIT.find_iter(content) {
+                let tag = opening_tag(unit.as_str());
+                if attribute(tag, "translate").as_deref() == Some("no") {
+                    continue;
+                }
+                let notes = notes(unit.as_str());
+                let context = attribute(tag, "resname");
+                slots.extend(slot(content, unit.range(), context, notes, version));
+            }
+        }
+        Version::V2 => {
+            for unit in UNIT.find_iter(content) {
+                let tag = opening_tag(unit.as_str());
+                if attribute(tag, "translate").as_deref() == Some("no") {
+                    continue;
+                }
+                let notes = notes(unit.as_str());
+                let context = attribute(tag, "name");
+                for segment in SEGMENT.find_iter(unit.as_str()) {
+                    let range = unit.start() + segment.start()..unit.start() + segment.end();
+                    slots.extend(slot(
+                        content,
+                        range,
+                        context.clone(),
+                        notes.clone(),
+                        version,
+                    ));
+                }
+            }
+        }
+    }
+
+    slots
+}
+
+/// Reads the source and target of the element at `range`
+fn slot(
+    content: &str,
+    range: Range<usize>,
+    context: Option<String>,
+    mut notes: Vec<String>,
+    version: Version,
+) -> Option<(Slot, Unit)> {
+    let element = &content[range.clone()];
+    let source = SOURCE.captures(element)?.get(2)?.as_str();
+    let markup = source.contains('<');
+    if markup {
+        notes.push(
+            "The message holds XLIFF inline tags, which you must keep as they are.".to_string(),
+        );
+    }
+
+    let target = TARGET.captures(element).map(|captures| {
+        (
+            captures.get(0).map(|m| m.as_str()).unwrap_or_default(),
+            captures.get(1),
+        )
+    });
+    let state = match version {
+        Version::V1 => target.and_then(|(tag, _)| attribute(opening_tag(tag), "state")),
+        Version::V2 => attribute(opening_tag(element), "state"),
+    };
+
+    // New segments may hold a copy of the source, which isn't a translation yet
+    let untranslated = matches!(
+        state.as_deref(),
+        Some("new" | "needs-translation" | "initial")
+    );
+    let text = target
+        .and_then(|(_, inner)| inner)
+        .map(|inner| read_text(inner.as_str(), markup))
+        .filter(|text| !text.is_empty() && !untranslated);
+
+    let unit = Unit {
+        context,
+        source: read_text(source, markup),
+        source_plural: None,
+        target: text.into_iter().collect(),
+        notes,
+        needs_review: state.is_some_and(|state| state.starts_with("needs-review")),
+    };
+    Some((Slot { range, markup }, unit))
+}
+
+/// The element at `range` with its target set to the translation
+fn rewrite(element: &str, translated: &Translated, markup: bool, version: Version) -> String {
+    let text = translated
+        .target
+        .first()
+        .map(String::as_str)
+        .unwrap_or_default();
+    let text = match markup {
+        true => text.to_string(),
+        false => escape(text),
+    };
+    let state = match (version, translated.needs_review) {
+        (Version::V1, true) => "needs-review-translation",
+        (Version::V1, false) | (Version::V2, _) => "translated",
+    };
+
+    let element = match TARGET.find(element) {
+        Some(target) => {
+            let tag = opening_tag(target.as_str())
+                .trim_end_matches("/>")
+                .trim_end_matches('>');
+            let mut tag = format!("{}>", tag.trim_end());
+            if version == Version::V1 {
+                tag = set_attribute(&tag, "state", state);
+            }
+            format!(
+                "{}{}{}</target>{}",
+                &element[..target.start()],
+                tag,
+                text,
+                &element[target.end()..]
+            )
+        }
+        None => {
+            let source = SOURCE.captures(element).expect("slots have a source");
+            let (whole, indent) = (source.get(0).unwrap(), source.get(1).unwrap().as_str());
+            let tag = match version {
+                Version::V1 => format!("<target state=\"{}\">", state),
+                Version::V2 => "<target>".to_string(),
+            };
+            format!(
+                "{}\n{}{}{}</target>{}",
+                &element[..whole.end()],
+                indent,
+                tag,
+                text,
+                &element[whole.end()..]
+            )
+        }
+    };
+
+    match version {
+        Version::V1 => element,
+        Version::V2 => {
+            let tag = opening_tag(&element);
+            format!(
+                "{}{}",
+                set_attribute(tag, "state", state),
+                &element[tag.len()..]
+            )
+        }
+    }
+}
+
+/// Sets the target language of a file that doesn't declare one
+fn declare_language(content: &str, lang: &str, version: Version) -> String {
+    match version {
+        Version::V1 => FILE_TAG
+            .replace_all(content, |captures: &regex::Captures| {
+                set_attribute(&captures[0], "target-language", lang)
+            })
+            .into_owned(),
+        Version::V2 => XLIFF_TAG
+            .replace(content, |captures: &regex::Captures| {
+                set_attribute(&captures[0], "trgLang", lang)
+            })
+            .into_owned(),
+    }
+}
+
+fn notes(element: &str) -> Vec<String> {
+    NOTE.captures_iter(element)
+        .map(|captures| unescape(captures[1].trim()))
+        .filter(|note| !note.is_empty())
+        .collect()
+}
+
+fn read_text(inner: &str, markup: bool) -> String {
+    match markup {
+        true => inner.to_string(),
+        false => unescape(inner),
+    }
+}
+
+fn opening_tag(element: &str) -> &str {
+    OPENING_TAG
+        .find(element)
+        .map(|m| m.as_str())
+        .unwrap_or_default()
+}
@@ -0,0 +1,89 @@
+use regex::Regex;
+
+/// Escapes the characters that can't appear as-is in XML text or attribute values
+pub fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Decodes the predefined entities and the `&#N;` / `&#xN;` character references
+pub fn unescape(text: &str) -> String {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(start) = rest.find('&') {
+        unescaped.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let decoded = rest.find(';').and_then(|end| {
+            let decoded = match &rest[1..end] {
+                "amp" => Some('&'),
+                "lt" => Some('<'),
+                "gt" => Some('>'),
+                "quot" => Some('"'),
+                "apos" => Some('\''),
+                entity => entity
+                    .strip_prefix("#x")
+                    .map(|hex| u32::from_str_radix(hex, 16))
+                    .or_else(|| entity.strip_prefix('#').map(str::parse))
+                    .and_then(|code| code.ok())
+                    .and_then(char::from_u32),
+            };
+            decoded.map(|c| (c, end))
+        });
+
+        match decoded {
+            Some((c, end)) => {
+                unescaped.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                unescaped.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+
+    unescaped.push_str(rest);
+    unescaped
+}
+
+/// The value of the attribute `name` of an opening tag like `<target state="new">`
+pub fn attribute(tag: &str, name: &str) -> Option<String> {
+    let regex = Regex::new(&format!(
+        r#"\s{}\s*=\s*(?:"([^"]*)"|'([^']*)')"#,
+        regex::escape(name)
+    ))
+    .expect("attribute names are escaped");
+    let captures = regex.captures(tag)?;
+    let value = captures.get(1).or_else(|| captures.get(2))?;
+    Some(unescape(value.as_str()))
+}
+
+/// The opening tag `tag` with its attribute `name` set to `value`, added before the `>` (or
+/// `/>`) when missing
+pub fn set_attribute(tag: &str, name: &str, value: &str) -> String {
+    let regex = Regex::new(&format!(
+        r#"(\s){}\s*=\s*(?:"[^"]*"|'[^']*')"#,
+        regex::escape(name)
+    ))
+    .expect("attribute names are escaped");
+    let attribute = format!("{}=\"{}\"", name, escape(value));
+
+    if regex.is_match(tag) {
+        return regex
+            .replace(tag, |captures: &regex::Captures| {
+                format!("{}{}", &captures[1], attribute)
+            })
+            .into_owned();
+    }
+
+    let end = match tag.ends_with("/>") {
+        true => tag.len() - 2,
+        false => tag.len() - 1,
+    };
+    let head = tag[..end].trim_end();
+    format!("{} {}{}", head, attribute, &tag[head.len()..].trim_start())
+}
@@ -0,0 +1,1164 @@
+//! Rails i18n YAML files, like `config/locales/en.yml`. Only the subset of YAML locale files
+//! use is parsed: block mappings and sequences of scalars, flow sequences, block scalars,
+//! anchors and aliases. Target files are edited in place, so their comments, anchors and
+//! quoting stay as written.
+
+use anyhow::Result;
+use std::collections::HashMap;
+use std::fs;
+use std::ops::Range;
+use std::path::{Component, Path, PathBuf};
+
+use super::{Plurals, Target, Translated, Unit, files, save, start_file, translate_units};
+use crate::filter::PathFilter;
+use crate::translator::Translator;
+
+/// Extensions of the files `yaml` translates in a folder
+pub const EXTENSIONS: &[&str] = &["yml", "yaml"];
+
+/// The keys of a Rails plural, like `one:` and `other:`
+const PLURAL_KEYS: &[&str] = &["zero", "one", "two", "few", "many", "other"];
+
+#[derive(Clone, Copy, PartialEq)]
+enum Style {
+    Plain,
+    Single,
+    Double,
+    /// A `|` block scalar
+    Literal,
+    /// A `>` block scalar
+    Folded,
+}
+
+struct Scalar {
+    text: String,
+    style: Style,
+    /// Byte range of the value in the file: the scalar with its quotes, or the lines of a
+    /// block scalar
+    span: Range<usize>,
+    /// Header of a block scalar, like `|-`, and the indentation of its lines
+    header: String,
+    block_indent: usize,
+    /// An item of a flow sequence, where plain scalars can't hold `,`, `[`, `]`, `{` or `}`
+    flow: bool,
+}
+
+impl Scalar {
+    /// The style to write `text` in, in place of this scalar
+    fn style_for(&self, text: &str) -> Style {
+        match self.flow && text.contains([',', '[', ']', '{', '}']) {
+            true => Style::Double,
+            false => self.style,
+        }
+    }
+}
+
+enum Value {
+    /// A mapping or a sequence, or a key without a value
+    Parent,
+    /// A sequence written `[a, b]`, whose items are its children
+    Flow,
+    Scalar(Scalar),
+    /// Aliases, merge keys, nulls, booleans, numbers and flow mappings, copied as written
+    Verbatim(String),
+}
+
+struct Node {
+    /// The key, or the index of a sequence item
+    key: String,
+    /// The line up to its value, like `title: &title` or `-`
+    head: String,
+    indent: usize,
+    /// Byte offset of the node's first line
+    start: usize,
+    /// Byte offset after the node's first line
+    header_end: usize,
+    /// Byte offset after the last line of the node and its children
+    end: usize,
+    value: Value,
+    children: Vec<usize>,
+}
+
+/// The nodes of a file; the first one is a root holding its top-level keys
+struct Document {
+    nodes: Vec<Node>,
+}
+
+/// A change to the target file: the text replacing a byte range, empty for insertions
+struct Edit {
+    range: Range<usize>,
+    text: String,
+}
+
+/// The source and target files, and the units of the source's strings
+struct Merge<'a> {
+    source: &'a Document,
+    target: &'a Document,
+    plurals: Plurals,
+    units: Vec<Unit>,
+    /// The unit of each source node translated
+    unit_of: HashMap<usize, usize>,
+    translated: Vec<Option<Translated>>,
+}
+
+/// The YAML files under `root` whose top-level key is `source_lang`, each paired with the
+/// file of every target language: `es.yml` for `en.yml`, `devise.es.yml` for `devise.en.yml`,
+/// `es/users.yml` for `en/users.yml`. Languages default to the top-level keys of the other
+/// files found.
+pub fn targets(
+    root: &Path,
+    source_lang: &str,
+    langs: Option<&[String]>,
+    filter: &PathFilter,
+) -> Result<Vec<Target>> {
+    let mut sources = Vec::new();
+    let mut found = Vec::new();
+    for path in files(root, EXTENSIONS, filter) {
+        let content = fs::read_to_string(&path)
+            .map_err(|e| anyhow::anyhow!("Error reading {}: {}", path.display(), e))?;
+        let document = Document::parse(&content);
+        let Some(lang) = document.children(0).next().map(|node| node.key.clone()) else {
+            continue;
+        };
+        match lang == source_lang {
+            true => sources.push(path),
+            false if !found.contains(&lang) => found.push(lang),
+            false => {}
+        }
+    }
+
+    if sources.is_empty() {
+        anyhow::bail!(
+            "{}: found no YAML file rooted at `{}:` to translate from",
+            root.display(),
+            source_lang
+        );
+    }
+
+    let mut langs: Vec<String> = match langs {
+        Some(langs) => langs.to_vec(),
+        None => found,
+    };
+    langs.retain(|lang| lang != source_lang);
+    langs.sort();
+
+    let mut targets = Vec::new();
+    for lang in &langs {
+        for source in &sources {
+            targets.push(Target {
+                source: source.clone(),
+                source_lang: source_lang.to_string(),
+                path: target_path(source, source_lang, lang),
+                lang: lang.clone(),
+            });
+        }
+    }
+    Ok(targets)
+}
+
+/// Adds the keys of the source file missing from the target, translated, after the keys that
+/// precede them in the source. Returns the number of strings translated.
+pub async fn translate_file(translator: &Translator, target: &Target) -> Result<usize> {
+    let source_content = fs::read_to_string(&target.source)
+        .map_err(|e| anyhow::anyhow!("Error reading {}: {}", target.source.display(), e))?;
+    let original = match target.path.exists() {
+        true => fs::read_to_string(&target.path)
+            .map_err(|e| anyhow::anyhow!("Error reading {}: {}", target.path.display(), e))?,
+        false => String::new(),
+    };
+    let lang = &target.lang;
+    start_file(translator, &target.path, lang);
+
+    let source = Document::parse(&source_content);
+    let existing = Document::parse(&original);
+    let Some(source_root) = source.child(0, &target.source_lang) else {
+        anyhow::bail!(
+            "{}: no `{}:` key at the top",
+            target.source.display(),
+            target.source_lang
+        );
+    };
+    let target_root = existing.child(0, lang);
+
+    let mut merge = Merge {
+        source: &source,
+        target: &existing,
+        plurals: Plurals::for_language(lang),
+        units: Vec::new(),
+        unit_of: HashMap::new(),
+        translated: Vec::new(),
+    };
+    for child in &source.nodes[source_root].children {
+        let target = target_root.and_then(|root| existing.child(root, &source.nodes[*child].key));
+        merge.collect(*child, target, "");
+    }
+
+    merge.translated = translate_units(translator, &target.path, &merge.units, lang).await?;
+    let changes = merge.translated.iter().filter(|t| t.is_some()).count();
+
+    let mut edits = Vec::new();
+    match target_root {
+        Some(root) => merge.edit_children(source_root, root, &mut edits),
+        None => {
+            let children: String = source.nodes[source_root]
+                .children
+                .iter()
+                .filter_map(|child| merge.generate(*child, source.nodes[*child].indent))
+                .collect();
+            edits.push(Edit {
+                range: original.len()..original.len(),
+                text: format!("{}:\n{}", lang, children),
+            });
+        }
+    }
+    let modified = apply(&original, edits);
+
+    save(
+        &target.path,
+        lang,
+        &original,
+        &modified,
+        changes,
+        translator.options(),
+    )?;
+    Ok(changes)
+}
+
+fn unit(key: String, source: String, source_plural: Option<String>, target: Vec<String>) -> Unit {
+    Unit {
+        context: Some(key),
+        source,
+        source_plural,
+        target,
+        notes: Vec::new(),
+        needs_review: false,
+    }
+}
+
+impl Merge<'_> {
+    /// Adds the units of a source node and its children, with the current translation of
+    /// the `target` node
+    fn collect(&mut self, node: usize, target: Option<usize>, key: &str) {
+        let (source, existing) = (self.source, self.target);
+        let key = join(key, &source.nodes[node].key);
+
+        if source.is_plural(node) {
+            let form = |document: &Document, node: usize, category: &str| {
+                document
+                    .child(node, category)
+                    .and_then(|child| document.scalar(child))
+                    .map(|scalar| scalar.text.clone())
+            };
+            let other = form(source, node, "other").unwrap_or_default();
+            let one = form(source, node, "one").unwrap_or_else(|| other.clone());
+            let current: Vec<String> = match target {
+                Some(target) => self
+                    .plurals
+                    .forms()
+                    .iter()
+                    .map(|category| form(existing, target, category).unwrap_or_default())
+                    .collect(),
+                None => Vec::new(),
+            };
+            let current = match current.iter().all(String::is_empty) {
+                true => Vec::new(),
+                false => current,
+            };
+
+            self.unit_of.insert(node, self.units.len());
+            self.units.push(unit(key, one, Some(other), current));
+            return;
+        }
+
+        match &source.nodes[node].value {
+            Value::Scalar(scalar) => {
+                let current = target
+                    .and_then(|target| existing.scalar(target))
+                    .map(|scalar| scalar.text.clone())
+                    .filter(|text| !text.is_empty());
+                self.unit_of.insert(node, self.units.len());
+                self.units.push(unit(
+                    key,
+                    scalar.text.clone(),
+                    None,
+                    current.into_iter().collect(),
+                ));
+            }
+            Value::Parent | Value::Flow => {
+                for child in &source.nodes[node].children {
+                    let target_child =
+                        target.and_then(|target| existing.child(target, &source.nodes[*child].key));
+                    self.collect(*child, target_child, &key);
+                }
+            }
+            Value::Verbatim(_) => {}
+        }
+    }
+
+    /// The new translation of a source node, if it got one
+    fn translation(&self, node: usize) -> Option<&[String]> {
+        let unit = *self.unit_of.get(&node)?;
+        self.translated[unit]
+            .as_ref()
+            .map(|translated| translated.target.as_slice())
+    }
+
+    /// The new translation of a source node, or else the one it had
+    fn text(&self, node: usize) -> Option<&[String]> {
+        let unit = *self.unit_of.get(&node)?;
+        let text = match &self.translated[unit] {
+            Some(translated) => &translated.target,
+            None => &self.units[unit].target,
+        };
+        match text.iter().any(|form| !form.is_empty()) {
+            true => Some(text),
+            false => None,
+        }
+    }
+
+    /// Brings the target node `target` up to date with the source node `node`
+    fn edit(&self, node: usize, target: usize, edits: &mut Vec<Edit>) {
+        let source = &self.source.nodes[node];
+        let existing = &self.target.nodes[target];
+
+        if self.source.is_plural(node) {
+            let Some(forms) = self.translation(node) else {
+                return;
+            };
+            let style = self.plural_style(node);
+            let indent = self
+                .target
+                .children(target)
+                .next()
+                .map(|child| child.indent)
+                .unwrap_or(existing.indent + 2);
+
+            let mut missing = String::new();
+            for (position, category) in self.plurals.categories.iter().enumerate() {
+                let Some(form) = forms.get(position).or(forms.last()) else {
+                    continue;
+                };
+                let child = self.target.child(target, category);
+                match child.and_then(|child| self.target.scalar(child)) {
+                    Some(scalar) => edits.push(Edit {
+                        range: scalar.span.clone(),
+                        text: encode(form, scalar.style, scalar.block_indent),
+                    }),
+                    None if child.is_none() => missing.push_str(&format!(
+                        "{}{}: {}\n",
+                        " ".repeat(indent),
+                        category,
+                        encode(form, style, indent + 2)
+                    )),
+                    None => {}
+                }
+            }
+            if !missing.is_empty() {
+                edits.push(Edit {
+                    range: existing.end..existing.end,
+                    text: missing,
+                });
+            }
+            return;
+        }
+
+        match (&source.value, &existing.value) {
+            (Value::Scalar(_), Value::Scalar(scalar)) => {
+                if let Some(text) = self.translation(node) {
+                    let text = text.join("");
+                    edits.push(Edit {
+                        range: scalar.span.clone(),
+                        text: encode(&text, scalar.style_for(&text), scalar.block_indent),
+                    });
+                }
+            }
+            (Value::Parent, Value::Parent) => self.edit_children(node, target, edits),
+            (Value::Flow, Value::Flow) if source.children.len() == existing.children.len() => {
+                self.edit_children(node, target, edits)
+            }
+            (Value::Verbatim(_), _) => {}
+            // The target holds another kind of value (or a flow sequence of another length),
+            // which the source's replaces
+            _ => {
+                if let Some(text) = self.generate(node, existing.indent) {
+                    edits.push(Edit {
+                        range: existing.start..existing.end,
+                        text,
+                    });
+                }
+            }
+        }
+    }
+
+    /// Updates the children the target has, and inserts those it lacks after the previous
+    /// child of the source
+    fn edit_children(&self, node: usize, target: usize, edits: &mut Vec<Edit>) {
+        let source = &self.source.nodes[node];
+        let existing = &self.target.nodes[target];
+        let indent = self
+            .target
+            .children(target)
+            .next()
+            .map(|child| child.indent)
+            .unwrap_or_else(|| {
+                let step = self
+                    .source
+                    .children(node)
+                    .next()
+                    .map(|child| child.indent.saturating_sub(source.indent))
+                    .unwrap_or(2);
+                existing.indent + step
+            });
+
+        let mut position = existing.header_end;
+        for child in &source.children {
+            match self.target.child(target, &self.source.nodes[*child].key) {
+                Some(target_child) => {
+                    self.edit(*child, target_child, edits);
+                    position = self.target.nodes[target_child].end;
+                }
+                None => {
+                    if let Some(text) = self.generate(*child, indent) {
+                        edits.push(Edit {
+                            range: position..position,
+                            text,
+                        });
+                    }
+                }
+            }
+        }
+    }
+
+    /// The lines of a source node missing from the target, at `indent`, with its strings
+    /// translated. None when a string of it got no translation.
+    fn generate(&self, node: usize, indent: usize) -> Option<String> {
+        let source = &self.source.nodes[node];
+        let padding = " ".repeat(indent);
+        let step = self
+            .source
+            .children(node)
+            .next()
+            .map(|child| child.indent.saturating_sub(source.indent))
+            .filter(|step| *step > 0)
+            .unwrap_or(2);
+
+        if self.source.is_plural(node) {
+            let forms = self.text(node)?;
+            let style = self.plural_style(node);
+            let mut lines = format!("{}{}\n", padding, source.head);
+            for (position, category) in self.plurals.categories.iter().enumerate() {
+                let form = forms.get(position).or(forms.last())?;
+                lines.push_str(&format!(
+                    "{}{}: {}\n",
+                    " ".repeat(indent + step),
+                    category,
+                    encode(form, style, indent + step + 2)
+                ));
+            }
+            return Some(lines);
+        }
+
+        match &source.value {
+            Value::Scalar(scalar) => {
+                let text = self.text(node)?.join("");
+                let block_indent = indent + scalar.block_indent.saturating_sub(source.indent);
+                Some(format!(
+                    "{}{} {}\n",
+                    padding,
+                    source.head,
+                    encode_scalar(&text, scalar, block_indent)
+                ))
+            }
+            Value::Verbatim(raw) => Some(format!("{}{} {}\n", padding, source.head, raw)),
+            Value::Flow => {
+                let items = source
+                    .children
+                    .iter()
+                    .map(|child| match &self.source.nodes[*child].value {
+                        Value::Scalar(scalar) => self
+                            .text(*child)
+                            .map(|text| text.join(""))
+                            .map(|text| encode(&text, scalar.style_for(&text), 0)),
+                        Value::Verbatim(raw) => Some(raw.clone()),
+                        _ => None,
+                    })
+                    .collect::<Option<Vec<String>>>()?;
+                Some(format!(
+                    "{}{} [{}]\n",
+                    padding,
+                    source.head,
+                    items.join(", ")
+                ))
+            }
+            Value::Parent => {
+                let children: Vec<String> = source
+                    .children
+                    .iter()
+                    .filter_map(|child| {
+                        let offset = self.source.nodes[*child].indent - source.indent;
+                        self.generate(*child, indent + offset)
+                    })
+                    .collect();
+                if children.is_empty() && !source.children.is_empty() {
+                    return None;
+                }
+                Some(format!("{}{}\n{}", padding, source.head, children.concat()))
+            }
+        }
+    }
+
+    /// The quoting of the `other:` form of a source plural
+    fn plural_style(&self, node: usize) -> Style {
+        self.source
+            .child(node, "other")
+            .and_then(|child| self.source.scalar(child))
+            .map(|scalar| scalar.style)
+            .unwrap_or(Style::Double)
+    }
+}
+
+impl Document {
+    fn parse(content: &str) -> Self {
+        let root = Node {
+            key: String::new(),
+            head: String::new(),
+            indent: 0,
+            start: 0,
+            header_end: 0,
+            end: 0,
+            value: Value::Parent,
+            children: Vec::new(),
+        };
+        let mut document = Self { nodes: vec![root] };
+        let mut stack = vec![0];
+
+        let lines = lines(content);
+        let mut index = 0;
+        while index < lines.len() {
+            let (start, line, next) = lines[index];
+            index += 1;
+
+            let content_start = line.len() - line.trim_start_matches(' ').len();
+            let trimmed = line[content_start..].trim_end();
+            if trimmed.is_empty()
+                || trimmed.starts_with('#')
+                || trimmed.starts_with("---")
+                || trimmed == "..."
+            {
+                continue;
+            }
+            let indent = content_start;
+            let item = trimmed == "-" || trimmed.starts_with("- ");
+
+            while stack.len() > 1 {
+                let top = &document.nodes[*stack.last().unwrap()];
+                let parent_of_item = item
+                    && top.indent == indent
+                    && top.head != "-"
+                    && matches!(top.value, Value::Parent);
+                if top.indent < indent || parent_of_item {
+                    break;
+                }
+                stack.pop();
+            }
+            let parent = *stack.last().unwrap();
+
+            // The key, and the offset of what follows its colon
+            let (key, head_len) = match item {
+                true => (document.nodes[parent].children.len().to_string(), 1),
+                false => match split_key(trimmed) {
+                    Some(split) => split,
+                    // Continuation of a multi-line scalar
+                    None => {
+                        for node in &stack {
+                            document.nodes[*node].end = next;
+                        }
+                        continue;
+                    }
+                },
+            };
+
+            let mut rest_start = start + content_start + head_len;
+            let mut rest = trimmed[head_len..].trim_start();
+            rest_start += trimmed[head_len..].len() - rest.len();
+            let mut head = trimmed[..head_len].to_string();
+            if let Some(anchor) = rest.strip_prefix('&') {
+                let length = anchor.find(' ').unwrap_or(anchor.len()) + 1;
+                head = format!("{} {}", head, &rest[..length]);
+                let after = rest[length..].trim_start();
+                rest_start += rest.len() - after.len();
+                rest = after;
+            }
+
+            let mut end = next;
+            let mut children = Vec::new();
+            let value = if rest.is_empty() || rest.starts_with('#') {
+                Value::Parent
+            } else if key == "<<" || rest.starts_with('*') || rest.starts_with('{') {
+                Value::Verbatim(rest.to_string())
+            } else if rest.starts_with('|') || rest.starts_with('>') {
+                let span_start = lines.get(index).map(|line| line.0).unwrap_or(next);
+                let mut span_end = span_start;
+                let mut block_lines = Vec::new();
+                while let Some((line_start, line, line_next)) = lines.get(index).copied() {
+                    let blank = line.trim().is_empty();
+                    if !blank && line.len() - line.trim_start_matches(' ').len() <= indent {
+                        break;
+                    }
+                    block_lines.push(line);
+                    if !blank {
+                        span_end = line_start + line.len();
+                        end = line_next;
+                    }
+                    index += 1;
+                }
+                let block_indent = block_lines
+                    .iter()
+                    .find(|line| !line.trim().is_empty())
+                    .map(|line| line.len() - line.trim_start_matches(' ').len())
+                    .unwrap_or(indent + 2);
+                let folded = rest.starts_with('>');
+                Value::Scalar(Scalar {
+                    text: read_block(&content[span_start..span_end], block_indent, folded),
+                    style: match folded {
+                        true => Style::Folded,
+                        false => Style::Literal,
+                    },
+                    span: span_start..span_end,
+                    header: strip_comment(rest).to_string(),
+                    block_indent,
+                    flow: false,
+                })
+            } else if rest.starts_with('[') {
+                let inner = strip_comment(rest);
+                match inner
+                    .strip_prefix('[')
+                    .and_then(|inner| inner.strip_suffix(']'))
+                {
+                    Some(items) => {
+                        let items_start = rest_start + 1;
+                        for (position, range) in flow_items(items).into_iter().enumerate() {
+                            let raw = &items[range.clone()];
+                            let span = items_start + range.start..items_start + range.end;
+                            children.push(Node {
+                                key: position.to_string(),
+                                head: String::new(),
+                                indent: indent + 2,
+                                start,
+                                header_end: next,
+                                end: next,
+                                value: scalar(raw, span, true),
+                                children: Vec::new(),
+                            });
+                        }
+                        Value::Flow
+                    }
+                    None => Value::Verbatim(rest.to_string()),
+                }
+            } else {
+                let raw = match rest.starts_with(['"', '\'']) {
+                    true => quoted_length(rest).map(|length| &rest[..length]),
+                    false => Some(strip_comment(rest)),
+                };
+                match raw {
+                    Some(raw) => scalar(raw, rest_start..rest_start + raw.len(), false),
+                    None => Value::Verbatim(rest.to_string()),
+                }
+            };
+
+            let node = document.nodes.len();
+            document.nodes.push(Node {
+                key,
+                head,
+                indent,
+                start,
+                header_end: next,
+                end,
+                value,
+                children: Vec::new(),
+            });
+            for child in children {
+                let index = document.nodes.len();
+                document.nodes.push(child);
+                document.nodes[node].children.push(index);
+            }
+            document.nodes[parent].children.push(node);
+            for ancestor in &stack {
+                document.nodes[*ancestor].end = end;
+            }
+            stack.push(node);
+        }
+
+        document
+    }
+
+    fn children(&self, node: usize) -> impl Iterator<Item = &Node> {
+        self.nodes[node]
+            .children
+            .iter()
+            .map(|child| &self.nodes[*child])
+    }
+
+    fn child(&self, node: usize, key: &str) -> Option<usize> {
+        self.nodes[node]
+            .children
+            .iter()
+            .copied()
+            .find(|child| self.nodes[*child].key == key)
+    }
+
+    fn scalar(&self, node: usize) -> Option<&Scalar> {
+        match &self.nodes[node].value {
+            Value::Scalar(scalar) => Some(scalar),
+            _ => None,
+        }
+    }
+
+    /// A mapping of plural forms, like `{one: ..., other: ...}`
+    fn is_plural(&self, node: usize) -> bool {
+        let mut children = self.children(node).peekable();
+        children.peek().is_some()
+            && matches!(self.nodes[node].value, Value::Parent)
+            && self.child(node, "other").is_some()
+            && children.all(|child| {
+                PLURAL_KEYS.contains(&child.key.as_str()) && matches!(child.value, Value::Scalar(_))
+            })
+    }
+}
+
+/// The lines of `content`, as (start offset, line without its newline, offset of the next line)
+fn lines(content: &str) -> Vec<(usize, &str, usize)> {
+    let mut lines = Vec::new();
+    let mut start = 0;
+    for line in content.split_inclusive('\n') {
+        let next = start + line.len();
+        lines.push((start, line.trim_end_matches(['\n', '\r']), next));
+        start = next;
+    }
+    lines
+}
+
+/// The decoded key of a `key: value` line, and the length of the line up to its colon
+fn split_key(line: &str) -> Option<(String, usize)> {
+    if line.starts_with(['"', '\'']) {
+        let length = quoted_length(line)?;
+        let rest = line[length..].trim_start();
+        rest.strip_prefix(':')
+            .filter(|after| after.is_empty() || after.starts_with(' '))?;
+        let colon = line.len() - rest.len() + 1;
+        return Some((unquote(&line[..length]), colon));
+    }
+
+    let colon = line
+        .find(": ")
+        .or_else(|| line.strip_suffix(':').map(str::len))?;
+    let key = line[..colon].trim_end();
+    if key.is_empty() || key.contains(" #") {
+        return None;
+    }
+    Some((key.to_string(), colon + 1))
+}
+
+/// The value of a scalar written `raw`: strings are translated, the rest copied
+fn scalar(raw: &str, span: Range<usize>, flow: bool) -> Value {
+    let style = match raw.chars().next() {
+        Some('"') => Style::Double,
+        Some('\'') => Style::Single,
+        _ => Style::Plain,
+    };
+    if style == Style::Plain && (is_special(raw) || raw.starts_with(['*', '&', '!'])) {
+        return Value::Verbatim(raw.to_string());
+    }
+
+    Value::Scalar(Scalar {
+        text: unquote(raw),
+        style,
+        span,
+        header: String::new(),
+        block_indent: 0,
+        flow,
+    })
+}
+
+/// The byte ranges of the items of a flow sequence, without the brackets
+fn flow_items(items: &str) -> Vec<Range<usize>> {
+    let mut ranges = Vec::new();
+    let mut position = 0;
+    while position < items.len() {
+        let rest = &items[position..];
+        let start = position + (rest.len() - rest.trim_start().len());
+        let rest = &items[start..];
+        let length = match rest.starts_with(['"', '\'']) {
+            true => quoted_length(rest).unwrap_or(rest.len()),
+            false => rest.find(',').unwrap_or(rest.len()),
+        };
+        let item = items[start..start + length].trim_end();
+        if !item.is_empty() {
+            ranges.push(start..start + item.len());
+        }
+        position = match items[start + length..].find(',') {
+            Some(comma) => start + length + comma + 1,
+            None => items.len(),
+        };
+    }
+    ranges
+}
+
+/// The length of the quoted scalar at the start of `text`, with its quotes
+fn quoted_length(text: &str) -> Option<usize> {
+    let quote = text.chars().next()?;
+    let mut chars = text.char_indices().skip(1).peekable();
+    while let Some((index, c)) = chars.next() {
+        match (quote, c) {
+            ('"', '\\') => {
+                chars.next();
+            }
+            ('\'', '\'') if chars.peek().is_some_and(|(_, next)| *next == '\'') => {
+                chars.next();
+            }
+            (quote, c) if c == quote => return Some(index + 1),
+            _ => {}
+        }
+    }
+    None
+}
+
+fn strip_comment(text: &str) -> &str {
+    match text.find(" #") {
+        Some(index) => text[..index].trim_end(),
+        None => text.trim_end(),
+    }
+}
+
+/// Decodes a plain, single-quoted or double-quoted scalar
+fn unquote(raw: &str) -> String {
+    if let Some(inner) = raw
+        .strip_prefix('\'')
+        .and_then(|raw| raw.strip_suffix('\''))
+    {
+        return inner.replace("''", "'");
+    }
+    let Some(inner) = raw.strip_prefix('"').and_then(|raw| raw.strip_suffix('"')) else {
+        return raw.to_string();
+    };
+
+    let mut text = String::with_capacity(inner.len());
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            text.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => text.push('\n'),
+            Some('t') => text.push('\t'),
+            Some('r') => text.push('\r'),
+            Some('0') => text.push('\0'),
+            Some('u') => {
+                let code: String = chars.by_ref().take(4).collect();
+                if let Some(c) = u32::from_str_radix(&code, 16).ok().and_then(char::from_u32) {
+                    text.push(c);
+                }
+            }
+            Some(other) => text.push(other),
+            None => {}
+        }
+    }
+    text
+}
+
+/// The text of a block scalar's lines, without their indentation
+fn read_block(lines: &str, indent: usize, folded: bool) -> String {
+    let lines: Vec<&str> = lines
+        .lines()
+        .map(|line| line.get(indent..).unwrap_or_default())
+        .collect();
+    match folded {
+        false => lines.join("\n"),
+        true => lines
+            .split(|line| line.is_empty())
+            .map(|paragraph| paragraph.join(" "))
+            .collect::<Vec<_>>()
+            .join("\n"),
+    }
+}
+
+/// Writes `text` in the quoting `style`, falling back to double quotes when it can't be
+/// written that way. Block scalars get their lines, at `indent`, without the header.
+fn encode(text: &str, style: Style, indent: usize) -> String {
+    match style {
+        Style::Plain if is_plain(text) => text.to_string(),
+        Style::Single if !text.contains('\n') => format!("'{}'", text.replace('\'', "''")),
+        Style::Literal | Style::Folded if !text.is_empty() => {
+            let separator = match style {
+                Style::Folded => "\n\n",
+                _ => "\n",
+            };
+            let padding = " ".repeat(indent);
+            text.split('\n')
+                .map(|line| match line.is_empty() {
+                    true => String::new(),
+                    false => format!("{}{}", padding, line),
+                })
+                .collect::<Vec<_>>()
+                .join(separator)
+        }
+        _ => {
+            let escaped = text
+                .replace('\\', "\\\\")
+                .replace('"', "\\\"")
+                .replace('\n', "\\n")
+                .replace('\t', "\\t");
+            format!("\"{}\"", escaped)
+        }
+    }
+}
+
+/// Writes `text` after the key of a new line, in the style of `scalar` with the header of
+/// block scalars
+fn encode_scalar(text: &str, scalar: &Scalar, indent: usize) -> String {
+    match scalar.style {
+        Style::Literal | Style::Folded if !text.is_empty() => {
+            format!("{}\n{}", scalar.header, encode(text, scalar.style, indent))
+        }
+        style => encode(text, style, indent),
+    }
+}
+
+/// Plain scalars YAML reads as something other than a string
+fn is_special(text: &str) -> bool {
+    let lowercase = text.to_lowercase();
+    matches!(
+        lowercase.as_str(),
+        "~" | "null" | "true" | "false" | "yes" | "no" | "on" | "off"
+    ) || text.parse::<f64>().is_ok()
+}
+
+/// Strings that can be written without quotes
+fn is_plain(text: &str) -> bool {
+    !text.is_empty()
+        && text.trim() == text
+        && !text.starts_with([
+            '-', '?', ':', ',', '[', ']', '{', '}', '#', '&', '*', '!', '|', '>', '\'', '"', '%',
+            '@', '`',
+        ])
+        && !text.contains(": ")
+        && !text.contains(" #")
+        && !text.ends_with(':')
+        && !text.contains(['\n', '\t'])
+        && !is_special(text)
+}
+
+/// Applies the edits, in the order they were made for the same position
+fn apply(content: &str, mut edits: Vec<Edit>) -> String {
+    edits.sort_by_key(|edit| edit.range.start);
+
+    let mut modified = String::with_capacity(content.len());
+    let mut position = 0;
+    for edit in edits {
+        if edit.range.start < position {
+            continue;
+        }
+        modified.push_str(&content[position..edit.range.start]);
+        if edit.range.is_empty() && !modified.is_empty() && !modified.ends_with('\n') {
+            modified.push('\n');
+        }
+        modified.push_str(&edit.text);
+        position = edit.range.end;
+    }
+    modified.push_str(&content[position..]);
+    modified
+}
+
+/// The file of `lang` for the source file `path` of `source_lang`
+fn target_path(path: &Path, source_lang: &str, lang: &str) -> PathBuf {
+    let extension = path.extension().and_then(|e| e.to_str()).unwrap_or("yml");
+    let stem = path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or_default();
+
+    if stem == source_lang {
+        return path.with_file_name(format!("{}.{}", lang, extension));
+    }
+    if let Some(prefix) = stem.strip_suffix(&format!(".{}", source_lang)) {
+        return path.with_file_name(format!("{}.{}.{}", prefix, lang, extension));
+    }
+    if path.components().any(|c| c.as_os_str() == source_lang) {
+        return path
+            .components()
+            .map(|component| match component {
+                Component::Normal(name) if name == source_lang => Component::Normal(lang.as_ref()),
+                component => component,
+            })
+            .collect();
+    }
+    path.with_file_name(format!("{}.{}.{}", stem, lang, extension))
+}
+
+fn join(key: &str, name: &str) -> String {
+    match key.is_empty() {
+        true => name.to_string(),
+        false => format!("{}.{}", key, name),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::provider::{Pseudo, pseudolocalize};
+    use crate::translator::TranslateOptions;
+    use std::sync::Arc;
+
+    const SOURCE: &str = r#"# Shared strings
+en:
+  title: &title Welcome  # the home page
+  apostrophe: 'It''s here'
+  escaped: "Café\ttime\n"
+  enabled: true
+  home: *title
+  intro: |
+    First line
+      indented
+  summary: >-
+    Folded
+    paragraph
+  days: [Monday, "Tuesday, maybe"]
+  items:
+    - Apple
+    - Pear
+  files:
+    one: One file
+    other: "%{count} files"
+"#;
+
+    fn text<'a>(document: &'a Document, path: &[&str]) -> Option<&'a str> {
+        let mut node = 0;
+        for key in path {
+            node = document.child(node, key)?;
+        }
+        document.scalar(node).map(|scalar| scalar.text.as_str())
+    }
+
+    #[test]
+    fn parses_locale_files() {
+        let document = Document::parse(SOURCE);
+        let root = document.child(0, "en").unwrap();
+        let keys: Vec<&str> = document
+            .children(root)
+            .map(|node| node.key.as_str())
+            .collect();
+        assert_eq!(
+            keys,
+            vec![
+                "title",
+                "apostrophe",
+                "escaped",
+                "enabled",
+                "home",
+                "intro",
+                "summary",
+                "days",
+                "items",
+                "files"
+            ]
+        );
+        assert_eq!(text(&document, &["en", "title"]), Some("Welcome"));
+        assert_eq!(text(&document, &["en", "apostrophe"]), Some("It's here"));
+        assert_eq!(text(&document, &["en", "escaped"]), Some("Café\ttime\n"));
+        assert_eq!(text(&document, &["en", "enabled"]), None);
+        assert_eq!(text(&document, &["en", "home"]), None);
+        assert_eq!(
+            text(&document, &["en", "intro"]),
+            Some("First line\n  indented")
+        );
+        assert_eq!(
+            text(&document, &["en", "summary"]),
+            Some("Folded paragraph")
+        );
+        assert_eq!(
+            text(&document, &["en", "days", "1"]),
+            Some("Tuesday, maybe")
+        );
+        assert_eq!(text(&document, &["en", "items", "1"]), Some("Pear"));
+
+        let files = document.child(root, "files").unwrap();
+        assert!(document.is_plural(files));
+        assert!(!document.is_plural(document.child(root, "items").unwrap()));
+    }
+
+    #[test]
+    fn quotes_what_plain_scalars_cant_hold() {
+        assert_eq!(encode("Hello", Style::Plain, 0), "Hello");
+        assert_eq!(encode("yes", Style::Plain, 0), "\"yes\"");
+        assert_eq!(encode("Note: this", Style::Plain, 0), "\"Note: this\"");
+        assert_eq!(
+            encode("%{count} files", Style::Plain, 0),
+            "\"%{count} files\""
+        );
+        assert_eq!(encode("C'est là", Style::Single, 0), "'C''est là'");
+        assert_eq!(encode("Two\nlines", Style::Single, 0), "\"Two\\nlines\"");
+        assert_eq!(
+            encode("Two\nlines", Style::Literal, 4),
+            "    Two\n    lines"
+        );
+        assert_eq!(
+            unquote(&encode("Say \"hi\"\n", Style::Double, 0)),
+            "Say \"hi\"\n"
+        );
+    }
+
+    #[test]
+    fn names_the_file_of_each_language() {
+        let target = |path: &str| target_path(Path::new(path), "en", "es");
+        assert_eq!(
+            target("config/locales/en.yml"),
+            Path::new("config/locales/es.yml")
+        );
+        assert_eq!(
+            target("config/locales/devise.en.yml"),
+            Path::new("config/locales/devise.es.yml")
+        );
+        assert_eq!(
+            target("config/locales/en/users.yml"),
+            Path::new("config/locales/es/users.yml")
+        );
+        assert_eq!(target("locales/app.yaml"), Path::new("locales/app.es.yaml"));
+    }
+
+    #[tokio::test]
+    async fn adds_the_missing_keys_in_place() {
+        let dir =
+            std::env::temp_dir().join(format!("ai-gettext-translator-yaml-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let source = dir.join("en.yml");
+        fs::write(
+            &source,
+            "en:\n  greeting: Hello\n  menu:\n    open: Open\n    close: Close\n",
+        )
+        .unwrap();
+        let path = dir.join("es.yml");
+        fs::write(
+            &path,
+            "# Reviewed\nes:\n  greeting: Hola # keep\n  menu:\n    open: Abrir\n",
+        )
+        .unwrap();
+
+        let translator = Translator::new(Arc::new(Pseudo), TranslateOptions::default()).unwrap();
+        let target = Target {
+            source,
+            source_lang: "en".to_string(),
+            path: path.clone(),
+            lang: "es".to_string(),
+        };
+        assert_eq!(translate_file(&translator, &target).await.unwrap(), 1);
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            format!(
+                "# Reviewed\nes:\n  greeting: Hola # keep\n  menu:\n    open: Abrir\n    close: {}\n",
+                encode(&pseudolocalize("Close"), Style::Plain, 0)
+            )
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,181 @@
+use anyhow::{Result, bail};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use crate::provider::AiRequest;
+
+/// Mandated translations of terms, loaded from a CSV file like:
+///
+/// ```text
+/// term,es,fr
+/// checkout,pago,paiement
+/// Acme Cloud,,
+/// ```
+///
+/// An empty cell means the term must be kept as is, which suits brand and product names.
+#[derive(Default)]
+pub struct Glossary {
+    terms: Vec<Term>,
+}
+
+struct Term {
+    source: String,
+    translations: BTreeMap<String, String>,
+}
+
+impl Glossary {
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)
+            .map_err(|e| anyhow::anyhow!("Error reading glossary {}: {}", path.display(), e))?;
+        Self::parse(&content)
+            .map_err(|e| anyhow::anyhow!("Error reading glossary {}: {}", path.display(), e))
+    }
+
+    pub fn parse(content: &str) -> Result<Self> {
+        let mut rows = content
+            .lines()
+            .filter(|line| !line.trim().is_empty() && !line.starts_with('#'))
+            .map(parse_csv_row);
+
+        let Some(header) = rows.next() else {
+            return Ok(Self::default());
+        };
+        let langs: Vec<String> = header?.into_iter().skip(1).collect();
+
+        let mut terms = Vec::new();
+        for row in rows {
+            let row = row?;
+            let Some(source) = row.first().filter(|source| !source.is_empty()) else {
+                continue;
+            };
+
+            let translations = langs
+                .iter()
+                .zip(row.iter().skip(1).chain(std::iter::repeat(&String::new())))
+                .map(|(lang, translation)| {
+                    let translation = match translation.is_empty() {
+                        true => source.clone(),
+                        false => translation.clone(),
+                    };
+                    (lang.clone(), translation)
+                })
+                .collect();
+
+            terms.push(Term {
+                source: source.clone(),
+                translations,
+            });
+        }
+
+        Ok(Self { terms })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.terms.is_empty()
+    }
+
+    /// The (term, translation) pairs for `lang` that appear in `text`
+    pub fn matches<'a>(&'a self, text: &str, lang: &str) -> Vec<(&'a str, &'a str)> {
+        self.terms
+            .iter()
+            .filter(|term| contains_term(text, &term.source))
+            .filter_map(|term| {
+                let translation = term.translations.get(lang)?;
+                Some((term.source.as_str(), translation.as_str()))
+            })
+            .collect()
+    }
+
+    /// Adds the terms used by the request's messages to its instructions
+    pub fn annotate(&self, request: &mut AiRequest) {
+        let mut terms: Vec<(&str, &str)> = request
+            .texts
+            .iter()
+            .flat_map(|text| self.matches(text, &request.lang))
+            .collect();
+        terms.sort();
+        terms.dedup();
+
+        if terms.is_empty() {
+            return;
+        }
+
+        request
+            .instructions
+            .push_str("\n\nAlways translate these terms as given by the glossary:\n");
+        for (source, translation) in terms {
+            request
+                .instructions
+                .push_str(&format!("\t- \"{}\" → \"{}\"\n", source, translation));
+        }
+    }
+
+    /// The glossary terms of `source` whose mandated translation is missing from `translation`
+    pub fn violations<'a>(
+        &'a self,
+        source: &str,
+        translation: &str,
+        lang: &str,
+    ) -> Vec<(&'a str, &'a str)> {
+        self.matches(source, lang)
+            .into_iter()
+            .filter(|(_, expected)| !contains_term(translation, expected))
+            .collect()
+    }
+
+    /// A stable rendering of the glossary, so cached translations follow glossary changes
+    pub fn signature(&self) -> String {
+        self.terms
+            .iter()
+            .map(|term| format!("{}={:?}", term.source, term.translations))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+/// Case-insensitive search for `term` as a whole word
+fn contains_term(text: &str, term: &str) -> bool {
+    let text = text.to_lowercase();
+    let term = term.to_lowercase();
+    if term.is_empty() {
+        return false;
+    }
+
+    text.match_indices(&term).any(|(start, _)| {
+        let before = text[..start].chars().next_back();
+        let after = text[start + term.len()..].chars().next();
+        !before.is_some_and(char::is_alphanumeric) && !after.is_some_and(char::is_alphanumeric)
+    })
+}
+
+/// Splits a CSV row, honoring double-quoted fields with `""` escapes
+fn parse_csv_row(line: &str) -> Result<Vec<String>> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = line.trim_end_matches('\r').chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match (c, quoted) {
+            ('"', true) if chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            ('"', true) => quoted = false,
+            ('"', false) if field.trim().is_empty() => {
+                field.clear();
+                quoted = true;
+            }
+            (',', false) => fields.push(std::mem::take(&mut field).trim().to_string()),
+            (c, _) => field.push(c),
+        }
+    }
+
+    if quoted {
+        bail!("unterminated quoted field: {}", line);
+    }
+
+    fields.push(field.trim().to_string());
+    Ok(fields)
+}
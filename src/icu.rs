@@ -0,0 +1,359 @@
+//! ICU MessageFormat in messages, like `{count, plural, one {# file} other {# files}}`: the
+//! skeleton of arguments, keywords, selectors and braces, and the text parts of each branch,
+//! which are the only ones translated. The translation is put back into the skeleton and
+//! must still parse with the same one.
+
+use crate::placeholders;
+
+/// The keywords of the arguments whose branches are messages of their own
+const COMPLEX: &[&str] = &["plural", "select", "selectordinal"];
+
+/// A piece of an ICU message
+enum Part {
+    /// Arguments, keywords, selectors and braces, kept as written
+    Syntax(String),
+    /// Text, with the simple arguments like `{name}` and `#` it has
+    Text(String),
+}
+
+/// A message with at least one `plural`, `select` or `selectordinal` argument
+pub struct Pattern {
+    parts: Vec<Part>,
+    /// The simple and formatted arguments, like `{name}` or `{n, number}`, without whitespace
+    arguments: Vec<String>,
+}
+
+impl Pattern {
+    /// The text parts to translate, in order and trimmed: those with letters
+    pub fn texts(&self) -> Vec<&str> {
+        self.parts
+            .iter()
+            .filter_map(|part| match part {
+                Part::Text(text) if is_translatable(text) => Some(text.trim()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Every text part, translated or not, one after another
+    pub fn text(&self) -> String {
+        let texts: Vec<&str> = self
+            .parts
+            .iter()
+            .filter_map(|part| match part {
+                Part::Text(text) => Some(text.as_str()),
+                Part::Syntax(_) => None,
+            })
+            .collect();
+        texts.join(" ")
+    }
+
+    /// The message with its text parts replaced by `translations`, one for each of `texts`,
+    /// each keeping the spaces around the part it replaces
+    pub fn fill(&self, translations: &[String]) -> String {
+        let mut translations = translations.iter();
+        let mut filled = String::new();
+        for part in &self.parts {
+            match part {
+                Part::Text(text) if is_translatable(text) => {
+                    let translation = translations.next().map_or(text.trim(), |t| t.trim());
+                    let leading = &text[..text.len() - text.trim_start().len()];
+                    let trailing = &text[text.trim_end().len()..];
+                    filled.push_str(leading);
+                    filled.push_str(translation);
+                    filled.push_str(trailing);
+                }
+                Part::Syntax(text) | Part::Text(text) => filled.push_str(text),
+            }
+        }
+        filled
+    }
+
+    /// The skeleton, without its whitespace, for comparing two messages
+    fn skeleton(&self) -> Vec<String> {
+        self.parts
+            .iter()
+            .filter_map(|part| match part {
+                Part::Syntax(syntax) => Some(without_whitespace(syntax)),
+                Part::Text(_) => None,
+            })
+            .collect()
+    }
+}
+
+/// The ICU pattern of `text`, if it has a `plural`, `select` or `selectordinal` argument and
+/// parses
+pub fn parse(text: &str) -> Option<Pattern> {
+    parse_any(text).filter(|pattern| {
+        pattern
+            .parts
+            .iter()
+            .any(|part| matches!(part, Part::Syntax(_)))
+    })
+}
+
+/// Checks that `translation` is still ICU MessageFormat with the skeleton of `source`, when
+/// `source` is. Returns what doesn't match.
+pub fn mismatch(source: &str, translation: &str) -> Option<String> {
+    let source = parse(source)?;
+    let Some(translated) = parse_any(translation) else {
+        return Some("doesn't parse as ICU MessageFormat".to_string());
+    };
+    if source.skeleton() != translated.skeleton() {
+        return Some("different arguments, selectors or branches".to_string());
+    }
+    let mut expected = source.arguments.clone();
+    let mut got = translated.arguments.clone();
+    expected.sort();
+    got.sort();
+    if let Some(missing) = expected.iter().find(|argument| !got.contains(argument)) {
+        return Some(format!("missing argument {}", missing));
+    }
+    if expected != got {
+        return Some("different arguments".to_string());
+    }
+    None
+}
+
+/// What the model is told about a message in ICU MessageFormat
+pub const GUIDANCE: &str = "The message is in ICU MessageFormat: translate only the text of its branches, keeping the argument names, the keywords like plural and select, the selectors like one and other, the braces and `#` exactly as written.";
+
+fn is_translatable(text: &str) -> bool {
+    placeholders::strip(text).chars().any(char::is_alphabetic)
+}
+
+fn without_whitespace(text: &str) -> String {
+    text.chars().filter(|c| !c.is_whitespace()).collect()
+}
+
+/// Parses any ICU message, with or without complex arguments
+fn parse_any(text: &str) -> Option<Pattern> {
+    let mut parser = Parser {
+        text,
+        pos: 0,
+        parts: Vec::new(),
+        arguments: Vec::new(),
+    };
+    parser.message(false)?;
+    Some(Pattern {
+        parts: parser.parts,
+        arguments: parser.arguments,
+    })
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    pos: usize,
+    parts: Vec<Part>,
+    arguments: Vec<String>,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<char> {
+        self.text[self.pos..].chars().next()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += c.len_utf8();
+        Some(c)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.pos += 1;
+        }
+    }
+
+    /// Reads up to the next `,` or `}`, returning it trimmed
+    fn word(&mut self) -> Option<&str> {
+        let start = self.pos;
+        let end = self.text[start..].find([',', '}', '{'])? + start;
+        self.pos = end;
+        Some(self.text[start..end].trim())
+    }
+
+    /// Parses a message up to its end, or up to the `}` closing it when `nested`, which is
+    /// left for the caller
+    fn message(&mut self, nested: bool) -> Option<()> {
+        let mut text_start = self.pos;
+        loop {
+            let start = self.pos;
+            match self.peek() {
+                None if nested => return None,
+                None => break,
+                Some('}') if nested => break,
+                Some('}') => return None,
+                Some('\'') => {
+                    self.bump();
+                    match self.peek() {
+                        Some('\'') => {
+                            self.bump();
+                        }
+                        Some('{' | '}' | '#' | '|') => {
+                            let end = self.text[self.pos + 1..].find('\'');
+                            self.pos = end.map_or(self.text.len(), |end| self.pos + 1 + end + 1);
+                        }
+                        _ => {}
+                    }
+                }
+                Some('{') => {
+                    self.bump();
+                    self.word()?;
+                    if self.peek() == Some('}') {
+                        self.bump();
+                        self.arguments
+                            .push(without_whitespace(&self.text[start..self.pos]));
+                        continue;
+                    }
+                    if self.bump()? != ',' {
+                        return None;
+                    }
+                    let kind = self.word()?.to_string();
+                    if !COMPLEX.contains(&kind.as_str()) {
+                        self.formatted()?;
+                        self.arguments
+                            .push(without_whitespace(&self.text[start..self.pos]));
+                        continue;
+                    }
+                    if self.bump()? != ',' {
+                        return None;
+                    }
+                    self.parts
+                        .push(Part::Text(self.text[text_start..start].to_string()));
+                    self.branches(start)?;
+                    text_start = self.pos;
+                }
+                Some(_) => {
+                    self.bump();
+                }
+            }
+        }
+        self.parts
+            .push(Part::Text(self.text[text_start..self.pos].to_string()));
+        Some(())
+    }
+
+    /// Skips the style of a formatted argument, like `number, integer`, up to its closing
+    /// brace
+    fn formatted(&mut self) -> Option<()> {
+        let mut depth = 1;
+        while depth > 0 {
+            match self.bump()? {
+                '{' => depth += 1,
+                '}' => depth -= 1,
+                _ => {}
+            }
+        }
+        Some(())
+    }
+
+    /// Parses the selectors and branches of a complex argument starting at `start`, after
+    /// its keyword, up to its closing brace
+    fn branches(&mut self, start: usize) -> Option<()> {
+        let mut syntax_start = start;
+        let mut branches = 0;
+        loop {
+            self.skip_whitespace();
+            match self.peek()? {
+                '}' => {
+                    self.bump();
+                    break;
+                }
+                '{' => return None,
+                _ => {}
+            }
+            let selector_start = self.pos;
+            while self
+                .peek()
+                .is_some_and(|c| !c.is_whitespace() && c != '{' && c != '}')
+            {
+                self.bump();
+            }
+            if self.text[selector_start..self.pos].starts_with("offset:") {
+                continue;
+            }
+            self.skip_whitespace();
+            if self.bump()? != '{' {
+                return None;
+            }
+            self.parts
+                .push(Part::Syntax(self.text[syntax_start..self.pos].to_string()));
+            self.message(true)?;
+            syntax_start = self.pos;
+            self.bump();
+            branches += 1;
+        }
+        self.parts
+            .push(Part::Syntax(self.text[syntax_start..self.pos].to_string()));
+        (branches > 0).then_some(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FILES: &str = "{count, plural, one {# file} other {# files}}";
+
+    #[test]
+    fn finds_the_text_of_each_branch() {
+        let pattern = parse(FILES).unwrap();
+        assert_eq!(pattern.texts(), vec!["# file", "# files"]);
+        assert_eq!(
+            pattern.fill(&["# archivo".to_string(), "# archivos".to_string()]),
+            "{count, plural, one {# archivo} other {# archivos}}"
+        );
+
+        let pattern =
+            parse("Hi {name}, {gender, select, female {she wrote} other {they wrote}} it").unwrap();
+        assert_eq!(
+            pattern.texts(),
+            vec!["Hi {name},", "she wrote", "they wrote", "it"]
+        );
+        assert_eq!(pattern.arguments, vec!["{name}"]);
+    }
+
+    #[test]
+    fn reads_nested_and_offset_arguments() {
+        let message = "{n, plural, offset:1 =0 {Nobody} one {{who, select, me {You} other {{who}}}} other {# others}}";
+        let pattern = parse(message).unwrap();
+        assert_eq!(pattern.texts(), vec!["Nobody", "You", "# others"]);
+        let translations: Vec<String> = pattern.texts().iter().map(|t| t.to_string()).collect();
+        assert_eq!(pattern.fill(&translations), message);
+    }
+
+    #[test]
+    fn only_takes_messages_with_complex_arguments() {
+        assert!(parse("Hello {name}").is_none());
+        assert!(parse("{n, number, integer} items").is_none());
+        assert!(parse("Plain text").is_none());
+        assert!(parse("{count, plural, one {# file} other {# files}").is_none());
+        assert!(parse("{count, plural, }").is_none());
+        // Quoted braces are text
+        let pattern = parse("'{'literal'}' {n, plural, other {# x}}").unwrap();
+        assert_eq!(pattern.texts()[0], "'{'literal'}'");
+    }
+
+    #[test]
+    fn checks_translations_keep_the_skeleton() {
+        let good = "{count, plural, one {# archivo} other {# archivos}}";
+        assert_eq!(mismatch(FILES, good), None);
+        assert_eq!(
+            mismatch(FILES, "{count, plural, one {# archivo} other {# archivos}"),
+            Some("doesn't parse as ICU MessageFormat".to_string())
+        );
+        assert_eq!(
+            mismatch(FILES, "{total, plural, one {# archivo} other {# archivos}}"),
+            Some("different arguments, selectors or branches".to_string())
+        );
+        assert_eq!(
+            mismatch(
+                "{n, plural, other {{name} has # files}}",
+                "{n, plural, other {# archivos}}"
+            ),
+            Some("missing argument {name}".to_string())
+        );
+        assert_eq!(mismatch("Hello {name}", "Hola"), None);
+    }
+}
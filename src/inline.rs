@@ -1,53 +1,95 @@
-use regex::Regex;
-use std::{fs, path::PathBuf};
-use walkdir::WalkDir;
+use std::{fs, ops::Range, path::PathBuf};
 
 use crate::{
-    logger::{log_change, log_diff},
-    openai::{AiRequest, OpenAI},
+    atomic,
+    cache::{CacheKey, TranslationCache},
+    filter::PathFilter,
+    logger::{log_change, log_diff, log_entry_failed},
+    placeholders,
+    prompt::{INJECTION_GUARD, delimit, strip_delimiters, unrelated_answer},
+    provider::{AiRequest, TranslationProvider},
+    sanitize,
+    scanner::{Call, Scanner},
+    translator::{self, iso_to_name},
 };
 
-pub async fn run(folder: PathBuf, dry_run: bool, api_key: Option<String>) -> anyhow::Result<()> {
-    let openai = OpenAI::new(api_key);
-    let gettext_regex = create_gettext_regex();
+/// Extensions scanned when `--ext` isn't given: Elixir's sources, scripts and templates
+pub const DEFAULT_EXTENSIONS: &str = "ex,exs,eex,heex,leex";
 
-    for entry in WalkDir::new(folder).into_iter().filter_map(Result::ok) {
-        if !is_processable_file(&entry) {
+/// Functions matched when `--functions` isn't given: the macros of Elixir's Gettext
+pub const DEFAULT_FUNCTIONS: &str =
+    "gettext,dgettext,pgettext,dpgettext,ngettext,dngettext,pngettext,dpngettext";
+
+/// The context of the answers of inline runs in the translation cache, whose prompt keeps the
+/// strings already in the language
+const CACHE_CONTEXT: &str = "inline";
+
+/// Settings of an inline run
+pub struct InlineOptions {
+    /// Extensions of the files scanned, with or without the leading dot
+    pub extensions: Vec<String>,
+    /// Gettext functions whose messages are translated, as `name` or `name:N`
+    pub functions: Vec<String>,
+    /// Language the messages are translated to
+    pub lang: String,
+    pub filter: PathFilter,
+    pub dry_run: bool,
+    /// Keep a `.bak` copy of every modified file
+    pub backup: bool,
+    /// Translation cache file, or None to disable caching. Strings already in the language
+    /// are cached as they are, so a run over unchanged files calls no API.
+    pub cache: Option<PathBuf>,
+}
+
+pub async fn run(
+    folder: PathBuf,
+    options: &InlineOptions,
+    provider: &dyn TranslationProvider,
+) -> anyhow::Result<()> {
+    let scanner = Scanner::new(&options.functions)?;
+    let mut cache = match &options.cache {
+        Some(path) => TranslationCache::load(path)?,
+        None => TranslationCache::disabled(),
+    };
+
+    for path in options.filter.files(&folder, &folder) {
+        if !is_processable_file(&path, &options.extensions) {
             continue;
         }
 
-        let path = entry.path();
-        process_file(path, &openai, &gettext_regex, dry_run).await?;
+        let processed = process_file(&path, provider, &scanner, options, &mut cache).await;
+        cache.save()?;
+        processed?;
     }
 
     Ok(())
 }
 
-fn create_gettext_regex() -> Regex {
-    Regex::new(r#"gettext\s*\(\s*"((?:\\.|[^"\\])*)"\s*(?:,\s*[^)]*)?\)"#).unwrap()
-}
-
-fn is_processable_file(entry: &walkdir::DirEntry) -> bool {
-    if !entry.file_type().is_file() {
-        return false;
-    }
-
-    match entry.path().extension() {
-        Some(ext) if ext == "ex" => true,
-        _ => false,
-    }
+pub(crate) fn is_processable_file(path: &std::path::Path, extensions: &[String]) -> bool {
+    matches!(
+        path.extension().and_then(|ext| ext.to_str()),
+        Some(ext) if extensions.iter().any(|e| e.trim_start_matches('.') == ext)
+    )
 }
 
 async fn process_file(
     path: &std::path::Path,
-    openai: &OpenAI,
-    regex: &Regex,
-    dry_run: bool,
+    provider: &dyn TranslationProvider,
+    scanner: &Scanner,
+    options: &InlineOptions,
+    cache: &mut TranslationCache,
 ) -> anyhow::Result<()> {
     let content = fs::read_to_string(path)?;
 
-    let (modified_content, changes_made) =
-        translate_gettext_strings(&content, openai, regex, dry_run).await?;
+    let (modified_content, changes_made) = translate_gettext_strings(
+        &content,
+        provider,
+        &scanner.calls(path, &content),
+        &options.lang,
+        options.dry_run,
+        cache,
+    )
+    .await?;
 
     if changes_made {
         log_diff(
@@ -56,8 +98,11 @@ async fn process_file(
             &modified_content,
         );
 
-        if !dry_run {
-            fs::write(path, modified_content)?;
+        if !options.dry_run {
+            if options.backup {
+                atomic::backup(path)?;
+            }
+            atomic::write(path, modified_content)?;
         }
     }
 
@@ -66,54 +111,179 @@ async fn process_file(
 
 async fn translate_gettext_strings(
     content: &str,
-    openai: &OpenAI,
-    regex: &Regex,
+    provider: &dyn TranslationProvider,
+    calls: &[Call],
+    lang: &str,
     dry_run: bool,
+    cache: &mut TranslationCache,
 ) -> anyhow::Result<(String, bool)> {
-    let mut modified = content.to_string();
-    let mut any_changes = false;
+    // The ranges of the strings to replace, and their translations
+    let mut edits: Vec<(Range<usize>, String)> = Vec::new();
+
+    for call in calls {
+        // The whitespace around the message, like the lines of a heredoc, is kept
+        let ranges: Vec<Range<usize>> = std::iter::once(&call.message)
+            .chain(&call.plural)
+            .map(|literal| literal.trimmed(content))
+            .collect();
+        let texts: Vec<&str> = ranges.iter().map(|range| &content[range.clone()]).collect();
+        // Placeholders and punctuation are the same in every language
+        if !texts
+            .iter()
+            .any(|text| placeholders::strip(text).chars().any(char::is_alphabetic))
+        {
+            continue;
+        }
+
+        let key = CacheKey {
+            msgctxt: None,
+            msgid: texts[0],
+            msgid_plural: texts.get(1).copied(),
+            lang,
+            context: Some(CACHE_CONTEXT),
+        };
+        let cached = cache
+            .get(&key)
+            .filter(|translations| translations.len() == texts.len());
+        let translations = match (cached, texts.as_slice()) {
+            (Some(translations), _) => translations,
+            (None, [text]) => vec![translate_text(provider, text, lang).await?],
+            (None, _) => {
+                let answer = translate_plural(provider, texts[0], texts[1], lang).await?;
+                match translator::parse_json_array(&answer, 2) {
+                    Ok(translations) => translations,
+                    Err(e) => {
+                        log_entry_failed(lang, texts[0], &e.to_string());
+                        continue;
+                    }
+                }
+            }
+        };
+        let rejection = texts
+            .iter()
+            .zip(&translations)
+            .find_map(|(text, translation)| {
+                sanitize::rejection(text, translation)
+                    .map(str::to_string)
+                    .or_else(|| {
+                        unrelated_answer(text, translation)
+                            .map(|reason| format!("{} (possible prompt injection)", reason))
+                    })
+            });
+        if let Some(reason) = rejection {
+            log_entry_failed(lang, texts[0], &reason);
+            continue;
+        }
 
-    for cap in regex.captures_iter(content) {
-        let original = &cap[0];
-        let text = &cap[1];
+        // The translation replaces the strings, and is already in the language next time
+        cache.insert(&key, translations.clone());
+        cache.insert(
+            &CacheKey {
+                msgid: &translations[0],
+                msgid_plural: translations.get(1).map(String::as_str),
+                ..key
+            },
+            translations.clone(),
+        );
+
+        for ((range, text), translation) in ranges.into_iter().zip(texts).zip(translations) {
+            if text != translation {
+                log_change(text, &translation, "INLINE", dry_run);
+                edits.push((range, translation));
+            }
+        }
+    }
 
-        let translation = translate_text(openai, text).await?;
-        let new_text = original.replace(text, &translation);
+    let any_changes = !edits.is_empty();
+    Ok((splice(content, edits), any_changes))
+}
 
-        if original != &new_text {
-            log_change(text, &translation, "INLINE", dry_run);
-            any_changes = true;
-            modified = modified.replace(original, &new_text);
+/// Replaces each range of `content` with its text, leaving every other occurrence of the
+/// same string alone. Ranges overlapping an earlier one are skipped.
+fn splice(content: &str, mut edits: Vec<(Range<usize>, String)>) -> String {
+    edits.sort_by_key(|(range, _)| range.start);
+    let mut spliced = String::with_capacity(content.len());
+    let mut copied = 0;
+    for (range, text) in edits {
+        if range.start < copied {
+            continue;
         }
+        spliced.push_str(&content[copied..range.start]);
+        spliced.push_str(&text);
+        copied = range.end;
     }
+    spliced.push_str(&content[copied..]);
+    spliced
+}
+
+async fn translate_text(
+    provider: &dyn TranslationProvider,
+    input: &str,
+    lang: &str,
+) -> anyhow::Result<String> {
+    let language = &iso_to_name(lang);
+    let prompt = build_translation_prompt(input, language);
+    let request =
+        build_translation_request(prompt, language).with_texts(lang, vec![input.to_string()]);
 
-    Ok((modified, any_changes))
+    let answer = strip_delimiters(provider.send(&request).await?.text);
+    Ok(sanitize::clean(input, &answer))
 }
 
-async fn translate_text(openai: &OpenAI, input: &str) -> anyhow::Result<String> {
-    let prompt = build_translation_prompt(input);
-    let request = build_translation_request(prompt);
+/// Translates the singular and plural forms of an `ngettext` call together, answered as a
+/// JSON array of the two
+async fn translate_plural(
+    provider: &dyn TranslationProvider,
+    singular: &str,
+    plural: &str,
+    lang: &str,
+) -> anyhow::Result<String> {
+    let language = &iso_to_name(lang);
+    let prompt = format!(
+        "Translate the singular and plural forms of this gettext message to {}, preserving placeholders like `%{{...}}`.
 
-    openai.send(request).await
+		Important:
+		- If they are already in {}, just return the original texts.
+		- Answer with a JSON array of exactly 2 strings: the singular, then the plural.
+		- Just return the JSON array, do not add any other text, comments or code fences.
+
+		Singular:
+		{}
+		Plural:
+		{}",
+        language,
+        language,
+        delimit(singular),
+        delimit(plural)
+    );
+    let request = build_translation_request(prompt, language)
+        .with_texts(lang, vec![singular.to_string(), plural.to_string()]);
+
+    Ok(provider.send(&request).await?.text)
 }
 
-fn build_translation_prompt(input: &str) -> String {
+fn build_translation_prompt(input: &str, language: &str) -> String {
     format!(
-        "Translate this gettext message to English, preserving placeholders like `%{{...}}`.
+        "Translate this gettext message to {}, preserving placeholders like `%{{...}}`.
 
 		Important:
-		- If it's already in English, just return the original text.
+		- If it's already in {}, just return the original text.
 		- Just return the translation, do not add any other text or comments.
 
-		Text to translate:
-		\"{}\"",
-        input
+		Text to translate, between <text> and </text>:
+		{}",
+        language,
+        language,
+        delimit(input)
     )
 }
 
-fn build_translation_request(prompt: String) -> AiRequest {
+fn build_translation_request(prompt: String, language: &str) -> AiRequest {
     AiRequest::new(
-		"You are a professional translator for gettext messages. You will translate the message to English. You must preserve placeholder, written in the format `%{placeholder}`.".into(),
-		prompt
-	)
+        format!(
+            "You are a professional translator for gettext messages. You will translate the message to {}. You must preserve placeholder, written in the format `%{{placeholder}}`.\n\n{}",
+            language, INJECTION_GUARD
+        ),
+        prompt,
+    )
 }
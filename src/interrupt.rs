@@ -0,0 +1,52 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use crate::logger::{log_interrupted, log_run_timeout};
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+static TIMED_OUT: AtomicBool = AtomicBool::new(false);
+
+/// Exit code of a run stopped with Ctrl-C, as shells report for SIGINT
+pub const EXIT_CODE: i32 = 130;
+/// Exit code of a run stopped by `--run-timeout`, as `timeout(1)` reports
+pub const TIMEOUT_EXIT_CODE: i32 = 124;
+
+/// Catches Ctrl-C so that a run can stop scheduling requests, wait for those in flight and
+/// write what it has translated. A second Ctrl-C quits right away.
+pub fn install() {
+    tokio::spawn(async {
+        if tokio::signal::ctrl_c().await.is_err() {
+            return;
+        }
+        INTERRUPTED.store(true, Ordering::Relaxed);
+        log_interrupted();
+
+        if tokio::signal::ctrl_c().await.is_ok() {
+            std::process::exit(EXIT_CODE);
+        }
+    });
+}
+
+/// Stops the run as Ctrl-C does once `timeout` has passed
+pub fn stop_after(timeout: Duration) {
+    tokio::spawn(async move {
+        tokio::time::sleep(timeout).await;
+        TIMED_OUT.store(true, Ordering::Relaxed);
+        if !INTERRUPTED.swap(true, Ordering::Relaxed) {
+            log_run_timeout(timeout);
+        }
+    });
+}
+
+/// Returns true once Ctrl-C has been pressed, or the run timed out
+pub fn is_interrupted() -> bool {
+    INTERRUPTED.load(Ordering::Relaxed)
+}
+
+/// The code to exit with after an interruption
+pub fn exit_code() -> i32 {
+    match TIMED_OUT.load(Ordering::Relaxed) {
+        true => TIMEOUT_EXIT_CODE,
+        false => EXIT_CODE,
+    }
+}
@@ -0,0 +1,56 @@
+//! Translate gettext catalogs and inline `gettext()` calls with LLM providers.
+//!
+//! The CLI in `main.rs` is a thin wrapper around this crate: build a provider with
+//! [`provider::create_provider`], then drive a [`Translator`] over a folder, a single file
+//! or an in-memory [`PoCatalog`].
+
+pub mod atomic;
+pub mod attribution;
+pub mod cache;
+pub mod check;
+pub mod checkpoint;
+pub mod chunk;
+pub mod config;
+pub mod consistency;
+pub mod coverage;
+pub mod extract;
+pub mod failures;
+pub mod filter;
+pub mod formats;
+pub mod glossary;
+pub mod icu;
+pub mod inline;
+pub mod interrupt;
+pub mod lock;
+mod logger;
+pub mod markup;
+pub mod memory;
+pub mod mo;
+pub mod neutral;
+pub mod placeholders;
+pub mod plural;
+pub mod po;
+pub mod progress;
+pub mod prompt;
+pub mod provider;
+pub mod report;
+pub mod review;
+pub mod sanitize;
+pub mod scanner;
+pub mod serve;
+pub mod since;
+pub mod stats;
+pub mod sync;
+pub mod template;
+pub mod tm;
+pub mod translator;
+pub mod verify;
+pub mod watch;
+
+pub use logger::{
+    LogFormat, OutputFormat, Verbosity, disable_colors, set_log_file, set_output_format,
+    set_verbosity,
+};
+pub use po::{PoCatalog, PoEntry};
+pub use provider::{AiRequest, AiResponse, TranslationProvider};
+pub use translator::{TranslateOptions, Translator};
@@ -0,0 +1,90 @@
+//! Entries reviewed by a human, which no run overwrites, not even with `--force`: those with a
+//! `#, reviewed` flag or a `# ai-translator: lock` comment. The `lock` and `unlock` subcommands
+//! add and remove the flag in bulk.
+
+use anyhow::Result;
+use std::path::Path;
+use walkdir::WalkDir;
+
+use crate::filter::Subset;
+use crate::logger::{log_lock_summary, log_locked};
+use crate::po::{PoCatalog, PoEntry};
+
+/// The flag `lock` adds to entries
+pub const LOCK_FLAG: &str = "reviewed";
+
+/// A translator comment locking an entry, for catalogs whose tools drop unknown flags
+pub const LOCK_COMMENT: &str = "# ai-translator: lock";
+
+/// Returns true if no run may overwrite the msgstrs of `entry`
+pub fn is_locked(entry: &PoEntry) -> bool {
+    entry.has_flag(LOCK_FLAG)
+        || entry
+            .comments
+            .iter()
+            .any(|line| line.trim() == LOCK_COMMENT)
+}
+
+/// Locks the translated entries of the catalogs under `root` in `langs` (every language if
+/// empty) that are in the `subset`, or unlocks them with `lock` false. Returns the number of
+/// entries changed.
+pub fn run(
+    root: &Path,
+    langs: &[String],
+    subset: &Subset,
+    lock: bool,
+    dry_run: bool,
+) -> Result<usize> {
+    let mut files = 0;
+    let mut total = 0;
+
+    for file in WalkDir::new(root).into_iter().filter_map(|e| e.ok()) {
+        let path = file.path();
+        if path.extension().is_none_or(|e| e != "po") || !subset.includes_file(path) {
+            continue;
+        }
+        let lang = path
+            .strip_prefix(root)
+            .ok()
+            .and_then(|relative| relative.components().next())
+            .and_then(|lang| lang.as_os_str().to_str())
+            .unwrap_or_default()
+            .to_string();
+        if !langs.is_empty() && !langs.iter().any(|known| known.trim() == lang) {
+            continue;
+        }
+
+        let mut catalog = PoCatalog::read(path)?;
+        let mut count = 0;
+        for entry in catalog.entries_mut() {
+            if entry.is_header() || !subset.includes(entry) || lock == is_locked(entry) {
+                continue;
+            }
+            if lock {
+                let translated = !entry.msgstr.is_empty()
+                    && entry.msgstr.iter().all(|msgstr| !msgstr.is_empty())
+                    && !entry.has_flag("fuzzy");
+                if !translated {
+                    continue;
+                }
+                entry.add_flag(LOCK_FLAG);
+            } else {
+                entry.remove_flag(LOCK_FLAG);
+                entry.comments.retain(|line| line.trim() != LOCK_COMMENT);
+            }
+            count += 1;
+        }
+
+        files += 1;
+        if count > 0 {
+            if !dry_run {
+                catalog.write(path)?;
+            }
+            log_locked(&lang, &path.display().to_string(), count, lock, dry_run);
+            total += count;
+        }
+    }
+
+    log_lock_summary(files, total, lock, dry_run);
+    Ok(total)
+}
@@ -1,12 +1,297 @@
+use std::collections::BTreeMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+use std::time::Duration;
+
 use chrono::Local;
+use clap::ValueEnum;
 use colored::*;
+use serde_json::{Value, json};
+use similar::{ChangeTag, DiffTag, TextDiff};
+
+use crate::consistency::Variant;
+use crate::coverage::Coverage;
+use crate::po::MergeStats;
+use crate::progress;
+use crate::stats::{LangStats, RunStats};
+
+/// `println!`, erasing the progress bar before the line and drawing it again after
+macro_rules! out {
+    ($($arg:tt)*) => {{
+        progress::clear();
+        println!($($arg)*);
+        progress::redraw();
+    }};
+}
+
+/// `eprintln!`, erasing the progress bar before the line and drawing it again after
+macro_rules! err {
+    ($($arg:tt)*) => {{
+        progress::clear();
+        eprintln!($($arg)*);
+        progress::redraw();
+    }};
+}
+
+/// How progress is reported on stdout
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// Colored, human-readable logs
+    #[default]
+    Human,
+    /// One JSON event per line (NDJSON), for CI dashboards and scripts
+    Json,
+}
+
+static JSON_OUTPUT: AtomicBool = AtomicBool::new(false);
+
+/// Selects the format of every log line printed from now on
+pub fn set_output_format(format: OutputFormat) {
+    JSON_OUTPUT.store(format == OutputFormat::Json, Ordering::Relaxed);
+}
+
+pub(crate) fn is_json() -> bool {
+    JSON_OUTPUT.load(Ordering::Relaxed)
+}
+
+/// How much the logs say
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Verbosity {
+    /// Only the summary and errors (and the diffs of a dry run)
+    Quiet,
+    #[default]
+    Normal,
+    /// Also the time and tokens of every request, and the delay before each retry
+    Verbose,
+}
+
+static VERBOSITY: AtomicU8 = AtomicU8::new(Verbosity::Normal as u8);
+
+/// Selects how much every log line printed from now on says
+pub fn set_verbosity(verbosity: Verbosity) {
+    VERBOSITY.store(verbosity as u8, Ordering::Relaxed);
+}
+
+fn is_quiet() -> bool {
+    VERBOSITY.load(Ordering::Relaxed) == Verbosity::Quiet as u8
+}
+
+fn is_verbose() -> bool {
+    VERBOSITY.load(Ordering::Relaxed) == Verbosity::Verbose as u8
+}
+
+/// Prints the human-readable logs without colors
+pub fn disable_colors() {
+    colored::control::set_override(false);
+}
+
+/// How the `--log-file` is written
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum LogFormat {
+    /// One line of plain text per event, without colors
+    #[default]
+    Text,
+    /// One JSON event per line, as `--output json` prints them
+    Json,
+}
+
+/// Rotated log files kept next to the log file, as `<path>.1` (the newest) to `<path>.5`
+const ROTATED_LOG_FILES: usize = 5;
+
+struct LogFile {
+    path: PathBuf,
+    format: LogFormat,
+    /// The size past which the file is rotated, in bytes; never when 0
+    max_size: u64,
+    file: File,
+    size: u64,
+}
+
+static LOG_FILE: Mutex<Option<LogFile>> = Mutex::new(None);
+
+/// Appends the changes, retries and errors of the run to the file at `path`, whatever the
+/// console shows. Once it grows past `max_size` bytes, it is renamed to `<path>.1` and a new
+/// one is started.
+pub fn set_log_file(path: &Path, format: LogFormat, max_size: u64) -> anyhow::Result<()> {
+    if let Some(parent) = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+    {
+        fs::create_dir_all(parent)?;
+    }
+    let file = open_log_file(path)?;
+    let size = file.metadata()?.len();
+    *LOG_FILE.lock().unwrap_or_else(|e| e.into_inner()) = Some(LogFile {
+        path: path.to_path_buf(),
+        format,
+        max_size,
+        file,
+        size,
+    });
+    Ok(())
+}
+
+fn open_log_file(path: &Path) -> anyhow::Result<File> {
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|e| anyhow::anyhow!("Error opening log file {}: {}", path.display(), e))
+}
+
+/// Writes an event to the log file, if there is one
+fn record(event: &Value) {
+    let mut log = LOG_FILE.lock().unwrap_or_else(|e| e.into_inner());
+    let Some(log) = log.as_mut() else {
+        return;
+    };
+
+    let now = Local::now();
+    let line = match log.format {
+        LogFormat::Json => {
+            let mut event = event.clone();
+            event["time"] = Value::String(now.to_rfc3339());
+            format!("{}\n", event)
+        }
+        LogFormat::Text => format!("{} {}\n", now.format("%Y-%m-%d %H:%M:%S"), text_line(event)),
+    };
+
+    if log.max_size > 0 && log.size > 0 && log.size + line.len() as u64 > log.max_size {
+        // A log that can't be rotated is still written to
+        if let Ok(file) = rotate(&log.path) {
+            log.file = file;
+            log.size = 0;
+        }
+    }
+    if log.file.write_all(line.as_bytes()).is_ok() {
+        log.size += line.len() as u64;
+    }
+}
+
+/// Moves `<path>` to `<path>.1`, `<path>.1` to `<path>.2`..., dropping the oldest, and opens
+/// a new `<path>`
+fn rotate(path: &Path) -> anyhow::Result<File> {
+    let rotated = |index: usize| PathBuf::from(format!("{}.{}", path.display(), index));
+    for index in (1..ROTATED_LOG_FILES).rev() {
+        if rotated(index).exists() {
+            fs::rename(rotated(index), rotated(index + 1))?;
+        }
+    }
+    fs::rename(path, rotated(1))?;
+    open_log_file(path)
+}
+
+/// An event as plain text: its name, then its fields as `key=value`
+fn text_line(event: &Value) -> String {
+    let Some(fields) = event.as_object() else {
+        return event.to_string();
+    };
+    let mut line = fields
+        .get("event")
+        .and_then(Value::as_str)
+        .unwrap_or("event")
+        .to_string();
+    for (key, value) in fields {
+        if key != "event" && !value.is_null() {
+            line.push_str(&format!(" {}={}", key, value));
+        }
+    }
+    line
+}
+
+/// Prints an event as a line of JSON, stamped with the current time
+fn emit(mut event: Value) {
+    event["time"] = Value::String(Local::now().to_rfc3339());
+    println!("{}", event);
+}
+
+/// Logs the start of a catalog. Human output stays quiet until the catalog is done.
+pub fn log_file_started(lang: &str, path: &str) {
+    progress::file_started(lang, path);
+    let event = json!({"event": "file_started", "lang": lang, "path": path});
+    record(&event);
+    if is_json() {
+        emit(event);
+    }
+}
+
+/// Logs a .mo file written from its catalog
+pub fn log_compiled(path: &str) {
+    if is_json() {
+        return emit(json!({"event": "compiled", "path": path}));
+    }
+    if is_quiet() {
+        return;
+    }
+
+    let timestamp = Local::now().format("[%Y-%m-%d %H:%M:%S]").to_string();
+    out!("{} {} Compiled {}", timestamp.dimmed(), "📦".green(), path);
+}
+
+/// Logs that the `watch` subcommand is waiting for changes
+pub fn log_watching(path: &str) {
+    if is_json() {
+        return emit(json!({"event": "watching", "path": path}));
+    }
+    if is_quiet() {
+        return;
+    }
+
+    let timestamp = Local::now().format("[%Y-%m-%d %H:%M:%S]").to_string();
+    out!(
+        "{} {} Watching {} for changes (Ctrl-C to stop)",
+        timestamp.dimmed(),
+        "👀".cyan(),
+        path
+    );
+}
+
+/// Logs an error of a `watch` pass, which keeps watching afterwards
+pub fn log_watch_error(error: &anyhow::Error) {
+    let event = json!({"event": "error", "message": format!("{:#}", error)});
+    record(&event);
+    if is_json() {
+        return emit(event);
+    }
+
+    let timestamp = Local::now().format("[%Y-%m-%d %H:%M:%S]").to_string();
+    err!("{} {} {:#}", timestamp.dimmed(), "❌".red(), error);
+}
+
+/// Logs an error that stopped the run
+pub fn log_error(error: &anyhow::Error) {
+    let event = json!({"event": "error", "message": format!("{:#}", error)});
+    record(&event);
+    if is_json() {
+        emit(event);
+    }
+}
 
 /// Logs a change made to a gettext string
 pub fn log_change(original: &str, translated: &str, lang: &str, dry_run: bool) {
+    let event = json!({
+        "event": "entry_translated",
+        "lang": lang,
+        "msgid": original,
+        "msgstr": translated,
+        "dry_run": dry_run,
+    });
+    record(&event);
+    if is_json() {
+        return emit(event);
+    }
+    // The progress bar counts them instead
+    if is_quiet() || progress::is_enabled() {
+        return;
+    }
+
     let timestamp = Local::now().format("[%Y-%m-%d %H:%M:%S]").to_string();
 
     if dry_run {
-        println!(
+        out!(
             "{} {} [{}] \"{}\" ➜ \"{}\"",
             timestamp.dimmed(),
             "🔍".cyan(),
@@ -15,7 +300,7 @@ pub fn log_change(original: &str, translated: &str, lang: &str, dry_run: bool) {
             translated
         );
     } else {
-        println!(
+        out!(
             "{} {} [{}] \"{}\" ➜ \"{}\"",
             timestamp.dimmed(),
             "✏️".yellow(),
@@ -28,10 +313,25 @@ pub fn log_change(original: &str, translated: &str, lang: &str, dry_run: bool) {
 
 /// Logs a successful file translation
 pub fn log_file_success(lang: &str, count: usize, path: &str, dry_run: bool) {
+    let event = json!({
+        "event": "file_finished",
+        "lang": lang,
+        "path": path,
+        "translated": count,
+        "dry_run": dry_run,
+    });
+    record(&event);
+    if is_json() {
+        return emit(event);
+    }
+    if is_quiet() {
+        return;
+    }
+
     let timestamp = Local::now().format("[%Y-%m-%d %H:%M:%S]").to_string();
 
     if dry_run {
-        println!(
+        out!(
             "{} {} {} → would update {} entries in {}",
             timestamp.dimmed(),
             "💡".cyan(),
@@ -40,7 +340,7 @@ pub fn log_file_success(lang: &str, count: usize, path: &str, dry_run: bool) {
             path
         );
     } else {
-        println!(
+        out!(
             "{} {} {} → updated {} entries in {}",
             timestamp.dimmed(),
             "✅".green(),
@@ -51,10 +351,40 @@ pub fn log_file_success(lang: &str, count: usize, path: &str, dry_run: bool) {
     }
 }
 
+/// Logs a catalog skipped because a previous run already finished it
+pub fn log_resumed(lang: &str, path: &str) {
+    if is_json() {
+        return emit(
+            json!({"event": "file_skipped", "lang": lang, "path": path, "reason": "resumed"}),
+        );
+    }
+    if is_quiet() {
+        return;
+    }
+
+    let timestamp = Local::now().format("[%Y-%m-%d %H:%M:%S]").to_string();
+    out!(
+        "{} {} {} already done in a previous run: {}",
+        timestamp.dimmed(),
+        "⏭️".cyan(),
+        lang.to_uppercase(),
+        path
+    );
+}
+
 /// Logs that a file is already complete
 pub fn log_no_changes(lang: &str, path: &str) {
+    if is_json() {
+        return emit(
+            json!({"event": "file_finished", "lang": lang, "path": path, "translated": 0, "dry_run": false}),
+        );
+    }
+    if is_quiet() {
+        return;
+    }
+
     let timestamp = Local::now().format("[%Y-%m-%d %H:%M:%S]").to_string();
-    println!(
+    out!(
         "{} {} {} has no missing translations in {}",
         timestamp.dimmed(),
         "🟢".bright_green(),
@@ -63,32 +393,1160 @@ pub fn log_no_changes(lang: &str, path: &str) {
     );
 }
 
-/// Logs a retry attempt with exponential backoff
-pub fn log_retry(attempt: u32, max: u32, error: &str) {
+/// Logs a switch to the next API key of the provider, after the one in use was rate limited
+/// or out of quota. Keys are numbered from 1, never shown.
+pub fn log_key_rotated(key: usize, keys: usize, error: &str) {
+    let event = json!({
+        "event": "key_rotated",
+        "key": key,
+        "keys": keys,
+        "error": error,
+    });
+    record(&event);
+    if is_json() {
+        return emit(event);
+    }
+    if is_quiet() {
+        return;
+    }
+
     let timestamp = Local::now().format("[%Y-%m-%d %H:%M:%S]").to_string();
-    println!(
-        "{} {} Retry {}/{} after error: {}",
+    out!(
+        "{} {} Switching to API key {}/{} after error: {}",
+        timestamp.dimmed(),
+        "🔑".yellow(),
+        key,
+        keys,
+        error
+    );
+}
+
+/// Logs a retry attempt, made after waiting `delay`
+pub fn log_retry(attempt: u32, max: u32, error: &str, delay: Duration) {
+    let event = json!({
+        "event": "retry",
+        "attempt": attempt,
+        "max": max,
+        "error": error,
+        "delay_ms": delay.as_millis() as u64,
+    });
+    record(&event);
+    if is_json() {
+        return emit(event);
+    }
+    if is_quiet() {
+        return;
+    }
+
+    let timestamp = Local::now().format("[%Y-%m-%d %H:%M:%S]").to_string();
+    let delay = match is_verbose() {
+        true => format!(" (retrying in {:.1}s)", delay.as_secs_f64()),
+        false => String::new(),
+    };
+    out!(
+        "{} {} Retry {}/{} after error: {}{}",
         timestamp.dimmed(),
         "🔁".yellow(),
         attempt,
         max,
+        error,
+        delay
+    );
+}
+
+/// Logs a request answered by the provider, with `--verbose` only. The tokens are
+/// `estimated` when the provider doesn't report them.
+pub fn log_request(
+    messages: usize,
+    elapsed: Duration,
+    input_tokens: u64,
+    output_tokens: u64,
+    estimated: bool,
+) {
+    if !is_verbose() {
+        return;
+    }
+    if is_json() {
+        return emit(json!({
+            "event": "request",
+            "messages": messages,
+            "duration_ms": elapsed.as_millis() as u64,
+            "input_tokens": input_tokens,
+            "output_tokens": output_tokens,
+            "estimated": estimated,
+        }));
+    }
+
+    let timestamp = Local::now().format("[%Y-%m-%d %H:%M:%S]").to_string();
+    let estimated = match estimated {
+        true => " (estimated)",
+        false => "",
+    };
+    out!(
+        "{} {} Request of {} {} answered in {:.2}s, {} input / {} output tokens{}",
+        timestamp.dimmed(),
+        "🌐".dimmed(),
+        messages,
+        match messages {
+            1 => "message",
+            _ => "messages",
+        },
+        elapsed.as_secs_f64(),
+        input_tokens,
+        output_tokens,
+        estimated
+    );
+}
+
+/// Logs, with `--verbose`, the part of a streamed answer to `msgid` received so far
+pub fn log_stream(msgid: &str, text: &str) {
+    if !is_verbose() {
+        return;
+    }
+    let received = text.chars().count();
+    if is_json() {
+        return emit(
+            json!({"event": "stream", "msgid": msgid, "received": received, "text": text}),
+        );
+    }
+
+    const WIDTH: usize = 40;
+
+    let msgid: String = msgid.chars().take(WIDTH).collect();
+    let tail: String = text.chars().skip(received.saturating_sub(WIDTH)).collect();
+    let timestamp = Local::now().format("[%Y-%m-%d %H:%M:%S]").to_string();
+    out!(
+        "{} {} Streaming \"{}\": {} characters so far, …{}",
+        timestamp.dimmed(),
+        "📡".dimmed(),
+        msgid.replace('\n', " "),
+        received,
+        tail.replace('\n', " ").dimmed()
+    );
+}
+
+/// Logs that a long entry is translated in `parts` parts
+pub fn log_chunked(lang: &str, msgid: &str, parts: usize) {
+    let event = json!({"event": "chunked", "lang": lang, "msgid": msgid, "parts": parts});
+    if is_json() {
+        return emit(event);
+    }
+    if is_quiet() {
+        return;
+    }
+
+    let msgid: String = msgid.chars().take(40).collect();
+    let timestamp = Local::now().format("[%Y-%m-%d %H:%M:%S]").to_string();
+    out!(
+        "{} {} [{}] \"{}…\" is too long for one answer, translating it in {} parts",
+        timestamp.dimmed(),
+        "✂️".blue(),
+        lang.to_uppercase().blue(),
+        msgid.replace('\n', " "),
+        parts
+    );
+}
+
+/// Logs that a batched request is being retried entry by entry
+pub fn log_batch_fallback(size: usize, error: &str) {
+    let event = json!({"event": "batch_fallback", "size": size, "error": error});
+    record(&event);
+    if is_json() {
+        return emit(event);
+    }
+    if is_quiet() {
+        return;
+    }
+
+    let timestamp = Local::now().format("[%Y-%m-%d %H:%M:%S]").to_string();
+    out!(
+        "{} {} Batch of {} entries failed ({}), translating them one by one",
+        timestamp.dimmed(),
+        "📦".yellow(),
+        size,
+        error
+    );
+}
+
+/// Logs a translation that failed the checks, asked for again with what is wrong with it
+pub fn log_correction(lang: &str, msgid: &str, reason: &str, attempt: usize, max: usize) {
+    let event = json!({
+        "event": "correction",
+        "lang": lang,
+        "msgid": msgid,
+        "reason": reason,
+        "attempt": attempt,
+        "max": max,
+    });
+    record(&event);
+    if is_json() {
+        return emit(event);
+    }
+    if is_quiet() {
+        return;
+    }
+
+    let timestamp = Local::now().format("[%Y-%m-%d %H:%M:%S]").to_string();
+    out!(
+        "{} {} [{}] \"{}\": {}, asking again ({}/{})",
+        timestamp.dimmed(),
+        "🩹".yellow(),
+        lang.to_uppercase().blue(),
+        msgid,
+        reason,
+        attempt,
+        max
+    );
+}
+
+/// Logs a translation that doesn't use the term mandated by the glossary
+pub fn log_glossary_violation(lang: &str, msgid: &str, term: &str, expected: &str) {
+    let event = json!({
+        "event": "glossary_violation",
+        "lang": lang,
+        "msgid": msgid,
+        "term": term,
+        "expected": expected,
+    });
+    record(&event);
+    if is_json() {
+        return emit(event);
+    }
+    if is_quiet() {
+        return;
+    }
+
+    let timestamp = Local::now().format("[%Y-%m-%d %H:%M:%S]").to_string();
+    out!(
+        "{} {} [{}] \"{}\": glossary term \"{}\" should be translated as \"{}\", marked fuzzy",
+        timestamp.dimmed(),
+        "📖".yellow(),
+        lang.to_uppercase().blue(),
+        msgid,
+        term,
+        expected
+    );
+}
+
+/// Logs the first Ctrl-C of a run
+pub fn log_interrupted() {
+    let event = json!({"event": "interrupted"});
+    record(&event);
+    if is_json() {
+        return emit(event);
+    }
+
+    let timestamp = Local::now().format("[%Y-%m-%d %H:%M:%S]").to_string();
+    err!(
+        "{} {} Interrupted: finishing the requests in flight and saving the catalogs. Press Ctrl-C again to quit now.",
+        timestamp.dimmed(),
+        "🛑".yellow()
+    );
+}
+
+/// Logs that the run reached its `--run-timeout`
+pub fn log_run_timeout(timeout: Duration) {
+    let event = json!({"event": "run_timeout", "timeout_secs": timeout.as_secs_f64()});
+    record(&event);
+    if is_json() {
+        return emit(event);
+    }
+
+    let timestamp = Local::now().format("[%Y-%m-%d %H:%M:%S]").to_string();
+    err!(
+        "{} {} Run timeout of {:?} reached: finishing the requests in flight and saving the catalogs.",
+        timestamp.dimmed(),
+        "⏰".yellow(),
+        timeout
+    );
+}
+
+/// Logs an entry left untranslated because its request failed
+pub fn log_entry_failed(lang: &str, msgid: &str, error: &str) {
+    let event = json!({"event": "entry_failed", "lang": lang, "msgid": msgid, "error": error});
+    record(&event);
+    if is_json() {
+        return emit(event);
+    }
+
+    let timestamp = Local::now().format("[%Y-%m-%d %H:%M:%S]").to_string();
+    err!(
+        "{} {} [{}] \"{}\": {}, left untranslated",
+        timestamp.dimmed(),
+        "❌".red(),
+        lang.to_uppercase().blue(),
+        msgid,
         error
     );
 }
 
+/// Logs a translation still longer than its entry's limit after asking for a shorter one
+pub fn log_too_long(lang: &str, msgid: &str, length: usize, max: usize) {
+    let event =
+        json!({"event": "too_long", "lang": lang, "msgid": msgid, "length": length, "max": max});
+    record(&event);
+    if is_json() {
+        return emit(event);
+    }
+    if is_quiet() {
+        return;
+    }
+
+    let timestamp = Local::now().format("[%Y-%m-%d %H:%M:%S]").to_string();
+    out!(
+        "{} {} [{}] \"{}\": translation is {} characters, over the limit of {}, marked fuzzy",
+        timestamp.dimmed(),
+        "📏".yellow(),
+        lang.to_uppercase().blue(),
+        msgid,
+        length,
+        max
+    );
+}
+
+/// Logs a translation rejected by `--verify`, with the correction applied if any
+pub fn log_verify_failed(lang: &str, msgid: &str, issue: &str, fixed: Option<&[String]>) {
+    let event = json!({
+        "event": "verify_failed",
+        "lang": lang,
+        "msgid": msgid,
+        "issue": issue,
+        "fixed": fixed,
+    });
+    record(&event);
+    if is_json() {
+        return emit(event);
+    }
+    if is_quiet() {
+        return;
+    }
+
+    let timestamp = Local::now().format("[%Y-%m-%d %H:%M:%S]").to_string();
+    let outcome = match fixed {
+        Some(fixed) => format!("fixed as \"{}\"", fixed.join(" | ")),
+        None => "marked fuzzy".to_string(),
+    };
+    out!(
+        "{} {} [{}] \"{}\": failed verification ({}), {}",
+        timestamp.dimmed(),
+        "🔎".yellow(),
+        lang.to_uppercase().blue(),
+        msgid,
+        issue,
+        outcome
+    );
+}
+
+/// Logs a translated entry that `--force --skip-unchanged` keeps as it is
+pub fn log_unchanged(lang: &str, msgid: &str) {
+    if is_json() {
+        return emit(json!({"event": "entry_unchanged", "lang": lang, "msgid": msgid}));
+    }
+    if is_quiet() {
+        return;
+    }
+
+    let timestamp = Local::now().format("[%Y-%m-%d %H:%M:%S]").to_string();
+    out!(
+        "{} {} [{}] \"{}\": needs no translation, kept",
+        timestamp.dimmed(),
+        "⏭️".dimmed(),
+        lang.to_uppercase().blue(),
+        msgid
+    );
+}
+
+/// Logs a language code that is well formed but not among the known ones, like `fil`, which
+/// the model is given as written
+pub fn log_unknown_lang(lang: &str, suggestions: &[String]) {
+    if is_json() {
+        return emit(json!({"event": "unknown_lang", "lang": lang, "suggestions": suggestions}));
+    }
+    if is_quiet() {
+        return;
+    }
+
+    let timestamp = Local::now().format("[%Y-%m-%d %H:%M:%S]").to_string();
+    let suggestions = match suggestions.is_empty() {
+        true => String::new(),
+        false => format!(" (did you mean {}?)", suggestions.join(", ")),
+    };
+    out!(
+        "{} {} [{}] not a known language code{}, given to the model as written",
+        timestamp.dimmed(),
+        "⚠️".yellow(),
+        lang.to_uppercase().blue(),
+        suggestions
+    );
+}
+
+/// Logs a msgid of `--memory` with a translated one like it, reused when `reused`, else
+/// given to the model as a reference
+pub fn log_memory_match(lang: &str, msgid: &str, similar: &str, similarity: f64, reused: bool) {
+    if is_json() {
+        return emit(
+            json!({"event": "memory_match", "lang": lang, "msgid": msgid, "similar": similar, "similarity": similarity, "reused": reused}),
+        );
+    }
+    if is_quiet() {
+        return;
+    }
+
+    let timestamp = Local::now().format("[%Y-%m-%d %H:%M:%S]").to_string();
+    out!(
+        "{} {} [{}] \"{}\": {} \"{}\" ({:.0}% similar)",
+        timestamp.dimmed(),
+        "🧠".dimmed(),
+        lang.to_uppercase().blue(),
+        msgid,
+        if reused {
+            "reused the translation of"
+        } else {
+            "given the translation of"
+        },
+        similar,
+        similarity * 100.0
+    );
+}
+
+/// Logs the obsolete entries of a catalog brought back by `--revive-obsolete`
+pub fn log_revived(lang: &str, path: &str, count: usize) {
+    let event = json!({"event": "obsolete_revived", "lang": lang, "path": path, "count": count});
+    record(&event);
+    if is_json() {
+        return emit(event);
+    }
+    if is_quiet() {
+        return;
+    }
+
+    let timestamp = Local::now().format("[%Y-%m-%d %H:%M:%S]").to_string();
+    out!(
+        "{} {} {} → revived {} obsolete entries in {}",
+        timestamp.dimmed(),
+        "♻️".green(),
+        lang.to_uppercase().blue(),
+        count,
+        path
+    );
+}
+
+/// Logs the creation of a catalog for a new language
+pub fn log_catalog_created(lang: &str, path: &str, dry_run: bool) {
+    let event = json!({"event": "catalog_created", "lang": lang, "path": path, "dry_run": dry_run});
+    record(&event);
+    if is_json() {
+        return emit(event);
+    }
+    if is_quiet() {
+        return;
+    }
+
+    let timestamp = Local::now().format("[%Y-%m-%d %H:%M:%S]").to_string();
+
+    if dry_run {
+        out!(
+            "{} {} {} → would create {}",
+            timestamp.dimmed(),
+            "💡".cyan(),
+            lang.to_uppercase(),
+            path
+        );
+    } else {
+        out!(
+            "{} {} {} → created {}",
+            timestamp.dimmed(),
+            "🆕".green(),
+            lang.to_uppercase(),
+            path
+        );
+    }
+}
+
+/// Logs the entries of a catalog locked, or unlocked, by the `lock` and `unlock` subcommands
+pub fn log_locked(lang: &str, path: &str, count: usize, locked: bool, dry_run: bool) {
+    let event = json!({
+        "event": if locked { "entries_locked" } else { "entries_unlocked" },
+        "lang": lang,
+        "path": path,
+        "count": count,
+        "dry_run": dry_run,
+    });
+    record(&event);
+    if is_json() {
+        return emit(event);
+    }
+    if is_quiet() {
+        return;
+    }
+
+    let timestamp = Local::now().format("[%Y-%m-%d %H:%M:%S]").to_string();
+    let (icon, action) = match locked {
+        true => ("🔒", "lock"),
+        false => ("🔓", "unlock"),
+    };
+    let action = match dry_run {
+        true => format!("would {}", action),
+        false => format!("{}ed", action),
+    };
+    out!(
+        "{} {} {} → {} {} entries in {}",
+        timestamp.dimmed(),
+        icon,
+        lang.to_uppercase().blue(),
+        action,
+        count,
+        path
+    );
+}
+
+/// Logs the .pot template written by the `extract` subcommand, or left as it was if its
+/// messages didn't change
+pub fn log_template_extracted(
+    path: &str,
+    messages: usize,
+    files: usize,
+    changed: bool,
+    dry_run: bool,
+) {
+    let event = json!({
+        "event": "template_extracted",
+        "path": path,
+        "messages": messages,
+        "files": files,
+        "changed": changed,
+        "dry_run": dry_run,
+    });
+    record(&event);
+    if is_json() {
+        return emit(event);
+    }
+    if is_quiet() {
+        return;
+    }
+
+    let timestamp = Local::now().format("[%Y-%m-%d %H:%M:%S]").to_string();
+    let action = match (changed, dry_run) {
+        (false, _) => "unchanged",
+        (true, false) => "written",
+        (true, true) => "would be written",
+    };
+    out!(
+        "{} 📤 {} {}: {} messages from {} files",
+        timestamp.dimmed(),
+        path,
+        action,
+        messages,
+        files
+    );
+}
+
+/// Logs a catalog updated to its .pot template by the `sync` subcommand
+pub fn log_synced(lang: &str, path: &str, stats: &MergeStats, dry_run: bool) {
+    let event = json!({
+        "event": "catalog_synced",
+        "lang": lang,
+        "path": path,
+        "kept": stats.kept,
+        "fuzzy": stats.fuzzy,
+        "added": stats.added,
+        "obsolete": stats.obsolete,
+        "dry_run": dry_run,
+    });
+    record(&event);
+    if is_json() {
+        return emit(event);
+    }
+    if is_quiet() {
+        return;
+    }
+
+    let timestamp = Local::now().format("[%Y-%m-%d %H:%M:%S]").to_string();
+    out!(
+        "{} 🔄 {} → {} {}: {} new, {} fuzzy, {} obsolete",
+        timestamp.dimmed(),
+        lang.to_uppercase().blue(),
+        if dry_run { "would sync" } else { "synced" },
+        path,
+        stats.added,
+        stats.fuzzy,
+        stats.obsolete
+    );
+}
+
+/// Logs the result of the `sync` subcommand
+pub fn log_sync_summary(files: usize, changed: usize, dry_run: bool) {
+    if is_json() {
+        return emit(
+            json!({"event": "sync_summary", "files": files, "changed": changed, "dry_run": dry_run}),
+        );
+    }
+
+    let timestamp = Local::now().format("[%Y-%m-%d %H:%M:%S]").to_string();
+    out!(
+        "{} {} {} {} of {} catalogs",
+        timestamp.dimmed(),
+        "🟢".bright_green(),
+        if dry_run { "Would update" } else { "Updated" },
+        changed,
+        files
+    );
+}
+
+/// Logs an entry the `verify` subcommand flags for review, with its back-translation when
+/// checked with one
+pub fn log_review_flagged(
+    lang: &str,
+    msgid: &str,
+    msgstr: &str,
+    back_translation: Option<&str>,
+    issue: &str,
+) {
+    if is_json() {
+        return emit(
+            json!({"event": "review_flagged", "lang": lang, "msgid": msgid, "msgstr": msgstr, "back_translation": back_translation, "issue": issue}),
+        );
+    }
+
+    let timestamp = Local::now().format("[%Y-%m-%d %H:%M:%S]").to_string();
+    out!(
+        "{} {} [{}] \"{}\" → \"{}\": {}",
+        timestamp.dimmed(),
+        "🔎".yellow(),
+        lang.to_uppercase().blue(),
+        msgid,
+        msgstr,
+        issue.yellow()
+    );
+    if let Some(back_translation) = back_translation {
+        out!("  {} \"{}\"", "Back-translated:".dimmed(), back_translation);
+    }
+}
+
+/// Logs the result of the `verify` subcommand, with the review list written if any entry
+/// was flagged
+pub fn log_review_summary(files: usize, checked: usize, flagged: usize, review: Option<&Path>) {
+    if is_json() {
+        return emit(
+            json!({"event": "review_summary", "files": files, "checked": checked, "flagged": flagged, "review": review.map(|path| path.display().to_string())}),
+        );
+    }
+
+    let timestamp = Local::now().format("[%Y-%m-%d %H:%M:%S]").to_string();
+    match review {
+        None => out!(
+            "{} {} Checked {} translations in {} files, none flagged",
+            timestamp.dimmed(),
+            "🟢".bright_green(),
+            checked,
+            files
+        ),
+        Some(review) => out!(
+            "{} {} Checked {} translations in {} files, flagged {} for review in {}",
+            timestamp.dimmed(),
+            "🚨".red(),
+            checked,
+            files,
+            flagged,
+            review.display()
+        ),
+    }
+}
+
+/// Logs the TMX file written by `tm export`, with its number of units
+pub fn log_tm_exported(path: &str, units: usize) {
+    if is_json() {
+        return emit(json!({"event": "tm_exported", "path": path, "units": units}));
+    }
+
+    let timestamp = Local::now().format("[%Y-%m-%d %H:%M:%S]").to_string();
+    out!(
+        "{} {} Exported {} translation units to {}",
+        timestamp.dimmed(),
+        "📤".bright_green(),
+        units,
+        path
+    );
+}
+
+/// Logs the address the `serve` subcommand listens on
+pub fn log_serving(addr: &str) {
+    if is_json() {
+        return emit(json!({"event": "serving", "addr": addr}));
+    }
+
+    let timestamp = Local::now().format("[%Y-%m-%d %H:%M:%S]").to_string();
+    out!(
+        "{} {} Listening on http://{}, Ctrl-C to stop",
+        timestamp.dimmed(),
+        "🌐".bright_green(),
+        addr
+    );
+}
+
+/// Logs a request answered by the `serve` subcommand, with the status of the response
+pub fn log_http_request(method: &str, path: &str, status: u16) {
+    if is_json() {
+        return emit(json!({
+            "event": "http_request",
+            "method": method,
+            "path": path,
+            "status": status,
+        }));
+    }
+    if is_quiet() {
+        return;
+    }
+
+    let timestamp = Local::now().format("[%Y-%m-%d %H:%M:%S]").to_string();
+    let status = match status {
+        200..=299 => status.to_string().green(),
+        400..=499 => status.to_string().yellow(),
+        _ => status.to_string().red(),
+    };
+    out!(
+        "{} {} {} {} {}",
+        timestamp.dimmed(),
+        "↔️".blue(),
+        method,
+        path,
+        status
+    );
+}
+
+/// Logs the TMX file read by `tm import`, with its number of units
+pub fn log_tm_imported(path: &str, units: usize) {
+    if is_json() {
+        return emit(json!({"event": "tm_imported", "path": path, "units": units}));
+    }
+
+    let timestamp = Local::now().format("[%Y-%m-%d %H:%M:%S]").to_string();
+    out!(
+        "{} {} Imported {} translation units from {}",
+        timestamp.dimmed(),
+        "📥".bright_green(),
+        units,
+        path
+    );
+}
+
+/// Logs a msgid of the `consistency` subcommand translated in several ways, with the
+/// translation the model proposes, if asked
+pub fn log_inconsistency(lang: &str, msgid: &str, variants: &[Variant], suggestion: Option<&str>) {
+    if is_json() {
+        let variants: Vec<Value> = variants
+            .iter()
+            .map(|variant| json!({"translation": variant.translation, "locations": variant.locations}))
+            .collect();
+        return emit(
+            json!({"event": "inconsistency", "lang": lang, "msgid": msgid, "variants": variants, "suggestion": suggestion}),
+        );
+    }
+
+    let timestamp = Local::now().format("[%Y-%m-%d %H:%M:%S]").to_string();
+    out!(
+        "{} {} [{}] \"{}\" is translated {} ways",
+        timestamp.dimmed(),
+        "🔀".yellow(),
+        lang.to_uppercase().blue(),
+        msgid,
+        variants.len()
+    );
+    for variant in variants {
+        out!(
+            "  {} ({}): {}",
+            format!("\"{}\"", variant.translation).bold(),
+            variant.locations.len(),
+            variant.locations.join(", ").dimmed()
+        );
+    }
+    if let Some(suggestion) = suggestion {
+        out!("  {} \"{}\"", "Suggested:".green(), suggestion);
+    }
+}
+
+/// Logs the result of the `consistency` subcommand
+pub fn log_consistency_summary(files: usize, inconsistent: usize) {
+    if is_json() {
+        return emit(
+            json!({"event": "consistency_summary", "files": files, "inconsistent": inconsistent}),
+        );
+    }
+
+    let timestamp = Local::now().format("[%Y-%m-%d %H:%M:%S]").to_string();
+    if inconsistent == 0 {
+        out!(
+            "{} {} Checked {} files, every msgid has one translation per language",
+            timestamp.dimmed(),
+            "🟢".bright_green(),
+            files
+        );
+    } else {
+        out!(
+            "{} {} Checked {} files, found {} msgids translated in several ways",
+            timestamp.dimmed(),
+            "🚨".red(),
+            files,
+            inconsistent
+        );
+    }
+}
+
+/// Logs the result of the `lock` and `unlock` subcommands
+pub fn log_lock_summary(files: usize, count: usize, locked: bool, dry_run: bool) {
+    if is_json() {
+        return emit(
+            json!({"event": "lock_summary", "files": files, "count": count, "locked": locked, "dry_run": dry_run}),
+        );
+    }
+
+    let timestamp = Local::now().format("[%Y-%m-%d %H:%M:%S]").to_string();
+    let action = match (locked, dry_run) {
+        (true, false) => "Locked",
+        (true, true) => "Would lock",
+        (false, false) => "Unlocked",
+        (false, true) => "Would unlock",
+    };
+    out!(
+        "{} {} {} {} entries in {} files",
+        timestamp.dimmed(),
+        "🟢".bright_green(),
+        action,
+        count,
+        files
+    );
+}
+
+/// Logs a problem found by the `check` subcommand
+pub fn log_check_issue(path: &str, line: usize, msgid: &str, issue: &impl std::fmt::Display) {
+    if is_json() {
+        return emit(
+            json!({"event": "check_issue", "path": path, "line": line, "msgid": msgid, "issue": issue.to_string()}),
+        );
+    }
+
+    out!("{} {}:{} \"{}\": {}", "❌".red(), path, line, msgid, issue);
+}
+
+/// Logs the result of the `check` subcommand
+pub fn log_check_summary(files: usize, issues: usize) {
+    if is_json() {
+        return emit(json!({"event": "check_summary", "files": files, "issues": issues}));
+    }
+
+    let timestamp = Local::now().format("[%Y-%m-%d %H:%M:%S]").to_string();
+
+    if issues == 0 {
+        out!(
+            "{} {} Checked {} files, no issues found",
+            timestamp.dimmed(),
+            "🟢".bright_green(),
+            files
+        );
+    } else {
+        out!(
+            "{} {} Checked {} files, found {} issues",
+            timestamp.dimmed(),
+            "🚨".red(),
+            files,
+            issues
+        );
+    }
+}
+
+/// Logs the per-language and overall coverage computed by the `stats` subcommand
+pub fn log_coverage(langs: &BTreeMap<String, Coverage>, total: &Coverage) {
+    if is_json() {
+        let langs: serde_json::Map<String, Value> = langs
+            .iter()
+            .map(|(lang, coverage)| (lang.clone(), coverage_json(coverage)))
+            .collect();
+        return emit(json!({"event": "coverage", "langs": langs, "total": coverage_json(total)}));
+    }
+
+    let timestamp = Local::now().format("[%Y-%m-%d %H:%M:%S]").to_string();
+
+    out!("{} {} Coverage", timestamp.dimmed(), "📈".blue());
+    for (lang, coverage) in langs {
+        out!("  {}", format_coverage(&lang.to_uppercase(), coverage));
+    }
+    out!("  {}", format_coverage("TOTAL", total).bold());
+}
+
+/// Logs a language whose coverage is under `--min-coverage`
+pub fn log_coverage_below(lang: &str, percent: f64, min: f64) {
+    if is_json() {
+        return emit(
+            json!({"event": "coverage_below", "lang": lang, "coverage": percent, "min_coverage": min}),
+        );
+    }
+
+    let timestamp = Local::now().format("[%Y-%m-%d %H:%M:%S]").to_string();
+    out!(
+        "{} {} [{}] coverage {:.1}% is below the minimum of {}%",
+        timestamp.dimmed(),
+        "🚨".red(),
+        lang.to_uppercase().blue(),
+        percent,
+        min
+    );
+}
+
+fn format_coverage(label: &str, coverage: &Coverage) -> String {
+    format!(
+        "{:<6} {:>5.1}%  {} translated, {} fuzzy, {} untranslated ({} files)",
+        label,
+        coverage.percent(),
+        coverage.translated,
+        coverage.fuzzy,
+        coverage.untranslated,
+        coverage.files
+    )
+}
+
+fn coverage_json(coverage: &Coverage) -> Value {
+    json!({
+        "files": coverage.files,
+        "translated": coverage.translated,
+        "fuzzy": coverage.fuzzy,
+        "untranslated": coverage.untranslated,
+        "coverage": coverage.percent(),
+    })
+}
+
+/// Logs the per-language and overall totals of a translator run
+pub fn log_summary(stats: &RunStats) {
+    let event = summary_event(stats);
+    record(&event);
+    if is_json() {
+        return emit(event);
+    }
+
+    let timestamp = Local::now().format("[%Y-%m-%d %H:%M:%S]").to_string();
+
+    out!();
+    out!("{} {} Summary", timestamp.dimmed(), "📊".blue());
+
+    for (lang, lang_stats) in &stats.langs {
+        out!("  {}", format_stats(&lang.to_uppercase(), lang_stats));
+    }
+
+    out!("  {}", format_stats("TOTAL", &stats.total()).bold());
+    let tokens = stats.tokens();
+    let cost = match stats.cost() {
+        Some(cost) => format!(", ~${:.4}", cost),
+        None => String::new(),
+    };
+    out!(
+        "  {} API calls, {} input / {} output tokens{} in {:.1}s",
+        stats.api_calls(),
+        tokens.input_tokens,
+        tokens.output_tokens,
+        cost,
+        stats.elapsed().as_secs_f64()
+    );
+
+    if !stats.failures.is_empty() {
+        out!();
+        out!(
+            "{} {} Failed entries, left untranslated ({}):",
+            timestamp.dimmed(),
+            "❌".red(),
+            stats.failures.len()
+        );
+        for failure in &stats.failures {
+            out!(
+                "  [{}] \"{}\": {}",
+                failure.lang.to_uppercase().blue(),
+                failure.msgid,
+                failure.error
+            );
+        }
+    }
+}
+
+/// Logs where the entries that failed were reported, for `--retry-failures`
+pub fn log_failures_report(path: &str, failed: usize) {
+    let event = json!({"event": "failures_report", "path": path, "failed": failed});
+    record(&event);
+    if is_json() {
+        return emit(event);
+    }
+
+    let timestamp = Local::now().format("[%Y-%m-%d %H:%M:%S]").to_string();
+    out!(
+        "{} {} Failed entries written to {}, retry them with --retry-failures {}",
+        timestamp.dimmed(),
+        "📝".yellow(),
+        path,
+        path
+    );
+}
+
+fn format_stats(label: &str, stats: &LangStats) -> String {
+    format!(
+        "{:<6} {} files, {} translated ({} from cache), {} skipped, {} unchanged, {} failed",
+        label,
+        stats.files,
+        stats.translated,
+        stats.cached,
+        stats.skipped,
+        stats.unchanged,
+        stats.failed
+    )
+}
+
 /// Logs the difference between the original and modified content
 pub fn log_diff(path: &str, original: &str, modified: &str) {
+    if is_json() {
+        return emit(diff_event(path, original, modified));
+    }
+
     let timestamp = Local::now().format("[%Y-%m-%d %H:%M:%S]").to_string();
 
-    println!("{} {} Diff for {}:", timestamp.dimmed(), "📝".blue(), path);
+    out!("{} {} Diff for {}:", timestamp.dimmed(), "📝".blue(), path);
 
     for (line_number, (old_line, new_line)) in original.lines().zip(modified.lines()).enumerate() {
         if old_line != new_line {
-            println!("  {} Line {}:", "🔄".purple(), line_number + 1);
-            println!("    {}", format!("- {}", old_line).red());
-            println!("    {}", format!("+ {}", new_line).green());
+            out!("  {} Line {}:", "🔄".purple(), line_number + 1);
+            out!("    {}", format!("- {}", old_line).red());
+            out!("    {}", format!("+ {}", new_line).green());
+        }
+    }
+
+    out!();
+}
+
+/// Logs a unified diff (as `diff -u` or `git diff` print it) of a catalog's proposed changes
+pub fn log_unified_diff(path: &str, original: &str, modified: &str) {
+    if is_json() {
+        return emit(diff_event(path, original, modified));
+    }
+
+    let timestamp = Local::now().format("[%Y-%m-%d %H:%M:%S]").to_string();
+    out!("{} {} Diff for {}:", timestamp.dimmed(), "📝".blue(), path);
+
+    let diff = TextDiff::from_lines(original, modified);
+    out!("{}", format!("--- a/{}", path).bold());
+    out!("{}", format!("+++ b/{}", path).bold());
+
+    for hunk in diff.unified_diff().context_radius(3).iter_hunks() {
+        out!("{}", hunk.header().to_string().cyan());
+        for change in hunk.iter_changes() {
+            let line = change.to_string_lossy();
+            let line = line.trim_end_matches('\n');
+            match change.tag() {
+                ChangeTag::Equal => out!(" {}", line),
+                ChangeTag::Delete => out!("{}", format!("-{}", line).red()),
+                ChangeTag::Insert => out!("{}", format!("+{}", line).green()),
+            }
         }
     }
 
-    println!();
+    out!();
+}
+
+/// Logs a catalog's proposed changes in two columns, the original on the left
+pub fn log_side_by_side_diff(path: &str, original: &str, modified: &str) {
+    if is_json() {
+        return emit(diff_event(path, original, modified));
+    }
+
+    const WIDTH: usize = 60;
+
+    let timestamp = Local::now().format("[%Y-%m-%d %H:%M:%S]").to_string();
+    out!("{} {} Diff for {}:", timestamp.dimmed(), "📝".blue(), path);
+
+    let diff = TextDiff::from_lines(original, modified);
+    let old: Vec<&str> = original.lines().collect();
+    let new: Vec<&str> = modified.lines().collect();
+    let column = |line: Option<&&str>| {
+        let line: String = line
+            .copied()
+            .unwrap_or_default()
+            .chars()
+            .take(WIDTH)
+            .collect();
+        format!("{:<WIDTH$}", line)
+    };
+
+    for (index, group) in diff.grouped_ops(3).iter().enumerate() {
+        if index > 0 {
+            out!("{}", "┄".repeat(WIDTH * 2 + 3).dimmed());
+        }
+
+        for op in group {
+            let (tag, old_range, new_range) = op.as_tag_tuple();
+            let rows = old_range.len().max(new_range.len());
+
+            for row in 0..rows {
+                let left = column(
+                    old.get(old_range.start + row)
+                        .filter(|_| row < old_range.len()),
+                );
+                let right = column(
+                    new.get(new_range.start + row)
+                        .filter(|_| row < new_range.len()),
+                );
+
+                // Markers as `sdiff` prints them, so changes stand out without colors too
+                let marker = match tag {
+                    DiffTag::Equal => {
+                        out!("{}   {}", left, right);
+                        continue;
+                    }
+                    DiffTag::Delete => "<",
+                    DiffTag::Insert => ">",
+                    DiffTag::Replace if row >= new_range.len() => "<",
+                    DiffTag::Replace if row >= old_range.len() => ">",
+                    DiffTag::Replace => "|",
+                };
+                out!("{} {} {}", left.red(), marker.yellow(), right.green());
+            }
+        }
+    }
+
+    out!();
+}
+
+/// The totals of a run, as the `summary` event gives them
+pub(crate) fn summary_event(stats: &RunStats) -> Value {
+    let tokens = stats.tokens();
+    let langs: serde_json::Map<String, Value> = stats
+        .langs
+        .iter()
+        .map(|(lang, lang_stats)| (lang.clone(), stats_json(lang_stats)))
+        .collect();
+
+    json!({
+        "event": "summary",
+        "langs": langs,
+        "total": stats_json(&stats.total()),
+        "api_calls": stats.api_calls(),
+        "input_tokens": tokens.input_tokens,
+        "output_tokens": tokens.output_tokens,
+        "cost": stats.cost(),
+        "elapsed_secs": stats.elapsed().as_secs_f64(),
+    })
+}
+
+fn stats_json(stats: &LangStats) -> Value {
+    json!({
+        "files": stats.files,
+        "translated": stats.translated,
+        "cached": stats.cached,
+        "skipped": stats.skipped,
+        "unchanged": stats.unchanged,
+        "failed": stats.failed,
+    })
+}
+
+fn diff_event(path: &str, original: &str, modified: &str) -> Value {
+    let diff = TextDiff::from_lines(original, modified)
+        .unified_diff()
+        .context_radius(3)
+        .header(&format!("a/{}", path), &format!("b/{}", path))
+        .to_string();
+
+    json!({"event": "diff", "path": path, "diff": diff})
 }
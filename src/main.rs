@@ -1,16 +1,67 @@
-mod inline;
-mod logger;
-mod openai;
-mod translator;
-
-use clap::{Parser, Subcommand};
+use ai_gettext_translator::filter::{PathFilter, Subset};
+use ai_gettext_translator::markup::FormatHints;
+use ai_gettext_translator::provider::{
+    self, LangOverride, PerLang, Pricing, ProviderConfig, ProviderKind, Recorder, Replay,
+    RetryPolicy, Sampling, StreamPolicy, TranslationProvider,
+};
+use ai_gettext_translator::report::ReportFormat;
+use ai_gettext_translator::since::Since;
+use ai_gettext_translator::{
+    LogFormat, OutputFormat, Verbosity, check, config, coverage, disable_colors, extract, failures,
+    formats, inline, interrupt, memory, mo, progress, serve, set_log_file, set_output_format,
+    set_verbosity, translator, verify::VerifyMode, watch,
+};
+use clap::{Args, CommandFactory, Parser, Subcommand, ValueEnum};
+use std::io::IsTerminal;
+use std::sync::Arc;
+use std::time::Duration;
 
 #[derive(Parser)]
-#[command(name = "gettext-translator")]
+#[command(name = "gettext-translator", args_override_self = true)]
 #[command(about = "Translates gettext() strings or .po files using OpenAI", long_about = None)]
 struct Cli {
     #[command(subcommand)]
     command: Commands,
+
+    /// Config file. Defaults to gettext-translator.toml in the working directory, if present
+    #[arg(long, global = true)]
+    config: Option<std::path::PathBuf>,
+
+    /// Output format: colored logs, or one JSON event per line
+    #[arg(long, global = true, value_enum, default_value = "human")]
+    output: OutputFormat,
+
+    /// Show a progress bar with the time left instead of a line per translated entry
+    #[arg(long, global = true)]
+    progress: bool,
+
+    /// Only print the summary and errors
+    #[arg(short, long, global = true, conflicts_with = "verbose")]
+    quiet: bool,
+
+    /// Also print the time and tokens of every request, and the delay before each retry
+    #[arg(short, long, global = true)]
+    verbose: bool,
+
+    /// Print the logs without colors, as when stdout isn't a terminal
+    #[arg(long, global = true)]
+    no_color: bool,
+
+    /// File to append every change, retry and error to, whatever the console shows
+    #[arg(long, global = true)]
+    log_file: Option<std::path::PathBuf>,
+
+    /// Format of the --log-file: plain text, or one JSON event per line
+    #[arg(long, global = true, value_enum, default_value = "text")]
+    log_format: LogFormat,
+
+    /// Size in MB past which the --log-file is rotated to <file>.1, keeping 5; 0 never rotates
+    #[arg(long, global = true, default_value_t = 10)]
+    log_max_size: u64,
+
+    /// Stop the run as Ctrl-C does after this long, like 30m or 2h
+    #[arg(long, global = true, value_parser = parse_duration)]
+    run_timeout: Option<Duration>,
 }
 
 #[derive(Subcommand)]
@@ -19,60 +70,1695 @@ enum Commands {
         /// Path to scan
         folder: std::path::PathBuf,
 
+        /// Comma-separated list of file extensions to scan
+        #[arg(long, value_delimiter = ',', default_value = inline::DEFAULT_EXTENSIONS)]
+        ext: Vec<String>,
+
+        /// Comma-separated list of gettext functions, as `name`, `name:N` when the message is the N-th argument, or `name:N:M` when the plural form is the M-th
+        #[arg(long, value_delimiter = ',', default_value = inline::DEFAULT_FUNCTIONS)]
+        functions: Vec<String>,
+
+        /// Language the strings are translated to
+        #[arg(long, default_value = "en")]
+        to: String,
+
+        /// A language to translate into even though its code isn't known, like "Klingon",
+        /// named to the model as written. May be repeated
+        #[arg(long)]
+        allow_unknown_lang: Vec<String>,
+
+        /// The name the model gets for a language code, like es_MX="Mexican Spanish". May
+        /// be repeated
+        #[arg(long)]
+        lang_map: Vec<String>,
+
+        #[command(flatten)]
+        filter: FilterArgs,
+
         /// Dry run
         #[arg(long)]
         dry_run: bool,
 
-        /// API Key
+        /// If set, every modified file is first copied to a .bak next to it
+        #[arg(long)]
+        backup: bool,
+
+        /// Translation cache file. Defaults to .gettext-translator-cache.json in the folder
+        #[arg(long)]
+        cache: Option<std::path::PathBuf>,
+
+        /// If set, the translation cache is neither read nor written
         #[arg(long)]
-        api_key: Option<String>,
+        no_cache: bool,
+
+        #[command(flatten)]
+        provider: ProviderArgs,
     },
-    Translator {
+    /// Writes the messages of the gettext calls in the source files to a .pot template, like
+    /// xgettext
+    Extract {
+        /// Path to scan
         folder: std::path::PathBuf,
 
-        /// Comma-separated list of target languages
+        /// Comma-separated list of file extensions to scan
+        #[arg(long, value_delimiter = ',', default_value = inline::DEFAULT_EXTENSIONS)]
+        ext: Vec<String>,
+
+        /// Comma-separated list of gettext functions, as `name`, `name:N` when the message is the N-th argument, or `name:N:M` when the plural form is the M-th
+        #[arg(long, value_delimiter = ',', default_value = inline::DEFAULT_FUNCTIONS)]
+        functions: Vec<String>,
+
+        #[command(flatten)]
+        filter: FilterArgs,
+
+        /// The .pot file to create or update. Defaults to messages.pot in the folder
+        #[arg(long = "out")]
+        out: Option<std::path::PathBuf>,
+
+        /// If set, no files are modified; the changes are shown as a diff
         #[arg(long)]
-        lang: String,
+        dry_run: bool,
+    },
+    /// Translates the missing entries of .po catalogs
+    Translator(TranslatorArgs),
+    /// Watches a folder and translates catalogs as soon as they change
+    Watch(TranslatorArgs),
+    /// Compiles every .po file into a .mo next to it, like msgfmt
+    Compile {
+        /// Path to scan
+        folder: std::path::PathBuf,
+    },
+    /// Validates .po files offline: empty msgstrs and lost or unexpected placeholders
+    Check {
+        /// Path to scan
+        folder: std::path::PathBuf,
+    },
+    /// Translates the targets of XLIFF 1.2 and 2.0 files
+    Xliff {
+        /// File to translate, or folder to scan
+        path: std::path::PathBuf,
 
-        /// If set, no files are modified
+        /// Language the strings are translated to. Defaults to the target language declared
+        /// by each file
+        #[arg(long)]
+        lang: Option<String>,
+
+        #[command(flatten)]
+        format: FormatArgs,
+    },
+    /// Fills the missing keys of JSON locale files (i18next, Rails or flat) from the file of
+    /// the source language
+    Json {
+        /// Folder with one `<lang>.json` file or `<lang>/` folder per language
+        folder: std::path::PathBuf,
+
+        /// Comma-separated list of target language codes. Defaults to the other files or
+        /// folders found
+        #[arg(long, value_delimiter = ',')]
+        lang: Option<Vec<String>>,
+
+        /// Language of the file the keys are translated from
+        #[arg(long, default_value = "en")]
+        source_lang: String,
+
+        #[command(flatten)]
+        format: FormatArgs,
+    },
+    /// Fills the missing keys of Rails YAML locale files from the files of the source language
+    Yaml {
+        /// Folder to scan, like config/locales
+        folder: std::path::PathBuf,
+
+        /// Comma-separated list of target language codes. Defaults to the other languages
+        /// found
+        #[arg(long, value_delimiter = ',')]
+        lang: Option<Vec<String>>,
+
+        /// Language of the files the keys are translated from
+        #[arg(long, default_value = "en")]
+        source_lang: String,
+
+        #[command(flatten)]
+        format: FormatArgs,
+    },
+    /// Fills the missing messages of Flutter .arb files from the template of the source
+    /// language
+    Arb {
+        /// Folder to scan, like lib/l10n
+        folder: std::path::PathBuf,
+
+        /// Comma-separated list of target language codes. Defaults to the other locales found
+        #[arg(long, value_delimiter = ',')]
+        lang: Option<Vec<String>>,
+
+        /// Locale of the template the messages are translated from
+        #[arg(long, default_value = "en")]
+        source_lang: String,
+
+        #[command(flatten)]
+        format: FormatArgs,
+    },
+    /// Fills the .strings and .stringsdict files of Apple `<lang>.lproj` folders from those
+    /// of the source language
+    Apple {
+        /// Folder to scan for .lproj folders
+        folder: std::path::PathBuf,
+
+        /// Comma-separated list of target language codes. Defaults to the other .lproj folders
+        /// found
+        #[arg(long, value_delimiter = ',')]
+        lang: Option<Vec<String>>,
+
+        /// Language of the .lproj folder the strings are translated from
+        #[arg(long, default_value = "en")]
+        source_lang: String,
+
+        #[command(flatten)]
+        format: FormatArgs,
+    },
+    /// Fills the `values-<lang>/strings.xml` files of Android apps from `values/strings.xml`
+    Android {
+        /// Folder to scan for res/values folders
+        folder: std::path::PathBuf,
+
+        /// Comma-separated list of target language codes. Defaults to the languages of the
+        /// values-* folders found
+        #[arg(long, value_delimiter = ',')]
+        lang: Option<Vec<String>>,
+
+        /// Language of the strings in values/strings.xml
+        #[arg(long, default_value = "en")]
+        source_lang: String,
+
+        #[command(flatten)]
+        format: FormatArgs,
+    },
+    /// Fills the columns of target languages of a CSV or TSV spreadsheet from its source column
+    Csv {
+        /// Spreadsheet to translate
+        path: std::path::PathBuf,
+
+        /// Comma-separated list of target language codes. Defaults to the columns named like a
+        /// language code, and those of --column
+        #[arg(long, value_delimiter = ',')]
+        lang: Option<Vec<String>>,
+
+        /// Language of the source column
+        #[arg(long, default_value = "en")]
+        source_lang: String,
+
+        /// Column of the strings to translate, by header or 1-based number. Defaults to the
+        /// column named like --source-lang, else the first one
+        #[arg(long)]
+        source_column: Option<String>,
+
+        /// Column sent to the model as the context of each string, like a key
+        #[arg(long)]
+        key_column: Option<String>,
+
+        /// Column of notes for translators, sent to the model with each string
+        #[arg(long)]
+        note_column: Option<String>,
+
+        /// Header of the column of a language not named by its code, like `es=Spanish`.
+        /// Repeatable, or comma-separated
+        #[arg(long = "column", value_delimiter = ',')]
+        columns: Vec<String>,
+
+        /// Field delimiter: one character, or `tab`. Defaults to a tab for .tsv files, else a
+        /// comma
+        #[arg(long)]
+        delimiter: Option<String>,
+
+        /// File to write the translated spreadsheet to. Defaults to the input file
+        #[arg(long = "out")]
+        out: Option<std::path::PathBuf>,
+
+        #[command(flatten)]
+        format: FormatArgs,
+    },
+    /// Prints the translated, fuzzy and untranslated entries of every language, offline
+    Stats {
+        /// Root folder, with one sub-folder per language
+        folder: std::path::PathBuf,
+
+        /// Exit with an error if a language has less than this percentage of its entries translated
+        #[arg(long)]
+        min_coverage: Option<f64>,
+    },
+    /// Marks translated entries as reviewed, so that no run overwrites them, not even --force
+    Lock(LockArgs),
+    /// Removes the marks of `lock`, so that entries can be translated again
+    Unlock(LockArgs),
+    /// Merges the new and removed msgids of the .pot templates into the catalogs, like
+    /// msgmerge: translations are kept, and those of changed msgids marked fuzzy
+    Sync {
+        /// Root folder, with one sub-folder per language
+        folder: std::path::PathBuf,
+
+        /// The .pot template. Defaults to the one named after each catalog's domain, or the
+        /// only one, in the folder
+        #[arg(long)]
+        template: Option<std::path::PathBuf>,
+
+        /// Comma-separated list of language codes. Defaults to every language
+        #[arg(long, value_delimiter = ',')]
+        lang: Vec<String>,
+
+        /// If set, no files are modified; the changes are shown as a diff
         #[arg(long)]
         dry_run: bool,
+    },
+    /// Reports the msgids translated in different ways across the catalogs of a language,
+    /// offline unless --suggest is set
+    Consistency {
+        /// Root folder, with one sub-folder per language
+        folder: std::path::PathBuf,
+
+        /// Comma-separated list of language codes. Defaults to every language
+        #[arg(long, value_delimiter = ',')]
+        lang: Vec<String>,
 
-        /// If set, all entries are re-translated, even if they have a value
+        /// If set, the model proposes the translation to use everywhere for each msgid
         #[arg(long)]
-        force: bool,
+        suggest: bool,
+
+        #[command(flatten)]
+        provider: ProviderArgs,
+    },
+    /// Exchanges the translation memory of --tm with CAT tools, as TMX
+    Tm {
+        #[command(subcommand)]
+        command: TmCommand,
+    },
+    /// Has the model check the existing translations of the catalogs, writing the entries it
+    /// flags to a review list
+    Verify {
+        /// Root folder, with one sub-folder per language
+        folder: std::path::PathBuf,
+
+        /// Comma-separated list of language codes. Defaults to every language
+        #[arg(long, value_delimiter = ',')]
+        lang: Vec<String>,
+
+        /// Translate each msgstr back to the source language and flag the entries whose round
+        /// trip doesn't mean what the msgid does
+        #[arg(long)]
+        back_translate: bool,
+
+        /// The language of the msgids, for --back-translate
+        #[arg(long, default_value = "en")]
+        source_lang: String,
+
+        /// Maximum number of requests in flight at once
+        #[arg(long, default_value_t = 1)]
+        concurrency: usize,
+
+        /// If set, the flagged entries are marked `#, fuzzy` in their catalog
+        #[arg(long)]
+        mark_fuzzy: bool,
+
+        /// The review list, a CSV file. Defaults to translation-review.csv in the folder
+        #[arg(long = "out")]
+        out: Option<std::path::PathBuf>,
 
         /// Extra context for the prompt. If not set, the program will look for a file named context.txt in the root folder
         #[arg(long)]
         context: Option<std::path::PathBuf>,
 
-        /// API Key
+        #[command(flatten)]
+        provider: ProviderArgs,
+    },
+    /// Serves an HTTP API translating messages and .po catalogs, with one cache, rate limit
+    /// and budget for every request
+    Serve {
+        /// Project folder, with the context, glossary and cache of the translations
+        #[arg(default_value = ".")]
+        folder: std::path::PathBuf,
+
+        /// Address to listen on; other machines can only connect with a public one, like
+        /// 0.0.0.0
+        #[arg(long, default_value = "127.0.0.1")]
+        host: std::net::IpAddr,
+
+        /// Port to listen on
+        #[arg(long, default_value_t = serve::DEFAULT_PORT)]
+        port: u16,
+
+        /// Language of the requests that don't give one
         #[arg(long)]
-        api_key: Option<String>,
+        lang: Option<String>,
+
+        /// Bearer token every request must give in its Authorization header
+        #[arg(long, env = "GETTEXT_TRANSLATOR_SERVE_TOKEN", hide_env_values = true)]
+        token: Option<String>,
+
+        #[command(flatten)]
+        translation: TranslationArgs,
     },
 }
 
+#[derive(Subcommand)]
+enum TmCommand {
+    /// Writes the translation memory as a TMX file
+    Export {
+        /// The SQLite translation memory
+        #[arg(long)]
+        tm: std::path::PathBuf,
+
+        /// The TMX file to write. Defaults to translation-memory.tmx
+        #[arg(long = "out", default_value = "translation-memory.tmx")]
+        out: std::path::PathBuf,
+
+        /// Comma-separated list of target language codes. Defaults to every language
+        #[arg(long, value_delimiter = ',')]
+        lang: Vec<String>,
+
+        /// Only the translations a human reviewed
+        #[arg(long)]
+        reviewed_only: bool,
+    },
+    /// Adds the translation units of a TMX file to the translation memory
+    Import {
+        /// The TMX file, like one exported by a CAT tool
+        file: std::path::PathBuf,
+
+        /// The SQLite translation memory, created if missing
+        #[arg(long)]
+        tm: std::path::PathBuf,
+
+        /// Language of the source segments. Defaults to the srclang of the file
+        #[arg(long)]
+        source_lang: Option<String>,
+
+        /// Record the units as reviewed by a human, as those of a CAT tool usually are
+        #[arg(long)]
+        reviewed: bool,
+    },
+}
+
+#[derive(Args)]
+struct LockArgs {
+    /// Root folder, with one sub-folder per language
+    folder: std::path::PathBuf,
+
+    /// Comma-separated list of language codes. Defaults to every language
+    #[arg(long, value_delimiter = ',')]
+    lang: Vec<String>,
+
+    /// Only this catalog, relative to the working directory or the folder. May be repeated
+    #[arg(long)]
+    file: Vec<std::path::PathBuf>,
+
+    /// Only the entries with this msgid. May be repeated
+    #[arg(long)]
+    only_msgid: Vec<String>,
+
+    /// Only the entries whose msgid matches this regex
+    #[arg(long = "match", value_name = "REGEX")]
+    match_msgid: Option<String>,
+
+    /// Only the entries with the comment of `translator --annotate`
+    #[arg(long)]
+    only_ai: bool,
+
+    /// If set, no files are modified
+    #[arg(long)]
+    dry_run: bool,
+}
+
+impl LockArgs {
+    fn run(self, lock: bool) -> anyhow::Result<()> {
+        let subset = Subset::new(
+            &self.folder,
+            &self.file,
+            &self.only_msgid,
+            self.match_msgid.as_deref(),
+            self.only_ai,
+        )?;
+        ai_gettext_translator::lock::run(&self.folder, &self.lang, &subset, lock, self.dry_run)?;
+        Ok(())
+    }
+}
+
+#[derive(Args)]
+struct TranslatorArgs {
+    /// Root folder, with one sub-folder per language
+    folder: std::path::PathBuf,
+
+    /// Comma-separated list of target languages
+    #[arg(long, required_unless_present_any = ["all_langs", "retry_failures", "file"])]
+    lang: Option<String>,
+
+    /// Translate every language found in LINGUAS, or else every language folder
+    #[arg(long, conflicts_with = "lang")]
+    all_langs: bool,
+
+    /// Language the msgids are written in, skipped by --all-langs
+    #[arg(long, default_value = "en")]
+    source_lang: String,
+
+    /// If set, no files are modified
+    #[arg(long)]
+    dry_run: bool,
+
+    /// If set, every modified catalog is first copied to a .po.bak next to it
+    #[arg(long)]
+    backup: bool,
+
+    /// If set, all entries are re-translated, even if they have a value
+    #[arg(long)]
+    force: bool,
+
+    /// Existing translations --force may overwrite before it asks for confirmation, or
+    /// fails when it can't ask
+    #[arg(long, default_value_t = 50)]
+    force_limit: usize,
+
+    /// If set, --force overwrites any number of translations without asking
+    #[arg(long, short = 'y')]
+    yes: bool,
+
+    /// With --force, keep translated entries that need no translation: URLs, numbers and
+    /// msgstrs identical to their msgid
+    #[arg(long, requires = "force")]
+    skip_unchanged: bool,
+
+    /// If set, translated entries are flagged `#, fuzzy` so they can be reviewed later
+    #[arg(long)]
+    mark_fuzzy: bool,
+
+    /// If set, updated catalogs get `Last-Translator: ai-gettext-translator` in their header
+    #[arg(long)]
+    set_last_translator: bool,
+
+    /// Maximum number of translation requests in flight at once
+    #[arg(long, default_value_t = 1)]
+    concurrency: usize,
+
+    /// Number of catalogs translated at once, with at most this many requests in flight in
+    /// total; --concurrency still limits the requests of each catalog
+    #[arg(long, conflicts_with = "interactive")]
+    jobs: Option<usize>,
+
+    /// Number of entries sent to the model in a single request
+    #[arg(long, default_value_t = 1)]
+    batch_size: usize,
+
+    /// Entries longer than this many characters are split between paragraphs and translated
+    /// part by part, so that long answers aren't cut short; 0 never splits them
+    #[arg(long, default_value_t = 4000)]
+    chunk_size: usize,
+
+    /// If set, providers with a structured output mode (OpenAI, Anthropic, Gemini, Ollama and
+    /// OpenAI-compatible) answer with JSON holding the translation, instead of free text
+    #[arg(long)]
+    structured_output: bool,
+
+    /// Times a translation that fails the checks (broken placeholders, not in the language,
+    /// much longer than the message, ...) is asked for again, with what is wrong with it
+    #[arg(long, default_value_t = 1)]
+    max_corrections: usize,
+
+    /// A language to translate into even though its code isn't known, like "Klingon", named
+    /// to the model as written. May be repeated
+    #[arg(long)]
+    allow_unknown_lang: Vec<String>,
+
+    /// The name the model gets for a language code, like es_MX="Mexican Spanish". May be
+    /// repeated
+    #[arg(long)]
+    lang_map: Vec<String>,
+
+    /// If set, languages without a folder get their catalogs created from the .pot templates
+    #[arg(long)]
+    create_missing: bool,
+
+    /// If set, obsolete (#~) entries whose msgid is back in the .pot template are revived and translated
+    #[arg(long)]
+    revive_obsolete: bool,
+
+    /// Language copied by --create-missing when the folder has no .pot template
+    #[arg(long)]
+    reference_lang: Option<String>,
+
+    /// Translation cache file. Defaults to .gettext-translator-cache.json in the root folder
+    #[arg(long)]
+    cache: Option<std::path::PathBuf>,
+
+    /// If set, the translation cache is neither read nor written
+    #[arg(long)]
+    no_cache: bool,
+
+    /// Glossary CSV (term, then one column per language). Defaults to glossary.csv in the folder
+    #[arg(long)]
+    glossary: Option<std::path::PathBuf>,
+
+    /// If set, a .mo file is compiled next to every processed catalog
+    #[arg(long)]
+    compile: bool,
+
+    /// Review every translation (accept, edit, skip or retry) before it is applied
+    #[arg(long)]
+    interactive: bool,
+
+    /// Longest translation allowed, relative to the length of the msgid (e.g. 1.3)
+    #[arg(long)]
+    max_length_ratio: Option<f64>,
+
+    /// The markup of the messages, `html`, `markdown` or `plain`, instead of detecting it;
+    /// `<glob>=<markup>` only for the matching catalogs. May be repeated
+    #[arg(long)]
+    format_hint: Vec<String>,
+
+    /// Have the model check each new translation; rejected ones are marked fuzzy, or
+    /// replaced by its correction with `fix`
+    #[arg(long, value_enum, num_args = 0..=1, default_missing_value = "fuzzy")]
+    verify: Option<VerifyMode>,
+
+    /// Look up each msgid among the translated ones of the language by the similarity of
+    /// their embeddings: one differing only in its final punctuation reuses its translation,
+    /// one meaning nearly the same is given to the model
+    #[arg(long)]
+    memory: bool,
+
+    /// How similar, from 0 to 1 in the cosine of their embeddings, a msgid must be to be given
+    /// with --memory
+    #[arg(long, default_value_t = memory::DEFAULT_THRESHOLD, value_parser = parse_ratio)]
+    memory_threshold: f64,
+
+    /// The provider whose embeddings API --memory uses: openai, gemini, ollama,
+    /// openai-compatible, or mock for offline n-grams. Defaults to --provider
+    #[arg(long, value_enum)]
+    embedding_provider: Option<ProviderKind>,
+
+    /// The embedding model of --memory. Defaults to the provider's (e.g.
+    /// text-embedding-3-small for OpenAI)
+    #[arg(long)]
+    embedding_model: Option<String>,
+
+    /// SQLite translation memory, shared by any number of projects: its translations are used
+    /// like the cache's, and every new one is recorded in it. Created if missing
+    #[arg(long)]
+    tm: Option<std::path::PathBuf>,
+
+    /// Stop before the run's estimated cost exceeds this many USD
+    #[arg(long)]
+    max_cost: Option<f64>,
+
+    /// Also print the summary in this format, e.g. markdown for a pull request comment
+    #[arg(long, value_enum)]
+    report: Option<ReportFormat>,
+
+    /// File the --report is appended to instead of being printed, like $GITHUB_STEP_SUMMARY
+    #[arg(long, requires = "report")]
+    report_file: Option<std::path::PathBuf>,
+
+    /// How dry runs show the changes of each catalog
+    #[arg(long, value_enum, default_value = "unified")]
+    diff_format: translator::DiffFormat,
+
+    /// If set, catalogs finished by an interrupted run are skipped
+    #[arg(long)]
+    resume: bool,
+
+    /// Translate only the entries of a translation-failures.json written by a previous run,
+    /// in its languages unless --lang is given
+    #[arg(long, conflicts_with = "all_langs")]
+    retry_failures: Option<std::path::PathBuf>,
+
+    /// Translate only this catalog, relative to the working directory or the folder, in its
+    /// language unless --lang is given. May be repeated
+    #[arg(long)]
+    file: Vec<std::path::PathBuf>,
+
+    /// Translate only the entries with this msgid, e.g. with --force to fix one string. May
+    /// be repeated
+    #[arg(long)]
+    only_msgid: Vec<String>,
+
+    /// Translate only the entries whose msgid matches this regex
+    #[arg(long = "match", value_name = "REGEX")]
+    match_msgid: Option<String>,
+
+    /// Translate only the entries with the comment of --annotate, e.g. with --force or
+    /// --interactive to redo or review the machine translations
+    #[arg(long)]
+    only_ai: bool,
+
+    /// Translate only the entries added, emptied or made fuzzy since this git commit or tag,
+    /// e.g. the last release
+    #[arg(long, value_name = "REF")]
+    since: Option<String>,
+
+    /// If set, translated entries get a `#. ai-translated: <model> <date>` comment, removed
+    /// when a reviewer edits them
+    #[arg(long)]
+    annotate: bool,
+
+    /// File replacing the built-in system instructions. May use {language}, {lang}, {context}, {text}, {msgctxt} and {notes}
+    #[arg(long)]
+    instructions_template: Option<std::path::PathBuf>,
+
+    /// File replacing the built-in prompt of single messages, with the same variables
+    #[arg(long)]
+    prompt_template: Option<std::path::PathBuf>,
+
+    #[command(flatten)]
+    filter: FilterArgs,
+
+    /// Extra context for the prompt. If not set, the program will look for a file named context.txt in the root folder
+    #[arg(long)]
+    context: Option<std::path::PathBuf>,
+
+    #[command(flatten)]
+    provider: ProviderArgs,
+}
+
+/// What `translator::run` and `watch::run` take, resolved from `TranslatorArgs`
+struct TranslatorRun {
+    folder: std::path::PathBuf,
+    lang: String,
+    context: Option<std::path::PathBuf>,
+    provider: Arc<dyn TranslationProvider>,
+    options: translator::TranslateOptions,
+}
+
+impl TranslatorArgs {
+    fn into_run(self) -> anyhow::Result<TranslatorRun> {
+        if self.verify.is_some()
+            && matches!(
+                self.provider.provider,
+                ProviderKind::Deepl | ProviderKind::Pseudo | ProviderKind::Mock
+            )
+        {
+            anyhow::bail!("--verify needs a language model, not --provider deepl, pseudo or mock");
+        }
+
+        let retry_failures = self
+            .retry_failures
+            .as_deref()
+            .map(failures::RetryFilter::load)
+            .transpose()?;
+        let subset = Subset::new(
+            &self.folder,
+            &self.file,
+            &self.only_msgid,
+            self.match_msgid.as_deref(),
+            self.only_ai,
+        )?;
+        translator::set_lang_map(&self.lang_map)?;
+        let lang = match (self.lang, &retry_failures) {
+            (Some(lang), _) => lang
+                .split(',')
+                .map(translator::lang_code)
+                .collect::<Vec<_>>()
+                .join(","),
+            (None, Some(retry)) => retry.langs().join(","),
+            (None, None) if !self.file.is_empty() => subset.langs(&self.folder).join(","),
+            (None, None) => translator::discover_langs(&self.folder, &self.source_lang)?.join(","),
+        };
+
+        // Pseudo and mock translations must never be served to a real run from the cache
+        let pseudo = matches!(
+            self.provider.provider,
+            ProviderKind::Pseudo | ProviderKind::Mock
+        );
+        let cache = match (self.no_cache || pseudo, self.cache) {
+            (true, _) => None,
+            (false, Some(path)) => Some(path),
+            (false, None) => Some(
+                self.folder
+                    .join(ai_gettext_translator::cache::DEFAULT_CACHE_FILE),
+            ),
+        };
+        let options = translator::TranslateOptions {
+            dry_run: self.dry_run,
+            backup: self.backup,
+            diff_format: self.diff_format,
+            force: self.force,
+            force_limit: Some(self.force_limit).filter(|_| !self.yes),
+            skip_unchanged: self.skip_unchanged,
+            mark_fuzzy: self.mark_fuzzy,
+            set_last_translator: self.set_last_translator,
+            annotate: self.annotate,
+            concurrency: self.concurrency,
+            jobs: self.jobs,
+            batch_size: self.batch_size,
+            chunk_size: Some(self.chunk_size).filter(|size| *size > 0),
+            structured_output: self.structured_output,
+            max_corrections: self.max_corrections,
+            allowed_langs: self.allow_unknown_lang,
+            create_missing: self.create_missing,
+            reference_lang: self.reference_lang,
+            revive_obsolete: self.revive_obsolete,
+            cache,
+            resume: self.resume,
+            max_cost: self.max_cost,
+            report: self.report,
+            report_file: self.report_file,
+            compile: self.compile,
+            interactive: self.interactive,
+            glossary: self.glossary,
+            max_length_ratio: self.max_length_ratio,
+            verify: self.verify,
+            memory: match self.memory {
+                true => Some(self.provider.memory(
+                    self.memory_threshold,
+                    self.embedding_provider,
+                    self.embedding_model,
+                )?),
+                false => None,
+            },
+            tm: self.tm.filter(|_| !pseudo),
+            source_lang: self.source_lang.clone(),
+            provider_name: self
+                .provider
+                .provider
+                .to_possible_value()
+                .map(|value| value.get_name().to_string()),
+            filter: self.filter.build()?,
+            format_hints: FormatHints::parse(&self.format_hint)?,
+            instructions_template: self.instructions_template,
+            prompt_template: self.prompt_template,
+            retry_failures,
+            subset,
+            since: self
+                .since
+                .as_deref()
+                .map(|git_ref| Since::new(&self.folder, git_ref))
+                .transpose()?,
+        };
+
+        Ok(TranslatorRun {
+            folder: self.folder,
+            lang,
+            context: self.context,
+            provider: self.provider.build()?.into(),
+            options,
+        })
+    }
+}
+
+/// Options of the subcommands translating other file formats, like `xliff`
+#[derive(Args)]
+struct FormatArgs {
+    /// If set, no files are modified
+    #[arg(long)]
+    dry_run: bool,
+
+    /// If set, every modified file is first copied to a .bak next to it
+    #[arg(long)]
+    backup: bool,
+
+    /// Also print the summary in this format, e.g. markdown for a pull request comment
+    #[arg(long, value_enum)]
+    report: Option<ReportFormat>,
+
+    /// File the --report is appended to instead of being printed, like $GITHUB_STEP_SUMMARY
+    #[arg(long, requires = "report")]
+    report_file: Option<std::path::PathBuf>,
+
+    #[command(flatten)]
+    filter: FilterArgs,
+
+    #[command(flatten)]
+    translation: TranslationArgs,
+}
+
+/// How the format commands and `serve` translate: the provider, cache, glossary and checks
+#[derive(Args)]
+struct TranslationArgs {
+    /// If set, all strings are re-translated, even if they have a translation
+    #[arg(long)]
+    force: bool,
+
+    /// If set, translated strings are marked as needing review
+    #[arg(long)]
+    mark_fuzzy: bool,
+
+    /// Maximum number of translation requests in flight at once
+    #[arg(long, default_value_t = 1)]
+    concurrency: usize,
+
+    /// Number of strings sent to the model in a single request
+    #[arg(long, default_value_t = 1)]
+    batch_size: usize,
+
+    /// Entries longer than this many characters are split between paragraphs and translated
+    /// part by part, so that long answers aren't cut short; 0 never splits them
+    #[arg(long, default_value_t = 4000)]
+    chunk_size: usize,
+
+    /// If set, providers with a structured output mode (OpenAI, Anthropic, Gemini, Ollama and
+    /// OpenAI-compatible) answer with JSON holding the translation, instead of free text
+    #[arg(long)]
+    structured_output: bool,
+
+    /// Times a translation that fails the checks (broken placeholders, not in the language,
+    /// much longer than the message, ...) is asked for again, with what is wrong with it
+    #[arg(long, default_value_t = 1)]
+    max_corrections: usize,
+
+    /// A language to translate into even though its code isn't known, like "Klingon", named
+    /// to the model as written. May be repeated
+    #[arg(long)]
+    allow_unknown_lang: Vec<String>,
+
+    /// The name the model gets for a language code, like es_MX="Mexican Spanish". May be
+    /// repeated
+    #[arg(long)]
+    lang_map: Vec<String>,
+
+    /// Translation cache file. Defaults to .gettext-translator-cache.json in the folder
+    #[arg(long)]
+    cache: Option<std::path::PathBuf>,
+
+    /// If set, the translation cache is neither read nor written
+    #[arg(long)]
+    no_cache: bool,
+
+    /// Glossary CSV (term, then one column per language). Defaults to glossary.csv in the folder
+    #[arg(long)]
+    glossary: Option<std::path::PathBuf>,
+
+    /// Have the model check each new translation; rejected ones are marked as needing review,
+    /// or replaced by its correction with `fix`
+    #[arg(long, value_enum, num_args = 0..=1, default_missing_value = "fuzzy")]
+    verify: Option<VerifyMode>,
+
+    /// Look up each msgid among the translated ones of the language by the similarity of
+    /// their embeddings: one differing only in its final punctuation reuses its translation,
+    /// one meaning nearly the same is given to the model
+    #[arg(long)]
+    memory: bool,
+
+    /// How similar, from 0 to 1 in the cosine of their embeddings, a msgid must be to be given
+    /// with --memory
+    #[arg(long, default_value_t = memory::DEFAULT_THRESHOLD, value_parser = parse_ratio)]
+    memory_threshold: f64,
+
+    /// The provider whose embeddings API --memory uses: openai, gemini, ollama,
+    /// openai-compatible, or mock for offline n-grams. Defaults to --provider
+    #[arg(long, value_enum)]
+    embedding_provider: Option<ProviderKind>,
+
+    /// The embedding model of --memory. Defaults to the provider's (e.g.
+    /// text-embedding-3-small for OpenAI)
+    #[arg(long)]
+    embedding_model: Option<String>,
+
+    /// The markup of the strings, `html`, `markdown` or `plain`, instead of detecting it;
+    /// `<glob>=<markup>` only for the matching files. May be repeated
+    #[arg(long)]
+    format_hint: Vec<String>,
+
+    /// Stop before the run's estimated cost exceeds this many USD
+    #[arg(long)]
+    max_cost: Option<f64>,
+
+    /// Extra context for the prompt. If not set, the program will look for a file named context.txt in the folder
+    #[arg(long)]
+    context: Option<std::path::PathBuf>,
+
+    #[command(flatten)]
+    provider: ProviderArgs,
+}
+
+/// What `formats::run` takes, resolved from `FormatArgs`
+struct FormatRun {
+    context: Option<std::path::PathBuf>,
+    provider: Arc<dyn TranslationProvider>,
+    options: translator::TranslateOptions,
+}
+
+impl FormatArgs {
+    /// Resolves the options of a run over the files of `folder`
+    fn into_run(self, folder: &std::path::Path) -> anyhow::Result<FormatRun> {
+        let mut run = self.translation.into_run(folder)?;
+        run.options.dry_run = self.dry_run;
+        run.options.backup = self.backup;
+        run.options.report = self.report;
+        run.options.report_file = self.report_file;
+        run.options.filter = self.filter.build()?;
+        Ok(run)
+    }
+}
+
+impl TranslationArgs {
+    /// Resolves the options of translations for the files of `folder`
+    fn into_run(self, folder: &std::path::Path) -> anyhow::Result<FormatRun> {
+        if self.verify.is_some()
+            && matches!(
+                self.provider.provider,
+                ProviderKind::Deepl | ProviderKind::Pseudo | ProviderKind::Mock
+            )
+        {
+            anyhow::bail!("--verify needs a language model, not --provider deepl, pseudo or mock");
+        }
+
+        translator::set_lang_map(&self.lang_map)?;
+        let pseudo = matches!(
+            self.provider.provider,
+            ProviderKind::Pseudo | ProviderKind::Mock
+        );
+        let cache = match (self.no_cache || pseudo, self.cache) {
+            (true, _) => None,
+            (false, Some(path)) => Some(path),
+            (false, None) => Some(folder.join(ai_gettext_translator::cache::DEFAULT_CACHE_FILE)),
+        };
+        let options = translator::TranslateOptions {
+            force: self.force,
+            mark_fuzzy: self.mark_fuzzy,
+            concurrency: self.concurrency,
+            batch_size: self.batch_size,
+            chunk_size: Some(self.chunk_size).filter(|size| *size > 0),
+            structured_output: self.structured_output,
+            max_corrections: self.max_corrections,
+            allowed_langs: self.allow_unknown_lang,
+            cache,
+            max_cost: self.max_cost,
+            glossary: self.glossary,
+            verify: self.verify,
+            memory: match self.memory {
+                true => Some(self.provider.memory(
+                    self.memory_threshold,
+                    self.embedding_provider,
+                    self.embedding_model,
+                )?),
+                false => None,
+            },
+            format_hints: FormatHints::parse(&self.format_hint)?,
+            ..Default::default()
+        };
+
+        Ok(FormatRun {
+            context: self.context,
+            provider: self.provider.build()?.into(),
+            options,
+        })
+    }
+}
+
+impl FormatRun {
+    /// Translates the `files` of `folder`, then exits like `translator` after an interruption
+    /// or failures
+    async fn translate<T, F>(
+        self,
+        folder: &std::path::Path,
+        files: Vec<T>,
+        translate_file: F,
+    ) -> anyhow::Result<()>
+    where
+        F: AsyncFn(&translator::Translator, &T) -> anyhow::Result<usize>,
+    {
+        interrupt::install();
+        let failed = formats::run(
+            folder,
+            files,
+            self.context,
+            self.provider,
+            self.options,
+            translate_file,
+        )
+        .await?;
+        if interrupt::is_interrupted() {
+            std::process::exit(interrupt::exit_code());
+        }
+        if failed > 0 {
+            std::process::exit(2);
+        }
+        Ok(())
+    }
+}
+
+#[derive(Args)]
+struct FilterArgs {
+    /// Only scan files matching this glob, relative to the folder. May be repeated
+    #[arg(long)]
+    include: Vec<String>,
+
+    /// Skip files and folders matching this glob, relative to the folder. May be repeated
+    #[arg(long)]
+    exclude: Vec<String>,
+}
+
+impl FilterArgs {
+    fn build(self) -> anyhow::Result<PathFilter> {
+        PathFilter::new(&self.include, &self.exclude)
+    }
+}
+
+#[derive(Args)]
+struct ProviderArgs {
+    /// Translation backend
+    #[arg(long, value_enum, default_value = "openai")]
+    provider: ProviderKind,
+
+    /// API Key. Defaults to OPENAI_API_KEY, ANTHROPIC_API_KEY, DEEPL_API_KEY, GEMINI_API_KEY or OPENAI_COMPATIBLE_API_KEY depending on the provider.
+    /// Several keys separated by commas are switched between when one is rate limited
+    #[arg(long, conflicts_with = "api_key_file")]
+    api_key: Option<String>,
+
+    /// File holding the API key, so that it doesn't show in the shell history or the process
+    /// list
+    #[arg(long, value_name = "FILE")]
+    api_key_file: Option<std::path::PathBuf>,
+
+    /// Read the API key of the provider from the keychain of the OS (macOS Keychain, Secret
+    /// Service on Linux, Windows Credential Manager), stored under the service
+    /// `ai-gettext-translator` with the provider as the account, when no key is given
+    #[arg(long)]
+    keychain: bool,
+
+    /// Model name. Defaults to the provider's default model (e.g. gpt-4o-mini for OpenAI)
+    #[arg(
+        long,
+        env = "GETTEXT_TRANSLATOR_MODEL",
+        required_if_eq("provider", "openai-compatible")
+    )]
+    model: Option<String>,
+
+    /// Ollama endpoint, used with `--provider ollama`
+    #[arg(long, default_value = provider::DEFAULT_OLLAMA_URL)]
+    ollama_url: String,
+
+    /// Base URL of the chat completions API, used with `--provider openai-compatible`
+    /// (e.g. https://openrouter.ai/api/v1)
+    #[arg(long, required_if_eq("provider", "openai-compatible"))]
+    base_url: Option<String>,
+
+    /// Formality of DeepL translations, used with `--provider deepl`
+    #[arg(long, value_parser = ["default", "more", "less", "prefer_more", "prefer_less"])]
+    formality: Option<String>,
+
+    /// Maximum requests per minute sent to the provider
+    #[arg(long)]
+    rpm: Option<u32>,
+
+    /// Maximum (estimated) tokens per minute sent to the provider
+    #[arg(long)]
+    tpm: Option<u32>,
+
+    /// Price of input tokens in USD per million, overriding the built-in prices
+    #[arg(long, requires = "output_price")]
+    input_price: Option<f64>,
+
+    /// Price of output tokens in USD per million, overriding the built-in prices
+    #[arg(long, requires = "input_price")]
+    output_price: Option<f64>,
+
+    /// Retries of a request that failed with a network error, a 429 or a 5xx before giving up
+    #[arg(long, default_value_t = 5)]
+    max_retries: u32,
+
+    /// Longest wait before the first retry, in milliseconds, doubled for each one after it;
+    /// the actual wait is random, up to that
+    #[arg(long, default_value_t = 100)]
+    initial_backoff: u64,
+
+    /// Longest time a request may take before it is retried, like 60s or 2m; 0 waits forever
+    #[arg(long, value_parser = parse_duration, default_value = "120s")]
+    timeout: Duration,
+
+    /// Proxy the requests go through, like http://proxy.corp:3128, with the credentials in the
+    /// URL if it needs any. Defaults to HTTPS_PROXY, HTTP_PROXY or ALL_PROXY; hosts in
+    /// NO_PROXY are reached directly
+    #[arg(long, value_name = "URL")]
+    proxy: Option<String>,
+
+    /// PEM file of CA certificates trusted besides the system's, like the private CA of a
+    /// proxy inspecting TLS
+    #[arg(long, value_name = "FILE")]
+    ca_cert: Option<std::path::PathBuf>,
+
+    /// HTTP header sent with every request, like `OpenAI-Project: proj_123` or the
+    /// authentication of an LLM gateway. May be repeated
+    #[arg(long, value_name = "NAME: VALUE", value_parser = parse_header)]
+    header: Vec<(String, String)>,
+
+    /// Stream the answers to requests of at least this many characters, as they are written,
+    /// instead of waiting for them whole (OpenAI, Anthropic, Gemini, Ollama and
+    /// OpenAI-compatible providers)
+    #[arg(long)]
+    stream_over: Option<usize>,
+
+    /// Give up on a streamed answer, and retry it, when nothing arrives for this long
+    #[arg(long, value_parser = parse_duration, default_value = "30s")]
+    stall_timeout: Duration,
+
+    /// Sampling temperature: 0 gives the most repeatable translations, higher values more
+    /// varied ones
+    #[arg(long)]
+    temperature: Option<f64>,
+
+    /// Nucleus sampling: only pick from the likeliest words whose probabilities add up to this
+    #[arg(long)]
+    top_p: Option<f64>,
+
+    /// Seed of the model's random picks, for repeatable runs (Gemini, Ollama and
+    /// OpenAI-compatible providers)
+    #[arg(long)]
+    seed: Option<u64>,
+
+    /// Another provider or model for a language, like `ja:provider=openai,model=gpt-4o`, set
+    /// with `[lang.ja]` tables in the config file. May be repeated
+    #[arg(long, value_name = "LANG:KEY=VALUE,...")]
+    lang_override: Vec<String>,
+
+    /// How long each request takes with `--provider mock`, like 200ms
+    #[arg(long, value_parser = parse_duration)]
+    mock_latency: Option<Duration>,
+
+    /// The share of the requests that fail with `--provider mock`, from 0 to 1, retried like
+    /// server errors
+    #[arg(long, default_value_t = 0.0, value_parser = parse_ratio)]
+    mock_failure_rate: f64,
+
+    /// Write the answers of the provider to this folder, one file per request, for `--replay`
+    #[arg(long, value_name = "DIR", conflicts_with = "replay")]
+    record: Option<std::path::PathBuf>,
+
+    /// Answer the requests with the ones `--record` wrote to this folder, without calling the
+    /// provider; a request that wasn't recorded fails
+    #[arg(long, value_name = "DIR")]
+    replay: Option<std::path::PathBuf>,
+}
+
+impl ProviderArgs {
+    fn build(mut self) -> anyhow::Result<Box<dyn TranslationProvider>> {
+        self.api_key = self.api_key()?;
+        if let Some(dir) = &self.replay {
+            let config = self.config();
+            return Ok(Box::new(Replay::new(dir, config.model, config.pricing)?));
+        }
+
+        let provider = self.build_live()?;
+        match &self.record {
+            Some(dir) => Ok(Box::new(Recorder::new(provider, dir)?)),
+            None => Ok(provider),
+        }
+    }
+
+    /// The API key of `--api-key`, or else of `--api-key-file`
+    fn api_key(&self) -> anyhow::Result<Option<String>> {
+        let Some(path) = &self.api_key_file else {
+            return Ok(self.api_key.clone());
+        };
+        let key = std::fs::read_to_string(path)
+            .map_err(|e| anyhow::anyhow!("Error reading {}: {}", path.display(), e))?;
+        let key = key.trim();
+        if key.is_empty() {
+            anyhow::bail!("The API key file {} is empty", path.display());
+        }
+        Ok(Some(key.to_string()))
+    }
+
+    /// The settings of `--memory`, with the embeddings API of `kind`, or else of
+    /// `--provider`. Another provider than `--provider` doesn't take its model, key or
+    /// headers, only its endpoints, proxy and retries.
+    fn memory(
+        &self,
+        threshold: f64,
+        kind: Option<ProviderKind>,
+        model: Option<String>,
+    ) -> anyhow::Result<memory::MemoryOptions> {
+        let mut config = self.config();
+        config.api_key = self.api_key()?;
+        config.model = model;
+        if let Some(kind) = kind.filter(|kind| *kind != config.kind) {
+            config.kind = kind;
+            config.api_key = None;
+            config.headers.clear();
+        }
+        Ok(memory::MemoryOptions {
+            threshold,
+            embedder: Arc::new(provider::create_embedder(config)?),
+        })
+    }
+
+    /// The provider of the command line, with the overrides of some languages
+    fn build_live(&self) -> anyhow::Result<Box<dyn TranslationProvider>> {
+        let overrides: Vec<LangOverride> = self
+            .lang_override
+            .iter()
+            .map(|spec| LangOverride::parse(spec))
+            .collect::<anyhow::Result<_>>()?;
+        let default = provider::create_provider(self.config())?;
+        if overrides.is_empty() {
+            return Ok(default);
+        }
+
+        let langs = overrides
+            .iter()
+            .map(|lang| {
+                let provider = provider::create_provider(lang.apply(self.config())?)?;
+                Ok((lang.lang.clone(), provider))
+            })
+            .collect::<anyhow::Result<_>>()?;
+        Ok(Box::new(PerLang::new(default, langs)))
+    }
+
+    fn config(&self) -> ProviderConfig {
+        ProviderConfig {
+            kind: self.provider,
+            api_key: self.api_key.clone(),
+            keychain: self.keychain,
+            model: self.model.clone(),
+            ollama_url: Some(self.ollama_url.clone()),
+            base_url: self.base_url.clone(),
+            formality: self.formality.clone(),
+            rpm: self.rpm,
+            tpm: self.tpm,
+            limiter: None,
+            pricing: self
+                .input_price
+                .zip(self.output_price)
+                .map(|(input, output)| Pricing { input, output }),
+            retry: RetryPolicy {
+                max_retries: self.max_retries,
+                initial_backoff: Duration::from_millis(self.initial_backoff),
+            },
+            timeout: Some(self.timeout).filter(|timeout| !timeout.is_zero()),
+            proxy: self.proxy.clone(),
+            ca_cert: self.ca_cert.clone(),
+            headers: self.header.clone(),
+            stream: self.stream_over.map(|min_length| StreamPolicy {
+                min_length,
+                stall_timeout: self.stall_timeout,
+            }),
+            sampling: Sampling {
+                temperature: self.temperature,
+                top_p: self.top_p,
+                seed: self.seed,
+            },
+            mock_latency: self.mock_latency,
+            mock_failure_rate: self.mock_failure_rate,
+        }
+    }
+}
+
+/// Parses a duration like `500ms`, `60s`, `2m` or `1h`; plain numbers are seconds
+fn parse_duration(value: &str) -> Result<Duration, String> {
+    let value = value.trim();
+    let split = value
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let number: f64 = number
+        .parse()
+        .map_err(|_| format!("invalid duration `{}`: expected e.g. 60s or 2m", value))?;
+    let seconds = match unit.trim() {
+        "ms" => number / 1000.0,
+        "" | "s" => number,
+        "m" => number * 60.0,
+        "h" => number * 3600.0,
+        unit => {
+            return Err(format!(
+                "invalid duration unit `{}`: expected ms, s, m or h",
+                unit
+            ));
+        }
+    };
+    Duration::try_from_secs_f64(seconds).map_err(|e| format!("invalid duration `{}`: {}", value, e))
+}
+
+/// Parses a number from 0 to 1
+fn parse_ratio(value: &str) -> Result<f64, String> {
+    match value.trim().parse::<f64>() {
+        Ok(ratio) if (0.0..=1.0).contains(&ratio) => Ok(ratio),
+        _ => Err(format!(
+            "invalid value `{}`: expected a number from 0 to 1",
+            value
+        )),
+    }
+}
+
+/// Parses an HTTP header given as `Name: value`
+fn parse_header(value: &str) -> Result<(String, String), String> {
+    match value.split_once(':') {
+        Some((name, value)) if !name.trim().is_empty() => {
+            Ok((name.trim().to_string(), value.trim().to_string()))
+        }
+        _ => Err(format!(
+            "invalid header `{}`: expected e.g. `OpenAI-Project: proj_123`",
+            value
+        )),
+    }
+}
+
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    let cli = Cli::parse();
+    let args = config::apply(std::env::args_os().collect(), &Cli::command())?;
+    let cli = Cli::parse_from(args);
+    set_output_format(cli.output);
+    set_verbosity(match (cli.quiet, cli.verbose) {
+        (true, _) => Verbosity::Quiet,
+        (_, true) => Verbosity::Verbose,
+        _ => Verbosity::Normal,
+    });
+    if cli.no_color || !std::io::stdout().is_terminal() {
+        disable_colors();
+    }
+    if let Some(log_file) = &cli.log_file {
+        set_log_file(log_file, cli.log_format, cli.log_max_size * 1024 * 1024)?;
+    }
+    if let Some(timeout) = cli.run_timeout {
+        interrupt::stop_after(timeout);
+    }
+    if cli.progress && cli.output == OutputFormat::Human {
+        progress::enable();
+    }
 
     match cli.command {
         Commands::Inline {
             folder,
+            ext,
+            functions,
+            to,
+            allow_unknown_lang,
+            lang_map,
+            filter,
+            dry_run,
+            backup,
+            cache,
+            no_cache,
+            provider,
+        } => {
+            translator::set_lang_map(&lang_map)?;
+            let to = translator::lang_code(&to);
+            translator::check_lang(&to, &allow_unknown_lang)?;
+            // Pseudo and mock translations must never be served to a real run from the cache
+            let pseudo = matches!(provider.provider, ProviderKind::Pseudo | ProviderKind::Mock);
+            let cache = match (no_cache || pseudo, cache) {
+                (true, _) => None,
+                (false, Some(path)) => Some(path),
+                (false, None) => {
+                    Some(folder.join(ai_gettext_translator::cache::DEFAULT_CACHE_FILE))
+                }
+            };
+            let options = inline::InlineOptions {
+                extensions: ext,
+                functions,
+                lang: to,
+                filter: filter.build()?,
+                dry_run,
+                backup,
+                cache,
+            };
+            inline::run(folder, &options, provider.build()?.as_ref()).await?;
+        }
+        Commands::Extract {
+            folder,
+            ext,
+            functions,
+            filter,
+            out,
             dry_run,
-            api_key,
         } => {
-            inline::run(folder, dry_run, api_key).await?;
+            let options = extract::ExtractOptions {
+                extensions: ext,
+                functions,
+                filter: filter.build()?,
+                output: out.unwrap_or_else(|| folder.join(extract::DEFAULT_OUTPUT)),
+                dry_run,
+            };
+            extract::run(&folder, &options)?;
+        }
+        Commands::Translator(args) => {
+            let run = args.into_run()?;
+            interrupt::install();
+            let failed = translator::run(
+                run.folder,
+                &run.lang,
+                run.context,
+                run.provider,
+                run.options,
+            )
+            .await?;
+            if interrupt::is_interrupted() {
+                std::process::exit(interrupt::exit_code());
+            }
+            if failed > 0 {
+                std::process::exit(2);
+            }
+        }
+        Commands::Watch(args) => {
+            let run = args.into_run()?;
+            watch::run(
+                run.folder,
+                &run.lang,
+                run.context,
+                run.provider,
+                run.options,
+            )
+            .await?;
+        }
+        Commands::Xliff { path, lang, format } => {
+            let folder = formats::folder(&path);
+            let run = format.into_run(&folder)?;
+            let lang = lang.map(|lang| translator::lang_code(&lang));
+            let files = formats::files(&path, formats::xliff::EXTENSIONS, &run.options.filter);
+            run.translate(&folder, files, async |translator, file| {
+                formats::xliff::translate_file(translator, file, lang.as_deref()).await
+            })
+            .await?;
+        }
+        Commands::Json {
+            folder,
+            lang,
+            source_lang,
+            format,
+        } => {
+            let run = format.into_run(&folder)?;
+            let lang = lang.map(translator::lang_codes);
+            let targets = formats::json::targets(
+                &folder,
+                &source_lang,
+                lang.as_deref(),
+                &run.options.filter,
+            )?;
+            run.translate(&folder, targets, formats::json::translate_file)
+                .await?;
         }
-        Commands::Translator {
+        Commands::Yaml {
             folder,
             lang,
+            source_lang,
+            format,
+        } => {
+            let run = format.into_run(&folder)?;
+            let lang = lang.map(translator::lang_codes);
+            let targets = formats::yaml::targets(
+                &folder,
+                &source_lang,
+                lang.as_deref(),
+                &run.options.filter,
+            )?;
+            run.translate(&folder, targets, formats::yaml::translate_file)
+                .await?;
+        }
+        Commands::Arb {
+            folder,
+            lang,
+            source_lang,
+            format,
+        } => {
+            let run = format.into_run(&folder)?;
+            let lang = lang.map(translator::lang_codes);
+            let targets =
+                formats::arb::targets(&folder, &source_lang, lang.as_deref(), &run.options.filter)?;
+            run.translate(&folder, targets, formats::arb::translate_file)
+                .await?;
+        }
+        Commands::Csv {
+            path,
+            lang,
+            source_lang,
+            source_column,
+            key_column,
+            note_column,
+            columns,
+            delimiter,
+            out,
+            format,
+        } => {
+            let folder = formats::folder(&path);
+            let run = format.into_run(&folder)?;
+            let columns = formats::csv::Columns {
+                delimiter: delimiter
+                    .as_deref()
+                    .map(formats::csv::Columns::parse_delimiter)
+                    .transpose()?,
+                source_lang,
+                source: source_column,
+                key: key_column,
+                note: note_column,
+                langs: lang.map(translator::lang_codes),
+                headers: formats::csv::Columns::parse_headers(&columns)?,
+                output: out,
+            };
+            run.translate(&folder, vec![path], async |translator, file| {
+                formats::csv::translate_file(translator, file, &columns).await
+            })
+            .await?;
+        }
+        Commands::Apple {
+            folder,
+            lang,
+            source_lang,
+            format,
+        } => {
+            let run = format.into_run(&folder)?;
+            let lang = lang.map(translator::lang_codes);
+            let targets = formats::apple::targets(
+                &folder,
+                &source_lang,
+                lang.as_deref(),
+                &run.options.filter,
+            )?;
+            run.translate(&folder, targets, formats::apple::translate_file)
+                .await?;
+        }
+        Commands::Android {
+            folder,
+            lang,
+            source_lang,
+            format,
+        } => {
+            let run = format.into_run(&folder)?;
+            let lang = lang.map(translator::lang_codes);
+            let targets = formats::android::targets(
+                &folder,
+                &source_lang,
+                lang.as_deref(),
+                &run.options.filter,
+            )?;
+            run.translate(&folder, targets, formats::android::translate_file)
+                .await?;
+        }
+        Commands::Compile { folder } => {
+            mo::run(folder)?;
+        }
+        Commands::Check { folder } => {
+            if check::run(folder)? > 0 {
+                std::process::exit(1);
+            }
+        }
+        Commands::Stats {
+            folder,
+            min_coverage,
+        } => {
+            if !coverage::run(folder, min_coverage)? {
+                std::process::exit(1);
+            }
+        }
+        Commands::Lock(args) => args.run(true)?,
+        Commands::Unlock(args) => args.run(false)?,
+        Commands::Sync {
+            folder,
+            template,
+            lang,
             dry_run,
-            force,
+        } => {
+            ai_gettext_translator::sync::run(&folder, template.as_deref(), &lang, dry_run)?;
+        }
+        Commands::Consistency {
+            folder,
+            lang,
+            suggest,
+            provider,
+        } => {
+            if suggest
+                && matches!(
+                    provider.provider,
+                    ProviderKind::Deepl | ProviderKind::Pseudo | ProviderKind::Mock
+                )
+            {
+                anyhow::bail!(
+                    "--suggest needs a language model, not --provider deepl, pseudo or mock"
+                );
+            }
+            let provider = suggest.then(|| provider.build()).transpose()?;
+            let found =
+                ai_gettext_translator::consistency::run(&folder, &lang, provider.as_deref())
+                    .await?;
+            if found > 0 {
+                std::process::exit(1);
+            }
+        }
+        Commands::Tm { command } => match command {
+            TmCommand::Export {
+                tm,
+                out,
+                lang,
+                reviewed_only,
+            } => {
+                ai_gettext_translator::tm::export(&tm, &out, &lang, reviewed_only)?;
+            }
+            TmCommand::Import {
+                file,
+                tm,
+                source_lang,
+                reviewed,
+            } => {
+                ai_gettext_translator::tm::import(&file, &tm, source_lang.as_deref(), reviewed)?;
+            }
+        },
+        Commands::Verify {
+            folder,
+            lang,
+            back_translate,
+            source_lang,
+            concurrency,
+            mark_fuzzy,
+            out,
             context,
-            api_key,
+            provider,
+        } => {
+            if matches!(
+                provider.provider,
+                ProviderKind::Deepl | ProviderKind::Pseudo | ProviderKind::Mock
+            ) {
+                anyhow::bail!(
+                    "verify needs a language model, not --provider deepl, pseudo or mock"
+                );
+            }
+            let options = ai_gettext_translator::verify::ReviewOptions {
+                context: ai_gettext_translator::translator::load_context(&folder, &context).await?,
+                out: out.unwrap_or_else(|| {
+                    folder.join(ai_gettext_translator::verify::DEFAULT_REVIEW_FILE)
+                }),
+                langs: lang,
+                back_translate,
+                source_lang,
+                concurrency,
+                mark_fuzzy,
+            };
+            let provider = provider.build()?;
+            let flagged =
+                ai_gettext_translator::verify::run(&folder, &options, provider.as_ref()).await?;
+            if flagged > 0 {
+                std::process::exit(1);
+            }
+        }
+        Commands::Serve {
+            folder,
+            host,
+            port,
+            lang,
+            token,
+            translation,
         } => {
-            translator::run(folder, &lang, dry_run, force, context, api_key).await?;
+            let run = translation.into_run(&folder)?;
+            let translator =
+                translator::for_folder(&folder, &run.context, run.provider, run.options).await?;
+            let options = serve::ServeOptions {
+                addr: std::net::SocketAddr::new(host, port),
+                lang,
+                token,
+            };
+            serve::run(translator, options).await?;
         }
     }
 
@@ -0,0 +1,333 @@
+//! HTML and Markdown in messages, like `<a href="%{url}">` or `**bold**`: what a message is
+//! written in, the guidance the model gets about it and the check that a translation keeps
+//! its tags, links and structure exactly. `--format-hint` gives the markup of a run, or of
+//! the catalogs matching a glob, instead of detecting it from each message.
+
+use anyhow::{Result, bail};
+use clap::ValueEnum;
+use globset::{Glob, GlobMatcher};
+use regex::Regex;
+use std::path::Path;
+use std::sync::LazyLock;
+
+/// What a message is written in
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Markup {
+    Html,
+    Markdown,
+    /// Text whose `<`, `*` or `[` aren't markup, never checked
+    Plain,
+}
+
+/// An HTML tag, opening, closing or self-closing, with its attributes
+static TAG: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"</?[A-Za-z][A-Za-z0-9-]*(?:\s+[^<>"']*(?:"[^"]*"|'[^']*')?)*\s*/?>"#).unwrap()
+});
+
+/// A named or numeric HTML entity, like `&nbsp;` or `&#8212;`
+static ENTITY: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"&(?:[A-Za-z]+|#[0-9]+|#x[0-9A-Fa-f]+);").unwrap());
+
+/// A Markdown link or image, `[text](url)`, capturing the URL
+static LINK: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"!?\[[^\]\n]*\]\(([^)\s]+)(?:\s+[^)]*)?\)").unwrap());
+
+/// A Markdown code span, left untranslated
+static CODE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"`[^`\n]+`").unwrap());
+
+/// Markdown emphasis around a word: `**`, `__` or `*`
+static EMPHASIS: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(\*\*|__)\S(?:[^\n]*?\S)?(\*\*|__)|\*\S[^*\n]*\*").unwrap());
+
+/// The marker of a Markdown heading, list item or quote at the start of a line
+static LINE_MARKER: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?m)^[ \t]*(#{1,6}|[-*+]|\d+\.|>)[ \t]").unwrap());
+
+static WHITESPACE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\s+").unwrap());
+
+/// The markup of `text`: HTML if it has tags or entities, Markdown if it has links, code
+/// spans, emphasis or headings and lists, else plain text
+pub fn detect(text: &str) -> Markup {
+    if TAG.is_match(text) || ENTITY.is_match(text) {
+        Markup::Html
+    } else if LINK.is_match(text)
+        || CODE.is_match(text)
+        || EMPHASIS.is_match(text)
+        || LINE_MARKER.find_iter(text).count() >= 2
+    {
+        Markup::Markdown
+    } else {
+        Markup::Plain
+    }
+}
+
+/// The markup of `text`: `hint` when given, else the detected one
+pub fn of(text: &str, hint: Option<Markup>) -> Markup {
+    hint.unwrap_or_else(|| detect(text))
+}
+
+/// What the model is told about a message in `markup`, None for plain text
+pub fn guidance(markup: Markup) -> Option<&'static str> {
+    match markup {
+        Markup::Html => Some(
+            "The message is HTML: keep every tag and entity exactly as written, with the same attributes and values, and translate only the text around and between them.",
+        ),
+        Markup::Markdown => Some(
+            "The message is Markdown: keep its structure exactly, with the same headings, list items, emphasis and links, each URL as written, and leave code spans untranslated.",
+        ),
+        Markup::Plain => None,
+    }
+}
+
+/// Checks that `translation` keeps the markup of `source`: the same HTML tags, or the same
+/// Markdown links, code spans, emphasis and line markers. Returns what doesn't match.
+pub fn mismatch(markup: Markup, source: &str, translation: &str) -> Option<String> {
+    match markup {
+        Markup::Html => {
+            let tag = |tag: &str| WHITESPACE.replace_all(tag, " ").into_owned();
+            let entity = |entity: &str| entity.to_string();
+            differs(
+                "tag",
+                &matches(&TAG, source, tag),
+                &matches(&TAG, translation, tag),
+            )
+            .or_else(|| {
+                differs(
+                    "entity",
+                    &matches(&ENTITY, source, entity),
+                    &matches(&ENTITY, translation, entity),
+                )
+            })
+        }
+        Markup::Markdown => {
+            let urls = |text: &str| -> Vec<String> {
+                let mut urls: Vec<String> =
+                    LINK.captures_iter(text).map(|c| c[1].to_string()).collect();
+                urls.sort();
+                urls
+            };
+            let code = |span: &str| span.to_string();
+            let marker = |marker: &str| {
+                let marker = marker.trim();
+                match marker.ends_with('.') {
+                    true => "1.".to_string(),
+                    false => marker.to_string(),
+                }
+            };
+            let emphasis = |text: &str| EMPHASIS.find_iter(text).count();
+            differs("link to", &urls(source), &urls(translation))
+                .or_else(|| {
+                    differs(
+                        "code span",
+                        &matches(&CODE, source, code),
+                        &matches(&CODE, translation, code),
+                    )
+                })
+                .or_else(|| {
+                    let markers = |text: &str| -> Vec<String> {
+                        LINE_MARKER
+                            .captures_iter(text)
+                            .map(|c| marker(&c[1]))
+                            .collect()
+                    };
+                    (markers(source) != markers(translation))
+                        .then(|| "different headings or list items".to_string())
+                })
+                .or_else(|| {
+                    let (expected, got) = (emphasis(source), emphasis(translation));
+                    (expected != got)
+                        .then(|| format!("{} emphasized spans instead of {}", got, expected))
+                })
+        }
+        Markup::Plain => None,
+    }
+}
+
+/// The matches of `pattern` in `text`, normalized and sorted
+fn matches(pattern: &Regex, text: &str, normalize: impl Fn(&str) -> String) -> Vec<String> {
+    let mut found: Vec<String> = pattern
+        .find_iter(text)
+        .map(|m| normalize(m.as_str()))
+        .collect();
+    found.sort();
+    found
+}
+
+/// The first of the sorted `expected` missing from the sorted `got`, or the first one of
+/// `got` that isn't expected
+fn differs(what: &str, expected: &[String], got: &[String]) -> Option<String> {
+    let mut got_left = got.to_vec();
+    for item in expected {
+        match got_left.iter().position(|other| other == item) {
+            Some(index) => {
+                got_left.remove(index);
+            }
+            None => return Some(format!("missing {} {}", what, item)),
+        }
+    }
+    got_left
+        .first()
+        .map(|item| format!("unexpected {} {}", what, item))
+}
+
+/// The `--format-hint` of a run: `html`, `markdown` or `plain` for every catalog, or
+/// `<glob>=<markup>` for those whose path or file name matches the glob. The last matching
+/// hint wins.
+#[derive(Default)]
+pub struct FormatHints {
+    hints: Vec<(Option<GlobMatcher>, Markup)>,
+}
+
+impl FormatHints {
+    pub fn parse(specs: &[String]) -> Result<Self> {
+        let mut hints = Vec::new();
+        for spec in specs {
+            let (glob, markup) = match spec.rsplit_once('=') {
+                Some((glob, markup)) => (Some(glob.trim()), markup.trim()),
+                None => (None, spec.trim()),
+            };
+            let Ok(markup) = Markup::from_str(markup, true) else {
+                bail!(
+                    "invalid --format-hint `{}`: expected html, markdown or plain, optionally after <glob>=",
+                    spec
+                );
+            };
+            let glob = glob
+                .map(|glob| {
+                    Glob::new(glob)
+                        .map(|glob| glob.compile_matcher())
+                        .map_err(|e| anyhow::anyhow!("invalid --format-hint glob {}: {}", glob, e))
+                })
+                .transpose()?;
+            hints.push((glob, markup));
+        }
+        Ok(Self { hints })
+    }
+
+    /// The markup of the messages of the catalog at `path`, None to detect it per message
+    pub fn for_path(&self, path: Option<&Path>) -> Option<Markup> {
+        self.hints
+            .iter()
+            .rev()
+            .find(|(glob, _)| match (glob, path) {
+                (None, _) => true,
+                (Some(glob), Some(path)) => {
+                    glob.is_match(path) || path.file_name().is_some_and(|name| glob.is_match(name))
+                }
+                (Some(_), None) => false,
+            })
+            .map(|(_, markup)| *markup)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_the_markup_of_messages() {
+        assert_eq!(detect("Read <a href=\"%{url}\">the docs</a>"), Markup::Html);
+        assert_eq!(detect("Fish &amp; chips"), Markup::Html);
+        assert_eq!(
+            detect("See [the docs](https://example.com)"),
+            Markup::Markdown
+        );
+        assert_eq!(detect("Run `mix deps.get` first"), Markup::Markdown);
+        assert_eq!(detect("This is **important**"), Markup::Markdown);
+        assert_eq!(detect("- one\n- two"), Markup::Markdown);
+        assert_eq!(detect("- a single dash"), Markup::Plain);
+        assert_eq!(detect("2 < 3 and 5 * 4"), Markup::Plain);
+        assert_eq!(of("<b>Hi</b>", Some(Markup::Plain)), Markup::Plain);
+    }
+
+    #[test]
+    fn checks_html_tags_and_entities() {
+        let source = "Read <a href=\"%{url}\">the docs</a>&nbsp;now";
+        assert_eq!(
+            mismatch(
+                Markup::Html,
+                source,
+                "Lee <a  href=\"%{url}\">la guía</a>&nbsp;ya"
+            ),
+            None
+        );
+        assert_eq!(
+            mismatch(
+                Markup::Html,
+                source,
+                "Lee <a href=\"%{link}\">la guía</a>&nbsp;ya"
+            ),
+            Some("missing tag <a href=\"%{url}\">".to_string())
+        );
+        assert_eq!(
+            mismatch(
+                Markup::Html,
+                source,
+                "Lee <a href=\"%{url}\">la guía</a> ya"
+            ),
+            Some("missing entity &nbsp;".to_string())
+        );
+        assert_eq!(
+            mismatch(Markup::Html, "Hi", "<b>Hola</b>"),
+            Some("unexpected tag </b>".to_string())
+        );
+    }
+
+    #[test]
+    fn checks_markdown_structure() {
+        let source = "## Setup\n- Run `make`\n- See [the docs](https://example.com) **now**";
+        let good = "## Instalación\n- Ejecuta `make`\n- Mira [la guía](https://example.com) **ya**";
+        assert_eq!(mismatch(Markup::Markdown, source, good), None);
+        assert_eq!(
+            mismatch(
+                Markup::Markdown,
+                source,
+                &good.replace("example.com", "example.es")
+            ),
+            Some("missing link to https://example.com".to_string())
+        );
+        assert_eq!(
+            mismatch(Markup::Markdown, source, &good.replace("`make`", "`hacer`")),
+            Some("missing code span `make`".to_string())
+        );
+        assert_eq!(
+            mismatch(Markup::Markdown, source, &good.replace("## ", "")),
+            Some("different headings or list items".to_string())
+        );
+        assert_eq!(
+            mismatch(Markup::Markdown, source, &good.replace("**ya**", "ya")),
+            Some("0 emphasized spans instead of 1".to_string())
+        );
+        assert_eq!(
+            mismatch(Markup::Markdown, "1. One\n2. Two", "1. Uno\n3. Dos"),
+            None
+        );
+        assert_eq!(mismatch(Markup::Plain, "<b>", "no"), None);
+    }
+
+    #[test]
+    fn takes_hints_by_glob() {
+        let hints = FormatHints::parse(&[
+            "markdown".to_string(),
+            "**/emails/*.po=html".to_string(),
+            "help.po=plain".to_string(),
+        ])
+        .unwrap();
+        assert_eq!(hints.for_path(None), Some(Markup::Markdown));
+        assert_eq!(
+            hints.for_path(Some(Path::new("priv/emails/es.po"))),
+            Some(Markup::Html)
+        );
+        assert_eq!(
+            hints.for_path(Some(Path::new("priv/es/help.po"))),
+            Some(Markup::Plain)
+        );
+        assert_eq!(
+            hints.for_path(Some(Path::new("priv/es/default.po"))),
+            Some(Markup::Markdown)
+        );
+        assert_eq!(FormatHints::default().for_path(None), None);
+        assert!(FormatHints::parse(&["rtf".to_string()]).is_err());
+        assert!(FormatHints::parse(&["[=html".to_string()]).is_err());
+    }
+}
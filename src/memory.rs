@@ -0,0 +1,515 @@
+//! The fuzzy translation memory of `--memory`: the translated msgids of each language, from
+//! the catalogs and from the run, compared by their embeddings, the vectors of the
+//! embeddings API of a provider, so that a message reworded, even with other words, finds the
+//! translation of the one it was reworded from. One that differs only in its final
+//! punctuation reuses the translation, and others get it in their prompt as a reference. The
+//! vectors are kept in an index next to the cache, so that later runs only ask for those of
+//! new msgids; the memory itself is rebuilt from the catalogs each run.
+
+use anyhow::Result;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use crate::atomic;
+use crate::po::{PoCatalog, PoEntry};
+use crate::provider::Embedder;
+
+/// The similarity, between 0 and 1, of the near-matches given by default
+pub const DEFAULT_THRESHOLD: f64 = 0.9;
+
+/// The index of the embeddings, in the folder of the cache
+pub const DEFAULT_INDEX_FILE: &str = ".gettext-translator-embeddings";
+
+/// The first line of an index file
+const INDEX_MAGIC: &[u8] = b"gettext-translator embeddings 1\n";
+
+/// The final punctuation a translation reused for a msgid takes from it
+const FINAL_PUNCTUATION: &[char] = &['.', ':', '!', '?', '…', '。', '：', '！', '？'];
+
+/// The settings of `--memory`
+pub struct MemoryOptions {
+    /// The similarity, between 0 and 1, of the translations given
+    pub threshold: f64,
+    /// Turns the msgids into the vectors compared
+    pub embedder: Arc<Embedder>,
+}
+
+/// A translated msgid, with its embedding
+struct Record {
+    msgid: String,
+    msgstr: String,
+    vector: Arc<[f32]>,
+}
+
+/// The translation of a msgid like the one looked up
+pub struct Match {
+    pub msgid: String,
+    pub msgstr: String,
+    /// The cosine similarity of the embeddings of the two msgids, up to 1
+    pub similarity: f64,
+}
+
+impl Match {
+    /// The translation for `msgid` when it only differs from the matched msgid in its final
+    /// punctuation, like "Save" and "Save…": the matched translation with the punctuation
+    /// of `msgid` instead
+    pub fn reuse(&self, msgid: &str) -> Option<String> {
+        let (text, ending) = split_ending(msgid);
+        let (matched_text, matched_ending) = split_ending(&self.msgid);
+        let (translation, translated_ending) = split_ending(&self.msgstr);
+        // Inverted marks like ¿ and ¡ go with the ending they open
+        if text != matched_text
+            || translated_ending != matched_ending
+            || translation.contains(['¿', '¡'])
+        {
+            return None;
+        }
+        Some(format!("{}{}", translation, ending))
+    }
+
+    /// What the model is told about the match
+    pub fn note(&self) -> String {
+        format!(
+            "A similar message, \"{}\", was translated as \"{}\": reuse its wording where the meaning is the same.",
+            self.msgid, self.msgstr
+        )
+    }
+}
+
+/// The translated msgids of every language, kept for the run. Looking one up compares its
+/// vector with those of every msgid of its language and msgctxt.
+#[derive(Default)]
+pub struct TranslationMemory {
+    /// The records of each language and msgctxt
+    records: HashMap<(String, Option<String>), Vec<Record>>,
+    /// The msgids of each language and msgctxt that have a record
+    msgids: HashSet<(String, Option<String>, String)>,
+    /// The catalog files added
+    files: HashSet<PathBuf>,
+}
+
+impl TranslationMemory {
+    /// The translated singular entries of `catalog` the memory takes, leaving out fuzzy
+    /// ones, with their translation
+    pub fn translations(catalog: &PoCatalog) -> Vec<(&PoEntry, &str)> {
+        catalog
+            .entries()
+            .filter(|entry| !entry.is_header() && !entry.has_flag("fuzzy"))
+            .filter_map(|entry| Some((entry, entry.msgstr.first()?.as_str())))
+            .filter(|(entry, msgstr)| Self::takes(entry, msgstr))
+            .collect()
+    }
+
+    /// Records that the catalog at `path` was added, returning false if it already was
+    pub fn add_file(&mut self, path: &Path) -> bool {
+        self.files.insert(path.to_path_buf())
+    }
+
+    /// Returns true if `lang` has a translation of the msgid of `entry` already
+    pub fn contains(&self, lang: &str, entry: &PoEntry) -> bool {
+        self.msgids
+            .contains(&(lang.to_string(), entry.msgctxt.clone(), entry.msgid.clone()))
+    }
+
+    /// Adds the translation of `entry` to `lang`, with the `vector` of its msgid, unless its
+    /// msgid has one already
+    pub fn add(&mut self, lang: &str, entry: &PoEntry, msgstr: &str, vector: Arc<[f32]>) {
+        if !Self::takes(entry, msgstr)
+            || !self
+                .msgids
+                .insert((lang.to_string(), entry.msgctxt.clone(), entry.msgid.clone()))
+        {
+            return;
+        }
+        self.records
+            .entry((lang.to_string(), entry.msgctxt.clone()))
+            .or_default()
+            .push(Record {
+                msgid: entry.msgid.clone(),
+                msgstr: msgstr.to_string(),
+                vector,
+            });
+    }
+
+    /// The translation to `lang` of the msgid most similar to that of `entry`, whose
+    /// embedding is `vector`, with the same msgctxt, if it is at least `threshold` similar.
+    /// The msgid itself is left out: its translation is the cache's.
+    pub fn find(
+        &self,
+        lang: &str,
+        entry: &PoEntry,
+        vector: &[f32],
+        threshold: f64,
+    ) -> Option<Match> {
+        if entry.is_plural() {
+            return None;
+        }
+        self.records
+            .get(&(lang.to_string(), entry.msgctxt.clone()))?
+            .iter()
+            .filter(|record| record.msgid != entry.msgid)
+            .map(|record| (similarity(vector, &record.vector), record))
+            .filter(|(similarity, _)| *similarity >= threshold)
+            .max_by(|(a, _), (b, _)| a.total_cmp(b))
+            .map(|(similarity, record)| Match {
+                msgid: record.msgid.clone(),
+                msgstr: record.msgstr.clone(),
+                similarity,
+            })
+    }
+
+    /// Whether the memory takes the translation `msgstr` of `entry`: only those of singular
+    /// entries with text
+    fn takes(entry: &PoEntry, msgstr: &str) -> bool {
+        !entry.is_plural() && !msgstr.trim().is_empty() && !entry.msgid.trim().is_empty()
+    }
+}
+
+/// The embeddings of the msgids, asked of `embedder` once and kept in the index
+pub struct Embeddings {
+    embedder: Arc<Embedder>,
+    index: Mutex<EmbeddingIndex>,
+}
+
+impl Embeddings {
+    /// The embeddings of `embedder`, with the index file at `path`, or only for the run
+    /// without one
+    pub fn load(embedder: Arc<Embedder>, path: Option<&Path>) -> Result<Self> {
+        let index = EmbeddingIndex::load(path, &embedder.model())?;
+        Ok(Self {
+            embedder,
+            index: Mutex::new(index),
+        })
+    }
+
+    fn index(&self) -> MutexGuard<'_, EmbeddingIndex> {
+        self.index.lock().expect("embeddings lock poisoned")
+    }
+
+    /// The vectors of `texts`, in their order, asking the embeddings API for those that
+    /// aren't in the index yet, each once
+    pub async fn vectors(&self, texts: &[&str]) -> Result<Vec<Arc<[f32]>>> {
+        let missing: Vec<String> = {
+            let index = self.index();
+            let mut seen = HashSet::new();
+            texts
+                .iter()
+                .filter(|text| index.get(text).is_none() && seen.insert(**text))
+                .map(|text| text.to_string())
+                .collect()
+        };
+        if !missing.is_empty() {
+            let vectors = self.embedder.embed(&missing).await?;
+            let mut index = self.index();
+            for (text, vector) in missing.into_iter().zip(vectors) {
+                index.insert(text, vector);
+            }
+        }
+
+        let index = self.index();
+        Ok(texts
+            .iter()
+            .map(|text| index.get(text).expect("every text was embedded"))
+            .collect())
+    }
+
+    /// Writes the vectors added since the last time to the index file
+    pub fn save(&self) -> Result<()> {
+        self.index().save()
+    }
+}
+
+/// The vectors of one embedding model, by text, scaled to a length of 1. On disk, the index
+/// is the magic line, the model and a line break, then for each text the length of its UTF-8
+/// bytes, the bytes, the length of its vector and its numbers, all little-endian 32 bits.
+pub struct EmbeddingIndex {
+    path: Option<PathBuf>,
+    model: String,
+    vectors: HashMap<String, Arc<[f32]>>,
+    dirty: bool,
+}
+
+impl EmbeddingIndex {
+    /// Loads the index of `model` at `path`, starting empty if the file doesn't exist yet or
+    /// has the vectors of another model, which can't be compared
+    pub fn load(path: Option<&Path>, model: &str) -> Result<Self> {
+        let mut index = Self {
+            path: path.map(Path::to_path_buf),
+            model: model.to_string(),
+            vectors: HashMap::new(),
+            dirty: false,
+        };
+        let Some(path) = path.filter(|path| path.is_file()) else {
+            return Ok(index);
+        };
+
+        let bytes = fs::read(path)
+            .map_err(|e| anyhow::anyhow!("Error reading {}: {}", path.display(), e))?;
+        let invalid = || {
+            anyhow::anyhow!(
+                "Invalid embedding index {}: delete it to build it again",
+                path.display()
+            )
+        };
+        let mut reader = Reader(bytes.strip_prefix(INDEX_MAGIC).ok_or_else(invalid)?);
+        let line = reader.bytes_until(b'\n').ok_or_else(invalid)?;
+        if line != model.as_bytes() {
+            return Ok(index);
+        }
+        while !reader.0.is_empty() {
+            let text = reader
+                .length()
+                .and_then(|len| reader.take(len))
+                .and_then(|text| String::from_utf8(text.to_vec()).ok())
+                .ok_or_else(invalid)?;
+            let vector: Vec<f32> = reader
+                .length()
+                .and_then(|len| reader.take(len.checked_mul(4)?))
+                .ok_or_else(invalid)?
+                .chunks_exact(4)
+                .map(|number| f32::from_le_bytes(number.try_into().expect("4 bytes")))
+                .collect();
+            index.vectors.insert(text, vector.into());
+        }
+        Ok(index)
+    }
+
+    /// The vector of `text`, if the index has it
+    pub fn get(&self, text: &str) -> Option<Arc<[f32]>> {
+        self.vectors.get(text).cloned()
+    }
+
+    /// Adds the `vector` of `text`, scaled to a length of 1
+    pub fn insert(&mut self, text: String, mut vector: Vec<f32>) {
+        let norm = vector.iter().map(|x| x * x).sum::<f32>().sqrt();
+        if norm > 0.0 {
+            vector.iter_mut().for_each(|x| *x /= norm);
+        }
+        self.vectors.insert(text, vector.into());
+        self.dirty = true;
+    }
+
+    /// Writes the index to its file, if it has one and anything was added, in the order of
+    /// the texts so that the file only changes with them
+    pub fn save(&mut self) -> Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if !self.dirty {
+            return Ok(());
+        }
+
+        let mut texts: Vec<&String> = self.vectors.keys().collect();
+        texts.sort();
+        let mut bytes = INDEX_MAGIC.to_vec();
+        bytes.extend(self.model.as_bytes());
+        bytes.push(b'\n');
+        for text in texts {
+            let vector = &self.vectors[text];
+            bytes.extend((text.len() as u32).to_le_bytes());
+            bytes.extend(text.as_bytes());
+            bytes.extend((vector.len() as u32).to_le_bytes());
+            for number in vector.iter() {
+                bytes.extend(number.to_le_bytes());
+            }
+        }
+        atomic::write(path, bytes)?;
+        self.dirty = false;
+        Ok(())
+    }
+}
+
+/// Reads an index file from its start
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        if len > self.0.len() {
+            return None;
+        }
+        let (taken, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Some(taken)
+    }
+
+    /// A length, as 32 bits
+    fn length(&mut self) -> Option<usize> {
+        let bytes = self.take(4)?;
+        Some(u32::from_le_bytes(bytes.try_into().ok()?) as usize)
+    }
+
+    /// The bytes up to `end`, skipping it
+    fn bytes_until(&mut self, end: u8) -> Option<&'a [u8]> {
+        let at = self.0.iter().position(|byte| *byte == end)?;
+        let line = self.take(at)?;
+        self.take(1)?;
+        Some(line)
+    }
+}
+
+/// The cosine similarity of two vectors of length 1
+fn similarity(a: &[f32], b: &[f32]) -> f64 {
+    a.iter().zip(b).map(|(a, b)| (a * b) as f64).sum()
+}
+
+/// `text` without its final punctuation and whitespace, and those
+fn split_ending(text: &str) -> (&str, &str) {
+    let trimmed =
+        text.trim_end_matches(|c: char| c.is_whitespace() || FINAL_PUNCTUATION.contains(&c));
+    (trimmed, &text[trimmed.len()..])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(msgctxt: Option<&str>, msgid: &str) -> PoEntry {
+        PoEntry::from_strings(
+            msgctxt.map(str::to_string),
+            msgid.to_string(),
+            None,
+            Vec::new(),
+            &[],
+        )
+    }
+
+    fn vector(numbers: &[f32]) -> Arc<[f32]> {
+        let norm = numbers.iter().map(|x| x * x).sum::<f32>().sqrt();
+        numbers.iter().map(|x| x / norm).collect()
+    }
+
+    fn memory() -> TranslationMemory {
+        let mut memory = TranslationMemory::default();
+        memory.add(
+            "es",
+            &entry(None, "Delete the file"),
+            "Borrar el archivo",
+            vector(&[1.0, 0.0, 0.0]),
+        );
+        memory.add(
+            "es",
+            &entry(None, "Remove the chosen file"),
+            "Quitar el archivo elegido",
+            vector(&[0.9, 0.3, 0.0]),
+        );
+        memory.add(
+            "es",
+            &entry(None, "Sign out"),
+            "Cerrar sesión",
+            vector(&[0.0, 0.0, 1.0]),
+        );
+        memory
+    }
+
+    #[test]
+    fn finds_the_most_similar_translation_above_the_threshold() {
+        let memory = memory();
+        let query = vector(&[0.95, 0.1, 0.0]);
+        let found = memory
+            .find("es", &entry(None, "Delete the selected file"), &query, 0.9)
+            .unwrap();
+        assert_eq!(found.msgid, "Delete the file");
+        assert_eq!(found.msgstr, "Borrar el archivo");
+        assert!(found.similarity > 0.99);
+
+        let query = vector(&[0.85, 0.35, 0.0]);
+        let found = memory
+            .find("es", &entry(None, "Remove the file"), &query, 0.9)
+            .unwrap();
+        assert_eq!(found.msgid, "Remove the chosen file");
+
+        let query = vector(&[0.5, 0.0, 0.5]);
+        assert!(
+            memory
+                .find("es", &entry(None, "Log"), &query, 0.9)
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn leaves_out_other_languages_contexts_and_the_msgid_itself() {
+        let memory = memory();
+        let query = vector(&[1.0, 0.0, 0.0]);
+        assert!(
+            memory
+                .find("fr", &entry(None, "Delete a file"), &query, 0.5)
+                .is_none()
+        );
+        assert!(
+            memory
+                .find("es", &entry(Some("menu"), "Delete a file"), &query, 0.5)
+                .is_none()
+        );
+        let found = memory
+            .find("es", &entry(None, "Delete the file"), &query, 0.5)
+            .unwrap();
+        assert_eq!(found.msgid, "Remove the chosen file");
+    }
+
+    #[test]
+    fn adds_each_msgid_once() {
+        let mut memory = memory();
+        let deleted = entry(None, "Delete the file");
+        assert!(memory.contains("es", &deleted));
+        assert!(!memory.contains("es", &entry(Some("menu"), "Delete the file")));
+        memory.add(
+            "es",
+            &deleted,
+            "Eliminar el archivo",
+            vector(&[1.0, 0.0, 0.0]),
+        );
+        let query = vector(&[1.0, 0.0, 0.0]);
+        let found = memory
+            .find("es", &entry(None, "Delete a file"), &query, 0.9)
+            .unwrap();
+        assert_eq!(found.msgstr, "Borrar el archivo");
+
+        assert!(memory.add_file(Path::new("es/default.po")));
+        assert!(!memory.add_file(Path::new("es/default.po")));
+    }
+
+    #[test]
+    fn reuses_a_translation_differing_only_in_final_punctuation() {
+        let found = Match {
+            msgid: "Save".to_string(),
+            msgstr: "Guardar".to_string(),
+            similarity: 0.97,
+        };
+        assert_eq!(found.reuse("Save…").as_deref(), Some("Guardar…"));
+        assert_eq!(found.reuse("Save all"), None);
+
+        let question = Match {
+            msgid: "Continue?".to_string(),
+            msgstr: "¿Continuar?".to_string(),
+            similarity: 0.97,
+        };
+        assert_eq!(question.reuse("Continue!"), None);
+    }
+
+    #[test]
+    fn saves_and_loads_the_index_of_its_model() {
+        let dir = std::env::temp_dir().join(format!("memory-index-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(DEFAULT_INDEX_FILE);
+
+        let mut index = EmbeddingIndex::load(Some(&path), "mock/ngrams").unwrap();
+        index.insert("Save".to_string(), vec![3.0, 4.0]);
+        index.insert("Ünïcode".to_string(), vec![0.0, 2.0]);
+        index.save().unwrap();
+
+        let loaded = EmbeddingIndex::load(Some(&path), "mock/ngrams").unwrap();
+        assert_eq!(&*loaded.get("Save").unwrap(), &[0.6, 0.8]);
+        assert_eq!(&*loaded.get("Ünïcode").unwrap(), &[0.0, 1.0]);
+        assert!(loaded.get("Open").is_none());
+
+        let other = EmbeddingIndex::load(Some(&path), "openai/text-embedding-3-small").unwrap();
+        assert!(other.get("Save").is_none());
+
+        let bytes = fs::read(&path).unwrap();
+        fs::write(&path, &bytes[..bytes.len() - 2]).unwrap();
+        assert!(EmbeddingIndex::load(Some(&path), "mock/ngrams").is_err());
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
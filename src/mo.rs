@@ -0,0 +1,198 @@
+use anyhow::{Result, bail};
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+use crate::atomic;
+use crate::logger::log_compiled;
+use crate::po::PoCatalog;
+
+const MO_MAGIC: u32 = 0x950412de;
+/// Size of the fixed header: magic, revision, count and the four table offsets/sizes
+const HEADER_SIZE: u32 = 28;
+
+/// Compiles a catalog into the binary .mo format, like `msgfmt` does: untranslated and
+/// fuzzy entries are left out (the header is always kept), and strings are encoded with the
+/// catalog's charset
+pub fn compile(catalog: &PoCatalog) -> Result<Vec<u8>> {
+    let encoding = catalog.encoding();
+    let encode = |text: &str| -> Result<Vec<u8>> {
+        let (bytes, _, had_errors) = encoding.encode(text);
+        if had_errors {
+            bail!("string cannot be represented in {}", encoding.name());
+        }
+        Ok(bytes.into_owned())
+    };
+
+    let mut messages: Vec<(Vec<u8>, Vec<u8>)> = Vec::new();
+    for entry in catalog.entries() {
+        let translated = !entry.msgstr.is_empty() && entry.msgstr.iter().all(|s| !s.is_empty());
+        if !entry.is_header() && (!translated || entry.has_flag("fuzzy")) {
+            continue;
+        }
+
+        let mut key = String::new();
+        if let Some(msgctxt) = &entry.msgctxt {
+            key.push_str(msgctxt);
+            key.push('\u{4}');
+        }
+        key.push_str(&entry.msgid);
+        if let Some(msgid_plural) = &entry.msgid_plural {
+            key.push('\0');
+            key.push_str(msgid_plural);
+        }
+
+        messages.push((encode(&key)?, encode(&entry.msgstr.join("\0"))?));
+    }
+
+    // Lookups binary-search the originals, so they must be sorted by their bytes
+    messages.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let count = messages.len() as u32;
+    let originals_offset = HEADER_SIZE;
+    let translations_offset = originals_offset + count * 8;
+    let mut data_offset = translations_offset + count * 8;
+
+    let mut out = Vec::new();
+    for value in [
+        MO_MAGIC,
+        0,
+        count,
+        originals_offset,
+        translations_offset,
+        0, // no hash table
+        data_offset,
+    ] {
+        out.extend_from_slice(&value.to_le_bytes());
+    }
+
+    // Both tables hold (length, offset) pairs; the strings follow, NUL-terminated
+    let mut data = Vec::new();
+    let mut tables = [Vec::new(), Vec::new()];
+    for (table, side) in tables.iter_mut().zip([0, 1]) {
+        for message in &messages {
+            let string = if side == 0 { &message.0 } else { &message.1 };
+            table.extend_from_slice(&(string.len() as u32).to_le_bytes());
+            table.extend_from_slice(&data_offset.to_le_bytes());
+            data.extend_from_slice(string);
+            data.push(0);
+            data_offset += string.len() as u32 + 1;
+        }
+    }
+
+    out.extend(tables.concat());
+    out.extend(data);
+    Ok(out)
+}
+
+/// Compiles a catalog and writes it next to `po_path`, with the .mo extension
+pub fn write_mo(catalog: &PoCatalog, po_path: &Path) -> Result<PathBuf> {
+    let mo_path = po_path.with_extension("mo");
+    let bytes = compile(catalog).map_err(|e| anyhow::anyhow!("{}: {}", po_path.display(), e))?;
+    atomic::write(&mo_path, bytes)?;
+    Ok(mo_path)
+}
+
+/// Compiles every .po file under `root`, returning how many were compiled
+pub fn run(root: PathBuf) -> Result<usize> {
+    let mut compiled = 0;
+
+    for entry in WalkDir::new(&root).into_iter().filter_map(|e| e.ok()) {
+        let path = entry.path();
+        if !path.extension().map(|e| e == "po").unwrap_or(false) {
+            continue;
+        }
+
+        let catalog = PoCatalog::read(path)?;
+        let mo_path = write_mo(&catalog, path)?;
+        log_compiled(&mo_path.display().to_string());
+        compiled += 1;
+    }
+
+    Ok(compiled)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn word(bytes: &[u8], offset: usize) -> usize {
+        u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap()) as usize
+    }
+
+    /// The (original, translation) pairs of a .mo file, in the order of its tables
+    fn messages(bytes: &[u8]) -> Vec<(String, String)> {
+        let count = word(bytes, 8);
+        let string = |table: usize, index: usize| {
+            let length = word(bytes, table + index * 8);
+            let offset = word(bytes, table + index * 8 + 4);
+            assert_eq!(bytes[offset + length], 0, "strings are NUL-terminated");
+            String::from_utf8(bytes[offset..offset + length].to_vec()).unwrap()
+        };
+        (0..count)
+            .map(|index| {
+                (
+                    string(word(bytes, 12), index),
+                    string(word(bytes, 16), index),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn compiles_the_translated_entries_sorted() {
+        let catalog = PoCatalog::parse(
+            r#"msgid ""
+msgstr "Content-Type: text/plain; charset=UTF-8\n"
+
+msgid "Zebra"
+msgstr "Cebra"
+
+msgctxt "menu"
+msgid "Open"
+msgstr "Abrir"
+
+msgid "One file"
+msgid_plural "%{count} files"
+msgstr[0] "Un archivo"
+msgstr[1] "%{count} archivos"
+
+msgid "Untranslated"
+msgstr ""
+
+#, fuzzy
+msgid "Unsure"
+msgstr "Inseguro"
+"#,
+        )
+        .unwrap();
+        let bytes = compile(&catalog).unwrap();
+
+        assert_eq!(word(&bytes, 0), MO_MAGIC as usize);
+        assert_eq!(word(&bytes, 4), 0);
+        assert_eq!(word(&bytes, 12), HEADER_SIZE as usize);
+        assert_eq!(
+            messages(&bytes),
+            [
+                (
+                    String::new(),
+                    "Content-Type: text/plain; charset=UTF-8\n".to_string()
+                ),
+                (
+                    "One file\0%{count} files".to_string(),
+                    "Un archivo\0%{count} archivos".to_string()
+                ),
+                ("Zebra".to_string(), "Cebra".to_string()),
+                ("menu\u{4}Open".to_string(), "Abrir".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn encodes_the_strings_with_the_catalog_charset() {
+        let content = "msgid \"\"\nmsgstr \"Content-Type: text/plain; charset=ISO-8859-1\\n\"\n\nmsgid \"Yes\"\nmsgstr \"Sí\"\n";
+        let (latin1, _, _) = encoding_rs::WINDOWS_1252.encode(content);
+        let catalog = PoCatalog::from_bytes(&latin1, Path::new("es.po")).unwrap();
+        let bytes = compile(&catalog).unwrap();
+        assert!(bytes.windows(3).any(|window| window == b"S\xed\0"));
+    }
+}
@@ -0,0 +1,36 @@
+use regex::Regex;
+use std::sync::LazyLock;
+
+use crate::placeholders;
+use crate::po::PoEntry;
+
+/// Matches URLs, email addresses and file paths, which are never translated
+static VERBATIM_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"[a-zA-Z][a-zA-Z0-9+.-]*://\S+|www\.\S+|[\w.+-]+@[\w-]+\.[\w.-]+|[~.]?/[\w./-]+")
+        .unwrap()
+});
+
+/// Returns true if a translated entry would come out the same if translated again: its
+/// translation is identical to the msgid (brand names, proper nouns, "OK"), or its msgid has
+/// no words once URLs, emails, paths and placeholders are taken out (numbers, symbols).
+/// Used by `--skip-unchanged` to spare API calls in forced runs.
+pub fn is_unchanged(entry: &PoEntry) -> bool {
+    let kept_as_is = match &entry.msgid_plural {
+        Some(plural) => entry
+            .msgstr
+            .iter()
+            .all(|msgstr| msgstr == plural || msgstr == &entry.msgid),
+        None => entry.msgstr.iter().all(|msgstr| msgstr == &entry.msgid),
+    };
+
+    kept_as_is || !has_words(&entry.msgid)
+}
+
+/// Returns true if `text` has letters outside of its URLs, emails, paths and placeholders
+fn has_words(text: &str) -> bool {
+    let mut text = VERBATIM_REGEX.replace_all(text, " ").into_owned();
+    for placeholder in placeholders::extract(&text) {
+        text = text.replace(&placeholder, " ");
+    }
+    text.chars().any(char::is_alphabetic)
+}
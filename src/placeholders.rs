@@ -0,0 +1,37 @@
+use regex::Regex;
+use std::collections::BTreeMap;
+use std::sync::LazyLock;
+
+/// Matches `%{name}`, `%(name)s`, printf-style `%s` / `%1$d` / `%lld` / `%@`, the `%#@var@`
+/// variables of .stringsdict files, `{{var}}`, `{0}` and `{name}`
+static PLACEHOLDER_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"%\{[^}]+\}|%\([^)]+\)[a-zA-Z]|%#@\w+@|%(?:\d+\$)?[-+ 0#]*\d*(?:\.\d+)?(?:hh|h|ll|l|q|z|t|j|L)?[sdifuxXoeEgGc@]|\{\{\s*[\w.]+\s*\}\}|\{\w+\}",
+    )
+    .unwrap()
+});
+
+/// Returns every placeholder token of `text`, in order of appearance
+pub fn extract(text: &str) -> Vec<String> {
+    let text = text.replace("%%", "");
+    PLACEHOLDER_REGEX
+        .find_iter(&text)
+        .map(|m| m.as_str().to_string())
+        .collect()
+}
+
+/// `text` without its placeholders, each replaced with a space
+pub fn strip(text: &str) -> String {
+    PLACEHOLDER_REGEX
+        .replace_all(&text.replace("%%", ""), " ")
+        .into_owned()
+}
+
+/// Counts the occurrences of each placeholder of `text`
+pub fn count(text: &str) -> BTreeMap<String, usize> {
+    let mut counts = BTreeMap::new();
+    for placeholder in extract(text) {
+        *counts.entry(placeholder).or_insert(0) += 1;
+    }
+    counts
+}
@@ -0,0 +1,431 @@
+use anyhow::{Result, bail};
+
+/// Most plural forms a catalog may have: languages have up to 6, and a larger `nplurals` or
+/// `msgstr[n]` index is a broken or hostile file
+pub const MAX_PLURAL_FORMS: usize = 16;
+
+/// The plural rules of a catalog, as declared by the `Plural-Forms` header
+pub struct PluralForms {
+    pub nplurals: usize,
+    expression: Expr,
+}
+
+impl PluralForms {
+    /// Parses a header value like `nplurals=3; plural=(n==1 ? 0 : n%10>=2 && n%10<=4 ? 1 : 2);`
+    pub fn parse(header: &str) -> Result<Self> {
+        let mut nplurals = None;
+        let mut plural = None;
+
+        for part in header.split(';') {
+            let Some((key, value)) = part.split_once('=') else {
+                continue;
+            };
+
+            match key.trim() {
+                "nplurals" => nplurals = value.trim().parse::<usize>().ok(),
+                "plural" => plural = Some(value.trim()),
+                _ => {}
+            }
+        }
+
+        let (Some(nplurals), Some(plural)) = (nplurals, plural) else {
+            bail!("Invalid Plural-Forms header: {header}");
+        };
+        if !(1..=MAX_PLURAL_FORMS).contains(&nplurals) {
+            bail!(
+                "Invalid Plural-Forms header, nplurals must be from 1 to {MAX_PLURAL_FORMS}: {header}"
+            );
+        }
+
+        let expression = ExprParser::new(plural)?.parse()?;
+        Ok(Self {
+            nplurals,
+            expression,
+        })
+    }
+
+    /// Built-in rules for languages whose catalog has no `Plural-Forms` header
+    pub fn for_language(code: &str) -> Self {
+        Self::parse(header_for_language(code)).expect("built-in plural forms are valid")
+    }
+
+    /// Returns the msgstr index used for the quantity `n`
+    pub fn index(&self, n: i64) -> usize {
+        self.expression.eval(n).max(0) as usize
+    }
+
+    /// Returns up to `count` sample quantities that select the msgstr at `index`
+    pub fn examples(&self, index: usize, count: usize) -> Vec<i64> {
+        (0..1000)
+            .filter(|n| self.index(*n) == index)
+            .take(count)
+            .collect()
+    }
+}
+
+/// The `Plural-Forms` header value gettext uses for a language code like `pl` or `pt_BR`
+pub fn header_for_language(code: &str) -> &'static str {
+    let base = code.split(['_', '-']).next().unwrap_or(code);
+    match base {
+        "ja" | "ko" | "zh" | "th" | "vi" | "id" | "ms" | "lo" | "my" | "km" | "bo" => {
+            "nplurals=1; plural=0;"
+        }
+        "fr" | "pt" | "hy" | "ak" | "am" | "ln" | "mg" | "oc" | "ti" | "tl" | "tr" | "uz"
+        | "wa" => "nplurals=2; plural=(n > 1);",
+        "ru" | "uk" | "be" | "sr" | "hr" | "bs" => {
+            "nplurals=3; plural=(n%10==1 && n%100!=11 ? 0 : n%10>=2 && n%10<=4 && (n%100<10 || n%100>=20) ? 1 : 2);"
+        }
+        "pl" => {
+            "nplurals=3; plural=(n==1 ? 0 : n%10>=2 && n%10<=4 && (n%100<10 || n%100>=20) ? 1 : 2);"
+        }
+        "cs" | "sk" => "nplurals=3; plural=(n==1) ? 0 : (n>=2 && n<=4) ? 1 : 2;",
+        "lt" => {
+            "nplurals=3; plural=(n%10==1 && n%100!=11 ? 0 : n%10>=2 && (n%100<10 || n%100>=20) ? 1 : 2);"
+        }
+        "lv" => "nplurals=3; plural=(n%10==1 && n%100!=11 ? 0 : n != 0 ? 1 : 2);",
+        "ro" => "nplurals=3; plural=(n==1 ? 0 : (n==0 || (n%100 > 0 && n%100 < 20)) ? 1 : 2);",
+        "sl" => "nplurals=4; plural=(n%100==1 ? 0 : n%100==2 ? 1 : n%100==3 || n%100==4 ? 2 : 3);",
+        "ga" => "nplurals=5; plural=n==1 ? 0 : n==2 ? 1 : (n>2 && n<7) ? 2 :(n>6 && n<11) ? 3 : 4;",
+        "cy" => "nplurals=4; plural=(n==1) ? 0 : (n==2) ? 1 : (n != 8 && n != 11) ? 2 : 3;",
+        "ar" => {
+            "nplurals=6; plural=(n==0 ? 0 : n==1 ? 1 : n==2 ? 2 : n%100>=3 && n%100<=10 ? 3 : n%100>=11 ? 4 : 5);"
+        }
+        _ => "nplurals=2; plural=(n != 1);",
+    }
+}
+
+/// The CLDR plural categories (`one`, `few`, `other`...) of a language, in the order of the
+/// msgstr forms of `header_for_language`, for formats like i18next JSON that name their forms.
+/// Categories past the `nplurals` of the language share its last form.
+pub fn categories_for_language(code: &str) -> &'static [&'static str] {
+    let base = code.split(['_', '-']).next().unwrap_or(code);
+    match PluralForms::for_language(base).nplurals {
+        1 => &["other"],
+        _ => match base {
+            "ru" | "uk" | "be" | "sr" | "hr" | "bs" | "pl" => &["one", "few", "many", "other"],
+            "cs" | "sk" | "lt" | "ro" => &["one", "few", "other"],
+            "lv" => &["one", "other", "zero"],
+            "sl" => &["one", "two", "few", "other"],
+            "ga" => &["one", "two", "few", "many", "other"],
+            "cy" => &["one", "two", "other", "many"],
+            "ar" => &["zero", "one", "two", "few", "many", "other"],
+            _ => &["one", "other"],
+        },
+    }
+}
+
+/// A C-like plural expression over the quantity `n`
+enum Expr {
+    N,
+    Number(i64),
+    Not(Box<Expr>),
+    Binary(Box<Expr>, BinaryOp, Box<Expr>),
+    Ternary(Box<Expr>, Box<Expr>, Box<Expr>),
+}
+
+#[derive(Clone, Copy)]
+enum BinaryOp {
+    Or,
+    And,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+}
+
+impl Expr {
+    fn eval(&self, n: i64) -> i64 {
+        match self {
+            Expr::N => n,
+            Expr::Number(value) => *value,
+            Expr::Not(inner) => (inner.eval(n) == 0) as i64,
+            Expr::Ternary(condition, then, otherwise) => {
+                if condition.eval(n) != 0 {
+                    then.eval(n)
+                } else {
+                    otherwise.eval(n)
+                }
+            }
+            Expr::Binary(left, op, right) => {
+                let l = left.eval(n);
+                let r = right.eval(n);
+                match op {
+                    BinaryOp::Or => (l != 0 || r != 0) as i64,
+                    BinaryOp::And => (l != 0 && r != 0) as i64,
+                    BinaryOp::Eq => (l == r) as i64,
+                    BinaryOp::Ne => (l != r) as i64,
+                    BinaryOp::Lt => (l < r) as i64,
+                    BinaryOp::Le => (l <= r) as i64,
+                    BinaryOp::Gt => (l > r) as i64,
+                    BinaryOp::Ge => (l >= r) as i64,
+                    BinaryOp::Add => l.wrapping_add(r),
+                    BinaryOp::Sub => l.wrapping_sub(r),
+                    BinaryOp::Mul => l.wrapping_mul(r),
+                    BinaryOp::Div => l.checked_div(r).unwrap_or(0),
+                    BinaryOp::Rem => l.checked_rem(r).unwrap_or(0),
+                }
+            }
+        }
+    }
+}
+
+/// How deep parentheses, `!` and `?:` may nest, so that a hostile header can't overflow the
+/// stack of the parser or of `Expr::eval`; real plural rules nest a handful of levels
+const MAX_DEPTH: usize = 64;
+
+/// How many tokens an expression may have, which bounds the depth of operator chains like
+/// `n+n+n...` too; the longest built-in rule has about 60
+const MAX_TOKENS: usize = 1024;
+
+/// Recursive-descent parser following C operator precedence
+struct ExprParser {
+    tokens: Vec<String>,
+    pos: usize,
+    /// The nesting of the expression being parsed
+    depth: usize,
+}
+
+impl ExprParser {
+    fn new(source: &str) -> Result<Self> {
+        let mut tokens = Vec::new();
+        let chars: Vec<char> = source.chars().collect();
+        let mut i = 0;
+
+        while i < chars.len() {
+            let c = chars[i];
+            if c.is_whitespace() {
+                i += 1;
+            } else if c.is_ascii_digit() {
+                let start = i;
+                while i < chars.len() && chars[i].is_ascii_digit() {
+                    i += 1;
+                }
+                tokens.push(chars[start..i].iter().collect());
+            } else {
+                let pair: String = chars[i..(i + 2).min(chars.len())].iter().collect();
+                if ["||", "&&", "==", "!=", "<=", ">="].contains(&pair.as_str()) {
+                    tokens.push(pair);
+                    i += 2;
+                } else if "n()?:!<>+-*/%".contains(c) {
+                    tokens.push(c.to_string());
+                    i += 1;
+                } else {
+                    bail!("Unexpected character `{c}` in plural expression: {source}");
+                }
+            }
+        }
+
+        if tokens.len() > MAX_TOKENS {
+            bail!("Plural expression longer than {MAX_TOKENS} tokens: {source}");
+        }
+
+        Ok(Self {
+            tokens,
+            pos: 0,
+            depth: 0,
+        })
+    }
+
+    fn parse(mut self) -> Result<Expr> {
+        let expr = self.ternary()?;
+        if let Some(token) = self.peek() {
+            bail!("Unexpected `{token}` in plural expression");
+        }
+        Ok(expr)
+    }
+
+    fn peek(&self) -> Option<&str> {
+        self.tokens.get(self.pos).map(String::as_str)
+    }
+
+    fn eat(&mut self, token: &str) -> bool {
+        if self.peek() == Some(token) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Parses a nested expression with `parse`, failing past `MAX_DEPTH`
+    fn nested(&mut self, parse: fn(&mut Self) -> Result<Expr>) -> Result<Expr> {
+        if self.depth == MAX_DEPTH {
+            bail!("Plural expression nested more than {MAX_DEPTH} levels deep");
+        }
+        self.depth += 1;
+        let expr = parse(self);
+        self.depth -= 1;
+        expr
+    }
+
+    fn ternary(&mut self) -> Result<Expr> {
+        let condition = self.binary(0)?;
+        if !self.eat("?") {
+            return Ok(condition);
+        }
+
+        let then = self.nested(Self::ternary)?;
+        if !self.eat(":") {
+            bail!("Expected `:` in plural expression");
+        }
+        let otherwise = self.nested(Self::ternary)?;
+
+        Ok(Expr::Ternary(
+            Box::new(condition),
+            Box::new(then),
+            Box::new(otherwise),
+        ))
+    }
+
+    /// Parses binary operators whose precedence level is at least `level`
+    fn binary(&mut self, level: usize) -> Result<Expr> {
+        const LEVELS: &[&[(&str, BinaryOp)]] = &[
+            &[("||", BinaryOp::Or)],
+            &[("&&", BinaryOp::And)],
+            &[("==", BinaryOp::Eq), ("!=", BinaryOp::Ne)],
+            &[
+                ("<", BinaryOp::Lt),
+                ("<=", BinaryOp::Le),
+                (">", BinaryOp::Gt),
+                (">=", BinaryOp::Ge),
+            ],
+            &[("+", BinaryOp::Add), ("-", BinaryOp::Sub)],
+            &[
+                ("*", BinaryOp::Mul),
+                ("/", BinaryOp::Div),
+                ("%", BinaryOp::Rem),
+            ],
+        ];
+
+        if level == LEVELS.len() {
+            return self.unary();
+        }
+
+        let mut left = self.binary(level + 1)?;
+        'outer: loop {
+            for (token, op) in LEVELS[level] {
+                if self.eat(token) {
+                    let right = self.binary(level + 1)?;
+                    left = Expr::Binary(Box::new(left), *op, Box::new(right));
+                    continue 'outer;
+                }
+            }
+            return Ok(left);
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expr> {
+        if self.eat("!") {
+            return Ok(Expr::Not(Box::new(self.nested(Self::unary)?)));
+        }
+
+        if self.eat("(") {
+            let inner = self.nested(Self::ternary)?;
+            if !self.eat(")") {
+                bail!("Expected `)` in plural expression");
+            }
+            return Ok(inner);
+        }
+
+        if self.eat("n") {
+            return Ok(Expr::N);
+        }
+
+        match self.peek().map(|token| token.parse::<i64>()) {
+            Some(Ok(value)) => {
+                self.pos += 1;
+                Ok(Expr::Number(value))
+            }
+            _ => bail!("Unexpected end of plural expression"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn indexes(forms: &PluralForms, quantities: &[i64]) -> Vec<usize> {
+        quantities.iter().map(|n| forms.index(*n)).collect()
+    }
+
+    #[test]
+    fn evaluates_the_plural_forms_of_languages() {
+        let english = PluralForms::for_language("en_US");
+        assert_eq!(english.nplurals, 2);
+        assert_eq!(indexes(&english, &[0, 1, 2, 11]), [1, 0, 1, 1]);
+
+        let french = PluralForms::for_language("fr");
+        assert_eq!(indexes(&french, &[0, 1, 2]), [0, 0, 1]);
+
+        let polish = PluralForms::for_language("pl");
+        assert_eq!(
+            indexes(&polish, &[1, 2, 4, 5, 12, 22, 25, 112]),
+            [0, 1, 1, 2, 2, 1, 2, 2]
+        );
+
+        let russian = PluralForms::for_language("ru");
+        assert_eq!(
+            indexes(&russian, &[1, 21, 11, 3, 13, 5]),
+            [0, 0, 2, 1, 2, 2]
+        );
+
+        let arabic = PluralForms::for_language("ar");
+        assert_eq!(arabic.nplurals, 6);
+        assert_eq!(
+            indexes(&arabic, &[0, 1, 2, 3, 10, 11, 99, 100, 102]),
+            [0, 1, 2, 3, 3, 4, 4, 5, 5]
+        );
+        assert_eq!(arabic.examples(3, 2), [3, 4]);
+    }
+
+    #[test]
+    fn parses_headers_without_parentheses_or_spaces() {
+        let forms = PluralForms::parse("nplurals=3;plural=n==1?0:n==2?1:2;").unwrap();
+        assert_eq!(indexes(&forms, &[1, 2, 3]), [0, 1, 2]);
+
+        let czech = PluralForms::for_language("cs");
+        assert_eq!(indexes(&czech, &[1, 3, 5]), [0, 1, 2]);
+    }
+
+    #[test]
+    fn rejects_invalid_headers() {
+        assert!(PluralForms::parse("plural=(n != 1);").is_err());
+        assert!(PluralForms::parse("nplurals=2; plural=(n != 1;").is_err());
+        assert!(PluralForms::parse("nplurals=2; plural=n ? 1;").is_err());
+        assert!(PluralForms::parse("nplurals=2; plural=n $ 1;").is_err());
+        assert!(PluralForms::parse("nplurals=0; plural=0;").is_err());
+        assert!(PluralForms::parse("nplurals=4000000000; plural=n;").is_err());
+        assert!(PluralForms::parse("nplurals=16; plural=n%16;").is_ok());
+    }
+
+    #[test]
+    fn survives_hostile_expressions() {
+        let deep = format!(
+            "nplurals=2; plural={}n{};",
+            "(".repeat(100_000),
+            ")".repeat(100_000)
+        );
+        assert!(PluralForms::parse(&deep).is_err());
+        let negations = format!("nplurals=2; plural={}n;", "!".repeat(100_000));
+        assert!(PluralForms::parse(&negations).is_err());
+        let chain = format!("nplurals=2; plural=n{};", "+n".repeat(100_000));
+        assert!(PluralForms::parse(&chain).is_err());
+
+        let nested = format!("nplurals=2; plural={}n{};", "(".repeat(60), ")".repeat(60));
+        assert!(PluralForms::parse(&nested).is_ok());
+
+        let overflow =
+            PluralForms::parse("nplurals=2; plural=n*n*n + 9223372036854775807 - n/0 + n%0;")
+                .unwrap();
+        overflow.index(i64::MAX);
+        overflow.index(i64::MIN);
+    }
+}
@@ -0,0 +1,1154 @@
+use anyhow::{Result, bail};
+use encoding_rs::{Encoding, UTF_8};
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+
+use crate::plural::MAX_PLURAL_FORMS;
+use crate::{atomic, attribution, lock, translator};
+
+/// A single entry of a .po catalog: comments, msgctxt, msgid, msgid_plural and msgstr(s)
+#[derive(Clone)]
+pub struct PoEntry {
+    pub comments: Vec<String>,
+    pub msgctxt: Option<String>,
+    pub msgid: String,
+    pub msgid_plural: Option<String>,
+    pub msgstr: Vec<String>,
+    /// 1-based line number of the msgid
+    pub line: usize,
+    source_lines: Vec<String>,
+    msgstr_lines: Vec<String>,
+    dirty: bool,
+}
+
+impl PoEntry {
+    fn new(comments: Vec<String>) -> Self {
+        Self {
+            comments,
+            msgctxt: None,
+            msgid: String::new(),
+            msgid_plural: None,
+            msgstr: Vec::new(),
+            line: 0,
+            source_lines: Vec::new(),
+            msgstr_lines: Vec::new(),
+            dirty: false,
+        }
+    }
+
+    /// An entry built in memory, with `#.` comments for the `notes`, to translate the strings
+    /// of other file formats with a `PoCatalog`, or to write the entries of a .pot template
+    pub fn from_strings(
+        msgctxt: Option<String>,
+        msgid: String,
+        msgid_plural: Option<String>,
+        msgstr: Vec<String>,
+        notes: &[String],
+    ) -> Self {
+        let comments = notes.iter().map(|note| format!("#. {}", note)).collect();
+        let mut entry = Self::new(comments);
+        entry.msgctxt = msgctxt;
+        entry.msgid = msgid;
+        entry.msgid_plural = msgid_plural;
+        entry.msgstr = match msgstr.is_empty() {
+            true => vec![String::new()],
+            false => msgstr,
+        };
+        if let Some(msgctxt) = &entry.msgctxt {
+            entry
+                .source_lines
+                .extend(format_po_string("msgctxt", msgctxt));
+        }
+        entry
+            .source_lines
+            .extend(format_po_string("msgid", &entry.msgid));
+        if let Some(plural) = &entry.msgid_plural {
+            entry
+                .source_lines
+                .extend(format_po_string("msgid_plural", plural));
+        }
+        entry.dirty = true;
+        entry
+    }
+
+    /// Returns true for the metadata entry (`msgid ""` without context)
+    pub fn is_header(&self) -> bool {
+        self.msgid.is_empty() && self.msgctxt.is_none()
+    }
+
+    pub fn is_plural(&self) -> bool {
+        self.msgid_plural.is_some()
+    }
+
+    /// Replaces the msgstr at `index` (0 for singular entries), growing the list if needed.
+    /// Panics past `MAX_PLURAL_FORMS`, which neither parsed catalogs nor plural rules reach.
+    pub fn set_msgstr(&mut self, index: usize, value: String) {
+        assert!(
+            index < MAX_PLURAL_FORMS,
+            "msgstr[{index}] is past the plural forms a catalog may have"
+        );
+        if self.msgstr.len() <= index {
+            self.msgstr.resize(index + 1, String::new());
+        }
+
+        self.msgstr[index] = value;
+        self.dirty = true;
+    }
+
+    /// Clears every msgstr, keeping `forms` empty `msgstr[n]` for plural entries
+    pub fn reset_msgstr(&mut self, forms: usize) {
+        let forms = if self.is_plural() { forms.max(1) } else { 1 };
+        self.msgstr = vec![String::new(); forms];
+        self.dirty = true;
+    }
+
+    /// Returns the flags of `#,` comment lines (e.g. `fuzzy`, `elixir-format`)
+    pub fn flags(&self) -> Vec<String> {
+        self.comments
+            .iter()
+            .filter_map(|line| line.trim().strip_prefix("#,"))
+            .flat_map(|flags| flags.split(','))
+            .map(|flag| flag.trim().to_string())
+            .filter(|flag| !flag.is_empty())
+            .collect()
+    }
+
+    /// Returns the text of `#.` extracted comments, the notes developers leave for translators
+    pub fn extracted_comments(&self) -> Vec<String> {
+        self.comments
+            .iter()
+            .filter_map(|line| line.trim().strip_prefix("#."))
+            .map(|comment| comment.trim().to_string())
+            .filter(|comment| !comment.is_empty())
+            .collect()
+    }
+
+    /// Returns the source locations of `#:` reference comments (e.g. `src/main.rs:42`)
+    pub fn references(&self) -> Vec<String> {
+        self.comments
+            .iter()
+            .filter_map(|line| line.trim().strip_prefix("#:"))
+            .flat_map(|references| references.split_whitespace())
+            .map(|reference| reference.to_string())
+            .collect()
+    }
+
+    pub fn has_flag(&self, flag: &str) -> bool {
+        self.flags().iter().any(|f| f == flag)
+    }
+
+    pub fn add_flag(&mut self, flag: &str) {
+        if self.has_flag(flag) {
+            return;
+        }
+
+        let mut flags = self.flags();
+        flags.push(flag.to_string());
+        self.set_flags(&flags);
+    }
+
+    pub fn remove_flag(&mut self, flag: &str) {
+        if !self.has_flag(flag) {
+            return;
+        }
+
+        let flags: Vec<String> = self.flags().into_iter().filter(|f| f != flag).collect();
+        self.set_flags(&flags);
+    }
+
+    /// Rewrites the flags into a single `#,` line, placed before any `#|` previous-msgid comments
+    fn set_flags(&mut self, flags: &[String]) {
+        let position = self
+            .comments
+            .iter()
+            .position(|line| line.trim().starts_with("#,") || line.trim().starts_with("#|"))
+            .unwrap_or(self.comments.len());
+
+        self.comments.retain(|line| !line.trim().starts_with("#,"));
+
+        if !flags.is_empty() {
+            self.comments
+                .insert(position, format!("#, {}", flags.join(", ")));
+        }
+    }
+
+    fn write(&self, out: &mut Vec<String>) {
+        out.extend(self.comments.iter().cloned());
+        out.extend(self.source_lines.iter().cloned());
+
+        if !self.dirty {
+            out.extend(self.msgstr_lines.iter().cloned());
+            return;
+        }
+
+        for (index, msgstr) in self.msgstr.iter().enumerate() {
+            let keyword = if self.is_plural() {
+                format!("msgstr[{}]", index)
+            } else {
+                "msgstr".to_string()
+            };
+            out.extend(format_po_string(&keyword, msgstr));
+        }
+    }
+}
+
+/// What [`PoCatalog::merge`] did to the entries of a catalog
+#[derive(Clone, Copy, Default)]
+pub struct MergeStats {
+    /// Entries still in the template, with their translation
+    pub kept: usize,
+    /// Entries given the translation of a similar old msgid, marked fuzzy
+    pub fuzzy: usize,
+    /// New entries, without a translation
+    pub added: usize,
+    /// Translations no longer in the template, made obsolete
+    pub obsolete: usize,
+}
+
+/// Old msgids at least this similar to a new one give it their translation, marked fuzzy
+const FUZZY_SIMILARITY: f64 = 0.6;
+
+fn is_translated(entry: &PoEntry) -> bool {
+    entry.msgstr.iter().any(|msgstr| !msgstr.is_empty())
+}
+
+/// `#` comments, written by translators, which the template doesn't have
+fn is_translator_comment(line: &str) -> bool {
+    line == "#" || line.starts_with("# ")
+}
+
+/// The entry of the template `source` with the translation, translator comments and flags
+/// of `old`, marked fuzzy with the previous msgid in `#|` comments when it is a guess
+fn merged(source: &PoEntry, old: &PoEntry, fuzzy: bool, nplurals: usize) -> PoEntry {
+    let mut comments: Vec<String> = old
+        .comments
+        .iter()
+        .filter(|line| is_translator_comment(line))
+        .cloned()
+        .collect();
+    if !fuzzy {
+        comments.extend(
+            old.comments
+                .iter()
+                .filter(|line| {
+                    line.strip_prefix("#.")
+                        .is_some_and(|comment| attribution::is_attribution(comment.trim()))
+                })
+                .cloned(),
+        );
+    }
+    comments.extend(
+        source
+            .comments
+            .iter()
+            .filter(|line| line.starts_with("#.") || line.starts_with("#:"))
+            .cloned(),
+    );
+
+    let mut entry = PoEntry::new(comments);
+    entry.msgctxt = source.msgctxt.clone();
+    entry.msgid = source.msgid.clone();
+    entry.msgid_plural = source.msgid_plural.clone();
+    entry.source_lines = source.source_lines.clone();
+    let mut fuzzy = fuzzy;
+    if entry.is_plural() == old.is_plural() {
+        entry.msgstr = old.msgstr.clone();
+        entry.msgstr_lines = old.msgstr_lines.clone();
+        entry.dirty = old.dirty;
+    } else {
+        let forms = if entry.is_plural() {
+            nplurals.max(1)
+        } else {
+            1
+        };
+        let msgstr = old.msgstr.first().cloned().unwrap_or_default();
+        entry.msgstr = vec![msgstr; forms];
+        entry.dirty = true;
+        fuzzy = true;
+    }
+
+    let mut flags = source.flags();
+    for flag in old.flags() {
+        // The format flags are the template's, and a guess isn't reviewed
+        let dropped =
+            flag.ends_with("-format") || flag == "fuzzy" || (fuzzy && flag == lock::LOCK_FLAG);
+        if !dropped && !flags.contains(&flag) {
+            flags.push(flag);
+        }
+    }
+    flags.retain(|flag| flag != "fuzzy");
+    if fuzzy || old.has_flag("fuzzy") {
+        flags.insert(0, "fuzzy".to_string());
+    }
+    entry.set_flags(&flags);
+
+    if !fuzzy && entry.has_flag("fuzzy") {
+        entry.comments.extend(
+            old.comments
+                .iter()
+                .filter(|line| line.starts_with("#|"))
+                .cloned(),
+        );
+    } else if fuzzy && old.msgid != source.msgid {
+        if let Some(msgctxt) = &old.msgctxt {
+            entry.comments.extend(previous("msgctxt", msgctxt));
+        }
+        entry.comments.extend(previous("msgid", &old.msgid));
+        if let Some(plural) = &old.msgid_plural {
+            entry.comments.extend(previous("msgid_plural", plural));
+        }
+    }
+    entry
+}
+
+/// `#| msgid "..."` lines, with the msgid an entry was translated from
+fn previous(keyword: &str, value: &str) -> Vec<String> {
+    format_po_string(keyword, value)
+        .into_iter()
+        .map(|line| format!("#| {}", line))
+        .collect()
+}
+
+/// A new entry of the template, without a translation
+fn untranslated(source: &PoEntry, nplurals: usize) -> PoEntry {
+    let mut entry = source.clone();
+    entry.reset_msgstr(nplurals);
+    entry.remove_flag("fuzzy");
+    entry
+}
+
+/// The translated old entry whose msgid is the most similar to that of `source`, if enough
+fn most_similar<'a>(source: &PoEntry, old: &[&'a PoEntry]) -> Option<&'a PoEntry> {
+    let length = source.msgid.chars().count();
+    old.iter()
+        .filter(|entry| entry.msgctxt == source.msgctxt && is_translated(entry))
+        .filter_map(|entry| {
+            let other = entry.msgid.chars().count();
+            let longest = length.max(other).max(1) as f64;
+            // The distance is at least the difference in length
+            if (length.min(other) as f64) / longest < FUZZY_SIMILARITY {
+                return None;
+            }
+            let distance = translator::edit_distance(&source.msgid, &entry.msgid) as f64;
+            let similarity = 1.0 - distance / longest;
+            (similarity >= FUZZY_SIMILARITY).then_some((similarity, *entry))
+        })
+        .max_by(|(a, _), (b, _)| a.total_cmp(b))
+        .map(|(_, entry)| entry)
+}
+
+/// An entry made obsolete: its lines in `#~` comments, after its translator comments and flags
+fn obsolete(entry: &PoEntry) -> PoItem {
+    let mut lines: Vec<String> = entry
+        .comments
+        .iter()
+        .filter(|line| is_translator_comment(line) || line.starts_with("#,"))
+        .cloned()
+        .collect();
+    let mut body = Vec::new();
+    entry.write(&mut body);
+    lines.extend(
+        body.into_iter()
+            .skip(entry.comments.len())
+            .map(|line| format!("#~ {}", line)),
+    );
+    PoItem::Obsolete {
+        lines,
+        entry: Some(entry.clone()),
+    }
+}
+
+#[derive(Clone)]
+enum PoItem {
+    Entry(PoEntry),
+    Line(String),
+    /// A `#~` block, written back verbatim, with the entry it holds if it could be parsed
+    Obsolete {
+        lines: Vec<String>,
+        entry: Option<PoEntry>,
+    },
+}
+
+/// The field that continuation lines (`"..."`) are appended to
+#[derive(Clone, Copy)]
+enum Field {
+    Msgctxt,
+    Msgid,
+    MsgidPlural,
+    Msgstr(usize),
+}
+
+/// A parsed .po file that can be written back preserving the lines it did not change
+pub struct PoCatalog {
+    items: Vec<PoItem>,
+    /// Charset declared by the `Content-Type` header, used when reading and writing the file
+    encoding: &'static Encoding,
+    /// `\r\n` for catalogs written on Windows, `\n` otherwise
+    line_ending: &'static str,
+    trailing_newline: bool,
+}
+
+impl PoCatalog {
+    pub fn parse(content: &str) -> Result<Self> {
+        let mut parser = Parser::default();
+
+        for (number, line) in content.lines().enumerate() {
+            parser.line = number + 1;
+            parser.feed(line)?;
+        }
+
+        let mut catalog = parser.finish();
+        if content.contains("\r\n") {
+            catalog.line_ending = "\r\n";
+        }
+        catalog.trailing_newline = content.ends_with('\n');
+        Ok(catalog)
+    }
+
+    /// An in-memory catalog of `entries`, without a header
+    pub fn from_entries(entries: Vec<PoEntry>) -> Self {
+        Self {
+            items: entries.into_iter().map(PoItem::Entry).collect(),
+            encoding: UTF_8,
+            line_ending: "\n",
+            trailing_newline: true,
+        }
+    }
+
+    /// Reads a .po file, decoding it with the charset of its `Content-Type` header
+    pub fn read(path: &Path) -> Result<Self> {
+        Self::from_bytes(&fs::read(path)?, path)
+    }
+
+    /// Parses the content of a .po file, like an older version of the one at `path`
+    pub fn from_bytes(bytes: &[u8], path: &Path) -> Result<Self> {
+        let encoding = detect_encoding(bytes);
+        let (content, _, had_errors) = encoding.decode(bytes);
+
+        if had_errors {
+            bail!("{}: invalid {} content", path.display(), encoding.name());
+        }
+
+        let mut catalog =
+            Self::parse(&content).map_err(|e| anyhow::anyhow!("{}: {}", path.display(), e))?;
+        catalog.encoding = encoding;
+        Ok(catalog)
+    }
+
+    /// Writes the catalog to `path`, encoded with the charset it was read with
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let content = self.to_content();
+        let (bytes, _, had_errors) = self.encoding.encode(&content);
+
+        if had_errors {
+            bail!(
+                "{}: translations cannot be represented in {}",
+                path.display(),
+                self.encoding.name()
+            );
+        }
+
+        atomic::write(path, bytes)
+    }
+
+    pub fn header(&self) -> Option<&PoEntry> {
+        self.items.iter().find_map(|item| match item {
+            PoItem::Entry(entry) if entry.is_header() => Some(entry),
+            _ => None,
+        })
+    }
+
+    /// Returns the value of a header field like `Plural-Forms`, if present
+    pub fn header_field(&self, name: &str) -> Option<String> {
+        let header = self.header()?;
+        let content = header.msgstr.first()?;
+
+        content.lines().find_map(|line| {
+            let (key, value) = line.split_once(':')?;
+            (key.trim().eq_ignore_ascii_case(name)).then(|| value.trim().to_string())
+        })
+    }
+
+    /// Sets (or adds) a header field, creating the header entry if the catalog has none
+    pub fn set_header_field(&mut self, name: &str, value: &str) {
+        let header = match self.header_index() {
+            Some(index) => index,
+            None => {
+                let mut header = PoEntry::new(Vec::new());
+                header.source_lines.push("msgid \"\"".to_string());
+                header.msgstr.push(String::new());
+                self.items.insert(0, PoItem::Entry(header));
+                self.items.insert(1, PoItem::Line(String::new()));
+                0
+            }
+        };
+
+        let PoItem::Entry(header) = &mut self.items[header] else {
+            unreachable!("header_index points to an entry");
+        };
+
+        let mut fields: Vec<String> = header
+            .msgstr
+            .first()
+            .map(|content| {
+                content
+                    .lines()
+                    .filter(|line| !line.is_empty())
+                    .map(str::to_string)
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let field = format!("{}: {}", name, value);
+        match fields.iter_mut().find(|line| {
+            line.split_once(':')
+                .is_some_and(|(key, _)| key.trim().eq_ignore_ascii_case(name))
+        }) {
+            Some(line) => *line = field,
+            None => fields.push(field),
+        }
+
+        let content: String = fields.iter().map(|line| format!("{}\n", line)).collect();
+        header.set_msgstr(0, content);
+    }
+
+    fn header_index(&self) -> Option<usize> {
+        self.items
+            .iter()
+            .position(|item| matches!(item, PoItem::Entry(entry) if entry.is_header()))
+    }
+
+    /// The active entries, without the obsolete `#~` ones
+    pub fn entries(&self) -> impl Iterator<Item = &PoEntry> {
+        self.items.iter().filter_map(|item| match item {
+            PoItem::Entry(entry) => Some(entry),
+            _ => None,
+        })
+    }
+
+    /// The obsolete `#~` entries, which are kept in the file but never translated
+    pub fn obsolete_entries(&self) -> impl Iterator<Item = &PoEntry> {
+        self.items.iter().filter_map(|item| match item {
+            PoItem::Obsolete { entry, .. } => entry.as_ref(),
+            _ => None,
+        })
+    }
+
+    /// Turns the obsolete entries whose msgid is back in `template` into active ones, with the
+    /// template's comments and their old translation marked fuzzy. Returns how many were revived.
+    pub fn revive_obsolete(&mut self, template: &PoCatalog) -> usize {
+        let key = |entry: &PoEntry| (entry.msgctxt.clone(), entry.msgid.clone());
+        let active: HashSet<_> = self.entries().map(key).collect();
+        let mut revived = 0;
+
+        for item in &mut self.items {
+            let PoItem::Obsolete {
+                entry: Some(entry), ..
+            } = item
+            else {
+                continue;
+            };
+            if active.contains(&key(entry)) {
+                continue;
+            }
+            let Some(source) = template.entries().find(|source| key(source) == key(entry)) else {
+                continue;
+            };
+
+            let msgstr = entry.msgstr.clone();
+            let mut entry = PoEntry::new(source.comments.clone());
+            entry.msgctxt = source.msgctxt.clone();
+            entry.msgid = source.msgid.clone();
+            entry.msgid_plural = source.msgid_plural.clone();
+            entry.source_lines = source.source_lines.clone();
+            entry.msgstr = msgstr;
+            if entry.msgstr.is_empty() {
+                entry.msgstr.push(String::new());
+            }
+            entry.dirty = true;
+            entry.add_flag("fuzzy");
+
+            *item = PoItem::Entry(entry);
+            revived += 1;
+        }
+
+        revived
+    }
+
+    /// The catalog updated to the msgids of `template`, the way GNU msgmerge does it: entries
+    /// still in the template keep their translation, those whose msgid changed take the
+    /// translation of the most similar old one, marked fuzzy, and the rest start empty, with
+    /// `nplurals` forms if plural. Old translations no longer in the template are kept as
+    /// obsolete `#~` entries. The comments and references are those of the template.
+    pub fn merge(&self, template: &PoCatalog, nplurals: usize) -> (PoCatalog, MergeStats) {
+        let key = |entry: &PoEntry| (entry.msgctxt.clone(), entry.msgid.clone());
+        let old: Vec<&PoEntry> = self.entries().filter(|e| !e.is_header()).collect();
+        let mut used = vec![false; old.len()];
+        let mut revived = HashSet::new();
+        let mut stats = MergeStats::default();
+
+        let mut items: Vec<PoItem> = match self.header_index() {
+            Some(index) => self.items[..=index].iter().map(PoItem::clone).collect(),
+            None => template
+                .header_index()
+                .map(|index| vec![template.items[index].clone()])
+                .unwrap_or_default(),
+        };
+
+        for source in template.entries().filter(|e| !e.is_header()) {
+            let exact = old
+                .iter()
+                .position(|entry| key(entry) == key(source))
+                .filter(|index| !used[*index]);
+            let entry = if let Some(index) = exact {
+                used[index] = true;
+                stats.kept += 1;
+                merged(source, old[index], false, nplurals)
+            } else if let Some(obsolete) = self
+                .obsolete_entries()
+                .find(|entry| key(entry) == key(source) && is_translated(entry))
+            {
+                revived.insert(key(obsolete));
+                stats.fuzzy += 1;
+                merged(source, obsolete, true, nplurals)
+            } else if let Some(similar) = most_similar(source, &old) {
+                stats.fuzzy += 1;
+                merged(source, similar, true, nplurals)
+            } else {
+                stats.added += 1;
+                untranslated(source, nplurals)
+            };
+            items.push(PoItem::Line(String::new()));
+            items.push(PoItem::Entry(entry));
+        }
+
+        for item in &self.items {
+            if let PoItem::Obsolete { entry, .. } = item
+                && entry
+                    .as_ref()
+                    .is_none_or(|entry| !revived.contains(&key(entry)))
+            {
+                items.push(PoItem::Line(String::new()));
+                items.push(item.clone());
+            }
+        }
+        for (entry, _) in old.iter().zip(&used).filter(|(_, used)| !**used) {
+            if is_translated(entry) {
+                items.push(PoItem::Line(String::new()));
+                items.push(obsolete(entry));
+                stats.obsolete += 1;
+            }
+        }
+
+        let mut catalog = PoCatalog {
+            items,
+            encoding: self.encoding,
+            line_ending: self.line_ending,
+            trailing_newline: true,
+        };
+        if let Some(date) = template.header_field("POT-Creation-Date")
+            && catalog
+                .header_field("POT-Creation-Date")
+                .is_some_and(|old| old != date)
+        {
+            catalog.set_header_field("POT-Creation-Date", &date);
+        }
+        (catalog, stats)
+    }
+
+    /// Adds `entry` at the end of the catalog, after a blank line
+    pub fn push(&mut self, entry: PoEntry) {
+        if !matches!(self.items.last(), None | Some(PoItem::Line(_))) {
+            self.items.push(PoItem::Line(String::new()));
+        }
+        self.items.push(PoItem::Entry(entry));
+    }
+
+    /// The charset the catalog is read and written with
+    pub fn encoding(&self) -> &'static Encoding {
+        self.encoding
+    }
+
+    pub fn entries_mut(&mut self) -> impl Iterator<Item = &mut PoEntry> {
+        self.items.iter_mut().filter_map(|item| match item {
+            PoItem::Entry(entry) => Some(entry),
+            _ => None,
+        })
+    }
+
+    /// Serializes the catalog with the line endings and final newline it was read with
+    pub fn to_content(&self) -> String {
+        let mut content = self.to_lines().join(self.line_ending);
+        if self.trailing_newline {
+            content.push_str(self.line_ending);
+        }
+        content
+    }
+
+    /// Serializes the catalog back into .po lines
+    pub fn to_lines(&self) -> Vec<String> {
+        let mut out = Vec::new();
+
+        for item in &self.items {
+            match item {
+                PoItem::Entry(entry) => entry.write(&mut out),
+                PoItem::Line(line) => out.push(line.clone()),
+                PoItem::Obsolete { lines, .. } => out.extend(lines.iter().cloned()),
+            }
+        }
+
+        out
+    }
+}
+
+#[derive(Default)]
+struct Parser {
+    items: Vec<PoItem>,
+    comments: Vec<String>,
+    /// Lines of the `#~` block being read
+    obsolete: Vec<String>,
+    current: Option<PoEntry>,
+    field: Option<Field>,
+    line: usize,
+}
+
+impl Parser {
+    fn feed(&mut self, line: &str) -> Result<()> {
+        let trimmed = line.trim();
+
+        // The comments right before an obsolete block (e.g. its flags) belong to it
+        if trimmed.starts_with("#~") {
+            self.finish_entry();
+            if self.obsolete.is_empty() {
+                self.obsolete.append(&mut self.comments);
+            }
+            self.obsolete.push(line.to_string());
+            return Ok(());
+        }
+        self.flush_obsolete();
+
+        if trimmed.is_empty() {
+            self.finish_entry();
+            self.flush_comments();
+            self.items.push(PoItem::Line(line.to_string()));
+        } else if trimmed.starts_with('#') {
+            self.finish_entry();
+            self.comments.push(line.to_string());
+        } else if trimmed.starts_with("msgctxt") {
+            self.finish_entry();
+            let entry = self.start_entry();
+            entry.msgctxt = Some(extract_po_string(trimmed)?);
+            entry.source_lines.push(line.to_string());
+            self.field = Some(Field::Msgctxt);
+        } else if trimmed.starts_with("msgid_plural") {
+            let entry = self.expect_entry(line)?;
+            entry.msgid_plural = Some(extract_po_string(trimmed)?);
+            entry.source_lines.push(line.to_string());
+            self.field = Some(Field::MsgidPlural);
+        } else if trimmed.starts_with("msgid") {
+            let continues_msgctxt = matches!(self.field, Some(Field::Msgctxt));
+            if !continues_msgctxt {
+                self.finish_entry();
+            }
+
+            let line_number = self.line;
+            let entry = match self.current {
+                Some(ref mut entry) => entry,
+                None => self.start_entry(),
+            };
+            entry.msgid = extract_po_string(trimmed)?;
+            entry.source_lines.push(line.to_string());
+            entry.line = line_number;
+            self.field = Some(Field::Msgid);
+        } else if trimmed.starts_with("msgstr") {
+            let index = parse_msgstr_index(trimmed)?;
+            let value = extract_po_string(trimmed)?;
+            let entry = self.expect_entry(line)?;
+
+            if entry.msgstr.len() <= index {
+                entry.msgstr.resize(index + 1, String::new());
+            }
+            entry.msgstr[index] = value;
+            entry.msgstr_lines.push(line.to_string());
+            self.field = Some(Field::Msgstr(index));
+        } else if trimmed.starts_with('"') {
+            let value = extract_po_string(trimmed)?;
+            let field = self.field;
+            let entry = self.expect_entry(line)?;
+
+            match field {
+                Some(Field::Msgctxt) => {
+                    entry
+                        .msgctxt
+                        .get_or_insert_with(String::new)
+                        .push_str(&value);
+                    entry.source_lines.push(line.to_string());
+                }
+                Some(Field::Msgid) => {
+                    entry.msgid.push_str(&value);
+                    entry.source_lines.push(line.to_string());
+                }
+                Some(Field::MsgidPlural) => {
+                    entry
+                        .msgid_plural
+                        .get_or_insert_with(String::new)
+                        .push_str(&value);
+                    entry.source_lines.push(line.to_string());
+                }
+                Some(Field::Msgstr(index)) => {
+                    entry.msgstr[index].push_str(&value);
+                    entry.msgstr_lines.push(line.to_string());
+                }
+                None => bail!("Malformed .po line: {line}"),
+            }
+        } else {
+            bail!("Malformed .po line: {line}");
+        }
+
+        Ok(())
+    }
+
+    fn start_entry(&mut self) -> &mut PoEntry {
+        let comments = std::mem::take(&mut self.comments);
+        self.current.insert(PoEntry::new(comments))
+    }
+
+    fn expect_entry(&mut self, line: &str) -> Result<&mut PoEntry> {
+        match self.current {
+            Some(ref mut entry) => Ok(entry),
+            None => bail!("Malformed .po line: {line}"),
+        }
+    }
+
+    fn finish_entry(&mut self) {
+        if let Some(entry) = self.current.take() {
+            self.items.push(PoItem::Entry(entry));
+        }
+        self.field = None;
+    }
+
+    /// Comments not followed by an entry (e.g. obsolete `#~` blocks) are kept as plain lines
+    fn flush_comments(&mut self) {
+        for comment in self.comments.drain(..) {
+            self.items.push(PoItem::Line(comment));
+        }
+    }
+
+    /// Ends the `#~` block being read, parsing the entry it holds without the `#~` markers
+    fn flush_obsolete(&mut self) {
+        if self.obsolete.is_empty() {
+            return;
+        }
+
+        let lines = std::mem::take(&mut self.obsolete);
+        let active = lines
+            .iter()
+            .map(|line| {
+                let line = line.trim_start();
+                match line.strip_prefix("#~") {
+                    // `#~|` previous-msgid lines become `#|` ones
+                    Some(rest) if rest.starts_with('|') => format!("#{}", rest),
+                    Some(rest) => rest.trim_start().to_string(),
+                    None => line.to_string(),
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        let entry = PoCatalog::parse(&active).ok().and_then(|catalog| {
+            catalog.items.into_iter().find_map(|item| match item {
+                PoItem::Entry(entry) => Some(entry),
+                _ => None,
+            })
+        });
+
+        self.items.push(PoItem::Obsolete { lines, entry });
+    }
+
+    fn finish(mut self) -> PoCatalog {
+        self.finish_entry();
+        self.flush_obsolete();
+        self.flush_comments();
+        PoCatalog {
+            items: self.items,
+            encoding: UTF_8,
+            line_ending: "\n",
+            trailing_newline: true,
+        }
+    }
+}
+
+/// Finds the `charset=` of the `Content-Type` header, defaulting to UTF-8 (also for the
+/// `CHARSET` placeholder of .pot templates)
+fn detect_encoding(bytes: &[u8]) -> &'static Encoding {
+    let head = String::from_utf8_lossy(&bytes[..bytes.len().min(4096)]);
+
+    head.find("charset=")
+        .map(|start| {
+            head[start + "charset=".len()..]
+                .chars()
+                .take_while(|c| c.is_ascii_alphanumeric() || *c == '-' || *c == '_')
+                .collect::<String>()
+        })
+        .and_then(|label| Encoding::for_label(label.as_bytes()))
+        .unwrap_or(UTF_8)
+}
+
+/// Parses the plural index of a `msgstr[n]` line, returning 0 for plain `msgstr`. Indexes
+/// past `MAX_PLURAL_FORMS` are rejected, so that a file can't make the entry allocate them.
+fn parse_msgstr_index(line: &str) -> Result<usize> {
+    let Some(rest) = line.strip_prefix("msgstr[") else {
+        return Ok(0);
+    };
+
+    rest.split(']')
+        .next()
+        .and_then(|index| index.trim().parse().ok())
+        .filter(|index| *index < MAX_PLURAL_FORMS)
+        .ok_or_else(|| anyhow::anyhow!("Malformed .po line: {line}"))
+}
+
+/// Extracts and unescapes the string from a .po line like msgid "text"
+fn extract_po_string(line: &str) -> Result<String> {
+    let malformed = || anyhow::anyhow!("Malformed .po line: {line}");
+
+    let quote_start = line.find('"').ok_or_else(malformed)?;
+    let mut chars = line[quote_start + 1..].chars();
+    let mut value = String::new();
+
+    loop {
+        match chars.next().ok_or_else(malformed)? {
+            '"' => break,
+            '\\' => push_escaped(&mut value, chars.next().ok_or_else(malformed)?),
+            c => value.push(c),
+        }
+    }
+
+    if !chars.as_str().trim().is_empty() {
+        bail!("Malformed .po line: {line}");
+    }
+
+    Ok(value)
+}
+
+/// Pushes the character written `\<escaped>`
+fn push_escaped(value: &mut String, escaped: char) {
+    match escaped {
+        'n' => value.push('\n'),
+        't' => value.push('\t'),
+        'r' => value.push('\r'),
+        'a' => value.push('\u{07}'),
+        'b' => value.push('\u{08}'),
+        'f' => value.push('\u{0c}'),
+        'v' => value.push('\u{0b}'),
+        '"' => value.push('"'),
+        '\\' => value.push('\\'),
+        // Unknown escapes are kept verbatim rather than silently dropped
+        other => {
+            value.push('\\');
+            value.push(other);
+        }
+    }
+}
+
+/// The text of a string literal of source code, with its C-like escapes (`\n`, `\"`)
+/// replaced. Quotes needn't be escaped, as in sigils and heredocs.
+pub fn unescape(literal: &str) -> String {
+    let mut value = String::with_capacity(literal.len());
+    let mut chars = literal.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some(escaped) => push_escaped(&mut value, escaped),
+                None => value.push('\\'),
+            },
+            c => value.push(c),
+        }
+    }
+    value
+}
+
+/// Escapes a value for use between the quotes of a .po string
+fn escape_po_string(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+
+    for c in value.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '"' => escaped.push_str("\\\""),
+            '\n' => escaped.push_str("\\n"),
+            '\t' => escaped.push_str("\\t"),
+            '\r' => escaped.push_str("\\r"),
+            '\u{07}' => escaped.push_str("\\a"),
+            '\u{08}' => escaped.push_str("\\b"),
+            '\u{0c}' => escaped.push_str("\\f"),
+            '\u{0b}' => escaped.push_str("\\v"),
+            c => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+/// Formats `keyword "value"`, splitting multi-line values into continuation lines like msgmerge does
+fn format_po_string(keyword: &str, value: &str) -> Vec<String> {
+    let newline = value.find('\n');
+    let is_multiline = matches!(newline, Some(pos) if pos + 1 < value.len());
+
+    if !is_multiline {
+        return vec![format!("{} \"{}\"", keyword, escape_po_string(value))];
+    }
+
+    let mut lines = vec![format!("{} \"\"", keyword)];
+    lines.extend(
+        value
+            .split_inclusive('\n')
+            .map(|chunk| format!("\"{}\"", escape_po_string(chunk))),
+    );
+
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CATALOG: &str = r#"# Spanish translations
+msgid ""
+msgstr ""
+"Language: es\n"
+"Content-Type: text/plain; charset=UTF-8\n"
+"Plural-Forms: nplurals=2; plural=(n != 1);\n"
+
+#: lib/app.ex:12
+#, elixir-format
+msgid ""
+"A long message\n"
+"on two lines"
+msgstr ""
+"Un mensaje largo\n"
+"en dos líneas"
+
+msgctxt "button"
+msgid "Say \"hi\"\tnow\\"
+msgstr "Di \"hola\"\tahora\\"
+
+msgid "One file"
+msgid_plural "%{count} files"
+msgstr[0] "Un archivo"
+msgstr[1] "%{count} archivos"
+
+#, fuzzy
+#~ msgid "Gone"
+#~ msgstr "Desaparecido"
+"#;
+
+    fn entry<'a>(catalog: &'a PoCatalog, msgid: &str) -> &'a PoEntry {
+        catalog
+            .entries()
+            .find(|entry| entry.msgid == msgid)
+            .unwrap_or_else(|| panic!("no entry for {:?}", msgid))
+    }
+
+    #[test]
+    fn writes_back_an_unchanged_catalog_as_it_was_read() {
+        let catalog = PoCatalog::parse(CATALOG).unwrap();
+        assert_eq!(catalog.to_content(), CATALOG);
+    }
+
+    #[test]
+    fn parses_multi_line_strings_and_escapes() {
+        let catalog = PoCatalog::parse(CATALOG).unwrap();
+
+        let long = entry(&catalog, "A long message\non two lines");
+        assert_eq!(long.msgstr, ["Un mensaje largo\nen dos líneas"]);
+        assert_eq!(long.line, 10);
+        assert!(long.has_flag("elixir-format"));
+
+        let quoted = entry(&catalog, "Say \"hi\"\tnow\\");
+        assert_eq!(quoted.msgctxt.as_deref(), Some("button"));
+        assert_eq!(quoted.msgstr, ["Di \"hola\"\tahora\\"]);
+
+        let plural = entry(&catalog, "One file");
+        assert_eq!(plural.msgid_plural.as_deref(), Some("%{count} files"));
+        assert_eq!(plural.msgstr, ["Un archivo", "%{count} archivos"]);
+    }
+
+    #[test]
+    fn round_trips_rewritten_strings() {
+        let mut catalog = PoCatalog::parse(CATALOG).unwrap();
+        let translations = [
+            "Línea uno\nLínea dos\n",
+            "Comillas \"dobles\", barra \\ y tab\t",
+            "Control \u{07}\u{08}\u{0c}\u{0b}\r fin",
+        ];
+        for (entry, translation) in catalog
+            .entries_mut()
+            .filter(|entry| !entry.is_header())
+            .zip(translations)
+        {
+            entry.set_msgstr(0, translation.to_string());
+        }
+
+        let content = catalog.to_content();
+        assert!(content.contains("msgstr \"\"\n\"Línea uno\\n\"\n\"Línea dos\\n\"\n"));
+        assert!(content.contains(r#"msgstr[0] "Control \a\b\f\v\r fin""#));
+
+        let reread = PoCatalog::parse(&content).unwrap();
+        let msgstrs: Vec<&str> = reread
+            .entries()
+            .filter(|entry| !entry.is_header())
+            .map(|entry| entry.msgstr[0].as_str())
+            .collect();
+        assert_eq!(msgstrs, translations);
+        assert_eq!(reread.to_content(), content);
+    }
+
+    #[test]
+    fn keeps_obsolete_entries_out_of_the_active_ones() {
+        let catalog = PoCatalog::parse(CATALOG).unwrap();
+
+        assert!(catalog.entries().all(|entry| entry.msgid != "Gone"));
+        let obsolete: Vec<&PoEntry> = catalog.obsolete_entries().collect();
+        assert_eq!(obsolete.len(), 1);
+        assert_eq!(obsolete[0].msgid, "Gone");
+        assert_eq!(obsolete[0].msgstr, ["Desaparecido"]);
+        assert!(
+            catalog
+                .to_content()
+                .ends_with("#, fuzzy\n#~ msgid \"Gone\"\n#~ msgstr \"Desaparecido\"\n")
+        );
+    }
+
+    #[test]
+    fn rejects_plural_indexes_past_the_limit() {
+        let huge = "msgid \"One\"\nmsgid_plural \"Many\"\nmsgstr[4000000000] \"\"\n";
+        let error = PoCatalog::parse(huge).err().unwrap();
+        assert!(error.to_string().contains("Malformed .po line"));
+        assert!(PoCatalog::parse(&huge.replace("4000000000", "16")).is_err());
+
+        let catalog = PoCatalog::parse(&huge.replace("4000000000", "15")).unwrap();
+        let entry = catalog.entries().next().unwrap();
+        assert_eq!(entry.msgstr.len(), 16);
+    }
+
+    #[test]
+    fn keeps_crlf_line_endings() {
+        let crlf = CATALOG.replace('\n', "\r\n");
+        let mut catalog = PoCatalog::parse(&crlf).unwrap();
+        assert_eq!(catalog.to_content(), crlf);
+        assert_eq!(
+            entry(&catalog, "A long message\non two lines").msgstr,
+            ["Un mensaje largo\nen dos líneas"]
+        );
+
+        catalog
+            .entries_mut()
+            .find(|entry| entry.msgid == "One file")
+            .unwrap()
+            .set_msgstr(0, "Un fichero".to_string());
+        let content = catalog.to_content();
+        assert!(content.contains("msgstr[0] \"Un fichero\"\r\n"));
+        assert!(!content.replace("\r\n", "").contains('\n'));
+    }
+}
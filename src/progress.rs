@@ -0,0 +1,236 @@
+//! The progress bar of `--progress`: the entries done out of those to translate in the
+//! current catalog and in the run, and the time left at the pace the requests have been
+//! coming back. It is drawn on stderr, when it is a terminal, in place of the line printed for
+//! every translated entry.
+
+use std::io::{IsTerminal, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{LazyLock, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use colored::*;
+
+/// Redraws closer together than this are skipped
+const REDRAW_INTERVAL: Duration = Duration::from_millis(100);
+const BAR_WIDTH: usize = 20;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static STATE: LazyLock<Mutex<State>> = LazyLock::new(|| Mutex::new(State::default()));
+
+#[derive(Default)]
+struct State {
+    /// The catalogs being translated, latest first
+    files: Vec<File>,
+    next_id: usize,
+    done: usize,
+    /// The entries to translate in the run: counted up front for .po folders, else added
+    /// as each file starts
+    total: usize,
+    counted: bool,
+    /// Entries that came back from a request, in the time since the first one was sent
+    requested: usize,
+    first_request: Option<Instant>,
+    drawn: Option<Instant>,
+    visible: bool,
+    /// The file each language last started, for catalogs translated without a path
+    started: Vec<(String, String)>,
+}
+
+struct File {
+    id: usize,
+    label: String,
+    done: usize,
+    total: usize,
+}
+
+/// Shows the progress bar instead of a line per translated entry
+pub fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+/// Returns true if `--progress` is set
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+fn state() -> MutexGuard<'static, State> {
+    STATE.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn is_drawn() -> bool {
+    is_enabled() && std::io::stderr().is_terminal()
+}
+
+/// Sets the number of entries the whole run has to translate, when known up front
+pub fn set_run_total(total: usize) {
+    let mut state = state();
+    state.total = total;
+    state.counted = true;
+}
+
+/// Notes the file `lang` is being translated in, like the XLIFF or JSON file of its units
+pub fn file_started(lang: &str, path: &str) {
+    let mut state = state();
+    state.started.retain(|(started, _)| started != lang);
+    state.started.push((lang.to_string(), path.to_string()));
+}
+
+/// Starts the bar of a catalog of `lang` with `total` entries to translate. Returns the id
+/// to advance it with.
+pub fn start(lang: &str, path: Option<&Path>, total: usize) -> usize {
+    let mut state = state();
+    let id = state.next_id;
+    state.next_id += 1;
+    if !state.counted {
+        state.total += total;
+    }
+
+    let started = state
+        .started
+        .iter()
+        .find(|(started, _)| started == lang)
+        .map(|(_, path)| Path::new(path));
+    let name = path
+        .or(started)
+        .and_then(Path::file_name)
+        .map(|name| format!(" {}", name.to_string_lossy()))
+        .unwrap_or_default();
+    state.files.insert(
+        0,
+        File {
+            id,
+            label: format!("{}{}", lang.to_uppercase(), name),
+            done: 0,
+            total,
+        },
+    );
+    draw(&mut state, false);
+    id
+}
+
+/// Marks that the requests of an entry are about to be sent, which starts the ETA's clock
+pub fn requests_started() {
+    state().first_request.get_or_insert_with(Instant::now);
+}
+
+/// Counts `count` more entries of the catalog `id` as done; `requested` if they came back from
+/// a request rather than from the cache
+pub fn advance(id: usize, count: usize, requested: bool) {
+    let mut state = state();
+    if let Some(position) = state.files.iter().position(|file| file.id == id) {
+        let mut file = state.files.remove(position);
+        file.done = (file.done + count).min(file.total);
+        state.files.insert(0, file);
+    }
+    state.done += count;
+    if requested {
+        state.requested += count;
+    }
+    draw(&mut state, false);
+}
+
+/// Ends the bar of the catalog `id`, counting the entries it didn't get to as done
+pub fn finish(id: usize) {
+    let mut state = state();
+    if let Some(position) = state.files.iter().position(|file| file.id == id) {
+        let file = state.files.remove(position);
+        state.done += file.total - file.done;
+    }
+    draw(&mut state, true);
+}
+
+/// Erases the bar, so that a log line can be printed in its place
+pub fn clear() {
+    if !is_drawn() {
+        return;
+    }
+    let mut state = state();
+    if state.visible {
+        eprint!("\r\x1b[2K");
+        state.visible = false;
+    }
+}
+
+/// Draws the bar again after a log line
+pub fn redraw() {
+    if is_drawn() {
+        draw(&mut state(), true);
+    }
+}
+
+fn draw(state: &mut State, now: bool) {
+    if !is_drawn() {
+        return;
+    }
+    if !now
+        && state
+            .drawn
+            .is_some_and(|drawn| drawn.elapsed() < REDRAW_INTERVAL)
+    {
+        return;
+    }
+    state.drawn = Some(Instant::now());
+
+    let Some(file) = state.files.first() else {
+        if state.visible {
+            eprint!("\r\x1b[2K");
+            state.visible = false;
+        }
+        return;
+    };
+
+    let filled = match file.total {
+        0 => BAR_WIDTH,
+        total => file.done * BAR_WIDTH / total,
+    };
+    let bar = format!(
+        "{}{}",
+        "█".repeat(filled).green(),
+        "░".repeat(BAR_WIDTH - filled).dimmed()
+    );
+    // Revived and re-checked entries can take the run past the count made up front
+    let done = state.done.min(state.total);
+    let percent = match state.total {
+        0 => 100,
+        total => done * 100 / total,
+    };
+
+    let mut stderr = std::io::stderr().lock();
+    let _ = write!(
+        stderr,
+        "\r\x1b[2K⏳ {} {} {}/{} │ run {}/{} ({}%) │ ETA {}",
+        file.label,
+        bar,
+        file.done,
+        file.total,
+        done,
+        state.total,
+        percent,
+        eta(state),
+    );
+    let _ = stderr.flush();
+    state.visible = true;
+}
+
+/// The time left at the pace entries have come back from requests so far
+fn eta(state: &State) -> String {
+    let remaining = state.total.saturating_sub(state.done);
+    let elapsed = state.first_request.map(|start| start.elapsed());
+    match elapsed {
+        _ if remaining == 0 => "0s".to_string(),
+        Some(elapsed) if state.requested > 0 => {
+            let seconds = elapsed.as_secs_f64() / state.requested as f64 * remaining as f64;
+            format_duration(seconds.round() as u64)
+        }
+        _ => "--".to_string(),
+    }
+}
+
+fn format_duration(seconds: u64) -> String {
+    match seconds {
+        0..60 => format!("{}s", seconds),
+        60..3600 => format!("{}m{:02}s", seconds / 60, seconds % 60),
+        _ => format!("{}h{:02}m", seconds / 3600, seconds % 3600 / 60),
+    }
+}
@@ -0,0 +1,298 @@
+use std::env;
+use std::sync::Arc;
+
+use anyhow::bail;
+use async_trait::async_trait;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use super::rate_limit::{RateLimiter, wait_for_slot};
+use super::stream::{STREAM_TIMEOUT, read_stream};
+use super::{
+    AiRequest, AiResponse, Pricing, ProviderConfig, RetryPolicy, Sampling, StreamPolicy,
+    TranslationProvider, handle_error_response, request_error, retry,
+};
+
+const DEFAULT_ANTHROPIC_MODEL: &str = "claude-3-5-haiku-latest";
+const ANTHROPIC_VERSION: &str = "2023-06-01";
+const MAX_TOKENS: u32 = 4096;
+
+#[derive(Serialize)]
+struct MessagesRequest<'a> {
+    model: &'a str,
+    max_tokens: u32,
+    system: &'a str,
+    messages: Vec<Message<'a>>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    stream: bool,
+    #[serde(flatten)]
+    sampling: Sampling,
+    /// A structured answer is the input of the one tool the model is made to call
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    tools: Vec<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tool_choice: Option<serde_json::Value>,
+}
+
+#[derive(Serialize)]
+struct Message<'a> {
+    role: &'a str,
+    content: &'a str,
+}
+
+#[derive(Deserialize)]
+struct MessagesResponse {
+    content: Vec<ContentBlock>,
+    usage: Option<MessagesUsage>,
+    /// `max_tokens` when the answer was cut at `MAX_TOKENS`
+    stop_reason: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct MessagesUsage {
+    input_tokens: u64,
+    output_tokens: u64,
+}
+
+#[derive(Deserialize)]
+struct ContentBlock {
+    #[serde(default)]
+    text: String,
+    /// The arguments of a tool call, which hold a structured answer
+    input: Option<serde_json::Value>,
+}
+
+/// A piece of a streamed content block: text, or the JSON of a tool call's arguments
+#[derive(Deserialize)]
+struct Delta {
+    #[serde(default)]
+    text: String,
+    #[serde(default)]
+    partial_json: String,
+    /// Sent by the `message_delta` event at the end of the message
+    stop_reason: Option<String>,
+}
+
+/// An event of a streamed message: its start and end carry the usage, and the deltas the text
+#[derive(Deserialize)]
+struct StreamEvent {
+    #[serde(rename = "type")]
+    kind: String,
+    message: Option<StreamedMessage>,
+    delta: Option<Delta>,
+    usage: Option<StreamUsage>,
+}
+
+#[derive(Deserialize)]
+struct StreamedMessage {
+    usage: Option<StreamUsage>,
+}
+
+#[derive(Deserialize)]
+struct StreamUsage {
+    input_tokens: Option<u64>,
+    output_tokens: Option<u64>,
+}
+
+pub struct Anthropic {
+    api_key: String,
+    model: String,
+    pricing: Option<Pricing>,
+    retry: RetryPolicy,
+    limiter: Option<Arc<RateLimiter>>,
+    stream: Option<StreamPolicy>,
+    sampling: Sampling,
+    client: Client,
+}
+
+impl Anthropic {
+    pub fn new(config: ProviderConfig, client: Client) -> anyhow::Result<Self> {
+        let api_key = match config.api_key {
+            Some(api_key) => api_key,
+            None => Self::get_api_key()?,
+        };
+
+        let model = config
+            .model
+            .unwrap_or_else(|| DEFAULT_ANTHROPIC_MODEL.into());
+
+        Ok(Self {
+            api_key,
+            pricing: config.pricing.or_else(|| model_pricing(&model)),
+            model,
+            retry: config.retry,
+            limiter: config.limiter,
+            stream: config.stream,
+            sampling: config.sampling,
+            client,
+        })
+    }
+
+    fn get_api_key() -> anyhow::Result<String> {
+        env::var("ANTHROPIC_API_KEY").map_err(|_| {
+            anyhow::anyhow!(
+                "ANTHROPIC_API_KEY must be set, or the key given with --api-key-file or --keychain"
+            )
+        })
+    }
+
+    fn extract_translation_result(&self, response: MessagesResponse) -> anyhow::Result<AiResponse> {
+        if response.stop_reason.as_deref() == Some("max_tokens") {
+            bail!(
+                "Anthropic cut the answer at {} output tokens; send fewer entries per request with --batch-size",
+                MAX_TOKENS
+            );
+        }
+
+        let text: String = response
+            .content
+            .iter()
+            .map(|block| match &block.input {
+                Some(input) => input.to_string(),
+                None => block.text.clone(),
+            })
+            .collect();
+        let result = AiResponse::new(text.trim().to_string());
+
+        Ok(match response.usage {
+            Some(usage) => result.with_usage(usage.input_tokens, usage.output_tokens),
+            None => result,
+        })
+    }
+}
+
+#[async_trait]
+impl TranslationProvider for Anthropic {
+    async fn send(&self, body: &AiRequest) -> anyhow::Result<AiResponse> {
+        let mut retries = 0;
+        let stream = self.stream.filter(|stream| stream.applies(body));
+
+        let request = MessagesRequest {
+            model: &self.model,
+            max_tokens: MAX_TOKENS,
+            system: &body.instructions,
+            messages: vec![Message {
+                role: "user",
+                content: &body.input,
+            }],
+            stream: stream.is_some(),
+            sampling: self.sampling,
+            tools: body
+                .format
+                .schema()
+                .map(|schema| {
+                    json!({
+                        "name": body.format.name(),
+                        "description": "Answers with the translation",
+                        "input_schema": schema,
+                    })
+                })
+                .into_iter()
+                .collect(),
+            tool_choice: body
+                .format
+                .schema()
+                .map(|_| json!({ "type": "tool", "name": body.format.name() })),
+        };
+
+        loop {
+            wait_for_slot(self.limiter.as_deref(), body).await;
+            let mut builder = self
+                .client
+                .post("https://api.anthropic.com/v1/messages")
+                .header("x-api-key", &self.api_key)
+                .header("anthropic-version", ANTHROPIC_VERSION)
+                .json(&request);
+            if stream.is_some() {
+                builder = builder.timeout(STREAM_TIMEOUT);
+            }
+            let response = builder.send().await;
+
+            let response = match response {
+                Ok(response) => response,
+                Err(err) => {
+                    retry(self.retry, &mut retries, &request_error(&err)).await?;
+                    continue;
+                }
+            };
+
+            match (response.status(), stream) {
+                (reqwest::StatusCode::OK, Some(stream)) => {
+                    let mut usage = MessagesUsage {
+                        input_tokens: 0,
+                        output_tokens: 0,
+                    };
+                    let mut stop_reason = None;
+                    let streamed = read_stream(response, stream, body, |data| {
+                        let event: StreamEvent = serde_json::from_str(data)?;
+                        let event_usage = match event.kind.as_str() {
+                            "content_block_delta" => {
+                                return Ok(event
+                                    .delta
+                                    .map(|delta| delta.text + &delta.partial_json));
+                            }
+                            "error" => anyhow::bail!("Anthropic stream failed: {}", data),
+                            "message_start" => event.message.and_then(|message| message.usage),
+                            "message_delta" => {
+                                stop_reason = event.delta.and_then(|delta| delta.stop_reason);
+                                event.usage
+                            }
+                            _ => event.usage,
+                        };
+                        if let Some(event_usage) = event_usage {
+                            usage.input_tokens =
+                                event_usage.input_tokens.unwrap_or(usage.input_tokens);
+                            usage.output_tokens =
+                                event_usage.output_tokens.unwrap_or(usage.output_tokens);
+                        }
+                        Ok(None)
+                    })
+                    .await;
+                    match streamed {
+                        Ok(text) => {
+                            return self.extract_translation_result(MessagesResponse {
+                                content: vec![ContentBlock { text, input: None }],
+                                usage: Some(usage),
+                                stop_reason,
+                            });
+                        }
+                        Err(err) => {
+                            retry(self.retry, &mut retries, &err.to_string()).await?;
+                            continue;
+                        }
+                    }
+                }
+                (reqwest::StatusCode::OK, None) => {
+                    let response = response.json::<MessagesResponse>().await?;
+                    return self.extract_translation_result(response);
+                }
+                _ => {
+                    handle_error_response("Anthropic", response, self.retry, &mut retries).await?;
+                    continue;
+                }
+            }
+        }
+    }
+
+    fn pricing(&self) -> Option<Pricing> {
+        self.pricing
+    }
+
+    fn model(&self, _lang: &str) -> Option<String> {
+        Some(self.model.clone())
+    }
+}
+
+/// List prices of common models, in USD per million tokens
+fn model_pricing(model: &str) -> Option<Pricing> {
+    let (input, output) = match model {
+        m if m.contains("haiku") && m.contains("3-5") => (0.8, 4.0),
+        m if m.contains("haiku") => (0.25, 1.25),
+        m if m.contains("sonnet") => (3.0, 15.0),
+        m if m.contains("opus") => (15.0, 75.0),
+        _ => return None,
+    };
+
+    Some(Pricing { input, output })
+}
@@ -0,0 +1,175 @@
+use std::env;
+use std::sync::Arc;
+
+use anyhow::bail;
+use async_trait::async_trait;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+
+use super::rate_limit::{RateLimiter, wait_for_slot};
+use super::{
+    AiRequest, AiResponse, Pricing, ProviderConfig, RetryPolicy, TranslationProvider,
+    handle_error_response, request_error, retry,
+};
+
+const DEEPL_URL: &str = "https://api.deepl.com/v2/translate";
+const DEEPL_FREE_URL: &str = "https://api-free.deepl.com/v2/translate";
+
+/// DeepL bills characters, which are reported as input tokens: USD 25 per million
+const DEEPL_PRICING: Pricing = Pricing {
+    input: 25.0,
+    output: 0.0,
+};
+
+#[derive(Serialize)]
+struct TranslateRequest<'a> {
+    text: &'a [String],
+    target_lang: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    formality: Option<&'a str>,
+}
+
+#[derive(Deserialize)]
+struct TranslateResponse {
+    translations: Vec<Translation>,
+}
+
+#[derive(Deserialize)]
+struct Translation {
+    text: String,
+}
+
+/// DeepL machine translation. It ignores the prompt and translates the request's raw texts;
+/// several texts are answered with a JSON array, like the batch prompts ask the LLMs to.
+pub struct DeepL {
+    api_key: String,
+    url: &'static str,
+    formality: Option<String>,
+    pricing: Pricing,
+    retry: RetryPolicy,
+    limiter: Option<Arc<RateLimiter>>,
+    client: Client,
+}
+
+impl DeepL {
+    pub fn new(config: ProviderConfig, client: Client) -> anyhow::Result<Self> {
+        let api_key = match config.api_key {
+            Some(api_key) => api_key,
+            None => Self::get_api_key()?,
+        };
+
+        // Free plan keys end in ":fx" and are served from a separate host
+        let url = match api_key.ends_with(":fx") {
+            true => DEEPL_FREE_URL,
+            false => DEEPL_URL,
+        };
+
+        Ok(Self {
+            api_key,
+            url,
+            formality: config.formality,
+            pricing: config.pricing.unwrap_or(DEEPL_PRICING),
+            retry: config.retry,
+            limiter: config.limiter,
+            client,
+        })
+    }
+
+    fn get_api_key() -> anyhow::Result<String> {
+        env::var("DEEPL_API_KEY").map_err(|_| {
+            anyhow::anyhow!(
+                "DEEPL_API_KEY must be set, or the key given with --api-key-file or --keychain"
+            )
+        })
+    }
+
+    fn extract_translation_result(
+        &self,
+        body: &AiRequest,
+        response: TranslateResponse,
+    ) -> anyhow::Result<AiResponse> {
+        let mut texts: Vec<String> = response.translations.into_iter().map(|t| t.text).collect();
+        let characters = body
+            .texts
+            .iter()
+            .map(|text| text.chars().count())
+            .sum::<usize>();
+
+        let text = match texts.len() {
+            1 => texts.remove(0),
+            _ => serde_json::to_string(&texts)?,
+        };
+
+        Ok(AiResponse::new(text).with_usage(characters as u64, 0))
+    }
+}
+
+#[async_trait]
+impl TranslationProvider for DeepL {
+    async fn send(&self, body: &AiRequest) -> anyhow::Result<AiResponse> {
+        if body.texts.is_empty() {
+            bail!("DeepL needs the raw messages of the request, but none were given");
+        }
+
+        let mut retries = 0;
+
+        let request = TranslateRequest {
+            text: &body.texts,
+            target_lang: target_lang(&body.lang),
+            formality: self.formality.as_deref(),
+        };
+
+        loop {
+            wait_for_slot(self.limiter.as_deref(), body).await;
+            let response = self
+                .client
+                .post(self.url)
+                .header("Authorization", format!("DeepL-Auth-Key {}", self.api_key))
+                .json(&request)
+                .send()
+                .await;
+
+            let response = match response {
+                Ok(response) => response,
+                Err(err) => {
+                    retry(self.retry, &mut retries, &request_error(&err)).await?;
+                    continue;
+                }
+            };
+
+            match response.status() {
+                reqwest::StatusCode::OK => {
+                    let response = response.json::<TranslateResponse>().await?;
+                    return self.extract_translation_result(body, response);
+                }
+                _ => {
+                    handle_error_response("DeepL", response, self.retry, &mut retries).await?;
+                    continue;
+                }
+            }
+        }
+    }
+
+    fn pricing(&self) -> Option<Pricing> {
+        Some(self.pricing)
+    }
+
+    fn model(&self, _lang: &str) -> Option<String> {
+        Some("deepl".to_string())
+    }
+}
+
+/// Maps an ISO code like `pt_BR` to DeepL's target language code (`PT-BR`). DeepL requires
+/// a variant for English and Portuguese, so bare `en` and `pt` get the most common one.
+fn target_lang(code: &str) -> String {
+    let code = code.replace('_', "-").to_uppercase();
+
+    match code.as_str() {
+        "EN" => "EN-US".into(),
+        "PT" => "PT-PT".into(),
+        "ZH" | "ZH-CN" | "ZH-SG" => "ZH-HANS".into(),
+        "ZH-TW" | "ZH-HK" => "ZH-HANT".into(),
+        "NO" | "NN" => "NB".into(),
+        _ => code,
+    }
+}
@@ -0,0 +1,292 @@
+use anyhow::bail;
+use reqwest::{Client, StatusCode};
+use serde::Deserialize;
+use serde_json::{Value, json};
+
+use super::{
+    DEFAULT_OLLAMA_URL, ProviderConfig, ProviderKind, RetryPolicy, api_keys, handle_error_response,
+    http_client, keychain, request_error, retry,
+};
+use crate::placeholders;
+
+const OPENAI_URL: &str = "https://api.openai.com/v1/embeddings";
+const GEMINI_URL: &str = "https://generativelanguage.googleapis.com/v1beta/models";
+const DEFAULT_OPENAI_MODEL: &str = "text-embedding-3-small";
+const DEFAULT_GEMINI_MODEL: &str = "gemini-embedding-001";
+const DEFAULT_OLLAMA_MODEL: &str = "nomic-embed-text";
+
+/// The length of the vectors asked of the models that can shorten theirs, which keeps the
+/// index small without losing much
+const DIMENSIONS: usize = 512;
+
+/// Most texts sent in one request
+pub const MAX_BATCH: usize = 100;
+
+/// Number of buckets the n-grams of the mock embeddings are hashed into
+const MOCK_BUCKETS: usize = 512;
+
+/// OpenAI's embeddings, also those of OpenAI-compatible servers
+#[derive(Deserialize)]
+struct OpenAIEmbeddings {
+    data: Vec<OpenAIEmbedding>,
+}
+
+#[derive(Deserialize)]
+struct OpenAIEmbedding {
+    index: usize,
+    embedding: Vec<f32>,
+}
+
+#[derive(Deserialize)]
+struct GeminiEmbeddings {
+    embeddings: Vec<GeminiEmbedding>,
+}
+
+#[derive(Deserialize)]
+struct GeminiEmbedding {
+    values: Vec<f32>,
+}
+
+#[derive(Deserialize)]
+struct OllamaEmbeddings {
+    embeddings: Vec<Vec<f32>>,
+}
+
+/// Turns texts into embedding vectors with the embeddings API of a provider, for `--memory`:
+/// OpenAI, Gemini, Ollama or an OpenAI-compatible server, or offline hashed n-grams with
+/// `mock`
+pub struct Embedder {
+    kind: ProviderKind,
+    client: Client,
+    api_key: Option<String>,
+    /// The endpoint of Ollama and OpenAI-compatible servers
+    base_url: String,
+    model: String,
+    retry: RetryPolicy,
+}
+
+impl Embedder {
+    /// What identifies the vectors: the provider and its model, like
+    /// `openai/text-embedding-3-small`. Vectors of different models can't be compared.
+    pub fn model(&self) -> String {
+        format!("{}/{}", provider_name(self.kind), self.model)
+    }
+
+    /// The vectors of `texts`, in their order, asked for `MAX_BATCH` at a time. Requests that
+    /// fail with a network error, a 429 or a 5xx are retried like translations.
+    pub async fn embed(&self, texts: &[String]) -> anyhow::Result<Vec<Vec<f32>>> {
+        let mut vectors = Vec::with_capacity(texts.len());
+        for batch in texts.chunks(MAX_BATCH) {
+            let embedded = match self.kind {
+                ProviderKind::Mock => batch.iter().map(|text| mock_embedding(text)).collect(),
+                _ => self.request(batch).await?,
+            };
+            if embedded.len() != batch.len() {
+                bail!(
+                    "The {} embeddings API gave {} vectors for {} texts",
+                    self.model(),
+                    embedded.len(),
+                    batch.len()
+                );
+            }
+            vectors.extend(embedded);
+        }
+        Ok(vectors)
+    }
+
+    /// Sends one batch of `texts` to the API, retrying it like a translation
+    async fn request(&self, texts: &[String]) -> anyhow::Result<Vec<Vec<f32>>> {
+        let (name, url, body) = match self.kind {
+            ProviderKind::Openai => ("OpenAI", OPENAI_URL.to_string(), self.openai_body(texts)),
+            ProviderKind::OpenaiCompatible => (
+                "The OpenAI-compatible server",
+                format!("{}/embeddings", self.base_url),
+                self.openai_body(texts),
+            ),
+            ProviderKind::Gemini => (
+                "Gemini",
+                format!("{}/{}:batchEmbedContents", GEMINI_URL, self.model),
+                json!({
+                    "requests": texts.iter().map(|text| json!({
+                        "model": format!("models/{}", self.model),
+                        "content": { "parts": [{ "text": text }] },
+                        "outputDimensionality": DIMENSIONS,
+                    })).collect::<Vec<_>>(),
+                }),
+            ),
+            ProviderKind::Ollama => (
+                "Ollama",
+                format!("{}/api/embed", self.base_url),
+                json!({ "model": self.model, "input": texts }),
+            ),
+            _ => unreachable!("create_embedder only builds embedders with an API"),
+        };
+
+        let mut retries = 0;
+        loop {
+            let mut builder = self.client.post(&url).json(&body);
+            builder = match (self.kind, &self.api_key) {
+                (ProviderKind::Gemini, Some(key)) => builder.header("x-goog-api-key", key),
+                (_, Some(key)) => builder.bearer_auth(key),
+                (_, None) => builder,
+            };
+            let response = match builder.send().await {
+                Ok(response) => response,
+                Err(err) => {
+                    retry(self.retry, &mut retries, &request_error(&err)).await?;
+                    continue;
+                }
+            };
+
+            match response.status() {
+                StatusCode::OK => return self.vectors(response.json::<Value>().await?),
+                StatusCode::NOT_FOUND if self.kind == ProviderKind::Ollama => bail!(
+                    "Ollama embedding model `{}` not found at {}: pull it with `ollama pull {}`",
+                    self.model,
+                    self.base_url,
+                    self.model
+                ),
+                _ => {
+                    handle_error_response(name, response, self.retry, &mut retries).await?;
+                }
+            }
+        }
+    }
+
+    /// The body of OpenAI's embeddings API, asking its newer models for shorter vectors
+    fn openai_body(&self, texts: &[String]) -> Value {
+        let mut body = json!({ "model": self.model, "input": texts });
+        if self.model.starts_with("text-embedding-3") {
+            body["dimensions"] = json!(DIMENSIONS);
+        }
+        body
+    }
+
+    /// The vectors of an answer of the API, in the order of the texts sent
+    fn vectors(&self, answer: Value) -> anyhow::Result<Vec<Vec<f32>>> {
+        let invalid = |e: serde_json::Error| {
+            anyhow::anyhow!(
+                "Invalid answer of the {} embeddings API: {}",
+                self.model(),
+                e
+            )
+        };
+        Ok(match self.kind {
+            ProviderKind::Gemini => serde_json::from_value::<GeminiEmbeddings>(answer)
+                .map_err(invalid)?
+                .embeddings
+                .into_iter()
+                .map(|embedding| embedding.values)
+                .collect(),
+            ProviderKind::Ollama => {
+                serde_json::from_value::<OllamaEmbeddings>(answer)
+                    .map_err(invalid)?
+                    .embeddings
+            }
+            _ => {
+                let mut data = serde_json::from_value::<OpenAIEmbeddings>(answer)
+                    .map_err(invalid)?
+                    .data;
+                data.sort_by_key(|embedding| embedding.index);
+                data.into_iter()
+                    .map(|embedding| embedding.embedding)
+                    .collect()
+            }
+        })
+    }
+}
+
+/// The embedder of `config`: the embeddings API of its provider, with its model if given,
+/// its key, endpoints, proxy and headers, and its retries. Only the first of several API keys
+/// is used.
+pub fn create_embedder(mut config: ProviderConfig) -> anyhow::Result<Embedder> {
+    let default_model = match config.kind {
+        ProviderKind::Openai => Some(DEFAULT_OPENAI_MODEL),
+        ProviderKind::Gemini => Some(DEFAULT_GEMINI_MODEL),
+        ProviderKind::Ollama => Some(DEFAULT_OLLAMA_MODEL),
+        ProviderKind::Mock => Some("ngrams"),
+        ProviderKind::OpenaiCompatible => None,
+        ProviderKind::Anthropic | ProviderKind::Deepl | ProviderKind::Pseudo => bail!(
+            "--memory compares embeddings, which {} doesn't make: pass --embedding-provider openai, gemini, ollama or openai-compatible",
+            provider_name(config.kind)
+        ),
+    };
+    let Some(model) = config.model.take().or(default_model.map(str::to_string)) else {
+        bail!("--embedding-model must be set with --embedding-provider openai-compatible");
+    };
+    if config.kind == ProviderKind::OpenaiCompatible && config.base_url.is_none() {
+        bail!("--base-url must be set with --embedding-provider openai-compatible");
+    }
+
+    if config.keychain && config.api_key.is_none() && config.kind.api_key_var().is_some() {
+        config.api_key = Some(keychain::lookup(provider_name(config.kind))?);
+    }
+    let api_key = api_keys(&config).into_iter().next();
+    if let (None, ProviderKind::Openai | ProviderKind::Gemini) = (&api_key, config.kind) {
+        bail!(
+            "{} must be set for the embeddings of --memory, or the key given with --api-key-file or --keychain",
+            config.kind.api_key_var().unwrap_or_default()
+        );
+    }
+    let base_url = match config.kind {
+        ProviderKind::Ollama => config.ollama_url.clone(),
+        _ => config.base_url.clone(),
+    }
+    .unwrap_or_else(|| DEFAULT_OLLAMA_URL.to_string());
+
+    Ok(Embedder {
+        kind: config.kind,
+        client: http_client(&config)?,
+        api_key,
+        base_url: base_url.trim_end_matches('/').to_string(),
+        model,
+        retry: config.retry,
+    })
+}
+
+/// The name of a provider on the command line, like `openai-compatible`
+fn provider_name(kind: ProviderKind) -> &'static str {
+    match kind {
+        ProviderKind::Openai => "openai",
+        ProviderKind::Ollama => "ollama",
+        ProviderKind::Anthropic => "anthropic",
+        ProviderKind::Deepl => "deepl",
+        ProviderKind::Gemini => "gemini",
+        ProviderKind::OpenaiCompatible => "openai-compatible",
+        ProviderKind::Pseudo => "pseudo",
+        ProviderKind::Mock => "mock",
+    }
+}
+
+/// The offline embedding of `text` of `--embedding-provider mock`: its lowercased character
+/// trigrams, words and placeholders counted in hashed buckets, for tests and demos. It only
+/// finds rewordings that keep most of the spelling.
+fn mock_embedding(text: &str) -> Vec<f32> {
+    let mut counts = vec![0.0f32; MOCK_BUCKETS];
+    let stripped = placeholders::strip(text).to_lowercase();
+    for word in stripped.split(|c: char| !c.is_alphanumeric()) {
+        if word.is_empty() {
+            continue;
+        }
+        counts[bucket("w", word)] += 1.0;
+        let chars: Vec<char> = format!(" {} ", word).chars().collect();
+        for trigram in chars.windows(3) {
+            let trigram: String = trigram.iter().collect();
+            counts[bucket("t", &trigram)] += 1.0;
+        }
+    }
+    for placeholder in placeholders::extract(text) {
+        counts[bucket("p", &placeholder)] += 1.0;
+    }
+    counts
+}
+
+/// The bucket of a feature, with FNV-1a, by its `kind` and text
+fn bucket(kind: &str, feature: &str) -> usize {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in kind.bytes().chain(feature.bytes()) {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    (hash % MOCK_BUCKETS as u64) as usize
+}
@@ -0,0 +1,144 @@
+use anyhow::{Result, bail};
+use async_trait::async_trait;
+use serde_json::{Value, json};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::{AiRequest, AiResponse, Pricing, TranslationProvider};
+use crate::atomic;
+
+/// A provider whose answers are also written to a folder of fixtures, one JSON file per
+/// request named after its hash, for `Replay` to give back later without the API
+pub struct Recorder {
+    inner: Box<dyn TranslationProvider>,
+    dir: PathBuf,
+}
+
+impl Recorder {
+    pub fn new(inner: Box<dyn TranslationProvider>, dir: &Path) -> Result<Self> {
+        fs::create_dir_all(dir)
+            .map_err(|e| anyhow::anyhow!("Error creating {}: {}", dir.display(), e))?;
+        Ok(Self {
+            inner,
+            dir: dir.to_path_buf(),
+        })
+    }
+}
+
+#[async_trait]
+impl TranslationProvider for Recorder {
+    async fn send(&self, body: &AiRequest) -> anyhow::Result<AiResponse> {
+        let response = self.inner.send(body).await?;
+        let mut fixture = request_json(body);
+        fixture["text"] = json!(response.text);
+        fixture["usage"] = match response.usage {
+            Some(usage) => json!({
+                "input_tokens": usage.input_tokens,
+                "output_tokens": usage.output_tokens,
+            }),
+            None => Value::Null,
+        };
+        atomic::write(
+            &fixture_path(&self.dir, body),
+            serde_json::to_string_pretty(&fixture)? + "\n",
+        )?;
+        Ok(response)
+    }
+
+    fn pricing(&self) -> Option<Pricing> {
+        self.inner.pricing()
+    }
+
+    fn request_pricing(&self, body: &AiRequest) -> Option<Pricing> {
+        self.inner.request_pricing(body)
+    }
+
+    fn model(&self, lang: &str) -> Option<String> {
+        self.inner.model(lang)
+    }
+}
+
+/// Answers requests with the fixtures a `Recorder` wrote to a folder, without any API call
+/// or key. A request that wasn't recorded fails, naming the fixture it looked for.
+pub struct Replay {
+    dir: PathBuf,
+    model: Option<String>,
+    pricing: Option<Pricing>,
+}
+
+impl Replay {
+    /// Replays the fixtures of `dir`, with the model name and prices of the command line, if
+    /// given, for the annotations and the cost of the run
+    pub fn new(dir: &Path, model: Option<String>, pricing: Option<Pricing>) -> Result<Self> {
+        if !dir.is_dir() {
+            bail!("Fixtures folder not found: {}", dir.display());
+        }
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            model,
+            pricing,
+        })
+    }
+}
+
+#[async_trait]
+impl TranslationProvider for Replay {
+    async fn send(&self, body: &AiRequest) -> anyhow::Result<AiResponse> {
+        let path = fixture_path(&self.dir, body);
+        let content = match fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => bail!(
+                "No recorded response for this request in {} (expected {}): record it again with --record",
+                self.dir.display(),
+                path.display()
+            ),
+            Err(e) => bail!("Error reading {}: {}", path.display(), e),
+        };
+        let fixture: Value = serde_json::from_str(&content)
+            .map_err(|e| anyhow::anyhow!("Error parsing {}: {}", path.display(), e))?;
+        let Some(text) = fixture["text"].as_str() else {
+            bail!("Invalid fixture {}: no text", path.display());
+        };
+
+        let response = AiResponse::new(text.to_string());
+        let usage = &fixture["usage"];
+        Ok(
+            match (
+                usage["input_tokens"].as_u64(),
+                usage["output_tokens"].as_u64(),
+            ) {
+                (Some(input), Some(output)) => response.with_usage(input, output),
+                _ => response,
+            },
+        )
+    }
+
+    fn pricing(&self) -> Option<Pricing> {
+        self.pricing
+    }
+
+    fn model(&self, _lang: &str) -> Option<String> {
+        self.model.clone()
+    }
+}
+
+/// What identifies a request: everything sent to the provider but its settings
+fn request_json(body: &AiRequest) -> Value {
+    json!({
+        "lang": body.lang,
+        "format": body.format.name(),
+        "instructions": body.instructions,
+        "input": body.input,
+        "texts": body.texts,
+    })
+}
+
+/// The fixture of `body` in `dir`, named after the FNV-1a hash of the request
+fn fixture_path(dir: &Path, body: &AiRequest) -> PathBuf {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in request_json(body).to_string().bytes() {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    dir.join(format!("{:016x}.json", hash))
+}
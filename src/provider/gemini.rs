@@ -0,0 +1,289 @@
+use std::env;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+
+use super::rate_limit::{RateLimiter, wait_for_slot};
+use super::stream::{STREAM_TIMEOUT, read_stream};
+use super::{
+    AiRequest, AiResponse, Pricing, ProviderConfig, RetryPolicy, Sampling, StreamPolicy,
+    TranslationProvider, handle_error_response, request_error, retry,
+};
+
+const DEFAULT_GEMINI_MODEL: &str = "gemini-2.0-flash";
+const GEMINI_URL: &str = "https://generativelanguage.googleapis.com/v1beta/models";
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct GenerateContentRequest<'a> {
+    system_instruction: Content<'a>,
+    contents: Vec<Content<'a>>,
+    #[serde(skip_serializing_if = "GenerationConfig::is_empty")]
+    generation_config: GenerationConfig,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct GenerationConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temperature: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_p: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    seed: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    response_mime_type: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    response_json_schema: Option<serde_json::Value>,
+}
+
+impl GenerationConfig {
+    fn is_empty(&self) -> bool {
+        self.temperature.is_none()
+            && self.top_p.is_none()
+            && self.seed.is_none()
+            && self.response_json_schema.is_none()
+    }
+}
+
+#[derive(Serialize)]
+struct Content<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    role: Option<&'a str>,
+    parts: Vec<Part<'a>>,
+}
+
+#[derive(Serialize)]
+struct Part<'a> {
+    text: &'a str,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GenerateContentResponse {
+    #[serde(default)]
+    candidates: Vec<Candidate>,
+    usage_metadata: Option<UsageMetadata>,
+}
+
+#[derive(Deserialize)]
+struct Candidate {
+    content: Option<CandidateContent>,
+}
+
+#[derive(Deserialize)]
+struct CandidateContent {
+    #[serde(default)]
+    parts: Vec<CandidatePart>,
+}
+
+#[derive(Deserialize)]
+struct CandidatePart {
+    #[serde(default)]
+    text: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct UsageMetadata {
+    #[serde(default)]
+    prompt_token_count: u64,
+    #[serde(default)]
+    candidates_token_count: u64,
+}
+
+pub struct Gemini {
+    api_key: String,
+    model: String,
+    pricing: Option<Pricing>,
+    retry: RetryPolicy,
+    limiter: Option<Arc<RateLimiter>>,
+    stream: Option<StreamPolicy>,
+    sampling: Sampling,
+    client: Client,
+}
+
+impl Gemini {
+    pub fn new(config: ProviderConfig, client: Client) -> anyhow::Result<Self> {
+        let api_key = match config.api_key {
+            Some(api_key) => api_key,
+            None => Self::get_api_key()?,
+        };
+
+        let model = config.model.unwrap_or_else(|| DEFAULT_GEMINI_MODEL.into());
+
+        Ok(Self {
+            api_key,
+            pricing: config.pricing.or_else(|| model_pricing(&model)),
+            model,
+            retry: config.retry,
+            limiter: config.limiter,
+            stream: config.stream,
+            sampling: config.sampling,
+            client,
+        })
+    }
+
+    fn get_api_key() -> anyhow::Result<String> {
+        env::var("GEMINI_API_KEY").map_err(|_| {
+            anyhow::anyhow!(
+                "GEMINI_API_KEY must be set, or the key given with --api-key-file or --keychain"
+            )
+        })
+    }
+
+    fn extract_translation_result(
+        &self,
+        response: GenerateContentResponse,
+    ) -> anyhow::Result<AiResponse> {
+        let Some(content) = response
+            .candidates
+            .into_iter()
+            .next()
+            .and_then(|candidate| candidate.content)
+        else {
+            anyhow::bail!("Gemini returned no candidates (the prompt may have been blocked)");
+        };
+
+        let text: String = content
+            .parts
+            .iter()
+            .map(|part| part.text.as_str())
+            .collect();
+        let result = AiResponse::new(text.trim().to_string());
+
+        Ok(match response.usage_metadata {
+            Some(usage) => {
+                result.with_usage(usage.prompt_token_count, usage.candidates_token_count)
+            }
+            None => result,
+        })
+    }
+}
+
+#[async_trait]
+impl TranslationProvider for Gemini {
+    async fn send(&self, body: &AiRequest) -> anyhow::Result<AiResponse> {
+        let mut retries = 0;
+        let stream = self.stream.filter(|stream| stream.applies(body));
+
+        let request = GenerateContentRequest {
+            system_instruction: Content {
+                role: None,
+                parts: vec![Part {
+                    text: &body.instructions,
+                }],
+            },
+            contents: vec![Content {
+                role: Some("user"),
+                parts: vec![Part { text: &body.input }],
+            }],
+            generation_config: GenerationConfig {
+                temperature: self.sampling.temperature,
+                top_p: self.sampling.top_p,
+                seed: self.sampling.seed,
+                response_mime_type: body.format.schema().map(|_| "application/json"),
+                response_json_schema: body.format.schema(),
+            },
+        };
+
+        loop {
+            wait_for_slot(self.limiter.as_deref(), body).await;
+            let url = match stream {
+                Some(_) => format!(
+                    "{}/{}:streamGenerateContent?alt=sse",
+                    GEMINI_URL, self.model
+                ),
+                None => format!("{}/{}:generateContent", GEMINI_URL, self.model),
+            };
+            let mut builder = self
+                .client
+                .post(url)
+                .header("x-goog-api-key", &self.api_key)
+                .json(&request);
+            if stream.is_some() {
+                builder = builder.timeout(STREAM_TIMEOUT);
+            }
+            let response = builder.send().await;
+
+            let response = match response {
+                Ok(response) => response,
+                Err(err) => {
+                    retry(self.retry, &mut retries, &request_error(&err)).await?;
+                    continue;
+                }
+            };
+
+            match (response.status(), stream) {
+                // Every chunk is a response with the next part of the text; the last one has
+                // the usage
+                (reqwest::StatusCode::OK, Some(stream)) => {
+                    let mut usage = None;
+                    let streamed = read_stream(response, stream, body, |data| {
+                        let chunk: GenerateContentResponse = serde_json::from_str(data)?;
+                        usage = chunk.usage_metadata.or(usage.take());
+                        let text: String = chunk
+                            .candidates
+                            .into_iter()
+                            .filter_map(|candidate| candidate.content)
+                            .flat_map(|content| content.parts)
+                            .map(|part| part.text)
+                            .collect();
+                        Ok(Some(text))
+                    })
+                    .await;
+                    match streamed {
+                        Ok(text) => {
+                            return self.extract_translation_result(GenerateContentResponse {
+                                candidates: vec![Candidate {
+                                    content: Some(CandidateContent {
+                                        parts: vec![CandidatePart { text }],
+                                    }),
+                                }],
+                                usage_metadata: usage,
+                            });
+                        }
+                        Err(err) => {
+                            retry(self.retry, &mut retries, &err.to_string()).await?;
+                            continue;
+                        }
+                    }
+                }
+                (reqwest::StatusCode::OK, None) => {
+                    let response = response.json::<GenerateContentResponse>().await?;
+                    return self.extract_translation_result(response);
+                }
+                _ => {
+                    handle_error_response("Gemini", response, self.retry, &mut retries).await?;
+                    continue;
+                }
+            }
+        }
+    }
+
+    fn pricing(&self) -> Option<Pricing> {
+        self.pricing
+    }
+
+    fn model(&self, _lang: &str) -> Option<String> {
+        Some(self.model.clone())
+    }
+}
+
+/// List prices of common models, in USD per million tokens
+fn model_pricing(model: &str) -> Option<Pricing> {
+    let (input, output) = match model {
+        m if m.starts_with("gemini-2.5-flash-lite") => (0.1, 0.4),
+        m if m.starts_with("gemini-2.5-flash") => (0.3, 2.5),
+        m if m.starts_with("gemini-2.5-pro") => (1.25, 10.0),
+        m if m.starts_with("gemini-2.0-flash-lite") => (0.075, 0.3),
+        m if m.starts_with("gemini-2.0-flash") => (0.1, 0.4),
+        m if m.starts_with("gemini-1.5-flash") => (0.075, 0.3),
+        m if m.starts_with("gemini-1.5-pro") => (1.25, 5.0),
+        _ => return None,
+    };
+
+    Some(Pricing { input, output })
+}
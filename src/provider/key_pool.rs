@@ -0,0 +1,154 @@
+use async_trait::async_trait;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use super::{AiRequest, AiResponse, KeyLimited, Pricing, TranslationProvider};
+use crate::logger::log_key_rotated;
+
+/// The same provider with several API keys: requests use one key until it is rate limited
+/// after every retry, or out of quota, then move on to the next, coming back to the first
+/// after the last. A request only fails that way once every key has failed it.
+pub struct KeyPool {
+    /// One provider per key
+    providers: Vec<Box<dyn TranslationProvider>>,
+    /// The index of the key in use
+    current: AtomicUsize,
+}
+
+impl KeyPool {
+    pub fn new(providers: Vec<Box<dyn TranslationProvider>>) -> Self {
+        Self {
+            providers,
+            current: AtomicUsize::new(0),
+        }
+    }
+}
+
+#[async_trait]
+impl TranslationProvider for KeyPool {
+    async fn send(&self, body: &AiRequest) -> anyhow::Result<AiResponse> {
+        let mut failed = 0;
+        loop {
+            let index = self.current.load(Ordering::Relaxed);
+            match self.providers[index].send(body).await {
+                Err(e) if e.is::<KeyLimited>() && failed + 1 < self.providers.len() => {
+                    failed += 1;
+                    let next = (index + 1) % self.providers.len();
+                    // Requests failing together with the same key switch it only once
+                    if self
+                        .current
+                        .compare_exchange(index, next, Ordering::Relaxed, Ordering::Relaxed)
+                        .is_ok()
+                    {
+                        log_key_rotated(next + 1, self.providers.len(), &e.to_string());
+                    }
+                }
+                result => return result,
+            }
+        }
+    }
+
+    fn pricing(&self) -> Option<Pricing> {
+        self.providers[0].pricing()
+    }
+
+    fn request_pricing(&self, body: &AiRequest) -> Option<Pricing> {
+        self.providers[0].request_pricing(body)
+    }
+
+    fn model(&self, lang: &str) -> Option<String> {
+        self.providers[0].model(lang)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    /// A provider answering with its key, or failing as rate limited while `limited`
+    struct Key {
+        name: &'static str,
+        limited: bool,
+        calls: Arc<AtomicUsize>,
+    }
+
+    #[async_trait]
+    impl TranslationProvider for Key {
+        async fn send(&self, _body: &AiRequest) -> anyhow::Result<AiResponse> {
+            self.calls.fetch_add(1, Ordering::Relaxed);
+            match self.limited {
+                true => Err(KeyLimited(format!("{} is rate limited", self.name)).into()),
+                false => Ok(AiResponse::new(self.name.to_string())),
+            }
+        }
+    }
+
+    fn pool(keys: &[(&'static str, bool)]) -> (KeyPool, Vec<Arc<AtomicUsize>>) {
+        let calls: Vec<_> = keys.iter().map(|_| Arc::new(AtomicUsize::new(0))).collect();
+        let providers = keys
+            .iter()
+            .zip(&calls)
+            .map(|((name, limited), calls)| {
+                Box::new(Key {
+                    name,
+                    limited: *limited,
+                    calls: calls.clone(),
+                }) as Box<dyn TranslationProvider>
+            })
+            .collect();
+        (KeyPool::new(providers), calls)
+    }
+
+    fn request() -> AiRequest {
+        AiRequest::new(String::new(), "Hello".to_string())
+    }
+
+    #[tokio::test]
+    async fn moves_on_to_the_next_key_when_one_is_limited() {
+        let (pool, calls) = pool(&[("first", true), ("second", false), ("third", false)]);
+        assert_eq!(pool.send(&request()).await.unwrap().text, "second");
+        assert_eq!(pool.send(&request()).await.unwrap().text, "second");
+        let calls: Vec<usize> = calls
+            .iter()
+            .map(|calls| calls.load(Ordering::Relaxed))
+            .collect();
+        assert_eq!(calls, vec![1, 2, 0]);
+    }
+
+    #[tokio::test]
+    async fn fails_once_every_key_is_limited() {
+        let (pool, calls) = pool(&[("first", true), ("second", true)]);
+        let Err(error) = pool.send(&request()).await else {
+            panic!("the request succeeded");
+        };
+        assert!(error.is::<KeyLimited>());
+        assert_eq!(error.to_string(), "second is rate limited");
+        for calls in calls {
+            assert_eq!(calls.load(Ordering::Relaxed), 1);
+        }
+    }
+
+    #[tokio::test]
+    async fn keeps_the_key_on_other_errors() {
+        struct Failing;
+        #[async_trait]
+        impl TranslationProvider for Failing {
+            async fn send(&self, _body: &AiRequest) -> anyhow::Result<AiResponse> {
+                anyhow::bail!("invalid API key")
+            }
+        }
+        let pool = KeyPool::new(vec![
+            Box::new(Failing),
+            Box::new(Key {
+                name: "second",
+                limited: false,
+                calls: Arc::new(AtomicUsize::new(0)),
+            }),
+        ]);
+        let Err(error) = pool.send(&request()).await else {
+            panic!("the request succeeded");
+        };
+        assert_eq!(error.to_string(), "invalid API key");
+        assert_eq!(pool.current.load(Ordering::Relaxed), 0);
+    }
+}
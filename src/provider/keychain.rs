@@ -0,0 +1,72 @@
+use anyhow::{Result, bail};
+use std::process::Command;
+
+/// The service the API keys are stored under, with the provider as the account
+pub const SERVICE: &str = "ai-gettext-translator";
+
+/// Loads the Credential Manager's type into PowerShell
+const PASSWORD_VAULT: &str = "[void][Windows.Security.Credentials.PasswordVault,Windows.Security.Credentials,ContentType=WindowsRuntime]";
+
+/// The API key of `provider` in the keychain of the OS: the login keychain on macOS, the
+/// Secret Service (GNOME Keyring, KWallet) on Linux and the Credential Manager on Windows.
+/// It is read by the tool each OS ships for it, so it never shows on a command line.
+pub fn lookup(provider: &str) -> Result<String> {
+    let (program, args, store) = command(provider);
+    let output = Command::new(program).args(&args).output().map_err(|e| {
+        anyhow::anyhow!(
+            "--keychain reads the key with `{}`, which couldn't be run: {}",
+            program,
+            e
+        )
+    })?;
+
+    let key = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if !output.status.success() || key.is_empty() {
+        bail!(
+            "No API key for {} in the keychain; store it with `{}`",
+            provider,
+            store
+        );
+    }
+    Ok(key)
+}
+
+/// The program and arguments reading the key of `provider`, and the command storing it
+fn command(provider: &str) -> (&'static str, Vec<String>, String) {
+    if cfg!(target_os = "macos") {
+        (
+            "security",
+            ["find-generic-password", "-s", SERVICE, "-a", provider, "-w"]
+                .map(String::from)
+                .to_vec(),
+            format!(
+                "security add-generic-password -s {} -a {} -w",
+                SERVICE, provider
+            ),
+        )
+    } else if cfg!(windows) {
+        let script = format!(
+            "{}; $credential = (New-Object Windows.Security.Credentials.PasswordVault).Retrieve('{}', '{}'); $credential.RetrievePassword(); $credential.Password",
+            PASSWORD_VAULT, SERVICE, provider
+        );
+        (
+            "powershell",
+            vec!["-NoProfile".into(), "-Command".into(), script],
+            format!(
+                "powershell -Command \"{}; (New-Object Windows.Security.Credentials.PasswordVault).Add((New-Object Windows.Security.Credentials.PasswordCredential('{}', '{}', (Read-Host))))\"",
+                PASSWORD_VAULT, SERVICE, provider
+            ),
+        )
+    } else {
+        (
+            "secret-tool",
+            ["lookup", "service", SERVICE, "account", provider]
+                .map(String::from)
+                .to_vec(),
+            format!(
+                "secret-tool store --label='{} {}' service {} account {}",
+                SERVICE, provider, SERVICE, provider
+            ),
+        )
+    }
+}
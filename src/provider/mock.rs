@@ -0,0 +1,110 @@
+use anyhow::bail;
+use async_trait::async_trait;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use tokio::time::sleep;
+
+use super::rate_limit::{RateLimiter, wait_for_slot};
+use super::{
+    AiRequest, AiResponse, Pricing, ProviderConfig, RetryPolicy, TranslationProvider, retry,
+};
+
+/// Fake translations, without any API call, for integration tests, demos and dry runs of a
+/// pipeline: every message comes back as `[[lang]] message`, e.g. `[[es]] Hello World`. Each
+/// attempt takes `latency`, and a `failure_rate` of them fail like a server error, retried
+/// with the retry policy. Which attempts fail only depends on `--seed` and their order, so a
+/// run with `--concurrency 1` is the same every time. Like DeepL, it answers the request's
+/// raw texts, with a JSON array when there are several.
+pub struct Mock {
+    latency: Option<Duration>,
+    failure_rate: f64,
+    retry: RetryPolicy,
+    limiter: Option<Arc<RateLimiter>>,
+    seed: u64,
+    /// Attempts made so far, numbering the next one
+    attempts: AtomicU64,
+}
+
+impl Mock {
+    pub fn new(config: ProviderConfig) -> Self {
+        Self {
+            latency: config.mock_latency,
+            failure_rate: config.mock_failure_rate,
+            retry: config.retry,
+            limiter: config.limiter,
+            seed: config.sampling.seed.unwrap_or_default(),
+            attempts: AtomicU64::new(0),
+        }
+    }
+
+    /// Whether the attempt numbered `attempt` fails
+    fn fails(&self, attempt: u64) -> bool {
+        let mut hash: u64 = 0xcbf29ce484222325;
+        for byte in self
+            .seed
+            .to_le_bytes()
+            .into_iter()
+            .chain(attempt.to_le_bytes())
+        {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+        ((hash >> 11) as f64 / (1u64 << 53) as f64) < self.failure_rate
+    }
+}
+
+#[async_trait]
+impl TranslationProvider for Mock {
+    async fn send(&self, body: &AiRequest) -> anyhow::Result<AiResponse> {
+        if body.texts.is_empty() {
+            bail!("The mock provider needs the raw messages of the request, but none were given");
+        }
+
+        let mut retries = 0;
+        loop {
+            wait_for_slot(self.limiter.as_deref(), body).await;
+            if let Some(latency) = self.latency {
+                sleep(latency).await;
+            }
+            let attempt = self.attempts.fetch_add(1, Ordering::Relaxed);
+            if !self.fails(attempt) {
+                break;
+            }
+            retry(
+                self.retry,
+                &mut retries,
+                "Mock error: 503 Service Unavailable (injected failure)",
+            )
+            .await?;
+        }
+
+        let mut texts: Vec<String> = body
+            .texts
+            .iter()
+            .map(|text| format!("[[{}]] {}", body.lang, text))
+            .collect();
+        let text = match texts.len() {
+            1 => texts.remove(0),
+            _ => serde_json::to_string(&texts)?,
+        };
+
+        // About 4 characters per token, so that the summary and budgets have numbers to show
+        let (input, output) = (
+            (body.instructions.len() + body.input.len()) as u64 / 4,
+            text.len() as u64 / 4,
+        );
+        Ok(AiResponse::new(text).with_usage(input, output))
+    }
+
+    fn pricing(&self) -> Option<Pricing> {
+        Some(Pricing {
+            input: 0.0,
+            output: 0.0,
+        })
+    }
+
+    fn model(&self, _lang: &str) -> Option<String> {
+        Some("mock".to_string())
+    }
+}
@@ -0,0 +1,182 @@
+use std::sync::Arc;
+
+use anyhow::bail;
+use async_trait::async_trait;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+
+use super::rate_limit::{RateLimiter, wait_for_slot};
+use super::stream::{STREAM_TIMEOUT, read_stream};
+use super::{
+    AiRequest, AiResponse, Pricing, ProviderConfig, RetryPolicy, Sampling, StreamPolicy,
+    TranslationProvider, handle_error_response, request_error, retry,
+};
+
+pub const DEFAULT_OLLAMA_URL: &str = "http://localhost:11434";
+const DEFAULT_OLLAMA_MODEL: &str = "llama3.2";
+
+#[derive(Serialize)]
+struct ChatRequest<'a> {
+    model: &'a str,
+    messages: Vec<ChatMessage<'a>>,
+    stream: bool,
+    #[serde(skip_serializing_if = "Sampling::is_default")]
+    options: Sampling,
+    /// The JSON schema of a structured answer
+    #[serde(skip_serializing_if = "Option::is_none")]
+    format: Option<serde_json::Value>,
+}
+
+#[derive(Serialize)]
+struct ChatMessage<'a> {
+    role: &'a str,
+    content: &'a str,
+}
+
+#[derive(Deserialize)]
+struct ChatResponse {
+    message: ResponseMessage,
+    #[serde(default)]
+    prompt_eval_count: u64,
+    #[serde(default)]
+    eval_count: u64,
+}
+
+#[derive(Deserialize)]
+struct ResponseMessage {
+    content: String,
+}
+
+pub struct Ollama {
+    base_url: String,
+    model: String,
+    pricing: Option<Pricing>,
+    retry: RetryPolicy,
+    limiter: Option<Arc<RateLimiter>>,
+    stream: Option<StreamPolicy>,
+    sampling: Sampling,
+    client: Client,
+}
+
+impl Ollama {
+    pub fn new(config: ProviderConfig, client: Client) -> Self {
+        let base_url = config
+            .ollama_url
+            .unwrap_or_else(|| DEFAULT_OLLAMA_URL.into());
+
+        Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            model: config.model.unwrap_or_else(|| DEFAULT_OLLAMA_MODEL.into()),
+            // Local models are free unless told otherwise
+            pricing: config.pricing.or(Some(Pricing {
+                input: 0.0,
+                output: 0.0,
+            })),
+            retry: config.retry,
+            limiter: config.limiter,
+            stream: config.stream,
+            sampling: config.sampling,
+            client,
+        }
+    }
+
+    fn extract_translation_result(&self, response: ChatResponse) -> anyhow::Result<AiResponse> {
+        let text = response.message.content.trim();
+        Ok(AiResponse::new(text.to_string())
+            .with_usage(response.prompt_eval_count, response.eval_count))
+    }
+}
+
+#[async_trait]
+impl TranslationProvider for Ollama {
+    async fn send(&self, body: &AiRequest) -> anyhow::Result<AiResponse> {
+        let mut retries = 0;
+        let stream = self.stream.filter(|stream| stream.applies(body));
+
+        let request = ChatRequest {
+            model: &self.model,
+            messages: vec![
+                ChatMessage {
+                    role: "system",
+                    content: &body.instructions,
+                },
+                ChatMessage {
+                    role: "user",
+                    content: &body.input,
+                },
+            ],
+            stream: stream.is_some(),
+            options: self.sampling,
+            format: body.format.schema(),
+        };
+
+        loop {
+            wait_for_slot(self.limiter.as_deref(), body).await;
+            let mut builder = self
+                .client
+                .post(format!("{}/api/chat", self.base_url))
+                .json(&request);
+            if stream.is_some() {
+                builder = builder.timeout(STREAM_TIMEOUT);
+            }
+            let response = builder.send().await;
+
+            let response = match response {
+                Ok(response) => response,
+                Err(err) => {
+                    retry(self.retry, &mut retries, &request_error(&err)).await?;
+                    continue;
+                }
+            };
+
+            match (response.status(), stream) {
+                (reqwest::StatusCode::OK, Some(stream)) => {
+                    // The last chunk has the token counts
+                    let mut usage = (0, 0);
+                    let streamed = read_stream(response, stream, body, |data| {
+                        let chunk: ChatResponse = serde_json::from_str(data)?;
+                        usage = (chunk.prompt_eval_count, chunk.eval_count);
+                        Ok(Some(chunk.message.content))
+                    })
+                    .await;
+                    match streamed {
+                        Ok(text) => {
+                            return self.extract_translation_result(ChatResponse {
+                                message: ResponseMessage { content: text },
+                                prompt_eval_count: usage.0,
+                                eval_count: usage.1,
+                            });
+                        }
+                        Err(err) => {
+                            retry(self.retry, &mut retries, &err.to_string()).await?;
+                            continue;
+                        }
+                    }
+                }
+                (reqwest::StatusCode::OK, None) => {
+                    let response = response.json::<ChatResponse>().await?;
+                    return self.extract_translation_result(response);
+                }
+                (reqwest::StatusCode::NOT_FOUND, _) => {
+                    bail!(
+                        "Ollama model `{}` not found at {}",
+                        self.model,
+                        self.base_url
+                    );
+                }
+                _ => {
+                    handle_error_response("Ollama", response, self.retry, &mut retries).await?;
+                    continue;
+                }
+            }
+        }
+    }
+
+    fn pricing(&self) -> Option<Pricing> {
+        self.pricing
+    }
+
+    fn model(&self, _lang: &str) -> Option<String> {
+        Some(self.model.clone())
+    }
+}
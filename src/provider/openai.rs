@@ -0,0 +1,250 @@
+use std::env;
+use std::sync::Arc;
+
+use anyhow::bail;
+use async_trait::async_trait;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use super::rate_limit::{RateLimiter, wait_for_slot};
+use super::stream::{STREAM_TIMEOUT, read_stream};
+use super::{
+    AiRequest, AiResponse, Pricing, ProviderConfig, RetryPolicy, Sampling, StreamPolicy,
+    TranslationProvider, handle_error_response, request_error, retry,
+};
+
+const DEFAULT_OPENAI_MODEL: &str = "gpt-4o-mini";
+
+#[derive(Serialize)]
+struct ResponsesRequest<'a> {
+    model: &'a str,
+    instructions: &'a str,
+    input: &'a str,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    stream: bool,
+    #[serde(flatten)]
+    sampling: Sampling,
+    /// The JSON schema of a structured answer
+    #[serde(skip_serializing_if = "Option::is_none")]
+    text: Option<serde_json::Value>,
+}
+
+#[derive(Deserialize)]
+pub struct AiReponse {
+    output: Vec<ResponseContent>,
+    usage: Option<ResponseUsage>,
+}
+
+#[derive(Deserialize)]
+struct ResponseUsage {
+    input_tokens: u64,
+    output_tokens: u64,
+}
+
+#[derive(Deserialize)]
+struct ResponseContent {
+    /// `message` for the answer; reasoning models put a `reasoning` item, without content, first
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(default)]
+    content: Vec<ContentLine>,
+}
+
+#[derive(Deserialize)]
+struct ContentLine {
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(default)]
+    text: String,
+}
+
+/// An event of a streamed response, of which only the text deltas and the final usage matter
+#[derive(Deserialize)]
+struct StreamEvent {
+    #[serde(rename = "type")]
+    kind: String,
+    delta: Option<String>,
+    response: Option<StreamedResponse>,
+}
+
+#[derive(Deserialize)]
+struct StreamedResponse {
+    usage: Option<ResponseUsage>,
+}
+
+pub struct OpenAI {
+    api_key: String,
+    model: String,
+    pricing: Option<Pricing>,
+    retry: RetryPolicy,
+    limiter: Option<Arc<RateLimiter>>,
+    stream: Option<StreamPolicy>,
+    sampling: Sampling,
+    client: Client,
+}
+
+impl OpenAI {
+    pub fn new(config: ProviderConfig, client: Client) -> anyhow::Result<Self> {
+        let api_key = match config.api_key {
+            Some(api_key) => api_key,
+            None => Self::get_api_key()?,
+        };
+
+        let model = config.model.unwrap_or_else(|| DEFAULT_OPENAI_MODEL.into());
+
+        Ok(Self {
+            api_key,
+            pricing: config.pricing.or_else(|| model_pricing(&model)),
+            model,
+            retry: config.retry,
+            limiter: config.limiter,
+            stream: config.stream,
+            sampling: config.sampling,
+            client,
+        })
+    }
+
+    fn get_api_key() -> anyhow::Result<String> {
+        env::var("OPENAI_API_KEY").map_err(|_| {
+            anyhow::anyhow!(
+                "OPENAI_API_KEY must be set, or the key given with --api-key-file or --keychain"
+            )
+        })
+    }
+
+    fn extract_translation_result(&self, response: AiReponse) -> anyhow::Result<AiResponse> {
+        let Some(text) = response
+            .output
+            .iter()
+            .filter(|item| item.kind == "message")
+            .flat_map(|item| &item.content)
+            .find(|part| part.kind == "output_text")
+            .map(|part| &part.text)
+        else {
+            bail!("OpenAI answered without a message");
+        };
+        let result = AiResponse::new(text.trim().to_string());
+
+        Ok(match response.usage {
+            Some(usage) => result.with_usage(usage.input_tokens, usage.output_tokens),
+            None => result,
+        })
+    }
+}
+
+#[async_trait]
+impl TranslationProvider for OpenAI {
+    async fn send(&self, body: &AiRequest) -> anyhow::Result<AiResponse> {
+        let mut retries = 0;
+        let stream = self.stream.filter(|stream| stream.applies(body));
+
+        let request = ResponsesRequest {
+            model: &self.model,
+            instructions: &body.instructions,
+            input: &body.input,
+            stream: stream.is_some(),
+            sampling: self.sampling,
+            text: body.format.schema().map(|schema| {
+                json!({
+                    "format": {
+                        "type": "json_schema",
+                        "name": body.format.name(),
+                        "schema": schema,
+                        "strict": true,
+                    }
+                })
+            }),
+        };
+
+        loop {
+            wait_for_slot(self.limiter.as_deref(), body).await;
+            let mut builder = self
+                .client
+                .post("https://api.openai.com/v1/responses")
+                .bearer_auth(self.api_key.clone())
+                .json(&request);
+            if stream.is_some() {
+                builder = builder.timeout(STREAM_TIMEOUT);
+            }
+            let response = builder.send().await;
+
+            let response = match response {
+                Ok(response) => response,
+                Err(err) => {
+                    retry(self.retry, &mut retries, &request_error(&err)).await?;
+                    continue;
+                }
+            };
+
+            match (response.status(), stream) {
+                (reqwest::StatusCode::OK, Some(stream)) => {
+                    let mut usage = None;
+                    let streamed = read_stream(response, stream, body, |data| {
+                        let event: StreamEvent = serde_json::from_str(data)?;
+                        match event.kind.as_str() {
+                            "response.output_text.delta" => return Ok(event.delta),
+                            "response.completed" => {
+                                usage = event.response.and_then(|response| response.usage);
+                            }
+                            "response.failed" | "error" => {
+                                anyhow::bail!("OpenAI stream failed: {}", data)
+                            }
+                            _ => {}
+                        }
+                        Ok(None)
+                    })
+                    .await;
+                    match streamed {
+                        Ok(text) => {
+                            let result = AiResponse::new(text.trim().to_string());
+                            return Ok(match usage {
+                                Some(usage) => {
+                                    result.with_usage(usage.input_tokens, usage.output_tokens)
+                                }
+                                None => result,
+                            });
+                        }
+                        Err(err) => {
+                            retry(self.retry, &mut retries, &err.to_string()).await?;
+                            continue;
+                        }
+                    }
+                }
+                (reqwest::StatusCode::OK, None) => {
+                    let response = response.json::<AiReponse>().await?;
+                    return self.extract_translation_result(response);
+                }
+                _ => {
+                    handle_error_response("OpenAI", response, self.retry, &mut retries).await?;
+                    continue;
+                }
+            }
+        }
+    }
+
+    fn pricing(&self) -> Option<Pricing> {
+        self.pricing
+    }
+
+    fn model(&self, _lang: &str) -> Option<String> {
+        Some(self.model.clone())
+    }
+}
+
+/// List prices of common models, in USD per million tokens. Snapshot names like
+/// `gpt-4o-2024-08-06` match their base model.
+fn model_pricing(model: &str) -> Option<Pricing> {
+    let (input, output) = match model {
+        m if m.starts_with("gpt-4o-mini") => (0.15, 0.6),
+        m if m.starts_with("gpt-4o") => (2.5, 10.0),
+        m if m.starts_with("gpt-4.1-nano") => (0.1, 0.4),
+        m if m.starts_with("gpt-4.1-mini") => (0.4, 1.6),
+        m if m.starts_with("gpt-4.1") => (2.0, 8.0),
+        m if m.starts_with("o4-mini") || m.starts_with("o3-mini") => (1.1, 4.4),
+        m if m.starts_with("gpt-3.5-turbo") => (0.5, 1.5),
+        _ => return None,
+    };
+
+    Some(Pricing { input, output })
+}
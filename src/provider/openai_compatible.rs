@@ -0,0 +1,234 @@
+use std::env;
+use std::sync::Arc;
+
+use anyhow::bail;
+use async_trait::async_trait;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use super::rate_limit::{RateLimiter, wait_for_slot};
+use super::stream::{STREAM_TIMEOUT, read_stream};
+use super::{
+    AiRequest, AiResponse, Pricing, ProviderConfig, RetryPolicy, Sampling, StreamPolicy,
+    TranslationProvider, handle_error_response, request_error, retry,
+};
+
+#[derive(Serialize)]
+struct ChatRequest<'a> {
+    model: &'a str,
+    messages: Vec<ChatMessage<'a>>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    stream: bool,
+    #[serde(flatten)]
+    sampling: Sampling,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    response_format: Option<serde_json::Value>,
+}
+
+#[derive(Serialize)]
+struct ChatMessage<'a> {
+    role: &'a str,
+    content: &'a str,
+}
+
+#[derive(Deserialize)]
+struct ChatResponse {
+    choices: Vec<Choice>,
+    usage: Option<ChatUsage>,
+}
+
+#[derive(Deserialize)]
+struct Choice {
+    message: ResponseMessage,
+}
+
+#[derive(Deserialize)]
+struct ResponseMessage {
+    #[serde(default)]
+    content: Option<String>,
+}
+
+/// A chunk of a streamed answer, with the next part of the text; servers that report the
+/// usage do it in the last one
+#[derive(Deserialize)]
+struct ChatChunk {
+    #[serde(default)]
+    choices: Vec<ChunkChoice>,
+    usage: Option<ChatUsage>,
+}
+
+#[derive(Deserialize)]
+struct ChunkChoice {
+    delta: ResponseMessage,
+}
+
+#[derive(Deserialize)]
+struct ChatUsage {
+    #[serde(default)]
+    prompt_tokens: u64,
+    #[serde(default)]
+    completion_tokens: u64,
+}
+
+/// Any server speaking OpenAI's chat completions format at `<base-url>/chat/completions`:
+/// OpenRouter, Groq, LM Studio, vLLM, the llama.cpp server... The API key is optional, as
+/// local servers don't need one, and the prices are unknown unless given.
+pub struct OpenAICompatible {
+    base_url: String,
+    api_key: Option<String>,
+    model: String,
+    pricing: Option<Pricing>,
+    retry: RetryPolicy,
+    limiter: Option<Arc<RateLimiter>>,
+    stream: Option<StreamPolicy>,
+    sampling: Sampling,
+    client: Client,
+}
+
+impl OpenAICompatible {
+    pub fn new(config: ProviderConfig, client: Client) -> anyhow::Result<Self> {
+        let Some(base_url) = config.base_url else {
+            bail!("--base-url must be set with --provider openai-compatible");
+        };
+        let Some(model) = config.model else {
+            bail!("--model must be set with --provider openai-compatible");
+        };
+
+        Ok(Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            api_key: config
+                .api_key
+                .or_else(|| env::var("OPENAI_COMPATIBLE_API_KEY").ok()),
+            model,
+            pricing: config.pricing,
+            retry: config.retry,
+            limiter: config.limiter,
+            stream: config.stream,
+            sampling: config.sampling,
+            client,
+        })
+    }
+
+    fn extract_translation_result(&self, response: ChatResponse) -> anyhow::Result<AiResponse> {
+        let Some(text) = response
+            .choices
+            .into_iter()
+            .next()
+            .and_then(|choice| choice.message.content)
+        else {
+            bail!("{} answered without a message", self.base_url);
+        };
+
+        let result = AiResponse::new(text.trim().to_string());
+        Ok(match response.usage {
+            Some(usage) => result.with_usage(usage.prompt_tokens, usage.completion_tokens),
+            None => result,
+        })
+    }
+}
+
+#[async_trait]
+impl TranslationProvider for OpenAICompatible {
+    async fn send(&self, body: &AiRequest) -> anyhow::Result<AiResponse> {
+        let mut retries = 0;
+        let stream = self.stream.filter(|stream| stream.applies(body));
+
+        let request = ChatRequest {
+            model: &self.model,
+            messages: vec![
+                ChatMessage {
+                    role: "system",
+                    content: &body.instructions,
+                },
+                ChatMessage {
+                    role: "user",
+                    content: &body.input,
+                },
+            ],
+            stream: stream.is_some(),
+            sampling: self.sampling,
+            response_format: body.format.schema().map(|schema| {
+                json!({
+                    "type": "json_schema",
+                    "json_schema": {
+                        "name": body.format.name(),
+                        "schema": schema,
+                        "strict": true,
+                    }
+                })
+            }),
+        };
+
+        loop {
+            wait_for_slot(self.limiter.as_deref(), body).await;
+            let mut builder = self
+                .client
+                .post(format!("{}/chat/completions", self.base_url))
+                .json(&request);
+            if stream.is_some() {
+                builder = builder.timeout(STREAM_TIMEOUT);
+            }
+            if let Some(api_key) = &self.api_key {
+                builder = builder.bearer_auth(api_key);
+            }
+
+            let response = match builder.send().await {
+                Ok(response) => response,
+                Err(err) => {
+                    retry(self.retry, &mut retries, &request_error(&err)).await?;
+                    continue;
+                }
+            };
+
+            match (response.status(), stream) {
+                (reqwest::StatusCode::OK, Some(stream)) => {
+                    let mut usage = None;
+                    let streamed = read_stream(response, stream, body, |data| {
+                        let chunk: ChatChunk = serde_json::from_str(data)?;
+                        usage = chunk.usage.or(usage.take());
+                        Ok(chunk
+                            .choices
+                            .into_iter()
+                            .next()
+                            .and_then(|choice| choice.delta.content))
+                    })
+                    .await;
+                    match streamed {
+                        Ok(text) => {
+                            return self.extract_translation_result(ChatResponse {
+                                choices: vec![Choice {
+                                    message: ResponseMessage {
+                                        content: Some(text),
+                                    },
+                                }],
+                                usage,
+                            });
+                        }
+                        Err(err) => {
+                            retry(self.retry, &mut retries, &err.to_string()).await?;
+                            continue;
+                        }
+                    }
+                }
+                (reqwest::StatusCode::OK, None) => {
+                    let response = response.json::<ChatResponse>().await?;
+                    return self.extract_translation_result(response);
+                }
+                _ => {
+                    handle_error_response(&self.base_url, response, self.retry, &mut retries)
+                        .await?;
+                    continue;
+                }
+            }
+        }
+    }
+
+    fn pricing(&self) -> Option<Pricing> {
+        self.pricing
+    }
+
+    fn model(&self, _lang: &str) -> Option<String> {
+        Some(self.model.clone())
+    }
+}
@@ -0,0 +1,179 @@
+use anyhow::{Result, bail};
+use async_trait::async_trait;
+use clap::ValueEnum;
+
+use super::{AiRequest, AiResponse, Pricing, ProviderConfig, ProviderKind, TranslationProvider};
+
+/// The provider settings of one language, from `--lang-override ja:provider=openai,model=gpt-4o`
+/// or a `[lang.ja]` table of the config file
+pub struct LangOverride {
+    pub lang: String,
+    provider: Option<ProviderKind>,
+    model: Option<String>,
+    api_key: Option<String>,
+    base_url: Option<String>,
+    ollama_url: Option<String>,
+    formality: Option<String>,
+    input_price: Option<f64>,
+    output_price: Option<f64>,
+}
+
+impl LangOverride {
+    /// Parses `<lang>:<key>=<value>,...`, with the keys `provider`, `model`, `api_key`,
+    /// `base_url`, `ollama_url`, `formality`, `input_price` and `output_price`
+    pub fn parse(spec: &str) -> Result<Self> {
+        let Some((lang, settings)) = spec.split_once(':') else {
+            bail!(
+                "invalid --lang-override `{}`: expected <lang>:<key>=<value>,...",
+                spec
+            );
+        };
+        let lang = lang.trim();
+        if lang.is_empty() {
+            bail!("invalid --lang-override `{}`: missing the language", spec);
+        }
+
+        let mut parsed = Self {
+            lang: lang.to_string(),
+            provider: None,
+            model: None,
+            api_key: None,
+            base_url: None,
+            ollama_url: None,
+            formality: None,
+            input_price: None,
+            output_price: None,
+        };
+        for setting in settings.split(',').filter(|s| !s.trim().is_empty()) {
+            let Some((key, value)) = setting.split_once('=') else {
+                bail!(
+                    "invalid setting `{}` for {} in --lang-override: expected <key>=<value>",
+                    setting,
+                    lang
+                );
+            };
+            let value = value.trim().to_string();
+            let price = |value: &str| -> Result<f64> {
+                value.parse().map_err(|_| {
+                    anyhow::anyhow!("invalid {} for {}: `{}` is not a number", key, lang, value)
+                })
+            };
+            match key.trim().replace('-', "_").as_str() {
+                "provider" => {
+                    parsed.provider = Some(
+                        ProviderKind::from_str(&value, true)
+                            .map_err(|e| anyhow::anyhow!("invalid provider for {}: {}", lang, e))?,
+                    )
+                }
+                "model" => parsed.model = Some(value),
+                "api_key" => parsed.api_key = Some(value),
+                "base_url" => parsed.base_url = Some(value),
+                "ollama_url" => parsed.ollama_url = Some(value),
+                "formality" => parsed.formality = Some(value),
+                "input_price" => parsed.input_price = Some(price(&value)?),
+                "output_price" => parsed.output_price = Some(price(&value)?),
+                key => bail!(
+                    "unknown setting `{}` for {} in --lang-override: expected provider, model, api_key, base_url, ollama_url, formality, input_price or output_price",
+                    key,
+                    lang
+                ),
+            }
+        }
+        if parsed.input_price.is_some() != parsed.output_price.is_some() {
+            bail!(
+                "--lang-override for {} needs both input_price and output_price",
+                lang
+            );
+        }
+        Ok(parsed)
+    }
+
+    /// The settings of the language: those of the command line, with the ones given for it
+    /// replaced. A different provider doesn't keep the model, key, URL and prices of the
+    /// command line's, nor its headers, only its limits, retries and timeouts.
+    pub fn apply(&self, mut config: ProviderConfig) -> Result<ProviderConfig> {
+        if let Some(provider) = self.provider
+            && provider != config.kind
+        {
+            config.kind = provider;
+            config.model = None;
+            config.api_key = None;
+            config.base_url = None;
+            config.pricing = None;
+            config.headers.clear();
+        }
+        config.model = self.model.clone().or(config.model);
+        config.api_key = self.api_key.clone().or(config.api_key);
+        config.base_url = self.base_url.clone().or(config.base_url);
+        config.ollama_url = self.ollama_url.clone().or(config.ollama_url);
+        config.formality = self.formality.clone().or(config.formality);
+        if let Some((input, output)) = self.input_price.zip(self.output_price) {
+            config.pricing = Some(Pricing { input, output });
+        }
+
+        if matches!(config.kind, ProviderKind::OpenaiCompatible)
+            && (config.model.is_none() || config.base_url.is_none())
+        {
+            bail!(
+                "--lang-override for {}: the openai-compatible provider needs a model and a base_url",
+                self.lang
+            );
+        }
+        Ok(config)
+    }
+}
+
+/// Sends the requests of some languages to their own provider or model, and the others to
+/// the default one
+pub struct PerLang {
+    default: Box<dyn TranslationProvider>,
+    langs: Vec<(String, Box<dyn TranslationProvider>)>,
+}
+
+impl PerLang {
+    pub fn new(
+        default: Box<dyn TranslationProvider>,
+        langs: Vec<(String, Box<dyn TranslationProvider>)>,
+    ) -> Self {
+        Self { default, langs }
+    }
+
+    /// The provider of `lang`: its own, or that of its base language (`ja` for `ja_JP`), else
+    /// the default one
+    fn provider(&self, lang: &str) -> &dyn TranslationProvider {
+        let base = lang.split(['_', '-', '@']).next().unwrap_or(lang);
+        let find = |lang: &str| {
+            self.langs
+                .iter()
+                .rev()
+                .find(|(code, _)| code.eq_ignore_ascii_case(lang))
+        };
+        find(lang)
+            .or_else(|| find(base))
+            .map_or(self.default.as_ref(), |(_, provider)| provider.as_ref())
+    }
+}
+
+#[async_trait]
+impl TranslationProvider for PerLang {
+    async fn send(&self, body: &AiRequest) -> anyhow::Result<AiResponse> {
+        self.provider(&body.lang).send(body).await
+    }
+
+    /// The prices of the default model, when those of every model are known
+    fn pricing(&self) -> Option<Pricing> {
+        self.langs
+            .iter()
+            .all(|(_, provider)| provider.pricing().is_some())
+            .then(|| self.default.pricing())
+            .flatten()
+    }
+
+    fn request_pricing(&self, body: &AiRequest) -> Option<Pricing> {
+        self.provider(&body.lang).request_pricing(body)
+    }
+
+    fn model(&self, lang: &str) -> Option<String> {
+        self.provider(lang).model(lang)
+    }
+}
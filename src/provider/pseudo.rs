@@ -0,0 +1,136 @@
+use anyhow::bail;
+use async_trait::async_trait;
+use regex::Regex;
+use std::sync::LazyLock;
+
+use super::{AiRequest, AiResponse, Pricing, TranslationProvider};
+use crate::placeholders;
+
+/// Matches HTML tags and entities, which are kept as written like placeholders
+static MARKUP_REGEX: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"<[^>]+>|&#?\w+;").unwrap());
+
+/// How much longer a pseudo-translation is than its source, as many languages run about
+/// 30% longer than English
+const EXPANSION: f64 = 0.3;
+
+/// Pseudo-localization, without any API call: every message comes back with accented
+/// letters, padded by about 30% and framed by `[!!! ` and ` !!!]`, e.g. "Hello World" becomes
+/// `[!!! Ĥéļļö Ŵöŕļð ~~~~ !!!]`. Placeholders and markup are kept. Like DeepL, it answers
+/// the request's raw texts, with a JSON array when there are several.
+pub struct Pseudo;
+
+#[async_trait]
+impl TranslationProvider for Pseudo {
+    async fn send(&self, body: &AiRequest) -> anyhow::Result<AiResponse> {
+        if body.texts.is_empty() {
+            bail!("The pseudo provider needs the raw messages of the request, but none were given");
+        }
+
+        let mut texts: Vec<String> = body.texts.iter().map(|text| pseudolocalize(text)).collect();
+        let text = match texts.len() {
+            1 => texts.remove(0),
+            _ => serde_json::to_string(&texts)?,
+        };
+
+        Ok(AiResponse::new(text).with_usage(0, 0))
+    }
+
+    fn pricing(&self) -> Option<Pricing> {
+        Some(Pricing {
+            input: 0.0,
+            output: 0.0,
+        })
+    }
+
+    fn model(&self, _lang: &str) -> Option<String> {
+        Some("pseudo".to_string())
+    }
+}
+
+/// Accents the letters of `text` outside of its placeholders and markup, then pads and
+/// frames it
+pub fn pseudolocalize(text: &str) -> String {
+    let mut kept: Vec<(usize, usize)> = placeholders::extract(text)
+        .iter()
+        .flat_map(|placeholder| text.match_indices(placeholder.as_str()))
+        .map(|(start, placeholder)| (start, start + placeholder.len()))
+        .chain(MARKUP_REGEX.find_iter(text).map(|m| (m.start(), m.end())))
+        .collect();
+    kept.sort();
+
+    let mut accented = String::with_capacity(text.len() * 2);
+    let mut position = 0;
+    for (start, end) in kept {
+        if start < position {
+            continue;
+        }
+        accented.extend(text[position..start].chars().map(accent));
+        accented.push_str(&text[start..end]);
+        position = end;
+    }
+    accented.extend(text[position..].chars().map(accent));
+
+    let padding = (text.chars().count() as f64 * EXPANSION).ceil() as usize;
+    match padding {
+        0 => format!("[!!! {} !!!]", accented),
+        _ => format!("[!!! {} {} !!!]", accented, "~".repeat(padding)),
+    }
+}
+
+fn accent(c: char) -> char {
+    match c {
+        'a' => 'á',
+        'b' => 'ƀ',
+        'c' => 'ç',
+        'd' => 'ð',
+        'e' => 'é',
+        'f' => 'ƒ',
+        'g' => 'ĝ',
+        'h' => 'ĥ',
+        'i' => 'î',
+        'j' => 'ĵ',
+        'k' => 'ķ',
+        'l' => 'ļ',
+        'm' => 'ɱ',
+        'n' => 'ñ',
+        'o' => 'ö',
+        'p' => 'þ',
+        'q' => 'ǫ',
+        'r' => 'ŕ',
+        's' => 'š',
+        't' => 'ţ',
+        'u' => 'û',
+        'v' => 'ṽ',
+        'w' => 'ŵ',
+        'x' => 'ẋ',
+        'y' => 'ý',
+        'z' => 'ž',
+        'A' => 'Å',
+        'B' => 'Ɓ',
+        'C' => 'Ç',
+        'D' => 'Ð',
+        'E' => 'É',
+        'F' => 'Ƒ',
+        'G' => 'Ĝ',
+        'H' => 'Ĥ',
+        'I' => 'Î',
+        'J' => 'Ĵ',
+        'K' => 'Ķ',
+        'L' => 'Ļ',
+        'M' => 'Ṁ',
+        'N' => 'Ñ',
+        'O' => 'Ö',
+        'P' => 'Þ',
+        'Q' => 'Ǫ',
+        'R' => 'Ŕ',
+        'S' => 'Š',
+        'T' => 'Ţ',
+        'U' => 'Û',
+        'V' => 'Ṽ',
+        'W' => 'Ŵ',
+        'X' => 'Ẋ',
+        'Y' => 'Ý',
+        'Z' => 'Ž',
+        _ => c,
+    }
+}
@@ -0,0 +1,174 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use tokio::sync::Semaphore;
+use tokio::time::sleep;
+
+use super::{AiRequest, AiResponse, Pricing, TranslationProvider};
+
+/// Spaces requests evenly so that a run stays under requests-per-minute and
+/// tokens-per-minute budgets, instead of bursting and relying on 429 retries
+pub struct RateLimiter {
+    rpm: Option<u32>,
+    tpm: Option<u32>,
+    next_slot: Mutex<Instant>,
+}
+
+impl RateLimiter {
+    pub fn new(rpm: Option<u32>, tpm: Option<u32>) -> Self {
+        Self {
+            rpm: rpm.filter(|rpm| *rpm > 0),
+            tpm: tpm.filter(|tpm| *tpm > 0),
+            next_slot: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// Waits until a request of `tokens` estimated tokens fits in the budget
+    pub async fn acquire(&self, tokens: u32) {
+        let per_request = self
+            .rpm
+            .map(|rpm| Duration::from_secs_f64(60.0 / rpm as f64))
+            .unwrap_or_default();
+        let per_tokens = self
+            .tpm
+            .map(|tpm| Duration::from_secs_f64(60.0 * tokens as f64 / tpm as f64))
+            .unwrap_or_default();
+
+        let wait = {
+            let mut next_slot = self.next_slot.lock().unwrap();
+            let now = Instant::now();
+            let start = (*next_slot).max(now);
+            *next_slot = start + per_request.max(per_tokens);
+            start - now
+        };
+
+        if !wait.is_zero() {
+            sleep(wait).await;
+        }
+    }
+}
+
+/// Rough token estimate (~4 characters per token) for the prompt plus an answer of similar
+/// length to the input
+pub fn estimate_tokens(request: &AiRequest) -> u32 {
+    let prompt = request.instructions.chars().count() + request.input.chars().count();
+    let answer = request.input.chars().count();
+    ((prompt + answer) / 4 + 1) as u32
+}
+
+/// Waits until an attempt of `request` fits in the budget of `limiter`, if there is one.
+/// Providers call it before every attempt, so that retries count against the budget too.
+pub async fn wait_for_slot(limiter: Option<&RateLimiter>, request: &AiRequest) {
+    if let Some(limiter) = limiter {
+        limiter.acquire(estimate_tokens(request)).await;
+    }
+}
+
+/// A provider with at most `permits` requests in flight at once, however many catalogs and
+/// tasks share it
+pub struct Bounded {
+    inner: Arc<dyn TranslationProvider>,
+    permits: Semaphore,
+}
+
+impl Bounded {
+    pub fn new(inner: Arc<dyn TranslationProvider>, permits: usize) -> Self {
+        Self {
+            inner,
+            permits: Semaphore::new(permits.max(1)),
+        }
+    }
+}
+
+#[async_trait]
+impl TranslationProvider for Bounded {
+    async fn send(&self, body: &AiRequest) -> anyhow::Result<AiResponse> {
+        let _permit = self.permits.acquire().await?;
+        self.inner.send(body).await
+    }
+
+    fn pricing(&self) -> Option<Pricing> {
+        self.inner.pricing()
+    }
+
+    fn request_pricing(&self, body: &AiRequest) -> Option<Pricing> {
+        self.inner.request_pricing(body)
+    }
+
+    fn model(&self, lang: &str) -> Option<String> {
+        self.inner.model(lang)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[tokio::test]
+    async fn spaces_requests_by_the_rpm() {
+        let limiter = RateLimiter::new(Some(1200), None);
+        let start = Instant::now();
+        limiter.acquire(1000).await;
+        assert!(start.elapsed() < Duration::from_millis(20));
+        limiter.acquire(1000).await;
+        limiter.acquire(1000).await;
+        assert!(start.elapsed() >= Duration::from_millis(100));
+    }
+
+    #[tokio::test]
+    async fn spaces_requests_by_their_tokens() {
+        let limiter = RateLimiter::new(None, Some(60_000));
+        let start = Instant::now();
+        limiter.acquire(60).await;
+        limiter.acquire(1).await;
+        assert!(start.elapsed() >= Duration::from_millis(60));
+    }
+
+    #[tokio::test]
+    async fn never_waits_without_a_budget() {
+        let limiter = RateLimiter::new(Some(0), None);
+        let start = Instant::now();
+        for _ in 0..100 {
+            limiter.acquire(u32::MAX).await;
+        }
+        assert!(start.elapsed() < Duration::from_millis(20));
+    }
+
+    #[test]
+    fn estimates_the_prompt_and_answer() {
+        let request = AiRequest::new("a".repeat(40), "b".repeat(20));
+        assert_eq!(estimate_tokens(&request), 21);
+    }
+
+    /// A provider counting the requests it is sent at once
+    #[derive(Default)]
+    struct Slow {
+        in_flight: AtomicUsize,
+        most: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl TranslationProvider for Slow {
+        async fn send(&self, _body: &AiRequest) -> anyhow::Result<AiResponse> {
+            let in_flight = self.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+            self.most.fetch_max(in_flight, Ordering::SeqCst);
+            sleep(Duration::from_millis(10)).await;
+            self.in_flight.fetch_sub(1, Ordering::SeqCst);
+            Ok(AiResponse::new(String::new()))
+        }
+    }
+
+    #[tokio::test]
+    async fn bounds_the_requests_in_flight() {
+        let slow = Arc::new(Slow::default());
+        let bounded = Bounded::new(slow.clone(), 2);
+        let request = AiRequest::new(String::new(), String::new());
+        let requests = (0..6).map(|_| bounded.send(&request));
+        for result in futures_util::future::join_all(requests).await {
+            assert!(result.is_ok());
+        }
+        assert_eq!(slow.most.load(Ordering::SeqCst), 2);
+    }
+}
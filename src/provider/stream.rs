@@ -0,0 +1,106 @@
+use std::time::{Duration, Instant};
+
+use anyhow::bail;
+use reqwest::Response;
+use tokio::time::timeout;
+
+use super::{AiRequest, request_error};
+use crate::logger::log_stream;
+
+/// Longest a streamed answer may take in total. The client's timeout would cut long answers
+/// short, so streams are watched for stalls instead.
+pub const STREAM_TIMEOUT: Duration = Duration::from_secs(3600);
+
+/// Progress of a stream is logged at most this often
+const LOG_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Which requests get their answer streamed, as it is written, instead of all at once
+#[derive(Clone, Copy, Debug)]
+pub struct StreamPolicy {
+    /// Requests whose messages add up to at least this many characters are streamed
+    pub min_length: usize,
+    /// A stream that sends nothing for this long is given up, and the request retried
+    pub stall_timeout: Duration,
+}
+
+impl StreamPolicy {
+    pub fn applies(&self, request: &AiRequest) -> bool {
+        let length = match request.texts.is_empty() {
+            true => request.input.chars().count(),
+            false => request.texts.iter().map(|text| text.chars().count()).sum(),
+        };
+        length >= self.min_length
+    }
+}
+
+/// Reads a streamed answer to `request`: the `data:` lines of server-sent events, or the lines
+/// of NDJSON, each given to `on_event`, which returns the text it adds to the answer. The text
+/// received so far is logged with `--verbose`. Fails if the stream stalls.
+pub async fn read_stream<F>(
+    mut response: Response,
+    policy: StreamPolicy,
+    request: &AiRequest,
+    mut on_event: F,
+) -> anyhow::Result<String>
+where
+    F: FnMut(&str) -> anyhow::Result<Option<String>>,
+{
+    let message = request.texts.first().map_or("", String::as_str);
+    let mut text = String::new();
+    let mut buffer: Vec<u8> = Vec::new();
+    let mut logged = Instant::now();
+
+    loop {
+        let chunk = match timeout(policy.stall_timeout, response.chunk()).await {
+            Ok(chunk) => chunk.map_err(|e| anyhow::anyhow!(request_error(&e)))?,
+            Err(_) => bail!(
+                "stream stalled: nothing received for {:?}, after {} characters",
+                policy.stall_timeout,
+                text.chars().count()
+            ),
+        };
+        let Some(chunk) = chunk else {
+            break;
+        };
+        buffer.extend_from_slice(&chunk);
+
+        while let Some(end) = buffer.iter().position(|byte| *byte == b'\n') {
+            let line: Vec<u8> = buffer.drain(..=end).collect();
+            if let Some(delta) = event_data(&line).map(&mut on_event).transpose()?.flatten() {
+                text.push_str(&delta);
+            }
+        }
+        if logged.elapsed() >= LOG_INTERVAL {
+            log_stream(message, &text);
+            logged = Instant::now();
+        }
+    }
+
+    if let Some(delta) = event_data(&buffer)
+        .map(&mut on_event)
+        .transpose()?
+        .flatten()
+    {
+        text.push_str(&delta);
+    }
+    Ok(text)
+}
+
+/// The data of a line of a stream, skipping the names, ids and comments of server-sent events
+fn event_data(line: &[u8]) -> Option<&str> {
+    let line = std::str::from_utf8(line).ok()?.trim();
+    if line.is_empty() || line.starts_with(':') {
+        return None;
+    }
+    if let Some((field, _)) = line.split_once(':')
+        && ["event", "id", "retry"].contains(&field)
+    {
+        return None;
+    }
+
+    let data = line.strip_prefix("data:").map_or(line, str::trim_start);
+    match data {
+        "[DONE]" => None,
+        data => Some(data),
+    }
+}
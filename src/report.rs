@@ -0,0 +1,146 @@
+//! `--report markdown`: the summary of a run as GitHub-flavored markdown, to post as a pull
+//! request comment or append to `$GITHUB_STEP_SUMMARY` from a workflow.
+
+use anyhow::Result;
+use clap::ValueEnum;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::Path;
+
+use crate::stats::RunStats;
+
+/// Failed entries listed in the report, the others only counted
+const MAX_FAILURES: usize = 50;
+
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+pub enum ReportFormat {
+    Markdown,
+}
+
+/// Prints the report of a run over the folder `root`, or appends it to `file`
+pub fn write(
+    format: ReportFormat,
+    file: Option<&Path>,
+    stats: &RunStats,
+    root: &Path,
+    dry_run: bool,
+) -> Result<()> {
+    let report = match format {
+        ReportFormat::Markdown => markdown(stats, root, dry_run),
+    };
+    match file {
+        Some(path) => {
+            let mut file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .map_err(|e| anyhow::anyhow!("Error writing {}: {}", path.display(), e))?;
+            writeln!(file, "{}", report)?;
+        }
+        None => println!("{}", report),
+    }
+    Ok(())
+}
+
+/// A table of the languages, with what each cost, one of the catalogs, and the failed entries
+pub fn markdown(stats: &RunStats, root: &Path, dry_run: bool) -> String {
+    let mut out = String::new();
+    let title = match dry_run {
+        true => "Translation summary (dry run)",
+        false => "Translation summary",
+    };
+    out.push_str(&format!("### 🌍 {}\n\n", title));
+
+    let cost = |cost: Option<f64>| cost.map_or("—".to_string(), |cost| format!("${:.4}", cost));
+    out.push_str("| Language | Files | Translated | From cache | Failed | Cost |\n");
+    out.push_str("| --- | ---: | ---: | ---: | ---: | ---: |\n");
+    for (lang, lang_stats) in &stats.langs {
+        out.push_str(&format!(
+            "| {} | {} | {} | {} | {} | {} |\n",
+            cell(lang),
+            lang_stats.files,
+            lang_stats.translated,
+            lang_stats.cached,
+            lang_stats.failed,
+            cost(stats.lang_cost(lang))
+        ));
+    }
+    let total = stats.total();
+    out.push_str(&format!(
+        "| **Total** | **{}** | **{}** | **{}** | **{}** | **{}** |\n",
+        total.files,
+        total.translated,
+        total.cached,
+        total.failed,
+        cost(stats.cost())
+    ));
+
+    let files: Vec<_> = stats
+        .files
+        .iter()
+        .filter(|file| file.translated > 0 || file.failed > 0)
+        .collect();
+    if !files.is_empty() {
+        out.push_str("\n| Language | File | Translated | Failed |\n");
+        out.push_str("| --- | --- | ---: | ---: |\n");
+        for file in files {
+            let path = file.path.strip_prefix(root).unwrap_or(&file.path);
+            out.push_str(&format!(
+                "| {} | `{}` | {} | {} |\n",
+                cell(&file.lang),
+                cell(&path.display().to_string()),
+                file.translated,
+                file.failed
+            ));
+        }
+    }
+
+    if !stats.failures.is_empty() {
+        out.push_str(&format!(
+            "\n<details><summary>❌ {} entries failed, left untranslated</summary>\n\n",
+            stats.failures.len()
+        ));
+        out.push_str("| Language | Entry | Error |\n");
+        out.push_str("| --- | --- | --- |\n");
+        for failure in stats.failures.iter().take(MAX_FAILURES) {
+            let location = match &failure.file {
+                Some(file) => {
+                    let path = file.strip_prefix(root).unwrap_or(file);
+                    format!("`{}:{}` ", cell(&path.display().to_string()), failure.line)
+                }
+                None => String::new(),
+            };
+            out.push_str(&format!(
+                "| {} | {}{} | {} |\n",
+                cell(&failure.lang),
+                location,
+                cell(&failure.msgid),
+                cell(&failure.error)
+            ));
+        }
+        if stats.failures.len() > MAX_FAILURES {
+            out.push_str(&format!(
+                "\n…and {} more.\n",
+                stats.failures.len() - MAX_FAILURES
+            ));
+        }
+        out.push_str("\n</details>\n");
+    }
+
+    out.push_str(&format!(
+        "\n{} API calls, {} input / {} output tokens in {:.1}s\n",
+        stats.api_calls(),
+        stats.tokens().input_tokens,
+        stats.tokens().output_tokens,
+        stats.elapsed().as_secs_f64()
+    ));
+    out
+}
+
+/// `text` as the content of a table cell, on one line and with its pipes escaped
+fn cell(text: &str) -> String {
+    text.replace('|', "\\|")
+        .replace(['\n', '\r'], " ")
+        .trim()
+        .to_string()
+}
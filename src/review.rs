@@ -0,0 +1,66 @@
+use anyhow::Result;
+use colored::*;
+use std::io::{self, BufRead, Write};
+
+/// What the reviewer decided for a proposed translation
+pub enum Review {
+    Accept,
+    /// The reviewer typed their own msgstrs
+    Edit(Vec<String>),
+    Skip,
+    /// Ask the model again, with a hint from the reviewer
+    Retry(String),
+}
+
+/// Shows a proposed translation and asks the reviewer what to do with it
+pub fn ask(lang: &str, msgid: &str, msgstrs: &[String]) -> Result<Review> {
+    println!();
+    println!("{} [{}] {}", "📝".cyan(), lang.to_uppercase().blue(), msgid);
+    for (index, msgstr) in msgstrs.iter().enumerate() {
+        match msgstrs.len() {
+            1 => println!("   ➜ {}", msgstr.green()),
+            _ => println!("   ➜ [{}] {}", index, msgstr.green()),
+        }
+    }
+
+    loop {
+        let answer = read_line("   [Enter] accept, (e)dit, (s)kip, (r)etry with a hint: ")?;
+
+        match answer.trim() {
+            "" => return Ok(Review::Accept),
+            "s" => return Ok(Review::Skip),
+            "e" => {
+                let mut edited = Vec::with_capacity(msgstrs.len());
+                for index in 0..msgstrs.len() {
+                    let label = match msgstrs.len() {
+                        1 => "   msgstr: ".to_string(),
+                        _ => format!("   msgstr[{}]: ", index),
+                    };
+                    edited.push(read_line(&label)?);
+                }
+                return Ok(Review::Edit(edited));
+            }
+            "r" => return Ok(Review::Retry(read_line("   hint: ")?)),
+            _ => println!("   {}", "Unknown choice".yellow()),
+        }
+    }
+}
+
+/// Asks a yes or no question, no being the answer to an empty line
+pub fn confirm(question: &str) -> Result<bool> {
+    println!();
+    let answer = read_line(&format!("{} {} [y/N] ", "⚠️".yellow(), question))?;
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+
+fn read_line(prompt: &str) -> Result<String> {
+    print!("{}", prompt);
+    io::stdout().flush()?;
+
+    let mut line = String::new();
+    if io::stdin().lock().read_line(&mut line)? == 0 {
+        anyhow::bail!("Standard input closed during the interactive review");
+    }
+
+    Ok(line.trim_end_matches(['\n', '\r']).to_string())
+}
@@ -0,0 +1,146 @@
+//! Cleaning of free-text answers, for the models that wrap the translation in more than asked
+//! for: quotes, a markdown code fence, a "Here's the translation:" label or a note after it.
+//! Whatever the message itself has, like quotes around it, is kept. Also the checks that an
+//! answer can be a translation at all.
+
+use regex::Regex;
+use std::sync::LazyLock;
+
+use crate::placeholders;
+
+/// A label before the translation, like "Translation:" or "Sure! Here's the translation into
+/// Spanish:"
+static LABEL: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"(?i)^(?:(?:sure|okay|ok|certainly|of course)[!,.]*\s*)?(?:here(?:'s| is) (?:the |your |my )?(?:\w+ )?translation(?: (?:in|to|into) [\w ]+?)?|(?:\w+ )?translation(?: \([\w ]+\))?)\s*:\s*",
+    )
+    .unwrap()
+});
+
+/// The start of a paragraph that explains the translation instead of being part of it
+static EXPLANATION: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"(?i)^(?:\(|\*+)?(?:notes?|explanation|translator'?s? notes?|this (?:translation|is)|i (?:have |'ve )?(?:translated|kept|used|left)|the (?:placeholders?|translation)|---)\b",
+    )
+    .unwrap()
+});
+
+static PARAGRAPH_BREAK: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\n[ \t]*\n").unwrap());
+
+/// The pairs of quotes models put around their answers
+const QUOTES: &[(char, char)] = &[
+    ('"', '"'),
+    ('\'', '\''),
+    ('“', '”'),
+    ('‘', '’'),
+    ('«', '»'),
+    ('„', '“'),
+    ('「', '」'),
+];
+
+/// Removes what the model put around the translation of `source`: a code fence, a label
+/// before it, an explanation after it and the quotes around it
+pub fn clean(source: &str, answer: &str) -> String {
+    let source = source.trim();
+    let mut answer = answer.trim();
+
+    if answer.starts_with("```") && !source.starts_with("```") {
+        answer = answer.split_once('\n').map_or("", |(_, rest)| rest);
+        answer = answer
+            .trim_end()
+            .strip_suffix("```")
+            .unwrap_or(answer)
+            .trim();
+    }
+
+    if !LABEL.is_match(source)
+        && let Some(label) = LABEL.find(answer)
+    {
+        answer = answer[label.end()..].trim();
+    }
+
+    let paragraphs = PARAGRAPH_BREAK.split(source).count();
+    let mut breaks = PARAGRAPH_BREAK.find_iter(answer).skip(paragraphs - 1);
+    if let Some(explanation) = breaks.find(|line_break| {
+        EXPLANATION.is_match(&answer[line_break.end()..]) && !EXPLANATION.is_match(source)
+    }) {
+        answer = answer[..explanation.start()].trim();
+    }
+
+    for (open, close) in QUOTES {
+        let quoted = |text: &str| {
+            text.chars().count() >= 2 && text.starts_with(*open) && text.ends_with(*close)
+        };
+        if quoted(answer) && !quoted(source) {
+            answer = answer[open.len_utf8()..answer.len() - close.len_utf8()].trim();
+            break;
+        }
+    }
+
+    answer.to_string()
+}
+
+/// Returns why `answer` can't be a translation of `source`, if it can't: it is empty, or it
+/// has paragraphs where the message is a single line
+pub fn rejection(source: &str, answer: &str) -> Option<&'static str> {
+    if answer.trim().is_empty() && !source.trim().is_empty() {
+        return Some("answer is empty");
+    }
+    if !source.trim().contains('\n') && PARAGRAPH_BREAK.is_match(answer.trim()) {
+        return Some("answer has several paragraphs, the message a single line");
+    }
+    None
+}
+
+/// The script of the languages that aren't written in Latin letters
+static SCRIPTS: LazyLock<Vec<(&[&str], Regex)>> = LazyLock::new(|| {
+    let scripts: [(&[&str], &str); 21] = [
+        (&["ja"], r"[\p{Hiragana}\p{Katakana}\p{Han}]"),
+        (&["zh"], r"\p{Han}"),
+        (&["ko"], r"\p{Hangul}"),
+        (
+            &["ru", "uk", "bg", "be", "mk", "kk", "ky", "mn", "tg"],
+            r"\p{Cyrillic}",
+        ),
+        (&["el"], r"\p{Greek}"),
+        (&["ar", "fa", "ur", "ps"], r"\p{Arabic}"),
+        (&["he", "yi"], r"\p{Hebrew}"),
+        (&["hi", "mr", "ne"], r"\p{Devanagari}"),
+        (&["bn", "as"], r"\p{Bengali}"),
+        (&["th"], r"\p{Thai}"),
+        (&["ta"], r"\p{Tamil}"),
+        (&["te"], r"\p{Telugu}"),
+        (&["kn"], r"\p{Kannada}"),
+        (&["ml"], r"\p{Malayalam}"),
+        (&["gu"], r"\p{Gujarati}"),
+        (&["pa"], r"\p{Gurmukhi}"),
+        (&["ka"], r"\p{Georgian}"),
+        (&["hy"], r"\p{Armenian}"),
+        (&["am"], r"\p{Ethiopic}"),
+        (&["km"], r"\p{Khmer}"),
+        (&["my"], r"\p{Myanmar}"),
+    ];
+    scripts
+        .into_iter()
+        .map(|(langs, script)| (langs, Regex::new(script).unwrap()))
+        .collect()
+});
+
+/// A word in Latin letters
+static LATIN_WORD: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\b[A-Za-z]{2,}\b").unwrap());
+
+/// Markup and links, which are in Latin letters whatever the language
+static MARKUP: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"<[^>]*>|https?://\S+").unwrap());
+
+/// Returns true if `answer` can't be in `lang`: the language has a script of its own, but the
+/// answer is a few words in Latin letters without a single character of it. Single words,
+/// like brand names, are often kept as they are.
+pub fn wrong_language(lang: &str, answer: &str) -> bool {
+    let base = lang.split(['_', '-', '@']).next().unwrap_or(lang);
+    let Some((_, script)) = SCRIPTS.iter().find(|(langs, _)| langs.contains(&base)) else {
+        return false;
+    };
+    let text = placeholders::strip(answer);
+    let text = MARKUP.replace_all(&text, " ");
+    LATIN_WORD.find_iter(&text).count() >= 3 && !script.is_match(&text)
+}
@@ -0,0 +1,644 @@
+//! Finding of the gettext calls of source files, for `inline` and `extract`. A small tokenizer
+//! per family of languages skips comments and reads the string literals of each: escapes,
+//! strings spanning lines, triple-quoted heredocs and, in Elixir, `~s` and `~S` sigils and
+//! calls without parentheses.
+
+use anyhow::{Result, bail};
+use std::ops::Range;
+use std::path::Path;
+
+use crate::po;
+
+/// The string literals and comments of a family of languages
+#[derive(Clone, Copy)]
+struct Syntax {
+    single_quotes: bool,
+    backticks: bool,
+    triple_quotes: bool,
+    /// Elixir's `~s(...)` and `~S(...)`, and heredocs whose indentation is removed
+    sigils: bool,
+    hash_comments: bool,
+    slash_comments: bool,
+    /// Calls like `gettext "Hello"`, whose arguments end with the line
+    paren_less: bool,
+}
+
+impl Syntax {
+    fn for_path(path: &Path) -> Self {
+        let extension = path.extension().and_then(|ext| ext.to_str()).unwrap_or("");
+        let c_like = Self {
+            single_quotes: true,
+            backticks: false,
+            triple_quotes: false,
+            sigils: false,
+            hash_comments: false,
+            slash_comments: true,
+            paren_less: false,
+        };
+        match extension {
+            "ex" | "exs" => Self {
+                single_quotes: false,
+                triple_quotes: true,
+                sigils: true,
+                hash_comments: true,
+                slash_comments: false,
+                paren_less: true,
+                ..c_like
+            },
+            // Templates, whose text has apostrophes and `#` outside of any string
+            "eex" | "heex" | "leex" => Self {
+                single_quotes: false,
+                triple_quotes: true,
+                sigils: true,
+                slash_comments: false,
+                paren_less: true,
+                ..c_like
+            },
+            "py" | "pyw" => Self {
+                triple_quotes: true,
+                hash_comments: true,
+                slash_comments: false,
+                ..c_like
+            },
+            "rb" | "sh" | "pl" => Self {
+                hash_comments: true,
+                slash_comments: false,
+                ..c_like
+            },
+            "js" | "jsx" | "mjs" | "cjs" | "ts" | "tsx" | "vue" | "svelte" => Self {
+                backticks: true,
+                ..c_like
+            },
+            "php" => Self {
+                hash_comments: true,
+                ..c_like
+            },
+            _ => c_like,
+        }
+    }
+}
+
+/// How the content of a string literal is written
+#[derive(Clone, Copy, PartialEq)]
+enum LiteralKind {
+    /// Between quotes, with backslash escapes
+    Quoted,
+    /// Without escapes, like `~S(...)`
+    Raw,
+    /// Between triple quotes; Elixir removes the indentation of the closing quotes from its
+    /// lines
+    Heredoc { escapes: bool, dedent: bool },
+}
+
+/// A string literal of a source file
+#[derive(Clone)]
+pub struct Literal {
+    /// The content between the delimiters, as written in the file
+    pub range: Range<usize>,
+    kind: LiteralKind,
+    /// The end of the literal, after its closing delimiter and modifiers
+    end: usize,
+    /// False for literals that aren't plain strings, like regex sigils or template literals
+    /// with interpolations
+    is_text: bool,
+}
+
+impl Literal {
+    /// The content of the literal as written in the file
+    pub fn raw<'a>(&self, content: &'a str) -> &'a str {
+        &content[self.range.clone()]
+    }
+
+    /// The range of the content without the whitespace around it, like the lines of a heredoc
+    pub fn trimmed(&self, content: &str) -> Range<usize> {
+        let raw = self.raw(content);
+        let start = self.range.start + raw.len() - raw.trim_start().len();
+        let end = self.range.end - (raw.len() - raw.trim_end().len());
+        start..end.max(start)
+    }
+
+    /// The string the literal stands for, with its escapes replaced
+    pub fn value(&self, content: &str) -> String {
+        let raw = self.raw(content);
+        match self.kind {
+            LiteralKind::Quoted => po::unescape(raw),
+            LiteralKind::Raw => raw.to_string(),
+            LiteralKind::Heredoc { escapes, dedent } => {
+                let text = match dedent {
+                    true => dedented(raw),
+                    false => raw.to_string(),
+                };
+                match escapes {
+                    true => po::unescape(&text),
+                    false => text,
+                }
+            }
+        }
+    }
+}
+
+/// The lines of an Elixir heredoc, after the newline of the opening quotes and without the
+/// indentation of the closing ones
+fn dedented(raw: &str) -> String {
+    let body = raw
+        .strip_prefix("\r\n")
+        .or_else(|| raw.strip_prefix('\n'))
+        .unwrap_or(raw);
+    let (body, indent) = match body.rsplit_once('\n') {
+        Some((body, last)) if last.trim().is_empty() => (body, last.len()),
+        _ => (body, 0),
+    };
+    let mut text: String = body
+        .split('\n')
+        .map(|line| {
+            let trimmed = line.len() - line.trim_start_matches([' ', '\t']).len();
+            format!("{}\n", &line[trimmed.min(indent)..])
+        })
+        .collect();
+    if body.is_empty() {
+        text.clear();
+    }
+    text
+}
+
+/// A call to a gettext function, with the string literals of its message
+pub struct Call {
+    /// From the function name to the end of its arguments
+    pub range: Range<usize>,
+    pub message: Literal,
+    /// The plural form of the message, for `ngettext` and its variants
+    pub plural: Option<Literal>,
+}
+
+/// A gettext function and the arguments holding its message and plural form
+struct Keyword {
+    name: String,
+    position: usize,
+    plural: Option<usize>,
+}
+
+/// Finds the calls to the gettext functions given as `name`, or `name:N` when the message is
+/// their N-th argument and `name:N:M` when the plural form is the M-th one, like xgettext's
+/// `--keyword`
+pub struct Scanner {
+    keywords: Vec<Keyword>,
+}
+
+impl Scanner {
+    pub fn new(functions: &[String]) -> Result<Self> {
+        let mut keywords = Vec::new();
+        for function in functions {
+            let mut parts = function.split(':');
+            let name = parts.next().unwrap_or_default().trim();
+            let positions = parts
+                .map(|position| position.trim().parse::<usize>().ok().filter(|p| *p > 0))
+                .collect::<Option<Vec<usize>>>();
+            let (position, plural) = match positions.as_deref() {
+                Some([]) => default_positions(name),
+                Some([position]) => (*position, None),
+                Some([position, plural]) => (*position, Some(*plural)),
+                _ => bail!("Invalid argument position in --functions: {}", function),
+            };
+            keywords.push(Keyword {
+                name: name.to_string(),
+                position,
+                plural,
+            });
+        }
+        Ok(Self { keywords })
+    }
+
+    /// The calls of `content`, the source of the file at `path`, in the order they appear.
+    /// Calls whose message isn't a single string literal are skipped.
+    pub fn calls(&self, path: &Path, content: &str) -> Vec<Call> {
+        let tokens = Tokenizer {
+            src: content.as_bytes(),
+            syntax: Syntax::for_path(path),
+        };
+        let mut calls = Vec::new();
+        let mut pos = 0;
+        while pos < tokens.src.len() {
+            if let Some(end) = tokens.comment_end(pos) {
+                pos = end;
+                continue;
+            }
+            if let Some(literal) = tokens.literal_at(pos) {
+                pos = literal.end;
+                continue;
+            }
+            if !is_identifier(tokens.src[pos]) || (pos > 0 && is_identifier(tokens.src[pos - 1])) {
+                pos += 1;
+                continue;
+            }
+
+            let start = pos;
+            while pos < tokens.src.len() && is_identifier(tokens.src[pos]) {
+                pos += 1;
+            }
+            let name = &content[start..pos];
+            let Some(keyword) = self.keywords.iter().find(|keyword| keyword.name == name) else {
+                continue;
+            };
+            let Some((args, end)) = tokens.arguments(pos) else {
+                continue;
+            };
+            let literal = |position: usize| {
+                args.get(position - 1)
+                    .cloned()
+                    .flatten()
+                    .filter(|literal| literal.is_text)
+            };
+            let Some(message) = literal(keyword.position) else {
+                continue;
+            };
+            let plural = match keyword.plural {
+                Some(position) => match literal(position) {
+                    Some(plural) => Some(plural),
+                    None => continue,
+                },
+                None => None,
+            };
+            calls.push(Call {
+                range: start..end,
+                message,
+                plural,
+            });
+        }
+        calls
+    }
+}
+
+/// Positions of the message and plural form in the standard gettext functions, after the
+/// domain and/or context
+fn default_positions(function: &str) -> (usize, Option<usize>) {
+    match function {
+        "ngettext" => (1, Some(2)),
+        "dgettext" | "pgettext" => (2, None),
+        "dngettext" | "npgettext" | "pngettext" => (2, Some(3)),
+        "dpgettext" => (3, None),
+        "dnpgettext" | "dpngettext" => (3, Some(4)),
+        _ => (1, None),
+    }
+}
+
+fn is_identifier(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || byte == b'_'
+}
+
+struct Tokenizer<'a> {
+    src: &'a [u8],
+    syntax: Syntax,
+}
+
+impl Tokenizer<'_> {
+    fn starts_with(&self, pos: usize, text: &str) -> bool {
+        self.src[pos..].starts_with(text.as_bytes())
+    }
+
+    /// The end of the comment starting at `pos`, if one does
+    fn comment_end(&self, pos: usize) -> Option<usize> {
+        let line_end = |from: usize| {
+            self.src[from..]
+                .iter()
+                .position(|byte| *byte == b'\n')
+                .map_or(self.src.len(), |offset| from + offset)
+        };
+        if (self.syntax.hash_comments && self.src[pos] == b'#')
+            || (self.syntax.slash_comments && self.starts_with(pos, "//"))
+        {
+            return Some(line_end(pos));
+        }
+        if self.syntax.slash_comments && self.starts_with(pos, "/*") {
+            let end = self.src[pos + 2..]
+                .windows(2)
+                .position(|pair| pair == b"*/")
+                .map_or(self.src.len(), |offset| pos + 2 + offset + 2);
+            return Some(end);
+        }
+        None
+    }
+
+    /// The string literal starting at `pos`, if one does
+    fn literal_at(&self, pos: usize) -> Option<Literal> {
+        let byte = self.src[pos];
+        if self.syntax.sigils && byte == b'~' {
+            return self.sigil(pos);
+        }
+        for quote in ["\"\"\"", "'''"] {
+            if self.syntax.triple_quotes
+                && self.starts_with(pos, quote)
+                && (quote == "\"\"\"" || self.syntax.single_quotes)
+            {
+                let kind = LiteralKind::Heredoc {
+                    escapes: true,
+                    dedent: self.syntax.sigils,
+                };
+                return Some(self.delimited(pos + 3, quote.as_bytes(), kind, true));
+            }
+        }
+        let quoted = byte == b'"'
+            || (byte == b'\'' && self.syntax.single_quotes)
+            || (byte == b'`' && self.syntax.backticks);
+        if !quoted {
+            return None;
+        }
+        let mut literal = self.delimited(pos + 1, &[byte], LiteralKind::Quoted, true);
+        if byte == b'`' {
+            literal.is_text = !self.src[literal.range.clone()]
+                .windows(2)
+                .any(|pair| pair == b"${");
+        }
+        Some(literal)
+    }
+
+    /// An Elixir sigil: `~s`, `~S` or another letter, then its delimiters and modifiers
+    fn sigil(&self, pos: usize) -> Option<Literal> {
+        let letter = *self.src.get(pos + 1)?;
+        let open = *self.src.get(pos + 2)?;
+        if !letter.is_ascii_alphabetic() {
+            return None;
+        }
+        let escapes = letter.is_ascii_lowercase();
+        let mut literal = if self.starts_with(pos + 2, "\"\"\"") || self.starts_with(pos + 2, "'''")
+        {
+            let quote = &self.src[pos + 2..pos + 5];
+            let kind = LiteralKind::Heredoc {
+                escapes,
+                dedent: true,
+            };
+            self.delimited(pos + 5, quote, kind, escapes)
+        } else {
+            let close = match open {
+                b'(' => b')',
+                b'[' => b']',
+                b'{' => b'}',
+                b'<' => b'>',
+                b'"' | b'\'' | b'/' | b'|' => open,
+                _ => return None,
+            };
+            let kind = match escapes {
+                true => LiteralKind::Quoted,
+                false => LiteralKind::Raw,
+            };
+            self.delimited(pos + 3, &[close], kind, escapes)
+        };
+        while literal.end < self.src.len() && self.src[literal.end].is_ascii_alphabetic() {
+            literal.end += 1;
+        }
+        literal.is_text = matches!(letter, b's' | b'S');
+        Some(literal)
+    }
+
+    /// The literal whose content starts at `start` and ends before `close`
+    fn delimited(&self, start: usize, close: &[u8], kind: LiteralKind, escapes: bool) -> Literal {
+        let mut pos = start;
+        while pos < self.src.len() {
+            if escapes && self.src[pos] == b'\\' {
+                pos += 2;
+                continue;
+            }
+            if self.src[pos..].starts_with(close) {
+                return Literal {
+                    range: start..pos,
+                    kind,
+                    end: pos + close.len(),
+                    is_text: true,
+                };
+            }
+            pos += 1;
+        }
+        // An unterminated literal runs to the end of the file, and is never a message
+        Literal {
+            range: start..self.src.len(),
+            kind,
+            end: self.src.len(),
+            is_text: false,
+        }
+    }
+
+    /// The arguments of a call whose function name ends at `pos`: for each, its string literal
+    /// if it is only that. Returns them with the end of the call, or None if no call follows.
+    /// A call without parentheses ends with its line, before a comment or the `%>` of an EEx
+    /// tag, after its last argument.
+    fn arguments(&self, pos: usize) -> Option<(Vec<Option<Literal>>, usize)> {
+        let spaces = |mut pos: usize, newlines: bool| {
+            while pos < self.src.len()
+                && (matches!(self.src[pos], b' ' | b'\t' | b'\r')
+                    || (newlines && self.src[pos] == b'\n'))
+            {
+                pos += 1;
+            }
+            pos
+        };
+
+        let name_end = pos;
+        let mut pos = spaces(pos, false);
+        let parens = self.src.get(pos) == Some(&b'(');
+        if parens {
+            pos += 1;
+        } else if !self.syntax.paren_less || pos == name_end || pos >= self.src.len() {
+            return None;
+        } else if self.literal_at(pos).is_none() {
+            // Only `gettext "Hello"`, not `gettext = ...` or `def gettext x`
+            return None;
+        }
+
+        let mut args = Vec::new();
+        // The end of the last argument, where a call without parentheses ends
+        let mut last_end = pos;
+        loop {
+            pos = spaces(pos, parens);
+            let start = pos;
+            let mut literal = None;
+            let mut only_literal = true;
+            let mut depth = 0usize;
+            loop {
+                let Some(&byte) = self.src.get(pos) else {
+                    match parens {
+                        true => return None,
+                        false => break,
+                    }
+                };
+                if depth == 0
+                    && (byte == b','
+                        || (parens && byte == b')')
+                        || (!parens
+                            && (matches!(byte, b'\n' | b')' | b']' | b'}')
+                                || self.starts_with(pos, "%>"))))
+                {
+                    break;
+                }
+                if let Some(end) = self.comment_end(pos) {
+                    if !parens {
+                        pos = end;
+                        break;
+                    }
+                    pos = end;
+                    continue;
+                }
+                if let Some(found) = self.literal_at(pos) {
+                    match pos == start {
+                        true => literal = Some(found.clone()),
+                        false => only_literal = false,
+                    }
+                    pos = found.end;
+                    last_end = pos;
+                    continue;
+                }
+                match byte {
+                    b' ' | b'\t' | b'\r' | b'\n' => {}
+                    b'(' | b'[' | b'{' => {
+                        depth += 1;
+                        only_literal = false;
+                    }
+                    b')' | b']' | b'}' => {
+                        depth = depth.checked_sub(1)?;
+                        only_literal = false;
+                    }
+                    _ => only_literal = false,
+                }
+                pos += 1;
+                if !matches!(byte, b' ' | b'\t' | b'\r' | b'\n') {
+                    last_end = pos;
+                }
+            }
+            args.push(literal.filter(|_| only_literal));
+
+            match self.src.get(pos) {
+                Some(b',') => pos += 1,
+                Some(b')') if parens => return Some((args, pos + 1)),
+                _ => return Some((args, last_end)),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The messages and plural forms of the calls of `content`, as if it were at `path`
+    fn messages(path: &str, content: &str) -> Vec<(String, Option<String>)> {
+        let functions = crate::inline::DEFAULT_FUNCTIONS
+            .split(',')
+            .map(str::to_string)
+            .collect::<Vec<_>>();
+        Scanner::new(&functions)
+            .unwrap()
+            .calls(Path::new(path), content)
+            .iter()
+            .map(|call| {
+                let plural = call.plural.as_ref().map(|plural| plural.value(content));
+                (call.message.value(content), plural)
+            })
+            .collect()
+    }
+
+    fn singular(message: &str) -> (String, Option<String>) {
+        (message.to_string(), None)
+    }
+
+    #[test]
+    fn reads_sigils() {
+        let content =
+            r#"gettext(~s(Say "hi"\n)) <> gettext(~S(No \n escapes)) <> gettext(~r/regex/i)"#;
+        assert_eq!(
+            messages("lib/a.ex", content),
+            vec![singular("Say \"hi\"\n"), singular("No \\n escapes")]
+        );
+        assert_eq!(
+            messages("lib/a.ex", "gettext(~s{Braces}) <> gettext(~s[Brackets])"),
+            vec![singular("Braces"), singular("Brackets")]
+        );
+    }
+
+    #[test]
+    fn removes_the_indentation_of_heredocs() {
+        let content = "    gettext(\"\"\"\n    First line\n      indented\n    \"\"\")\n";
+        assert_eq!(
+            messages("lib/a.ex", content),
+            vec![singular("First line\n  indented\n")]
+        );
+        let content = "gettext(~S\"\"\"\n  Raw \\n\n  \"\"\")";
+        assert_eq!(messages("lib/a.ex", content), vec![singular("Raw \\n\n")]);
+    }
+
+    #[test]
+    fn reads_calls_without_parentheses() {
+        let content = "gettext \"Hello\"\nx = gettext \"Bye\" # a comment\n";
+        let calls = Scanner::new(&["gettext".to_string()])
+            .unwrap()
+            .calls(Path::new("lib/a.ex"), content);
+        assert_eq!(calls.len(), 2);
+        assert_eq!(&content[calls[0].range.clone()], "gettext \"Hello\"");
+        assert_eq!(&content[calls[1].range.clone()], "gettext \"Bye\"");
+
+        assert_eq!(
+            messages("lib/a.ex", "gettext \"Hello \" <> name\ngettext = 1\n"),
+            vec![]
+        );
+        assert_eq!(
+            messages("lib/a.ex", "gettext \"Last\""),
+            vec![singular("Last")]
+        );
+    }
+
+    #[test]
+    fn ends_calls_without_parentheses_at_eex_tags() {
+        let content = "<h1><%= gettext \"Welcome home\" %></h1>\n<p><%= gettext(\"Hi\") %></p>\n";
+        let calls = Scanner::new(&["gettext".to_string()])
+            .unwrap()
+            .calls(Path::new("index.html.heex"), content);
+        assert_eq!(calls.len(), 2);
+        assert_eq!(&content[calls[0].range.clone()], "gettext \"Welcome home\"");
+        assert_eq!(calls[0].message.value(content), "Welcome home");
+        assert_eq!(calls[1].message.value(content), "Hi");
+
+        // Apostrophes and `#` are text in templates
+        assert_eq!(
+            messages("page.html.eex", "<p>It's #1</p><%= gettext \"Top\"%>"),
+            vec![singular("Top")]
+        );
+    }
+
+    #[test]
+    fn reads_plural_forms() {
+        let content = r#"ngettext("One file", "%{count} files", n)
+dngettext("errors", "One error", "%{count} errors", n)
+pngettext("menu", "One tab", "%{count} tabs", n)
+dpngettext "errors", "menu", "One window", "%{count} windows", n
+ngettext("One " <> x, "Many", n)"#;
+        assert_eq!(
+            messages("lib/a.ex", content),
+            vec![
+                ("One file".to_string(), Some("%{count} files".to_string())),
+                ("One error".to_string(), Some("%{count} errors".to_string())),
+                ("One tab".to_string(), Some("%{count} tabs".to_string())),
+                (
+                    "One window".to_string(),
+                    Some("%{count} windows".to_string())
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn skips_comments_and_other_functions() {
+        let content =
+            "# gettext(\"Commented\")\nmy_gettext(\"Other\")\ndgettext(\"domain\", \"Kept\")\n";
+        assert_eq!(messages("lib/a.ex", content), vec![singular("Kept")]);
+        let content = "// gettext('No')\n/* gettext('No') */\ngettext('Yes', `also ${not}`)";
+        assert_eq!(messages("src/a.js", content), vec![singular("Yes")]);
+    }
+
+    #[test]
+    fn takes_argument_positions() {
+        let scanner = Scanner::new(&["t:2".to_string(), "n:1:2".to_string()]).unwrap();
+        let content = "t(ctx, \"Second\"); n(\"One\", \"Many\", 3)";
+        let calls = scanner.calls(Path::new("a.js"), content);
+        assert_eq!(calls[0].message.value(content), "Second");
+        assert_eq!(calls[1].plural.as_ref().unwrap().value(content), "Many");
+        assert!(Scanner::new(&["t:0".to_string()]).is_err());
+        assert!(Scanner::new(&["t:x".to_string()]).is_err());
+    }
+}
@@ -0,0 +1,552 @@
+//! The `serve` subcommand: an HTTP API over one translator, so that a web admin can translate
+//! without running the command. `POST /translate` translates a message, `POST /catalog` a
+//! whole .po file, and `GET /stats` gives the totals so far. Every request shares the cache,
+//! the rate limits and the budget of the server. HTTP is hyper's; connections are answered
+//! concurrently, up to `MAX_CONNECTIONS` at once, one request each, and must send their
+//! request within a deadline.
+
+use anyhow::Result;
+use http_body_util::{BodyExt, Full, LengthLimitError, Limited};
+use hyper::body::{Body, Bytes};
+use hyper::header::{AUTHORIZATION, CONTENT_TYPE, HeaderMap, HeaderValue};
+use hyper::server::conn::http1;
+use hyper::service::service_fn;
+use hyper::{Method, StatusCode};
+use hyper_util::rt::{TokioIo, TokioTimer};
+use serde::Deserialize;
+use serde_json::{Value, json};
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+use tokio::time::{MissedTickBehavior, interval, sleep, timeout};
+
+use crate::logger::{log_error, log_http_request, log_serving, log_summary, summary_event};
+use crate::po::{PoCatalog, PoEntry};
+use crate::translator::{Translator, check_lang, lang_code};
+
+/// The port listened on by default
+pub const DEFAULT_PORT: u16 = 8080;
+
+/// Longest request body, like an uploaded catalog; one of several thousand entries is well
+/// under it
+const MAX_BODY: usize = 4 * 1024 * 1024;
+/// Connections answered at once; the next ones wait to be accepted
+const MAX_CONNECTIONS: usize = 64;
+/// How long a connection may take to send its request head, idle time included
+const HEAD_TIMEOUT: Duration = Duration::from_secs(10);
+/// How long a request may take to send its body
+const BODY_TIMEOUT: Duration = Duration::from_secs(30);
+/// How often the translations of the requests are written to the cache
+const CACHE_SAVE_INTERVAL: Duration = Duration::from_secs(30);
+/// How long to wait after failing to accept a connection, like when out of file descriptors,
+/// before trying again
+const ACCEPT_BACKOFF: Duration = Duration::from_millis(100);
+
+/// Settings of the server
+pub struct ServeOptions {
+    pub addr: SocketAddr,
+    /// The language of the requests that don't give one
+    pub lang: Option<String>,
+    /// The bearer token every request needs in its `Authorization` header, if any
+    pub token: Option<String>,
+}
+
+/// The body of `POST /translate`
+#[derive(Deserialize)]
+struct TranslateRequest {
+    text: String,
+    lang: Option<String>,
+    /// The msgctxt of the message
+    context: Option<String>,
+    /// The plural form of the message, to get the translations of every form
+    plural: Option<String>,
+}
+
+/// A request, with its body read
+struct Request {
+    method: Method,
+    path: String,
+    query: String,
+    body: Bytes,
+}
+
+impl Request {
+    /// The value of the query parameter `name`, like `lang` in `/catalog?lang=es`
+    fn param(&self, name: &str) -> Option<String> {
+        self.query.split('&').find_map(|pair| {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            (key == name).then(|| percent_decode(value))
+        })
+    }
+}
+
+/// The answer to a request
+struct Response {
+    status: u16,
+    content_type: &'static str,
+    body: Vec<u8>,
+}
+
+impl Response {
+    fn json(status: u16, value: Value) -> Self {
+        Self {
+            status,
+            content_type: "application/json",
+            body: (value.to_string() + "\n").into_bytes(),
+        }
+    }
+
+    fn error(status: u16, message: impl std::fmt::Display) -> Self {
+        Self::json(status, json!({ "error": message.to_string() }))
+    }
+}
+
+impl From<Response> for hyper::Response<Full<Bytes>> {
+    fn from(response: Response) -> Self {
+        let mut answer = hyper::Response::new(Full::new(Bytes::from(response.body)));
+        *answer.status_mut() =
+            StatusCode::from_u16(response.status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+        answer.headers_mut().insert(
+            CONTENT_TYPE,
+            HeaderValue::from_static(response.content_type),
+        );
+        answer
+    }
+}
+
+/// What the connections share
+struct Server {
+    translator: Translator,
+    options: ServeOptions,
+    /// Requests answered so far
+    requests: AtomicUsize,
+}
+
+/// Answers requests on `options.addr` with `translator` until Ctrl-C, then waits for the
+/// requests in flight, saves the cache and prints the summary of everything translated
+pub async fn run(translator: Translator, options: ServeOptions) -> Result<()> {
+    let listener = TcpListener::bind(options.addr)
+        .await
+        .map_err(|e| anyhow::anyhow!("Error listening on {}: {}", options.addr, e))?;
+    log_serving(&listener.local_addr()?.to_string());
+
+    let server = Arc::new(Server {
+        translator,
+        options,
+        requests: AtomicUsize::new(0),
+    });
+    let slots = Arc::new(Semaphore::new(MAX_CONNECTIONS));
+    let mut connections = JoinSet::new();
+    let mut save = interval(CACHE_SAVE_INTERVAL);
+    save.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    let shutdown = tokio::signal::ctrl_c();
+    tokio::pin!(shutdown);
+
+    loop {
+        tokio::select! {
+            // Past `MAX_CONNECTIONS`, connections wait in the backlog until one is answered
+            (slot, accepted) = async {
+                let slot = slots.clone().acquire_owned().await;
+                (slot, listener.accept().await)
+            } => {
+                let slot = slot.expect("the connection slots are never closed");
+                let stream = match accepted {
+                    Ok((stream, _)) => stream,
+                    Err(e) => {
+                        log_error(&anyhow::anyhow!("Error accepting a connection: {}", e));
+                        sleep(ACCEPT_BACKOFF).await;
+                        continue;
+                    }
+                };
+                let server = server.clone();
+                connections.spawn(async move {
+                    server.serve_connection(stream).await;
+                    drop(slot);
+                });
+            }
+            _ = save.tick() => {
+                if let Err(e) = server.translator.save_cache() {
+                    log_error(&e);
+                }
+            }
+            Some(_) = connections.join_next(), if !connections.is_empty() => {}
+            _ = &mut shutdown => break,
+        }
+    }
+
+    while connections.join_next().await.is_some() {}
+    server.translator.save_cache()?;
+    log_summary(&server.translator.stats());
+    Ok(())
+}
+
+impl Server {
+    /// Answers the one request of `stream`, closing it if the head doesn't come in time
+    async fn serve_connection(self: Arc<Self>, stream: TcpStream) {
+        let service = service_fn(|request| {
+            let server = self.clone();
+            async move { Ok::<_, Infallible>(server.handle(request).await.into()) }
+        });
+        let _ = http1::Builder::new()
+            .timer(TokioTimer::new())
+            .header_read_timeout(HEAD_TIMEOUT)
+            .keep_alive(false)
+            .serve_connection(TokioIo::new(stream), service)
+            .await;
+    }
+
+    async fn handle<B>(&self, request: hyper::Request<B>) -> Response
+    where
+        B: Body,
+        B::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+    {
+        let (parts, body) = request.into_parts();
+        let path = parts.uri.path().to_string();
+        // Checked before the body is read, which strangers don't get to send
+        let response = match self.authorize(&parts.headers) {
+            Err(response) => response,
+            Ok(()) => match read_body(body).await {
+                Err(response) => response,
+                Ok(body) => {
+                    let request = Request {
+                        method: parts.method.clone(),
+                        path: path.clone(),
+                        query: parts.uri.query().unwrap_or_default().to_string(),
+                        body,
+                    };
+                    self.respond(&request).await
+                }
+            },
+        };
+        log_http_request(parts.method.as_str(), &path, response.status);
+        self.requests.fetch_add(1, Ordering::Relaxed);
+        response
+    }
+
+    /// Fails with a 401 unless the request gives the `--token`, if any
+    fn authorize(&self, headers: &HeaderMap) -> Result<(), Response> {
+        let Some(token) = &self.options.token else {
+            return Ok(());
+        };
+        let given = headers
+            .get(AUTHORIZATION)
+            .and_then(|value| value.as_bytes().strip_prefix(b"Bearer "));
+        match given {
+            Some(given) if same_token(given, token.as_bytes()) => Ok(()),
+            _ => Err(Response::error(401, "missing or wrong bearer token")),
+        }
+    }
+
+    async fn respond(&self, request: &Request) -> Response {
+        let result = match (&request.method, request.path.as_str()) {
+            (&Method::POST, "/translate") => self.translate(request).await,
+            (&Method::POST, "/catalog") => self.catalog(request).await,
+            (&Method::GET, "/stats") => Ok(self.stats()),
+            (_, "/translate" | "/catalog" | "/stats") => {
+                Ok(Response::error(405, "method not allowed"))
+            }
+            _ => Ok(Response::error(404, "not found")),
+        };
+        result.unwrap_or_else(|e| {
+            log_error(&e);
+            Response::error(500, format!("{:#}", e))
+        })
+    }
+
+    /// `POST /translate`: `{"text": "...", "lang": "es"}`, with an optional `context` and
+    /// `plural`, answered with the `translation`, or the `translations` of a plural message
+    async fn translate(&self, request: &Request) -> Result<Response> {
+        let body: TranslateRequest = match serde_json::from_slice(&request.body) {
+            Ok(body) => body,
+            Err(e) => return Ok(Response::error(400, format!("invalid request: {}", e))),
+        };
+        let lang = match self.lang(body.lang.as_deref()) {
+            Ok(lang) => lang,
+            Err(response) => return Ok(response),
+        };
+        if body.text.is_empty() {
+            return Ok(Response::error(400, "empty text"));
+        }
+
+        let plural = body.plural.is_some();
+        let entry =
+            PoEntry::from_strings(body.context, body.text.clone(), body.plural, vec![], &[]);
+        let mut catalog = PoCatalog::from_entries(vec![entry]);
+        let (_, failures) = self
+            .translator
+            .translate_catalog_failures(&mut catalog, &lang)
+            .await?;
+
+        let Some(entry) = catalog.entries().find(|entry| !entry.is_header()) else {
+            return Ok(Response::error(500, "the message was lost"));
+        };
+        if entry.msgstr.iter().all(String::is_empty) {
+            let error = failures
+                .into_iter()
+                .next()
+                .map(|failure| failure.error)
+                .unwrap_or_else(|| "the message wasn't translated".to_string());
+            return Ok(Response::error(502, error));
+        }
+
+        let needs_review = entry.has_flag("fuzzy");
+        Ok(Response::json(
+            200,
+            match plural {
+                true => json!({
+                    "lang": lang,
+                    "translations": entry.msgstr,
+                    "needs_review": needs_review,
+                }),
+                false => json!({
+                    "lang": lang,
+                    "translation": entry.msgstr[0],
+                    "needs_review": needs_review,
+                }),
+            },
+        ))
+    }
+
+    /// `POST /catalog?lang=es`: a .po file, answered with it translated. The language
+    /// defaults to the `Language` of its header.
+    async fn catalog(&self, request: &Request) -> Result<Response> {
+        let mut catalog = match PoCatalog::from_bytes(&request.body, Path::new("catalog")) {
+            Ok(catalog) => catalog,
+            Err(e) => return Ok(Response::error(400, format!("invalid catalog: {:#}", e))),
+        };
+        let given = request
+            .param("lang")
+            .or_else(|| catalog.header_field("Language"))
+            .filter(|lang| !lang.trim().is_empty());
+        let lang = match self.lang(given.as_deref()) {
+            Ok(lang) => lang,
+            Err(response) => return Ok(response),
+        };
+
+        self.translator.stats().lang(&lang).files += 1;
+        // The failed entries stay untranslated in the answer
+        self.translator
+            .translate_catalog_failures(&mut catalog, &lang)
+            .await?;
+
+        let content = catalog.to_content();
+        let (body, _, had_errors) = catalog.encoding().encode(&content);
+        if had_errors {
+            return Ok(Response::error(
+                422,
+                format!(
+                    "the translations cannot be represented in {}",
+                    catalog.encoding().name()
+                ),
+            ));
+        }
+        Ok(Response {
+            status: 200,
+            content_type: "text/x-gettext-translation",
+            body: body.into_owned(),
+        })
+    }
+
+    /// `GET /stats`: the totals of the translator summary, and the requests answered
+    fn stats(&self) -> Response {
+        let mut stats = summary_event(&self.translator.stats());
+        if let Some(stats) = stats.as_object_mut() {
+            stats.remove("event");
+            stats.insert(
+                "requests".into(),
+                json!(self.requests.load(Ordering::Relaxed)),
+            );
+        }
+        Response::json(200, stats)
+    }
+
+    /// The code of the language `given`, or else of `--lang`, if the translator takes it
+    fn lang(&self, given: Option<&str>) -> Result<String, Response> {
+        let Some(lang) = given.or(self.options.lang.as_deref()) else {
+            return Err(Response::error(400, "no lang given"));
+        };
+        let lang = lang_code(lang);
+        check_lang(&lang, &self.translator.options().allowed_langs)
+            .map_err(|e| Response::error(400, e))?;
+        Ok(lang)
+    }
+}
+
+/// Reads a request body of up to `MAX_BODY` bytes within `BODY_TIMEOUT`, or gives the error
+/// response
+async fn read_body<B>(body: B) -> Result<Bytes, Response>
+where
+    B: Body,
+    B::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    match timeout(BODY_TIMEOUT, Limited::new(body, MAX_BODY).collect()).await {
+        Ok(Ok(body)) => Ok(body.to_bytes()),
+        Ok(Err(e)) if e.is::<LengthLimitError>() => {
+            Err(Response::error(413, "request body too large"))
+        }
+        Ok(Err(_)) => Err(Response::error(400, "incomplete request body")),
+        Err(_) => Err(Response::error(408, "request body not received in time")),
+    }
+}
+
+/// Whether `given` is `token`, looking at every byte whatever the first difference, so that
+/// the time taken doesn't tell how much of a guess was right
+fn same_token(given: &[u8], token: &[u8]) -> bool {
+    let difference = given
+        .iter()
+        .zip(token)
+        .fold(0u8, |difference, (a, b)| difference | (a ^ b));
+    std::hint::black_box(difference) == 0 && given.len() == token.len()
+}
+
+/// Decodes the `%XX` escapes and `+` of a query parameter
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = bytes
+            .get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[i], escaped) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+                continue;
+            }
+            (b'+', _) => decoded.push(b' '),
+            (byte, _) => decoded.push(byte),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::provider::Pseudo;
+    use crate::translator::TranslateOptions;
+
+    fn server(token: Option<&str>) -> Server {
+        Server {
+            translator: Translator::new(Arc::new(Pseudo), TranslateOptions::default()).unwrap(),
+            options: ServeOptions {
+                addr: SocketAddr::from(([127, 0, 0, 1], 0)),
+                lang: Some("es".to_string()),
+                token: token.map(str::to_string),
+            },
+            requests: AtomicUsize::new(0),
+        }
+    }
+
+    fn request(
+        method: &str,
+        uri: &str,
+        token: Option<&str>,
+        body: &[u8],
+    ) -> hyper::Request<Full<Bytes>> {
+        let mut request = hyper::Request::builder().method(method).uri(uri);
+        if let Some(token) = token {
+            request = request.header(AUTHORIZATION, format!("Bearer {}", token));
+        }
+        request
+            .body(Full::new(Bytes::copy_from_slice(body)))
+            .unwrap()
+    }
+
+    fn error(response: &Response) -> String {
+        let body: Value = serde_json::from_slice(&response.body).unwrap();
+        body["error"].as_str().unwrap_or_default().to_string()
+    }
+
+    #[tokio::test]
+    async fn needs_the_token_when_one_is_set() {
+        let server = server(Some("s3cret"));
+        let response = server.handle(request("GET", "/stats", None, b"")).await;
+        assert_eq!(response.status, 401);
+        let response = server
+            .handle(request("GET", "/stats", Some("s3cres"), b""))
+            .await;
+        assert_eq!(response.status, 401);
+        let response = server
+            .handle(request("GET", "/stats", Some("s3cret"), b""))
+            .await;
+        assert_eq!(response.status, 200);
+
+        let open = self::server(None);
+        let response = open.handle(request("GET", "/stats", None, b"")).await;
+        assert_eq!(response.status, 200);
+    }
+
+    #[test]
+    fn compares_tokens_whole() {
+        assert!(same_token(b"s3cret", b"s3cret"));
+        assert!(!same_token(b"s3cre", b"s3cret"));
+        assert!(!same_token(b"s3crets", b"s3cret"));
+        assert!(!same_token(b"", b"s3cret"));
+        assert!(!same_token(b"S3cret", b"s3cret"));
+    }
+
+    #[tokio::test]
+    async fn rejects_bodies_over_the_limit() {
+        let server = server(None);
+        let body = vec![b' '; MAX_BODY + 1];
+        let response = server
+            .handle(request("POST", "/translate", None, &body))
+            .await;
+        assert_eq!(response.status, 413);
+        assert_eq!(error(&response), "request body too large");
+    }
+
+    #[tokio::test]
+    async fn translates_messages_and_catalogs() {
+        let server = server(None);
+        let response = server
+            .handle(request(
+                "POST",
+                "/translate",
+                None,
+                br#"{"text": "Hello %{name}"}"#,
+            ))
+            .await;
+        assert_eq!(response.status, 200);
+        let body: Value = serde_json::from_slice(&response.body).unwrap();
+        assert_eq!(body["lang"], "es");
+        assert!(body["translation"].as_str().unwrap().contains("%{name}"));
+
+        let catalog =
+            "msgid \"\"\nmsgstr \"\"\n\"Language: fr\\n\"\n\nmsgid \"Bye\"\nmsgstr \"\"\n";
+        let response = server
+            .handle(request("POST", "/catalog", None, catalog.as_bytes()))
+            .await;
+        assert_eq!(response.status, 200);
+        let translated = String::from_utf8(response.body).unwrap();
+        assert!(!translated.contains("msgid \"Bye\"\nmsgstr \"\"\n"));
+
+        let response = server
+            .handle(request("POST", "/translate", None, b"{\"text\": 1}"))
+            .await;
+        assert_eq!(response.status, 400);
+        let response = server.handle(request("DELETE", "/stats", None, b"")).await;
+        assert_eq!(response.status, 405);
+    }
+
+    #[tokio::test]
+    async fn answers_a_catalog_with_a_huge_plural_index_with_400() {
+        let server = server(None);
+        let catalog = "msgid \"One\"\nmsgid_plural \"Many\"\nmsgstr[4000000000] \"\"\n";
+        let response = server
+            .handle(request("POST", "/catalog", None, catalog.as_bytes()))
+            .await;
+        assert_eq!(response.status, 400);
+        assert!(error(&response).contains("Malformed .po line"));
+    }
+}
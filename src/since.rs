@@ -0,0 +1,85 @@
+//! `--since <ref>`: only the entries added or emptied since a commit or tag of the repository,
+//! like those a msgmerge brought in after the last release, instead of every untranslated one.
+
+use anyhow::{Result, bail};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Mutex;
+
+use crate::po::{PoCatalog, PoEntry};
+
+/// The msgctxt and msgid of an entry
+type Key = (Option<String>, String);
+
+pub struct Since {
+    git_ref: String,
+    /// The entries each catalog had translated at the ref, read once per catalog
+    translated: Mutex<HashMap<PathBuf, HashSet<Key>>>,
+}
+
+impl Since {
+    /// Fails if `root` isn't in a git repository, or `git_ref` isn't a commit of it
+    pub fn new(root: &Path, git_ref: &str) -> Result<Self> {
+        let output = Command::new("git")
+            .arg("-C")
+            .arg(root)
+            .args(["rev-parse", "--verify", "--quiet"])
+            .arg(format!("{}^{{commit}}", git_ref))
+            .output()
+            .map_err(|e| anyhow::anyhow!("--since needs git: {}", e))?;
+        if !output.status.success() {
+            bail!(
+                "--since {}: not a commit of the git repository of {}",
+                git_ref,
+                root.display()
+            );
+        }
+        Ok(Self {
+            git_ref: git_ref.to_string(),
+            translated: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Returns true if `entry` of the catalog at `path` wasn't translated at the ref: it was
+    /// added since, emptied or marked fuzzy, or the whole catalog is new
+    pub fn includes(&self, path: &Path, entry: &PoEntry) -> bool {
+        let mut translated = self.translated.lock().unwrap_or_else(|e| e.into_inner());
+        let keys = translated
+            .entry(path.to_path_buf())
+            .or_insert_with(|| self.translated_at_ref(path));
+        !keys.contains(&(entry.msgctxt.clone(), entry.msgid.clone()))
+    }
+
+    /// The entries of the catalog at `path` translated at the ref, none if it didn't exist
+    fn translated_at_ref(&self, path: &Path) -> HashSet<Key> {
+        let (Some(dir), Some(name)) = (path.parent(), path.file_name()) else {
+            return HashSet::new();
+        };
+        let dir = match dir.as_os_str().is_empty() {
+            true => Path::new("."),
+            false => dir,
+        };
+        let output = Command::new("git")
+            .arg("-C")
+            .arg(dir)
+            .arg("show")
+            .arg(format!("{}:./{}", self.git_ref, name.to_string_lossy()))
+            .output();
+        let catalog = match output {
+            Ok(output) if output.status.success() => PoCatalog::from_bytes(&output.stdout, path),
+            _ => return HashSet::new(),
+        };
+        let Ok(catalog) = catalog else {
+            return HashSet::new();
+        };
+        catalog
+            .entries()
+            .filter(|entry| !entry.is_header() && !entry.has_flag("fuzzy"))
+            .filter(|entry| {
+                !entry.msgstr.is_empty() && entry.msgstr.iter().all(|msgstr| !msgstr.is_empty())
+            })
+            .map(|entry| (entry.msgctxt.clone(), entry.msgid.clone()))
+            .collect()
+    }
+}
@@ -0,0 +1,278 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use anyhow::bail;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+use crate::logger::log_request;
+use crate::provider::{AiRequest, AiResponse, Pricing, TranslationProvider, Usage};
+
+/// Counters for one target language
+#[derive(Default, Clone)]
+pub struct LangStats {
+    pub files: usize,
+    pub translated: usize,
+    pub cached: usize,
+    pub skipped: usize,
+    /// Entries a forced run kept because they need no translation
+    pub unchanged: usize,
+    pub failed: usize,
+}
+
+impl LangStats {
+    fn add(&mut self, other: &LangStats) {
+        self.files += other.files;
+        self.translated += other.translated;
+        self.cached += other.cached;
+        self.skipped += other.skipped;
+        self.unchanged += other.unchanged;
+        self.failed += other.failed;
+    }
+}
+
+/// Counters for one catalog of a translator run, for `--report`
+pub struct FileStats {
+    pub lang: String,
+    pub path: PathBuf,
+    pub translated: usize,
+    pub failed: usize,
+}
+
+/// An entry left untranslated because its request failed, as written to the failure report
+#[derive(Serialize, Deserialize)]
+pub struct EntryFailure {
+    /// The catalog of the entry, unless it was translated in memory
+    pub file: Option<PathBuf>,
+    /// 1-based line number of its msgid in the catalog
+    pub line: usize,
+    pub lang: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub msgctxt: Option<String>,
+    pub msgid: String,
+    pub error: String,
+}
+
+/// The error of a request refused by `--max-cost`, which stops the run instead of failing
+/// only its entries
+#[derive(Debug)]
+pub struct BudgetExceeded {
+    max_cost: f64,
+    spent: f64,
+}
+
+impl std::fmt::Display for BudgetExceeded {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "Stopping before exceeding --max-cost ${:.2}: ${:.4} spent so far",
+            self.max_cost, self.spent
+        )
+    }
+}
+
+impl std::error::Error for BudgetExceeded {}
+
+/// Tokens sent and received so far, with their cost in USD
+#[derive(Default, Clone)]
+pub struct TokenStats {
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub cost: f64,
+    /// The cost of the requests of each language
+    pub lang_costs: BTreeMap<String, f64>,
+    /// Estimated cost of the requests still in flight
+    reserved: f64,
+}
+
+/// Totals of a translator run, reported when it finishes
+pub struct RunStats {
+    pub langs: BTreeMap<String, LangStats>,
+    /// The catalogs processed, in the order they were finished
+    pub files: Vec<FileStats>,
+    pub failures: Vec<EntryFailure>,
+    api_calls: Arc<AtomicUsize>,
+    tokens: Arc<Mutex<TokenStats>>,
+    pricing: Option<Pricing>,
+    started: Instant,
+}
+
+impl RunStats {
+    pub fn new() -> Self {
+        Self {
+            langs: BTreeMap::new(),
+            files: Vec::new(),
+            failures: Vec::new(),
+            api_calls: Arc::new(AtomicUsize::new(0)),
+            tokens: Arc::new(Mutex::new(TokenStats::default())),
+            pricing: None,
+            started: Instant::now(),
+        }
+    }
+
+    pub fn lang(&mut self, lang: &str) -> &mut LangStats {
+        self.langs.entry(lang.to_string()).or_default()
+    }
+
+    pub fn total(&self) -> LangStats {
+        let mut total = LangStats::default();
+        for stats in self.langs.values() {
+            total.add(stats);
+        }
+        total
+    }
+
+    pub fn api_calls(&self) -> usize {
+        self.api_calls.load(Ordering::Relaxed)
+    }
+
+    pub fn tokens(&self) -> TokenStats {
+        self.tokens
+            .lock()
+            .expect("token stats lock poisoned")
+            .clone()
+    }
+
+    /// The cost of the run so far, if the prices of the model are known
+    pub fn cost(&self) -> Option<f64> {
+        self.pricing.map(|_| self.tokens().cost)
+    }
+
+    /// The cost of the requests of `lang` so far, if the prices of the model are known
+    pub fn lang_cost(&self, lang: &str) -> Option<f64> {
+        self.pricing
+            .map(|_| self.tokens().lang_costs.get(lang).copied().unwrap_or(0.0))
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    /// Wraps a provider so that every request it sends, and the tokens it uses, are counted in
+    /// these stats. With `max_cost`, a request that could take the run over budget fails instead.
+    pub fn count_calls(
+        &mut self,
+        inner: Arc<dyn TranslationProvider>,
+        max_cost: Option<f64>,
+    ) -> anyhow::Result<Arc<dyn TranslationProvider>> {
+        self.pricing = inner.pricing();
+
+        if max_cost.is_some() && self.pricing.is_none() {
+            bail!(
+                "--max-cost needs the model's prices; pass them with --input-price and --output-price"
+            );
+        }
+
+        Ok(Arc::new(CountingProvider {
+            inner,
+            calls: Arc::clone(&self.api_calls),
+            tokens: Arc::clone(&self.tokens),
+            pricing: self.pricing,
+            max_cost,
+        }))
+    }
+}
+
+impl Default for RunStats {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+struct CountingProvider {
+    inner: Arc<dyn TranslationProvider>,
+    calls: Arc<AtomicUsize>,
+    tokens: Arc<Mutex<TokenStats>>,
+    pricing: Option<Pricing>,
+    max_cost: Option<f64>,
+}
+
+impl CountingProvider {
+    /// Reserves the estimated cost of a request, failing if it could exceed the budget
+    fn reserve(&self, body: &AiRequest) -> anyhow::Result<f64> {
+        let Some(pricing) = self.inner.request_pricing(body) else {
+            return Ok(0.0);
+        };
+
+        let estimate = pricing.cost(estimate_usage(body));
+        let mut tokens = self.tokens.lock().expect("token stats lock poisoned");
+
+        if let Some(max_cost) = self.max_cost
+            && tokens.cost + tokens.reserved + estimate > max_cost
+        {
+            return Err(BudgetExceeded {
+                max_cost,
+                spent: tokens.cost,
+            }
+            .into());
+        }
+
+        tokens.reserved += estimate;
+        Ok(estimate)
+    }
+}
+
+#[async_trait]
+impl TranslationProvider for CountingProvider {
+    async fn send(&self, body: &AiRequest) -> anyhow::Result<AiResponse> {
+        let reserved = self.reserve(body)?;
+        self.calls.fetch_add(1, Ordering::Relaxed);
+        let started = Instant::now();
+        let response = self.inner.send(body).await;
+
+        let mut tokens = self.tokens.lock().expect("token stats lock poisoned");
+        tokens.reserved -= reserved;
+
+        let response = response?;
+        let usage = response.usage.unwrap_or_else(|| Usage {
+            input_tokens: estimate_usage(body).input_tokens,
+            output_tokens: response.text.chars().count() as u64 / 4 + 1,
+        });
+        log_request(
+            body.texts.len().max(1),
+            started.elapsed(),
+            usage.input_tokens,
+            usage.output_tokens,
+            response.usage.is_none(),
+        );
+        tokens.input_tokens += usage.input_tokens;
+        tokens.output_tokens += usage.output_tokens;
+        if let Some(pricing) = self.inner.request_pricing(body) {
+            let cost = pricing.cost(usage);
+            tokens.cost += cost;
+            *tokens.lang_costs.entry(body.lang.clone()).or_default() += cost;
+        }
+
+        Ok(response)
+    }
+
+    fn pricing(&self) -> Option<Pricing> {
+        self.pricing
+    }
+
+    fn request_pricing(&self, body: &AiRequest) -> Option<Pricing> {
+        self.inner.request_pricing(body)
+    }
+
+    fn model(&self, lang: &str) -> Option<String> {
+        self.inner.model(lang)
+    }
+}
+
+/// Rough usage estimate (~4 characters per token): the prompt, and an answer up to twice as
+/// long as the messages it translates
+fn estimate_usage(body: &AiRequest) -> Usage {
+    let prompt = body.instructions.chars().count() + body.input.chars().count();
+    let messages = match body.texts.is_empty() {
+        true => body.input.chars().count(),
+        false => body.texts.iter().map(|text| text.chars().count()).sum(),
+    };
+
+    Usage {
+        input_tokens: (prompt / 4 + 1) as u64,
+        output_tokens: (messages * 2 / 4 + 1) as u64,
+    }
+}
@@ -0,0 +1,86 @@
+//! The `sync` subcommand: brings the catalogs of every language up to date with their .pot
+//! template, like GNU gettext's `msgmerge --update`, so that a project doesn't need gettext
+//! installed before translating. New msgids are added empty, removed ones kept as obsolete
+//! `#~` entries and changed ones given the old translation, marked fuzzy.
+
+use anyhow::{Result, bail};
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+use crate::logger::{log_sync_summary, log_synced, log_unified_diff};
+use crate::plural::PluralForms;
+use crate::po::PoCatalog;
+use crate::template;
+
+/// Merges the .pot templates into the catalogs under `root` in `langs` (every language if
+/// empty): `template` for the catalogs named after it, or every catalog if none is, else the
+/// template of each catalog's domain next to the language folders. With `dry_run` the changes
+/// are shown as a diff instead of written. Returns the number of catalogs changed.
+pub fn run(root: &Path, template: Option<&Path>, langs: &[String], dry_run: bool) -> Result<usize> {
+    if let Some(template) = template
+        && !template.is_file()
+    {
+        bail!("Template not found: {}", template.display());
+    }
+
+    let mut catalogs: Vec<(PathBuf, String)> = Vec::new();
+    for file in WalkDir::new(root).into_iter().filter_map(|e| e.ok()) {
+        let path = file.path();
+        if path.extension().is_none_or(|e| e != "po") {
+            continue;
+        }
+        let lang = path
+            .strip_prefix(root)
+            .ok()
+            .and_then(|relative| relative.components().next())
+            .and_then(|lang| lang.as_os_str().to_str())
+            .unwrap_or_default()
+            .to_string();
+        if !langs.is_empty() && !langs.iter().any(|known| known.trim() == lang) {
+            continue;
+        }
+        catalogs.push((path.to_path_buf(), lang));
+    }
+
+    let named =
+        |path: &Path| template.is_some_and(|template| template.file_stem() == path.file_stem());
+    let any_named = catalogs.iter().any(|(path, _)| named(path));
+
+    let mut files = 0;
+    let mut changed = 0;
+    for (path, lang) in &catalogs {
+        let pot = match template {
+            Some(_) if any_named && !named(path) => continue,
+            Some(template) => template.to_path_buf(),
+            None => match template::template_for(path, lang) {
+                Some(pot) => pot,
+                None => bail!(
+                    "No .pot template found for {}. Use --template to give it",
+                    path.display()
+                ),
+            },
+        };
+
+        let catalog = PoCatalog::read(path)?;
+        let nplurals = match catalog.header_field("Plural-Forms") {
+            Some(header) => PluralForms::parse(&header)?.nplurals,
+            None => PluralForms::for_language(lang).nplurals,
+        };
+        let (merged, stats) = catalog.merge(&PoCatalog::read(&pot)?, nplurals);
+
+        files += 1;
+        let (original, updated) = (catalog.to_content(), merged.to_content());
+        if original == updated {
+            continue;
+        }
+        changed += 1;
+        log_synced(lang, &path.display().to_string(), &stats, dry_run);
+        match dry_run {
+            true => log_unified_diff(&path.display().to_string(), &original, &updated),
+            false => merged.write(path)?,
+        }
+    }
+
+    log_sync_summary(files, changed, dry_run);
+    Ok(changed)
+}
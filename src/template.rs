@@ -0,0 +1,143 @@
+use anyhow::{Result, bail};
+use std::fs;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+use crate::logger::log_catalog_created;
+use crate::plural::{PluralForms, header_for_language};
+use crate::po::PoCatalog;
+
+/// Creates the .po files of a language that has no folder yet, from the .pot templates in
+/// `root` or, if there are none, from the catalogs of the `reference` language.
+/// Returns the number of catalogs created.
+pub fn create_language(
+    root: &Path,
+    lang: &str,
+    reference: Option<&str>,
+    dry_run: bool,
+) -> Result<usize> {
+    let templates = find_templates(root, lang, reference)?;
+
+    for (template, target) in &templates {
+        let catalog = PoCatalog::read(template)?;
+        let skeleton = build_skeleton(catalog, lang);
+
+        log_catalog_created(lang, &target.display().to_string(), dry_run);
+        if !dry_run {
+            if let Some(parent) = target.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            skeleton.write(target)?;
+        }
+    }
+
+    Ok(templates.len())
+}
+
+/// The .pot template of the catalog at `po_path`, in the root above its `lang` folder: the one
+/// named after the catalog's domain, or else the root's only template
+pub fn template_for(po_path: &Path, lang: &str) -> Option<PathBuf> {
+    let root = po_path
+        .ancestors()
+        .find(|dir| dir.file_name().is_some_and(|name| name == lang))?
+        .parent()?;
+
+    let named = root.join(po_path.file_stem()?).with_extension("pot");
+    if named.is_file() {
+        return Some(named);
+    }
+
+    let mut pots = WalkDir::new(root)
+        .max_depth(1)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .map(|e| e.into_path())
+        .filter(|path| path.extension().map(|e| e == "pot").unwrap_or(false));
+    match (pots.next(), pots.next()) {
+        (Some(pot), None) => Some(pot),
+        _ => None,
+    }
+}
+
+/// Returns (template, target .po) pairs for the new language
+fn find_templates(
+    root: &Path,
+    lang: &str,
+    reference: Option<&str>,
+) -> Result<Vec<(PathBuf, PathBuf)>> {
+    let pots: Vec<PathBuf> = WalkDir::new(root)
+        .max_depth(1)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .map(|e| e.into_path())
+        .filter(|path| path.extension().map(|e| e == "pot").unwrap_or(false))
+        .collect();
+
+    if !pots.is_empty() {
+        // gettext layout: <root>/<lang>/LC_MESSAGES/<domain>.po
+        return Ok(pots
+            .into_iter()
+            .map(|pot| {
+                let domain = pot.file_stem().unwrap_or_default().to_os_string();
+                let target = root
+                    .join(lang)
+                    .join("LC_MESSAGES")
+                    .join(domain)
+                    .with_extension("po");
+                (pot, target)
+            })
+            .collect());
+    }
+
+    let Some(reference) = reference else {
+        bail!(
+            "No .pot template found in {} to create the `{}` catalogs. Use --reference-lang to copy another language",
+            root.display(),
+            lang
+        );
+    };
+
+    let reference_root = root.join(reference);
+    if !reference_root.is_dir() {
+        bail!(
+            "Reference language folder not found: {}",
+            reference_root.display()
+        );
+    }
+
+    let mut pairs = Vec::new();
+    for entry in WalkDir::new(&reference_root)
+        .into_iter()
+        .filter_map(|e| e.ok())
+    {
+        let path = entry.path();
+        if path.extension().map(|e| e == "po").unwrap_or(false) {
+            let relative = path.strip_prefix(&reference_root)?;
+            pairs.push((path.to_path_buf(), root.join(lang).join(relative)));
+        }
+    }
+
+    Ok(pairs)
+}
+
+/// Empties every msgstr of the template and writes a header for the new language
+fn build_skeleton(mut catalog: PoCatalog, lang: &str) -> PoCatalog {
+    let nplurals = PluralForms::for_language(lang).nplurals;
+
+    for entry in catalog.entries_mut() {
+        if entry.is_header() {
+            entry.remove_flag("fuzzy");
+        } else {
+            entry.reset_msgstr(nplurals);
+            entry.remove_flag("fuzzy");
+        }
+    }
+
+    catalog.set_header_field("Language", lang);
+    catalog.set_header_field("MIME-Version", "1.0");
+    catalog.set_header_field("Content-Type", "text/plain; charset=UTF-8");
+    catalog.set_header_field("Content-Transfer-Encoding", "8bit");
+    catalog.set_header_field("Plural-Forms", header_for_language(lang));
+
+    catalog
+}
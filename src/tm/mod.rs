@@ -0,0 +1,58 @@
+//! The shared translation memory of `--tm`: an SQLite database of every translation made,
+//! with the provider and model behind it, when and whether a human reviewed it, which any
+//! number of projects can point to. A msgid found there for the language isn't sent again.
+//! `tm export` and `tm import` exchange it with CAT tools as TMX.
+
+mod sqlite;
+pub mod tmx;
+
+use anyhow::{Result, bail};
+use std::fs;
+use std::path::Path;
+
+use crate::atomic;
+use crate::logger::{log_tm_exported, log_tm_imported};
+pub use sqlite::TmStore;
+
+/// A translation of the memory
+#[derive(Clone, Debug)]
+pub struct TmUnit {
+    pub source: String,
+    /// The msgctxt, empty if none
+    pub context: String,
+    pub source_lang: String,
+    pub lang: String,
+    pub target: String,
+    pub provider: Option<String>,
+    pub model: Option<String>,
+    /// RFC 3339
+    pub timestamp: String,
+    pub reviewed: bool,
+}
+
+/// Writes the units of the translation memory at `tm` into `langs` (every language if empty),
+/// only the reviewed ones with `reviewed_only`, to the TMX file `out`
+pub fn export(tm: &Path, out: &Path, langs: &[String], reviewed_only: bool) -> Result<()> {
+    if !tm.is_file() {
+        bail!("Translation memory not found: {}", tm.display());
+    }
+    let units = TmStore::open(tm)?.units(langs, reviewed_only)?;
+    atomic::write(out, tmx::write(&units))?;
+    log_tm_exported(&out.display().to_string(), units.len());
+    Ok(())
+}
+
+/// Adds the units of the TMX file `file` to the translation memory at `tm`, whose source
+/// segments are in `source_lang` or else the language the file gives. With `reviewed`,
+/// they are recorded as reviewed by a human.
+pub fn import(file: &Path, tm: &Path, source_lang: Option<&str>, reviewed: bool) -> Result<()> {
+    let content = fs::read_to_string(file)
+        .map_err(|e| anyhow::anyhow!("Error reading {}: {}", file.display(), e))?;
+    let mut units = tmx::parse(&content, source_lang)?;
+    if reviewed {
+        units.iter_mut().for_each(|unit| unit.reviewed = true);
+    }
+    TmStore::open(tm)?.put_all(&units)?;
+    log_tm_imported(&file.display().to_string(), units.len());
+    Ok(())
+}
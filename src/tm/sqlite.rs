@@ -0,0 +1,277 @@
+//! The SQL of the translation memory. SQLite is compiled in by rusqlite, with the `tm`
+//! feature; without it, opening a database fails.
+
+use anyhow::Result;
+use std::path::Path;
+#[cfg(feature = "tm")]
+use std::sync::Mutex;
+#[cfg(feature = "tm")]
+use std::time::Duration;
+
+#[cfg(feature = "tm")]
+use rusqlite::{Connection, OptionalExtension, params};
+
+use super::TmUnit;
+
+/// The table of the database, one translation per source, context and languages
+#[cfg(feature = "tm")]
+const SCHEMA: &str = "CREATE TABLE IF NOT EXISTS units (
+    source TEXT NOT NULL,
+    context TEXT NOT NULL DEFAULT '',
+    source_lang TEXT NOT NULL,
+    lang TEXT NOT NULL,
+    target TEXT NOT NULL,
+    provider TEXT,
+    model TEXT,
+    timestamp TEXT NOT NULL,
+    reviewed INTEGER NOT NULL DEFAULT 0,
+    PRIMARY KEY (source, context, source_lang, lang)
+)";
+
+/// How long a statement waits for another process holding the database
+#[cfg(feature = "tm")]
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A translation memory database, whose statements run one at a time
+#[cfg(feature = "tm")]
+pub struct TmStore {
+    connection: Mutex<Connection>,
+}
+
+/// A translation memory that can't be opened, in builds without the `tm` feature
+#[cfg(not(feature = "tm"))]
+pub enum TmStore {}
+
+#[cfg(feature = "tm")]
+fn query_error(e: rusqlite::Error) -> anyhow::Error {
+    anyhow::anyhow!("Error in translation memory query: {}", e)
+}
+
+#[cfg(feature = "tm")]
+impl TmStore {
+    /// Opens the database at `path`, creating it if it doesn't exist
+    pub fn open(path: &Path) -> Result<Self> {
+        let connection = Connection::open(path)
+            .and_then(|connection| {
+                connection.busy_timeout(BUSY_TIMEOUT)?;
+                connection.execute(SCHEMA, [])?;
+                Ok(connection)
+            })
+            .map_err(|e| {
+                anyhow::anyhow!("Error opening translation memory {}: {}", path.display(), e)
+            })?;
+        Ok(Self {
+            connection: Mutex::new(connection),
+        })
+    }
+
+    fn connection(&self) -> std::sync::MutexGuard<'_, Connection> {
+        self.connection.lock().expect("database lock poisoned")
+    }
+
+    /// The translation of `source` with the msgctxt `context` from `source_lang` to `lang`
+    pub fn get(
+        &self,
+        source: &str,
+        context: Option<&str>,
+        source_lang: &str,
+        lang: &str,
+    ) -> Result<Option<String>> {
+        self.connection()
+            .query_row(
+                "SELECT target FROM units
+                 WHERE source = ?1 AND context = ?2 AND source_lang = ?3 AND lang = ?4",
+                params![source, context.unwrap_or_default(), source_lang, lang],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(query_error)
+    }
+
+    /// Adds `unit`, replacing the translation it had unless that one was reviewed and
+    /// `unit` isn't. The same translation keeps its record.
+    pub fn put(&self, unit: &TmUnit) -> Result<()> {
+        put(&self.connection(), unit)
+    }
+
+    /// Adds every unit of `units` at once
+    pub fn put_all(&self, units: &[TmUnit]) -> Result<()> {
+        let mut connection = self.connection();
+        let transaction = connection.transaction().map_err(query_error)?;
+        for unit in units {
+            put(&transaction, unit)?;
+        }
+        transaction.commit().map_err(query_error)
+    }
+
+    /// The units translating into `langs`, every language if empty, and only the reviewed
+    /// ones with `reviewed_only`, by source language, source and context
+    pub fn units(&self, langs: &[String], reviewed_only: bool) -> Result<Vec<TmUnit>> {
+        let connection = self.connection();
+        let mut statement = connection
+            .prepare(
+                "SELECT source, context, source_lang, lang, target, provider, model, timestamp,
+                    reviewed
+                 FROM units WHERE reviewed >= ?1
+                 ORDER BY source_lang, source, context, lang",
+            )
+            .map_err(query_error)?;
+        let units = statement
+            .query_map(params![reviewed_only as i64], |row| {
+                Ok(TmUnit {
+                    source: row.get(0)?,
+                    context: row.get(1)?,
+                    source_lang: row.get(2)?,
+                    lang: row.get(3)?,
+                    target: row.get(4)?,
+                    provider: row.get(5)?,
+                    model: row.get(6)?,
+                    timestamp: row.get(7)?,
+                    reviewed: row.get::<_, bool>(8)?,
+                })
+            })
+            .and_then(|rows| rows.collect::<rusqlite::Result<Vec<_>>>())
+            .map_err(query_error)?;
+        Ok(units
+            .into_iter()
+            .filter(|unit| langs.is_empty() || langs.iter().any(|lang| lang.trim() == unit.lang))
+            .collect())
+    }
+}
+
+/// Upserts `unit`, within a transaction or not
+#[cfg(feature = "tm")]
+fn put(connection: &Connection, unit: &TmUnit) -> Result<()> {
+    connection
+        .execute(
+            "INSERT INTO units
+                (source, context, source_lang, lang, target, provider, model, timestamp, reviewed)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)
+             ON CONFLICT (source, context, source_lang, lang) DO UPDATE SET
+                target = excluded.target,
+                provider = excluded.provider,
+                model = excluded.model,
+                timestamp = excluded.timestamp,
+                reviewed = excluded.reviewed
+             WHERE (units.target != excluded.target OR excluded.reviewed > units.reviewed)
+                AND (units.reviewed = 0 OR excluded.reviewed = 1)",
+            params![
+                unit.source,
+                unit.context,
+                unit.source_lang,
+                unit.lang,
+                unit.target,
+                unit.provider,
+                unit.model,
+                unit.timestamp,
+                unit.reviewed,
+            ],
+        )
+        .map(|_| ())
+        .map_err(query_error)
+}
+
+#[cfg(not(feature = "tm"))]
+impl TmStore {
+    pub fn open(_path: &Path) -> Result<Self> {
+        anyhow::bail!(
+            "This build has no translation memory: --tm needs the `tm` feature, e.g. `cargo install ai_gettext_translator --features tm`"
+        )
+    }
+
+    pub fn get(
+        &self,
+        _source: &str,
+        _context: Option<&str>,
+        _source_lang: &str,
+        _lang: &str,
+    ) -> Result<Option<String>> {
+        match *self {}
+    }
+
+    pub fn put(&self, _unit: &TmUnit) -> Result<()> {
+        match *self {}
+    }
+
+    pub fn put_all(&self, _units: &[TmUnit]) -> Result<()> {
+        match *self {}
+    }
+
+    pub fn units(&self, _langs: &[String], _reviewed_only: bool) -> Result<Vec<TmUnit>> {
+        match *self {}
+    }
+}
+
+#[cfg(all(test, feature = "tm"))]
+mod tests {
+    use super::*;
+
+    fn store(test: &str) -> (TmStore, std::path::PathBuf) {
+        let path = std::env::temp_dir().join(format!(
+            "ai-gettext-translator-tm-{}-{}.db",
+            test,
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&path);
+        (TmStore::open(&path).unwrap(), path)
+    }
+
+    fn unit(target: &str, reviewed: bool) -> TmUnit {
+        TmUnit {
+            source: "Save".to_string(),
+            context: String::new(),
+            source_lang: "en".to_string(),
+            lang: "es".to_string(),
+            target: target.to_string(),
+            provider: Some("openai".to_string()),
+            model: None,
+            timestamp: "2026-01-01T00:00:00Z".to_string(),
+            reviewed,
+        }
+    }
+
+    #[test]
+    fn keeps_reviewed_translations_over_machine_ones() {
+        let (tm, path) = store("reviewed");
+        tm.put(&unit("Salvar", false)).unwrap();
+        tm.put(&unit("Guardar", false)).unwrap();
+        assert_eq!(
+            tm.get("Save", None, "en", "es").unwrap().as_deref(),
+            Some("Guardar")
+        );
+
+        tm.put(&unit("Guardar", true)).unwrap();
+        tm.put(&unit("Almacenar", false)).unwrap();
+        let units = tm.units(&[], false).unwrap();
+        assert_eq!(units.len(), 1);
+        assert_eq!(units[0].target, "Guardar");
+        assert!(units[0].reviewed);
+        assert_eq!(units[0].provider.as_deref(), Some("openai"));
+        assert_eq!(units[0].model, None);
+
+        tm.put(&unit("Grabar", true)).unwrap();
+        assert_eq!(
+            tm.get("Save", None, "en", "es").unwrap().as_deref(),
+            Some("Grabar")
+        );
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn filters_units_by_language_and_review() {
+        let (tm, path) = store("units");
+        let mut french = unit("Enregistrer", true);
+        french.lang = "fr".to_string();
+        tm.put_all(&[unit("Guardar", false), french]).unwrap();
+
+        assert_eq!(tm.units(&[], false).unwrap().len(), 2);
+        let reviewed = tm.units(&[], true).unwrap();
+        assert_eq!(reviewed.len(), 1);
+        assert_eq!(reviewed[0].lang, "fr");
+        let spanish = tm.units(&["es".to_string()], false).unwrap();
+        assert_eq!(spanish.len(), 1);
+        assert_eq!(spanish[0].target, "Guardar");
+        assert_eq!(tm.get("Save", Some("menu"), "en", "es").unwrap(), None);
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,202 @@
+//! TMX 1.4, the translation memory exchange format of CAT tools: one `<tu>` per unit of the
+//! memory, with its source and target `<tuv>`s and the provider, model, context and review
+//! state as `<prop>`s.
+
+use anyhow::{Result, bail};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use regex::Regex;
+use std::sync::LazyLock;
+
+use super::TmUnit;
+use crate::formats::xml::{attribute, escape, unescape};
+
+/// The format of the `creationdate` of a unit
+const DATE_FORMAT: &str = "%Y%m%dT%H%M%SZ";
+
+static HEADER: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"<header\b[^>]*>").unwrap());
+static TU: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?s)(<tu\b[^>]*>)(.*?)</tu>").unwrap());
+static TUV: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?s)(<tuv\b[^>]*>).*?<seg>(.*?)</seg>").unwrap());
+static PROP: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?s)(<prop\b[^>]*>)(.*?)</prop>").unwrap());
+/// The inline elements holding the native codes of the original format, like `<b>`
+static NATIVE_CODE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?s)<(?:bpt|ept|ph|it)\b[^>]*>(.*?)</(?:bpt|ept|ph|it)>").unwrap()
+});
+static TAG: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"<[^>]*>").unwrap());
+
+/// The TMX document of `units`
+pub fn write(units: &[TmUnit]) -> String {
+    let source_lang = match units.first() {
+        Some(first)
+            if units
+                .iter()
+                .all(|unit| unit.source_lang == first.source_lang) =>
+        {
+            tmx_lang(&first.source_lang)
+        }
+        Some(_) => "*all*".to_string(),
+        None => "en".to_string(),
+    };
+
+    let mut tmx =
+        String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<tmx version=\"1.4\">\n");
+    tmx.push_str(&format!(
+        "  <header creationtool=\"ai-gettext-translator\" creationtoolversion=\"{}\" segtype=\"sentence\" o-tmf=\"gettext\" adminlang=\"en\" srclang=\"{}\" datatype=\"plaintext\"/>\n  <body>\n",
+        env!("CARGO_PKG_VERSION"),
+        source_lang
+    ));
+    for unit in units {
+        let date = DateTime::parse_from_rfc3339(&unit.timestamp)
+            .map(|date| date.with_timezone(&Utc))
+            .unwrap_or_else(|_| Utc::now());
+        tmx.push_str(&format!(
+            "    <tu srclang=\"{}\" creationdate=\"{}\">\n",
+            escape(&tmx_lang(&unit.source_lang)),
+            date.format(DATE_FORMAT)
+        ));
+        let mut props = vec![("x-reviewed", unit.reviewed.to_string())];
+        if !unit.context.is_empty() {
+            props.push(("x-context", unit.context.clone()));
+        }
+        if let Some(provider) = &unit.provider {
+            props.push(("x-provider", provider.clone()));
+        }
+        if let Some(model) = &unit.model {
+            props.push(("x-model", model.clone()));
+        }
+        for (kind, value) in props {
+            tmx.push_str(&format!(
+                "      <prop type=\"{}\">{}</prop>\n",
+                kind,
+                escape(&value)
+            ));
+        }
+        for (lang, text) in [
+            (&unit.source_lang, &unit.source),
+            (&unit.lang, &unit.target),
+        ] {
+            tmx.push_str(&format!(
+                "      <tuv xml:lang=\"{}\"><seg>{}</seg></tuv>\n",
+                escape(&tmx_lang(lang)),
+                escape(text)
+            ));
+        }
+        tmx.push_str("    </tu>\n");
+    }
+    tmx.push_str("  </body>\n</tmx>\n");
+    tmx
+}
+
+/// The units of a TMX document: one per `<tuv>` of each `<tu>` besides the source one, in
+/// `source_lang` or else the `srclang` of the unit or header. Native codes of inline elements
+/// are kept as the text they stand for, and the `x-` props written by `write` are read back.
+pub fn parse(content: &str, source_lang: Option<&str>) -> Result<Vec<TmUnit>> {
+    let Some(header) = HEADER.find(content) else {
+        bail!("Not a TMX file: no <header>");
+    };
+    let header_lang = attribute(header.as_str(), "srclang");
+    let now = Utc::now().to_rfc3339();
+
+    let mut units = Vec::new();
+    for tu in TU.captures_iter(content) {
+        let (tag, body) = (&tu[1], &tu[2]);
+        let timestamp = attribute(tag, "changedate")
+            .or_else(|| attribute(tag, "creationdate"))
+            .and_then(|date| NaiveDateTime::parse_from_str(&date, DATE_FORMAT).ok())
+            .map_or_else(|| now.clone(), |date| date.and_utc().to_rfc3339());
+
+        let props = PROP
+            .captures_iter(body)
+            .filter_map(|prop| Some((attribute(&prop[1], "type")?, unescape(&prop[2]))));
+        let (mut context, mut provider, mut model, mut reviewed) =
+            (String::new(), None, None, false);
+        for (kind, value) in props {
+            match kind.as_str() {
+                "x-context" => context = value,
+                "x-provider" => provider = Some(value),
+                "x-model" => model = Some(value),
+                "x-reviewed" => reviewed = value == "true",
+                _ => {}
+            }
+        }
+
+        let variants: Vec<(String, String)> = TUV
+            .captures_iter(body)
+            .filter_map(|tuv| {
+                let lang = attribute(&tuv[1], "xml:lang").or_else(|| attribute(&tuv[1], "lang"))?;
+                Some((lang, segment_text(&tuv[2])))
+            })
+            .collect();
+        let srclang = source_lang
+            .map(str::to_string)
+            .or_else(|| attribute(tag, "srclang"))
+            .or_else(|| header_lang.clone())
+            .filter(|lang| lang != "*all*");
+        let source = match &srclang {
+            Some(srclang) => variants
+                .iter()
+                .position(|(lang, _)| same_lang(lang, srclang)),
+            None => (!variants.is_empty()).then_some(0),
+        };
+        let Some(source) = source else {
+            continue;
+        };
+
+        let (lang, source_text) = &variants[source];
+        let source_lang = srclang.as_deref().unwrap_or(lang);
+        for (index, (lang, target)) in variants.iter().enumerate() {
+            if index == source || target.trim().is_empty() {
+                continue;
+            }
+            units.push(TmUnit {
+                source: source_text.clone(),
+                context: context.clone(),
+                source_lang: catalog_lang(source_lang),
+                lang: catalog_lang(lang),
+                target: target.clone(),
+                provider: provider.clone(),
+                model: model.clone(),
+                timestamp: timestamp.clone(),
+                reviewed,
+            });
+        }
+    }
+    Ok(units)
+}
+
+/// The text of a `<seg>`, with the native codes of its inline elements and without the tags
+fn segment_text(segment: &str) -> String {
+    let mut text = String::new();
+    let mut last = 0;
+    for code in NATIVE_CODE.captures_iter(segment) {
+        let whole = code.get(0).expect("the whole match");
+        text.push_str(&unescape(
+            &TAG.replace_all(&segment[last..whole.start()], ""),
+        ));
+        text.push_str(&unescape(&TAG.replace_all(&code[1], "")));
+        last = whole.end();
+    }
+    text.push_str(&unescape(&TAG.replace_all(&segment[last..], "")));
+    text
+}
+
+/// A language code as TMX writes it, like `pt-BR`
+fn tmx_lang(lang: &str) -> String {
+    lang.replace('_', "-")
+}
+
+/// A TMX language code as the catalogs are named, like `pt_BR`
+fn catalog_lang(lang: &str) -> String {
+    lang.replace('-', "_")
+}
+
+/// Two language codes equal but for case and `-` or `_`, or one the language of the other,
+/// like `en` and `en-US`
+fn same_lang(a: &str, b: &str) -> bool {
+    let (a, b) = (
+        catalog_lang(a).to_lowercase(),
+        catalog_lang(b).to_lowercase(),
+    );
+    a == b || a.split('_').next() == Some(b.as_str()) || b.split('_').next() == Some(a.as_str())
+}
@@ -0,0 +1,443 @@
+//! Checks of translations by the model: `--verify` of the new ones of a run, and the
+//! `verify` subcommand over the existing ones of the catalogs, which can also translate each
+//! msgstr back to the source language and compare the round trip with the msgid. The
+//! entries it flags go to a review list for humans.
+
+use anyhow::Result;
+use clap::ValueEnum;
+use futures_util::stream::{self, StreamExt};
+use serde::Deserialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::atomic;
+use crate::coverage::lang_of;
+use crate::filter::PathFilter;
+use crate::formats::csv;
+use crate::lock::is_locked;
+use crate::logger::{log_review_flagged, log_review_summary};
+use crate::po::{PoCatalog, PoEntry};
+use crate::prompt::{INJECTION_GUARD, delimit, strip_delimiters, with_context};
+use crate::provider::{AiRequest, TranslationProvider};
+use crate::sanitize;
+use crate::translator::iso_to_name;
+
+pub const DEFAULT_REVIEW_FILE: &str = "translation-review.csv";
+
+/// What `--verify` does with a translation the reviewing model rejects
+#[derive(Clone, Copy, Debug, Default, PartialEq, ValueEnum)]
+pub enum VerifyMode {
+    /// Keep the translation, marked `#, fuzzy`
+    #[default]
+    Fuzzy,
+    /// Apply the model's corrected translation
+    Fix,
+}
+
+/// The model's judgment of a translation
+#[derive(Deserialize)]
+pub struct Verdict {
+    pub ok: bool,
+    #[serde(default)]
+    pub issue: Option<String>,
+    #[serde(default)]
+    translation: Option<Correction>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Correction {
+    Single(String),
+    Forms(Vec<String>),
+}
+
+impl Verdict {
+    /// The corrected msgstrs, if the model gave as many as the entry needs
+    pub fn correction(&self, forms: usize) -> Option<Vec<String>> {
+        let msgstrs = match self.translation.as_ref()? {
+            Correction::Single(msgstr) => vec![msgstr.clone()],
+            Correction::Forms(msgstrs) => msgstrs.clone(),
+        };
+
+        match msgstrs.len() == forms && msgstrs.iter().all(|msgstr| !msgstr.is_empty()) {
+            true => Some(msgstrs),
+            false => None,
+        }
+    }
+}
+
+/// Asks the model to judge the translation `msgstrs` of an entry, given the same msgctxt and
+/// notes `hints` as the translation prompt
+pub fn build_request(
+    msgid: &str,
+    msgid_plural: Option<&str>,
+    msgstrs: &[String],
+    iso_code: &str,
+    hints: &str,
+    context: &Option<String>,
+) -> AiRequest {
+    let language = &iso_to_name(iso_code);
+    let instructions = format!(
+        "You are a meticulous reviewer of {} translations of gettext messages. You answer with a JSON object only.",
+        language
+    );
+
+    let mut prompt = format!(
+        "Check this {} translation of a gettext message. Look for mistranslations, such as dropped or inverted negations, wrong meanings, missing or altered placeholders like `%{{...}}`, and text left untranslated.",
+        language
+    );
+
+    prompt.push_str(
+        "\n\n
+    Important:
+		- If the translation is correct, answer {\"ok\": true}.
+		- Otherwise answer {\"ok\": false, \"issue\": \"<what is wrong>\", \"translation\": <the corrected translation>}, with the translation as a string, or as a JSON array of one string per plural form for plural messages.
+		- Just return the JSON object, do not add any other text, comments or code fences.
+
+    ",
+    );
+
+    prompt.push_str(hints);
+
+    match msgid_plural {
+        Some(msgid_plural) => {
+            prompt.push_str(&format!(
+                "Singular:\n{}\nPlural:\n{}\nTranslation, one string per plural form:\n{}",
+                delimit(msgid),
+                delimit(msgid_plural),
+                serde_json::to_string(msgstrs).unwrap_or_default()
+            ));
+        }
+        None => {
+            prompt.push_str(&format!(
+                "Message:\n{}\nTranslation:\n{}",
+                delimit(msgid),
+                delimit(&msgstrs.join(""))
+            ));
+        }
+    }
+
+    let instructions = format!(
+        "{}\n\n{}",
+        with_context(instructions, context),
+        INJECTION_GUARD
+    );
+    AiRequest::new(instructions, prompt).with_texts(
+        iso_code,
+        [msgid]
+            .into_iter()
+            .chain(msgid_plural)
+            .map(str::to_string)
+            .collect(),
+    )
+}
+
+/// Parses the model's answer, with or without code fences
+pub fn parse_verdict(response: &str) -> Result<Verdict> {
+    let json = response
+        .trim()
+        .trim_start_matches("```json")
+        .trim_start_matches("```")
+        .trim_end_matches("```")
+        .trim();
+
+    serde_json::from_str(json)
+        .map_err(|e| anyhow::anyhow!("verification answer is not a JSON verdict: {}", e))
+}
+
+/// Options of the `verify` subcommand
+pub struct ReviewOptions {
+    /// Languages to check; every language if empty
+    pub langs: Vec<String>,
+    /// Translate each msgstr back to `source_lang` and compare it with the msgid, instead of
+    /// having the model judge the translation itself
+    pub back_translate: bool,
+    pub source_lang: String,
+    pub concurrency: usize,
+    /// Mark the flagged entries `#, fuzzy` in their catalog
+    pub mark_fuzzy: bool,
+    /// The review list, a CSV file
+    pub out: PathBuf,
+    pub context: Option<String>,
+}
+
+/// An entry the model found wrong
+struct Flagged {
+    line: usize,
+    msgid: String,
+    msgstr: String,
+    back_translation: Option<String>,
+    issue: String,
+}
+
+/// Checks the translated entries of the catalogs under `root` with the model, logging those
+/// it flags and writing them to the review list, which is removed when none is. Fuzzy
+/// entries, which already await a reviewer, and locked ones are left out. Returns the number
+/// of entries flagged.
+pub async fn run(
+    root: &Path,
+    options: &ReviewOptions,
+    provider: &dyn TranslationProvider,
+) -> Result<usize> {
+    let mut paths: Vec<PathBuf> = PathFilter::default()
+        .files(root, root)
+        .filter(|path| path.extension().is_some_and(|e| e == "po"))
+        .collect();
+    paths.sort();
+
+    let mut rows = vec![
+        [
+            "file",
+            "line",
+            "lang",
+            "msgid",
+            "msgstr",
+            "back_translation",
+            "issue",
+        ]
+        .map(str::to_string)
+        .to_vec(),
+    ];
+    let (mut files, mut checked) = (0, 0);
+    for path in &paths {
+        let lang = lang_of(root, path);
+        if !options.langs.is_empty() && !options.langs.iter().any(|known| known.trim() == lang) {
+            continue;
+        }
+        files += 1;
+
+        let mut catalog = PoCatalog::read(path)?;
+        let entries: Vec<&PoEntry> = catalog
+            .entries()
+            .filter(|entry| {
+                !entry.is_header()
+                    && !entry.has_flag("fuzzy")
+                    && !is_locked(entry)
+                    && !entry.msgstr.is_empty()
+                    && entry.msgstr.iter().all(|msgstr| !msgstr.is_empty())
+            })
+            .collect();
+        checked += entries.len();
+
+        let flagged: Vec<Flagged> = stream::iter(entries)
+            .map(|entry| review_entry(entry, &lang, options, provider))
+            .buffered(options.concurrency.max(1))
+            .filter_map(|flagged| async { flagged })
+            .collect()
+            .await;
+
+        for flagged in &flagged {
+            log_review_flagged(
+                &lang,
+                &flagged.msgid,
+                &flagged.msgstr,
+                flagged.back_translation.as_deref(),
+                &flagged.issue,
+            );
+            rows.push(vec![
+                path.display().to_string(),
+                flagged.line.to_string(),
+                lang.clone(),
+                flagged.msgid.clone(),
+                flagged.msgstr.clone(),
+                flagged.back_translation.clone().unwrap_or_default(),
+                flagged.issue.clone(),
+            ]);
+        }
+
+        if options.mark_fuzzy && !flagged.is_empty() {
+            for entry in catalog.entries_mut() {
+                if flagged.iter().any(|flagged| flagged.line == entry.line) {
+                    entry.add_flag("fuzzy");
+                }
+            }
+            catalog.write(path)?;
+        }
+    }
+
+    let flagged = rows.len() - 1;
+    match flagged {
+        0 if options.out.is_file() => fs::remove_file(&options.out)?,
+        0 => {}
+        _ => atomic::write(&options.out, csv::write(&rows, ',', "\n"))?,
+    }
+    log_review_summary(
+        files,
+        checked,
+        flagged,
+        (flagged > 0).then_some(options.out.as_path()),
+    );
+    Ok(flagged)
+}
+
+/// Has the model check the translation of `entry` to `lang`. Returns it if it is flagged,
+/// which a check that can't be done is too.
+async fn review_entry(
+    entry: &PoEntry,
+    lang: &str,
+    options: &ReviewOptions,
+    provider: &dyn TranslationProvider,
+) -> Option<Flagged> {
+    let hints = match &entry.msgctxt {
+        Some(msgctxt) => format!(
+            "This string appears in context: {}. Translate it accordingly.\n\n",
+            msgctxt
+        ),
+        None => String::new(),
+    };
+    let msgstr = entry.msgstr.join(" | ");
+    let flag = |back_translation: Option<String>, issue: String| Flagged {
+        line: entry.line,
+        msgid: entry.msgid.clone(),
+        msgstr: msgstr.clone(),
+        back_translation,
+        issue,
+    };
+
+    if !options.back_translate {
+        let request = build_request(
+            &entry.msgid,
+            entry.msgid_plural.as_deref(),
+            &entry.msgstr,
+            lang,
+            &hints,
+            &options.context,
+        );
+        return match judge(provider, &request).await {
+            Ok(None) => None,
+            Ok(Some(issue)) => Some(flag(None, issue)),
+            Err(e) => Some(flag(None, format!("could not be checked: {}", e))),
+        };
+    }
+
+    let request = build_back_translation_request(
+        &entry.msgstr[0],
+        lang,
+        &options.source_lang,
+        &options.context,
+    );
+    let back_translation = match provider.send(&request).await {
+        Ok(response) => sanitize::clean(&entry.msgstr[0], &strip_delimiters(response.text)),
+        Err(e) => return Some(flag(None, format!("could not be checked: {}", e))),
+    };
+    if same_text(&back_translation, &entry.msgid) {
+        return None;
+    }
+
+    let request = build_comparison_request(
+        &entry.msgid,
+        &back_translation,
+        lang,
+        &options.source_lang,
+        &hints,
+        &options.context,
+    );
+    match judge(provider, &request).await {
+        Ok(None) => None,
+        Ok(Some(issue)) => Some(flag(Some(back_translation), issue)),
+        Err(e) => Some(flag(
+            Some(back_translation),
+            format!("could not be checked: {}", e),
+        )),
+    }
+}
+
+/// Sends a request answered with a verdict. Returns the issue the model found, if any.
+async fn judge(provider: &dyn TranslationProvider, request: &AiRequest) -> Result<Option<String>> {
+    let verdict = parse_verdict(&provider.send(request).await?.text)?;
+    Ok((!verdict.ok).then(|| {
+        verdict
+            .issue
+            .unwrap_or_else(|| "no reason given".to_string())
+    }))
+}
+
+/// Two texts equal but for case, surrounding spaces and final punctuation
+fn same_text(a: &str, b: &str) -> bool {
+    let normalize = |text: &str| {
+        text.trim()
+            .trim_end_matches(['.', '!', '?', ':', '…'])
+            .to_lowercase()
+    };
+    normalize(a) == normalize(b)
+}
+
+/// Asks for the translation of `msgstr`, in `iso_code`, back to `source_lang`, as it reads
+fn build_back_translation_request(
+    msgstr: &str,
+    iso_code: &str,
+    source_lang: &str,
+    context: &Option<String>,
+) -> AiRequest {
+    let (language, source) = (&iso_to_name(iso_code), &iso_to_name(source_lang));
+    let instructions = format!(
+        "You are a professional translator. You will translate {} text back to {}. You must preserve placeholder, written in the format `%{{placeholder}}`.",
+        language, source
+    );
+
+    let prompt = format!(
+        "Translate this {} text to {}, preserving placeholders like `%{{...}}`. Translate what it says as literally as reads well, even where it seems wrong: it is used to check a translation.
+
+    Important:
+		- Just return the translation, do not add any other text or comments.
+
+    Text to translate, between <text> and </text>:
+{}",
+        language,
+        source,
+        delimit(msgstr)
+    );
+
+    let instructions = format!(
+        "{}\n\n{}",
+        with_context(instructions, context),
+        INJECTION_GUARD
+    );
+    AiRequest::new(instructions, prompt).with_texts(source_lang, vec![msgstr.to_string()])
+}
+
+/// Asks the model whether `back_translation`, the `source_lang` back-translation of the
+/// `iso_code` translation of `msgid`, means what `msgid` does
+fn build_comparison_request(
+    msgid: &str,
+    back_translation: &str,
+    iso_code: &str,
+    source_lang: &str,
+    hints: &str,
+    context: &Option<String>,
+) -> AiRequest {
+    let (language, source) = (&iso_to_name(iso_code), &iso_to_name(source_lang));
+    let instructions = format!(
+        "You are a meticulous reviewer of {} translations of gettext messages. You answer with a JSON object only.",
+        language
+    );
+
+    let mut prompt = format!(
+        "A {} translation of a gettext message was translated back to {}. Compare the message with the back-translation: does the translation keep its meaning? Ignore differences of wording, word order and formality; look for dropped or inverted negations, wrong meanings, and missing parts or placeholders like `%{{...}}`.",
+        language, source
+    );
+
+    prompt.push_str(
+        "\n\n
+    Important:
+		- If the meanings match, answer {\"ok\": true}.
+		- Otherwise answer {\"ok\": false, \"issue\": \"<what differs>\"}.
+		- Just return the JSON object, do not add any other text, comments or code fences.
+
+    ",
+    );
+
+    prompt.push_str(hints);
+    prompt.push_str(&format!(
+        "Message:\n{}\nBack-translation:\n{}",
+        delimit(msgid),
+        delimit(back_translation)
+    ));
+
+    let instructions = format!(
+        "{}\n\n{}",
+        with_context(instructions, context),
+        INJECTION_GUARD
+    );
+    AiRequest::new(instructions, prompt).with_texts(iso_code, vec![msgid.to_string()])
+}
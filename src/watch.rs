@@ -0,0 +1,106 @@
+use anyhow::Result;
+use notify::{Event, EventKind, RecursiveMode, Watcher};
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::time::timeout;
+
+use crate::logger::{log_watch_error, log_watching};
+use crate::provider::TranslationProvider;
+use crate::translator::{self, TranslateOptions, Translator};
+
+/// Changes closer together than this are handled at once, so that a `msgmerge` over many
+/// catalogs triggers a single pass
+const DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// Translates the catalogs under `root` once, then again whenever a .po or .pot file changes
+pub async fn run(
+    root: PathBuf,
+    langs: &str,
+    context: Option<PathBuf>,
+    provider: Arc<dyn TranslationProvider>,
+    options: TranslateOptions,
+) -> Result<()> {
+    let root = root.canonicalize()?;
+    let lang_list: Vec<&str> = langs.split(',').map(|s| s.trim()).collect();
+    let mut translator = translator::for_folder(&root, &context, provider, options).await?;
+
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<Event>| {
+        if let Ok(event) = event {
+            let _ = tx.send(event);
+        }
+    })?;
+    watcher.watch(&root, RecursiveMode::Recursive)?;
+
+    if let Err(e) = translator.translate_dir(&root, &lang_list).await {
+        log_watch_error(&e);
+    }
+    log_watching(&root.display().to_string());
+
+    while let Some(event) = rx.recv().await {
+        let mut changed = BTreeSet::new();
+        collect_changes(&event, &mut changed);
+        while let Ok(Some(event)) = timeout(DEBOUNCE, rx.recv()).await {
+            collect_changes(&event, &mut changed);
+        }
+
+        // Our own writes come back as events too, but the pass they trigger finds nothing
+        // left to translate and writes nothing, so it doesn't loop
+        if let Err(e) = handle_changes(&mut translator, &root, &lang_list, &changed).await {
+            log_watch_error(&e);
+        }
+    }
+
+    Ok(())
+}
+
+fn collect_changes(event: &Event, changed: &mut BTreeSet<PathBuf>) {
+    if !matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) {
+        return;
+    }
+
+    for path in &event.paths {
+        if matches!(
+            path.extension().and_then(|e| e.to_str()),
+            Some("po" | "pot")
+        ) {
+            changed.insert(path.clone());
+        }
+    }
+}
+
+/// A changed template re-runs every language (creating missing catalogs if asked to);
+/// a changed catalog is translated on its own
+async fn handle_changes(
+    translator: &mut Translator,
+    root: &Path,
+    langs: &[&str],
+    changed: &BTreeSet<PathBuf>,
+) -> Result<()> {
+    if changed
+        .iter()
+        .any(|path| path.extension().is_some_and(|e| e == "pot"))
+    {
+        return translator.translate_dir(root, langs).await;
+    }
+
+    for path in changed {
+        let Some(lang) = path
+            .strip_prefix(root)
+            .ok()
+            .and_then(|relative| relative.components().next())
+            .and_then(|lang| lang.as_os_str().to_str())
+        else {
+            continue;
+        };
+
+        if langs.contains(&lang) && path.is_file() && translator.includes(root, path) {
+            translator.translate_file(path, lang).await?;
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,68 @@
+// Each test crate uses some of the helpers
+#![allow(dead_code)]
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+/// A copy of the `tests/fixtures/gettext` tree in a folder of its own, for a test to change
+pub fn fixture_tree(test: &str) -> PathBuf {
+    let dir = empty_dir(test);
+    copy_dir(&fixtures().join("gettext"), &dir);
+    dir
+}
+
+/// An empty folder of its own for a test
+pub fn empty_dir(test: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!(
+        "ai-gettext-translator-{}-{}",
+        test,
+        std::process::id()
+    ));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+pub fn fixtures() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures")
+}
+
+/// Runs the command in `dir`, away from any config file of the repository
+pub fn translator(dir: &Path, args: &[&str]) -> Output {
+    let output = Command::new(env!("CARGO_BIN_EXE_ai_gettext_translator"))
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .expect("the binary runs");
+    assert!(
+        output.status.success(),
+        "{:?} failed: {}",
+        args,
+        String::from_utf8_lossy(&output.stderr)
+    );
+    output
+}
+
+/// The content of a catalog without its `PO-Revision-Date`, which changes with every run
+pub fn catalog_without_date(path: &Path) -> String {
+    fs::read_to_string(path)
+        .unwrap()
+        .lines()
+        .filter(|line| !line.starts_with("\"PO-Revision-Date:"))
+        .map(|line| format!("{}\n", line))
+        .collect()
+}
+
+fn copy_dir(from: &Path, to: &Path) {
+    fs::create_dir_all(to).unwrap();
+    for entry in fs::read_dir(from).unwrap() {
+        let entry = entry.unwrap();
+        let target = to.join(entry.file_name());
+        if entry.file_type().unwrap().is_dir() {
+            copy_dir(&entry.path(), &target);
+        } else {
+            fs::copy(entry.path(), target).unwrap();
+        }
+    }
+}
@@ -0,0 +1,87 @@
+mod common;
+
+use std::fs;
+
+use common::{empty_dir, translator};
+
+#[test]
+fn extracts_every_gettext_macro_by_default() {
+    let dir = empty_dir("extract-macros");
+    fs::create_dir_all(dir.join("lib")).unwrap();
+    fs::write(
+        dir.join("lib/page.ex"),
+        r#"defmodule Page do
+  def labels(n) do
+    [
+      gettext("Home"),
+      dgettext("errors", "Not found"),
+      pgettext("menu", "Open"),
+      dpgettext("errors", "menu", "Close"),
+      ngettext("One file", "%{count} files", n),
+      dngettext("errors", "One error", "%{count} errors", n),
+      pngettext("menu", "One tab", "%{count} tabs", n),
+      dpngettext("errors", "menu", "One window", "%{count} windows", n)
+    ]
+  end
+end
+"#,
+    )
+    .unwrap();
+
+    translator(&dir, &["extract", "."]);
+    let template = fs::read_to_string(dir.join("messages.pot")).unwrap();
+    for msgid in [
+        "Home",
+        "Not found",
+        "Open",
+        "Close",
+        "One file",
+        "One error",
+        "One tab",
+        "One window",
+    ] {
+        assert!(
+            template.contains(&format!("msgid \"{}\"", msgid)),
+            "{} is missing from {}",
+            msgid,
+            template
+        );
+    }
+    assert!(template.contains("msgid_plural \"%{count} windows\""));
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn scans_elixir_scripts_and_templates_by_default() {
+    let dir = empty_dir("extract-extensions");
+    fs::create_dir_all(dir.join("lib/templates")).unwrap();
+    fs::write(dir.join("lib/seeds.exs"), "IO.puts(gettext(\"Seeded\"))\n").unwrap();
+    fs::write(
+        dir.join("lib/templates/index.html.eex"),
+        "<h1><%= gettext(\"Welcome\") %></h1>\n",
+    )
+    .unwrap();
+    fs::write(
+        dir.join("lib/templates/page.html.heex"),
+        "<p>{gettext(\"Read more\")}</p>\n",
+    )
+    .unwrap();
+    fs::write(
+        dir.join("lib/templates/live.html.leex"),
+        "<%= gettext(\"Live\") %>\n",
+    )
+    .unwrap();
+    fs::write(dir.join("lib/notes.txt"), "gettext(\"Ignored\")\n").unwrap();
+
+    translator(&dir, &["extract", "."]);
+    let template = fs::read_to_string(dir.join("messages.pot")).unwrap();
+    for msgid in ["Seeded", "Welcome", "Read more", "Live"] {
+        assert!(
+            template.contains(&format!("msgid \"{}\"", msgid)),
+            "{}",
+            msgid
+        );
+    }
+    assert!(!template.contains("Ignored"));
+    fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,80 @@
+mod common;
+
+use std::fs;
+
+use common::{empty_dir, translator};
+
+const CATALOG: &str = r#"msgid ""
+msgstr ""
+"Language: es\n"
+"Content-Type: text/plain; charset=UTF-8\n"
+"Plural-Forms: nplurals=2; plural=(n != 1);\n"
+
+msgid "Save"
+msgstr "Guardar"
+
+msgid "Save…"
+msgstr ""
+
+msgid "Sign out"
+msgstr ""
+"#;
+
+#[test]
+fn reuses_the_translation_of_a_msgid_differing_in_its_punctuation() {
+    let dir = empty_dir("memory");
+    let catalog = dir.join("es/LC_MESSAGES/default.po");
+    fs::create_dir_all(catalog.parent().unwrap()).unwrap();
+    fs::write(&catalog, CATALOG).unwrap();
+
+    let output = translator(
+        &dir,
+        &[
+            "translator",
+            ".",
+            "--lang",
+            "es",
+            "--provider",
+            "mock",
+            "--memory",
+            "--output",
+            "json",
+        ],
+    );
+
+    let written = fs::read_to_string(&catalog).unwrap();
+    assert!(written.contains("msgid \"Save…\"\nmsgstr \"Guardar…\""));
+    assert!(written.contains("msgid \"Sign out\"\nmsgstr \"[[es]] Sign out\""));
+    let matches: Vec<serde_json::Value> = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+        .filter(|event| event["event"] == "memory_match")
+        .collect();
+    assert_eq!(matches.len(), 1);
+    assert_eq!(matches[0]["msgid"], "Save…");
+    assert_eq!(matches[0]["similar"], "Save");
+    assert_eq!(matches[0]["reused"], true);
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn needs_a_provider_with_embeddings() {
+    let dir = empty_dir("memory-pseudo");
+    fs::create_dir_all(dir.join("es")).unwrap();
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_ai_gettext_translator"))
+        .args([
+            "translator",
+            ".",
+            "--lang",
+            "es",
+            "--provider",
+            "pseudo",
+            "--memory",
+        ])
+        .current_dir(&dir)
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("--embedding-provider"));
+    fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,62 @@
+mod common;
+
+use std::fs;
+
+use common::{catalog_without_date, fixture_tree, fixtures, translator};
+
+#[test]
+fn translates_the_missing_entries_of_a_tree() {
+    let dir = fixture_tree("mock");
+    translator(
+        &dir,
+        &["translator", ".", "--lang", "es", "--provider", "mock"],
+    );
+
+    let catalog = dir.join("es/LC_MESSAGES/default.po");
+    assert_eq!(
+        catalog_without_date(&catalog),
+        fs::read_to_string(fixtures().join("mock-es.po")).unwrap()
+    );
+    assert!(
+        fs::read_to_string(&catalog)
+            .unwrap()
+            .contains("\"PO-Revision-Date: ")
+    );
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn leaves_the_catalogs_alone_in_a_dry_run() {
+    let dir = fixture_tree("mock-dry-run");
+    let catalog = dir.join("es/LC_MESSAGES/default.po");
+    let original = fs::read_to_string(&catalog).unwrap();
+
+    let output = translator(
+        &dir,
+        &[
+            "translator",
+            ".",
+            "--lang",
+            "es",
+            "--provider",
+            "mock",
+            "--dry-run",
+            "--output",
+            "json",
+        ],
+    );
+
+    assert_eq!(fs::read_to_string(&catalog).unwrap(), original);
+    let events: Vec<serde_json::Value> = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    let translated: Vec<&serde_json::Value> = events
+        .iter()
+        .filter(|event| event["event"] == "entry_translated")
+        .collect();
+    assert_eq!(translated.len(), 3);
+    assert!(translated.iter().all(|event| event["dry_run"] == true));
+    assert_eq!(translated[0]["msgstr"], "[[es]] Hello %{name}");
+    fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,55 @@
+mod common;
+
+use std::fs;
+use std::process::Command;
+
+use common::{catalog_without_date, fixture_tree, fixtures, translator};
+
+#[test]
+fn replays_the_recorded_answers_without_the_provider() {
+    let dir = fixture_tree("replay");
+    let catalog = dir.join("es/LC_MESSAGES/default.po");
+    let original = fs::read_to_string(&catalog).unwrap();
+    translator(
+        &dir,
+        &[
+            "translator",
+            ".",
+            "--lang",
+            "es",
+            "--provider",
+            "mock",
+            "--record",
+            "recorded",
+        ],
+    );
+    assert_eq!(fs::read_dir(dir.join("recorded")).unwrap().count(), 3);
+
+    fs::write(&catalog, &original).unwrap();
+    translator(
+        &dir,
+        &["translator", ".", "--lang", "es", "--replay", "recorded"],
+    );
+    assert_eq!(
+        catalog_without_date(&catalog),
+        fs::read_to_string(fixtures().join("mock-es.po")).unwrap()
+    );
+
+    // A request that wasn't recorded fails, and so does the run
+    fs::write(
+        &catalog,
+        original.replace("msgid \"Save\"", "msgid \"Save now\""),
+    )
+    .unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_ai_gettext_translator"))
+        .args(["translator", ".", "--lang", "es", "--replay", "recorded"])
+        .current_dir(&dir)
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("No recorded response"));
+    let content = fs::read_to_string(&catalog).unwrap();
+    assert!(content.contains("msgid \"Save now\"\nmsgstr \"\"\n"));
+    assert!(content.contains("msgstr \"[[es]] Hello %{name}\""));
+    fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,30 @@
+mod common;
+
+use std::fs;
+use std::process::Command;
+
+use common::empty_dir;
+
+#[test]
+fn rejects_the_flags_it_ignores() {
+    let dir = empty_dir("serve-flags");
+    for flag in [
+        "--dry-run",
+        "--backup",
+        "--report=markdown",
+        "--include=*.po",
+    ] {
+        let output = Command::new(env!("CARGO_BIN_EXE_ai_gettext_translator"))
+            .args(["serve", ".", "--provider", "pseudo", flag])
+            .current_dir(&dir)
+            .output()
+            .unwrap();
+        assert!(!output.status.success(), "serve took {}", flag);
+        assert!(
+            String::from_utf8_lossy(&output.stderr).contains("unexpected argument"),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+    fs::remove_dir_all(dir).unwrap();
+}